@@ -1,8 +1,25 @@
 use crate::core::{
-    self, ArchiveStatus, ArchiverOperations, ConfigManagerOperations, ContentSearchProgress,
-    FileSystemScannerOperations, NodeStateApplicatorOperations, Profile, ProfileManagerOperations,
-    ProfileName, ProfileRuntimeDataOperations, ProjectContext, SelectionState,
-    TokenCounterOperations, TokenProgress, TokenProgressChannel,
+    self, ArchiveError, ArchiveFormat, ArchiveOptions, ArchiveRequest, ArchiveSplitOptions,
+    ArchiveStatus, ArchiverOperations, ConfigManagerOperations, ContentSearchProgress,
+    FilePriority, FileSystemScannerOperations, NodeStateApplicatorOperations, Profile,
+    ProfileManagerOperations, ProfileName, ProfileRuntimeDataOperations, ProjectContext,
+    SelectionState, TokenBudgetReport, TokenCounterOperations, TokenProgress, TokenProgressChannel,
+    archive_apply::{ApplyPreview, ApplyReport},
+    archive_history::{self, ArchiveHistory, HistoryCommand},
+    archive_path_template::{self, ArchivePathContext, ArchivePathError},
+    archive_prompt::PromptContext,
+    archive_record::ArchiveRecord,
+    archive_tree::DirectoryTreeOptions,
+    archiver,
+    batch_generate::{self, BatchProgress, BatchServices},
+    composite_profile::ProjectComponentLoader,
+    content_class::{self, ContentPolicy, ContentReader, FileContent, SkippedFile},
+    content_transform::{self, ContentTransform},
+    file_watcher::{self, FileChange, FileWatcher},
+    line_numbers,
+    patch_apply::{PatchPlan, PatchWriteReport},
+    secret_scan::{self, SecretFinding, SecretPolicy, SecretScanReport},
+    text_encoding::{self, EncodingRules},
 };
 use crate::platform_layer::{
     AppEvent, CheckState, Color, ControlStyle, FontDescription, FontWeight, MessageSeverity,
@@ -24,6 +41,20 @@ use std::thread::JoinHandle;
 use log::{error, info, warn};
 
 pub(crate) const APP_NAME_FOR_PROFILES: &str = "SourcePacker";
+const ARCHIVE_FORMAT_CONTEXT_TAG: &str = "ArchiveFormat";
+const ARCHIVE_SPLIT_CONTEXT_TAG: &str = "ArchiveSplit";
+const TOKEN_BUDGET_CONTEXT_TAG: &str = "TokenBudget";
+const FILE_PRIORITY_CONTEXT_TAG: &str = "FilePriority";
+const CONTENT_TRANSFORMS_CONTEXT_TAG: &str = "ContentTransforms";
+const DIRECTORY_TREE_CONTEXT_TAG: &str = "DirectoryTree";
+const APPLY_EDITED_ARCHIVE_CONTEXT_TAG: &str = "ApplyEditedArchive";
+const APPLY_PATCH_CONTEXT_TAG: &str = "ApplyPatch";
+const SECRET_SCAN_CONTEXT_TAG: &str = "SecretScan";
+const CONTENT_LIMITS_CONTEXT_TAG: &str = "ContentLimits";
+const ENCODINGS_CONTEXT_TAG: &str = "Encodings";
+const LINE_NUMBERS_CONTEXT_TAG: &str = "LineNumbers";
+const ARCHIVE_HISTORY_CONTEXT_TAG: &str = "ArchiveHistory";
+const PROMPT_VARIANT_CONTEXT_TAG: &str = "PromptVariant";
 
 // These type aliases are used by MainWindowUiState.
 pub(crate) type PathToTreeItemIdMap = HashMap<PathBuf, TreeItemId>;
@@ -35,6 +66,8 @@ pub(crate) enum PendingAction {
     CreatingNewProfileGetRoot,
     SettingArchivePath,
     OpeningProjectFolder,
+    ApplyingEditedArchive,
+    ApplyingPatch,
 }
 
 /*
@@ -59,6 +92,15 @@ struct ContentSearchDriver {
     receiver: Mutex<Receiver<ContentSearchProgress>>,
 }
 
+/*
+ * Tracks a running "Generate All Archives" request. Progress arrives once per profile and
+ * is polled like the other drivers; the worker is joined when the summary has arrived.
+ */
+struct BatchGenerationDriver {
+    receiver: Mutex<Receiver<BatchProgress>>,
+    worker_handle: Option<JoinHandle<()>>,
+}
+
 // --- Status Message Macros ---
 macro_rules! status_message {
     ($self:expr, $severity:expr, $log_macro:ident, $($arg:tt)*) => {{
@@ -105,7 +147,16 @@ pub struct MyAppLogic {
     state_manager: Arc<dyn NodeStateApplicatorOperations>,
     synchronous_command_queue: VecDeque<PlatformCommand>,
     token_recalc_driver: Option<TokenRecalcDriver>,
+    // Token total of the selected files from the last finished recalculation, without the prompt.
+    file_token_total: usize,
     content_search_driver: Option<ContentSearchDriver>,
+    batch_generation_driver: Option<BatchGenerationDriver>,
+    // Watch of the active profile's root folder; its changes are applied to the tree as they come.
+    file_watcher: Option<FileWatcher>,
+    // Parsed edited archive awaiting the user's choice of files to write.
+    pending_apply_preview: Option<ApplyPreview>,
+    // Dry run of a patch awaiting confirmation before it is written.
+    pending_patch_plan: Option<PatchPlan>,
 }
 
 impl MyAppLogic {
@@ -136,7 +187,12 @@ impl MyAppLogic {
             state_manager,
             synchronous_command_queue: VecDeque::new(),
             token_recalc_driver: None,
+            file_token_total: 0,
             content_search_driver: None,
+            batch_generation_driver: None,
+            file_watcher: None,
+            pending_apply_preview: None,
+            pending_patch_plan: None,
         }
     }
 
@@ -153,6 +209,64 @@ impl MyAppLogic {
         }
     }
 
+    /*
+     * The content record of the active profile's archive, or `None` without an open
+     * project or a valid profile name. [ArchiveSyncContentRecordV1]
+     */
+    fn archive_record_path(&self, profile_name: &str) -> Option<PathBuf> {
+        let project = self.active_project.as_ref()?;
+        let profile_name = ProfileName::new(profile_name).ok()?;
+        Some(project.resolve_archive_record_file(&profile_name))
+    }
+
+    /* The archive history of the given profile, or `None` as for `archive_record_path`. */
+    fn archive_history_dir(&self, profile_name: &str) -> Option<PathBuf> {
+        let project = self.active_project.as_ref()?;
+        let profile_name = ProfileName::new(profile_name).ok()?;
+        Some(project.resolve_archive_history_dir(&profile_name))
+    }
+
+    /*
+     * Resolves the placeholders of a profile's archive path for a new archive.
+     * [ArchivePathTemplateV1] Relative paths are taken relative to the project root, or to
+     * the profile's scan root without an open project.
+     */
+    fn resolve_archive_path_for_generation(
+        &self,
+        profile_name: &str,
+        archive_path: &Path,
+        scan_root: &Path,
+    ) -> Result<PathBuf, ArchivePathError> {
+        let project_root = self
+            .active_project
+            .as_ref()
+            .map_or(scan_root, |project| project.root_path());
+        let context = ArchivePathContext::new(project_root, profile_name);
+        archive_path_template::resolve_archive_path(archive_path, &context)
+    }
+
+    /*
+     * The archive the status and delta refer to. For a templated path this is the one last
+     * generated, as kept in the archive record, falling back to what the template resolves
+     * to now when the profile has not been generated yet.
+     */
+    fn latest_archive_path(
+        &self,
+        profile_name: &str,
+        archive_path: &Path,
+        scan_root: &Path,
+    ) -> Option<PathBuf> {
+        if archive_path_template::is_templated(archive_path)
+            && let Some(recorded) = self
+                .archive_record_path(profile_name)
+                .and_then(|record| archive_path_template::last_resolved_archive_path(&record))
+        {
+            return Some(recorded);
+        }
+        self.resolve_archive_path_for_generation(profile_name, archive_path, scan_root)
+            .ok()
+    }
+
     fn start_project_folder_prompt(&mut self, window_id: WindowId) {
         if let Some(ui_state_mut) = self.ui_state.as_mut() {
             ui_state_mut.set_pending_action(Some(PendingAction::OpeningProjectFolder));
@@ -348,12 +462,25 @@ impl MyAppLogic {
     /*
      * Converts an `ArchiveStatus` enum to a user-friendly string.
      * This function provides plain English descriptions for each status variant.
+     * For an outdated archive with a content record, the changed files are named.
      */
     pub(crate) fn archive_status_to_plain_string(status: &ArchiveStatus) -> String {
         match status {
             ArchiveStatus::UpToDate => "Up to date.".to_string(),
             ArchiveStatus::NotYetGenerated => "Not yet generated.".to_string(),
-            ArchiveStatus::OutdatedRequiresUpdate => "Out of date.".to_string(),
+            ArchiveStatus::OutdatedRequiresUpdate(None) => "Out of date.".to_string(),
+            ArchiveStatus::OutdatedRequiresUpdate(Some(changes)) => {
+                let described: Vec<String> = [
+                    ("added", &changes.added),
+                    ("removed", &changes.removed),
+                    ("modified", &changes.modified),
+                ]
+                .into_iter()
+                .filter(|(_, paths)| !paths.is_empty())
+                .map(|(kind, paths)| format!("{kind} {}", Self::summarize_paths(paths)))
+                .collect();
+                format!("Out of date: {}.", described.join("; "))
+            }
             ArchiveStatus::ArchiveFileMissing => "File missing.".to_string(),
             ArchiveStatus::NoFilesSelected => "No files selected.".to_string(),
             ArchiveStatus::ErrorChecking(Some(kind)) => {
@@ -363,6 +490,24 @@ impl MyAppLogic {
         }
     }
 
+    /* Names up to three files (by file name) and counts the rest. */
+    fn summarize_paths(paths: &[PathBuf]) -> String {
+        const MAX_NAMED_FILES: usize = 3;
+        let mut names: Vec<String> = paths
+            .iter()
+            .take(MAX_NAMED_FILES)
+            .map(|p| {
+                p.file_name()
+                    .map(|n| n.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| p.display().to_string())
+            })
+            .collect();
+        if paths.len() > MAX_NAMED_FILES {
+            names.push(format!("and {} more", paths.len() - MAX_NAMED_FILES));
+        }
+        names.join(", ")
+    }
+
     pub(crate) fn update_current_archive_status(&mut self) {
         log::debug!("AppLogic: update_current_archive_status called.");
         let ui_state_mut = match self.ui_state.as_mut() {
@@ -376,12 +521,13 @@ impl MyAppLogic {
         };
         let main_window_id = ui_state_mut.window_id();
 
-        let (current_profile_name_opt, archive_path_opt, snapshot_nodes_clone) = {
+        let (current_profile_name_opt, archive_path_opt, snapshot_nodes_clone, scan_root) = {
             let data = self.app_session_data_ops.lock().unwrap();
             (
                 data.get_profile_name(),
                 data.get_archive_path(),
                 data.get_snapshot_nodes().to_vec(),
+                data.get_root_path_for_scan(),
             )
         };
 
@@ -400,9 +546,20 @@ impl MyAppLogic {
             return;
         }
 
-        let status = self
-            .archiver
-            .check_status(archive_path_opt.as_deref(), &snapshot_nodes_clone);
+        let record_path = current_profile_name_opt
+            .as_deref()
+            .and_then(|name| self.archive_record_path(name));
+        let archive_path_opt = match (current_profile_name_opt.as_deref(), archive_path_opt) {
+            (Some(name), Some(archive_path)) => {
+                self.latest_archive_path(name, &archive_path, &scan_root)
+            }
+            _ => None,
+        };
+        let status = self.archiver.check_status(
+            archive_path_opt.as_deref(),
+            &snapshot_nodes_clone,
+            record_path.as_deref(),
+        );
         log::debug!(
             "AppLogic: Checked archive status for profile '{:?}', archive path '{:?}', status: {:?}",
             current_profile_name_opt,
@@ -410,8 +567,6 @@ impl MyAppLogic {
             status
         );
 
-        ui_state_mut.set_archive_status(Some(status));
-
         let plain_status_string = Self::archive_status_to_plain_string(&status);
         let archive_label_text = format!("Archive: {plain_status_string}");
 
@@ -419,6 +574,10 @@ impl MyAppLogic {
             ArchiveStatus::ErrorChecking(_) => MessageSeverity::Error,
             _ => MessageSeverity::Information,
         };
+        let Some(ui_state_mut) = self.ui_state.as_mut() else {
+            return;
+        };
+        ui_state_mut.set_archive_status(Some(status.clone()));
 
         self.synchronous_command_queue
             .push_back(PlatformCommand::UpdateLabelText {
@@ -502,9 +661,20 @@ impl MyAppLogic {
         total_files: usize,
         is_final: bool,
     ) {
+        let prompt_tokens = if is_final {
+            self.file_token_total = total_tokens;
+            self.active_prompt_token_count(total_tokens)
+        } else {
+            0
+        };
         if let Some(ui_state_ref) = &self.ui_state {
             let label_text = if !is_final && total_files > 0 {
                 format!("Tokens: {total_tokens} ({processed}/{total_files})")
+            } else if prompt_tokens > 0 {
+                format!(
+                    "Tokens: {} (incl. {prompt_tokens} prompt)",
+                    total_tokens + prompt_tokens
+                )
             } else {
                 format!("Tokens: {total_tokens}")
             };
@@ -519,6 +689,43 @@ impl MyAppLogic {
         }
     }
 
+    /*
+     * The tokens of the profile's active prompt variant, rendered for the selected files.
+     * They are part of every generated archive, so the displayed count includes them.
+     * [ArchiveGenPromptsV1]
+     */
+    fn active_prompt_token_count(&self, file_tokens: usize) -> usize {
+        let data = self.app_session_data_ops.lock().unwrap();
+        let prompt = match data.get_archive_options().prompts.compile_active() {
+            Ok(Some(prompt)) => prompt,
+            Ok(None) => return 0,
+            Err(e) => {
+                log::warn!("Prompt not counted: {e}");
+                return 0;
+            }
+        };
+        let root = data.get_root_path_for_scan();
+        let file_list = archiver::collect_selected_files(data.get_snapshot_nodes())
+            .into_iter()
+            .map(|node| {
+                node.path()
+                    .strip_prefix(&root)
+                    .unwrap_or(node.path())
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect();
+        let context = PromptContext::new(
+            &root,
+            &data.get_profile_name().unwrap_or_default(),
+            file_list,
+            file_tokens,
+        );
+        prompt
+            .render(&context)
+            .count_tokens(&*self.token_counter_manager)
+    }
+
     fn poll_token_recalc_progress(&mut self) {
         let mut driver_opt = self.token_recalc_driver.take();
         if let Some(mut driver) = driver_opt.take() {
@@ -612,6 +819,7 @@ impl MyAppLogic {
                 self.ui_state.as_ref().unwrap().window_id()
             );
             self.ui_state = None;
+            self.file_watcher = None;
         } else {
             log::debug!(
                 "AppLogic: Window (ID: {window_id:?}) destroyed, but it was not the main window tracked by ui_state."
@@ -789,7 +997,7 @@ impl MyAppLogic {
             if let Some(tree_item_id_to_update) = ui_state_ref.tree_item_id_for_path(&changed_path)
             {
                 let check_state_for_ui = match new_file_state {
-                    SelectionState::Selected => CheckState::Checked,
+                    SelectionState::Selected | SelectionState::Outline => CheckState::Checked,
                     _ => CheckState::Unchecked,
                 };
                 self.synchronous_command_queue.push_back(
@@ -952,33 +1160,44 @@ impl MyAppLogic {
             return;
         }
 
-        match fs::read(&path) {
-            Ok(raw_bytes) => match String::from_utf8(raw_bytes) {
-                Ok(content) => {
-                    log::debug!(
-                        "AppLogic: Loaded {} bytes for viewer from path {path:?}.",
-                        content.len()
-                    );
-                    let normalized = Self::normalize_viewer_content(&content);
-                    self.synchronous_command_queue
-                        .push_back(PlatformCommand::SetViewerContent {
-                            window_id,
-                            control_id: ui_constants::ID_VIEWER_EDIT_CTRL,
-                            text: normalized,
-                        });
-                }
-                Err(_) => {
-                    log::warn!(
-                        "AppLogic: File at {path:?} is not valid UTF-8; showing binary placeholder."
-                    );
-                    self.synchronous_command_queue
-                        .push_back(PlatformCommand::SetViewerContent {
-                            window_id,
-                            control_id: ui_constants::ID_VIEWER_EDIT_CTRL,
-                            text: "[Binary file preview unavailable]".to_string(),
-                        });
-                }
-            },
+        // The viewer classifies and decodes files the same way the archiver does.
+        // [ArchiveGenBinaryFilesV1] [FutureEncodingSupportOtherV1]
+        let reader = {
+            let data = self.app_session_data_ops.lock().unwrap();
+            let options = data.get_archive_options();
+            let encodings = EncodingRules::compile(&options.encodings).unwrap_or_else(|e| {
+                log::warn!("AppLogic: Ignoring encoding rules for the viewer: {e}");
+                EncodingRules::default()
+            });
+            ContentReader::new(
+                &data.get_root_path_for_scan(),
+                options.content_limits.max_file_size,
+                encodings,
+            )
+        };
+        match reader.read(&path) {
+            Ok(FileContent::Text(content)) => {
+                log::debug!(
+                    "AppLogic: Loaded {} bytes for viewer from path {path:?}.",
+                    content.len()
+                );
+                let normalized = Self::normalize_viewer_content(&content);
+                self.synchronous_command_queue
+                    .push_back(PlatformCommand::SetViewerContent {
+                        window_id,
+                        control_id: ui_constants::ID_VIEWER_EDIT_CTRL,
+                        text: normalized,
+                    });
+            }
+            Ok(FileContent::NotText { class, size }) => {
+                log::debug!("AppLogic: File at {path:?} is not shown as text ({class}).");
+                self.synchronous_command_queue
+                    .push_back(PlatformCommand::SetViewerContent {
+                        window_id,
+                        control_id: ui_constants::ID_VIEWER_EDIT_CTRL,
+                        text: content_class::placeholder(class, size),
+                    });
+            }
             Err(err) => {
                 log::warn!(
                     "AppLogic: Failed to read file content for viewer path {path:?}: {err:?}."
@@ -1025,17 +1244,23 @@ impl MyAppLogic {
             .filter(|s| !s.is_empty())
             .unwrap_or_else(|| path.to_string_lossy().into_owned());
 
-        let should_show_indicator = {
+        let (should_show_indicator, is_outline) = {
             let data_guard = self.app_session_data_ops.lock().unwrap();
             match data_guard.get_node_attributes_for_path(path) {
                 Some((state, is_dir)) => {
                     if is_dir {
-                        data_guard.does_path_or_descendants_contain_new_file(path)
+                        (
+                            data_guard.does_path_or_descendants_contain_new_file(path),
+                            false,
+                        )
                     } else {
-                        state == SelectionState::New
+                        (
+                            state == SelectionState::New,
+                            state == SelectionState::Outline,
+                        )
                     }
                 }
-                None => false,
+                None => (false, false),
             }
         };
 
@@ -1043,24 +1268,117 @@ impl MyAppLogic {
             display.push(' ');
             display.push(ui_constants::NEW_ITEM_INDICATOR_CHAR);
         }
+        if is_outline {
+            display.push(' ');
+            display.push(ui_constants::OUTLINE_ITEM_INDICATOR_CHAR);
+        }
 
         display
     }
 
+    /*
+     * Handles "Generate Archive". A profile with prompt variants is first asked which one
+     * to wrap the archive in; the choice becomes the profile's active variant.
+     * [ArchiveGenPromptsV1]
+     */
     fn _do_generate_archive(&mut self) {
-        if self.ui_state.is_none() {
+        let Some(window_id) = self.ui_state.as_ref().map(|s| s.window_id()) else {
             log::error!("Cannot generate archive: No UI state (main window).");
             return;
-        }
+        };
         log::debug!("'Generate Archive' (via menu or old button) triggered.");
 
-        let (current_profile_name_opt, archive_path_opt, snapshot_nodes_clone, root_path_clone) = {
+        let prompts = self
+            .app_session_data_ops
+            .lock()
+            .unwrap()
+            .get_archive_options()
+            .prompts;
+        if prompts.variants.is_empty() {
+            self._generate_archive_with_active_prompt();
+            return;
+        }
+        let default_text = prompts
+            .active_variant()
+            .map_or("none".to_string(), |variant| variant.name.clone());
+        self.synchronous_command_queue
+            .push_back(PlatformCommand::ShowInputDialog {
+                window_id,
+                title: "Archive Prompt".to_string(),
+                prompt: format!(
+                    "Prompt to wrap the archive in ({}), or 'none':",
+                    prompts.variant_names().join(", ")
+                ),
+                default_text: Some(default_text),
+                context_tag: Some(PROMPT_VARIANT_CONTEXT_TAG.to_string()),
+            });
+    }
+
+    fn _handle_input_dialog_for_prompt_variant(
+        &mut self,
+        window_id: WindowId,
+        variant_input_opt: Option<String>,
+    ) {
+        let Some(variant_text) = variant_input_opt else {
+            log::debug!("Prompt selection cancelled; no archive generated.");
+            return;
+        };
+
+        let mut options = self
+            .app_session_data_ops
+            .lock()
+            .unwrap()
+            .get_archive_options();
+        let trimmed = variant_text.trim();
+        let active = if trimmed.is_empty() || trimmed.eq_ignore_ascii_case("none") {
+            None
+        } else if let Some(variant) = options.prompts.variant(trimmed) {
+            Some(variant.name.clone())
+        } else {
+            app_warn!(self, "Unknown prompt '{}'.", trimmed);
+            self.synchronous_command_queue
+                .push_back(PlatformCommand::ShowInputDialog {
+                    window_id,
+                    title: "Archive Prompt".to_string(),
+                    prompt: format!(
+                        "Unknown prompt. Enter one of ({}), or 'none':",
+                        options.prompts.variant_names().join(", ")
+                    ),
+                    default_text: Some(variant_text),
+                    context_tag: Some(PROMPT_VARIANT_CONTEXT_TAG.to_string()),
+                });
+            return;
+        };
+
+        if options.prompts.active != active {
+            options.prompts.active = active;
+            if !self.save_archive_options_to_active_profile(options) {
+                return;
+            }
+            if self.token_recalc_driver.is_none() {
+                self.enqueue_token_label(self.file_token_total, 0, 0, true);
+            }
+        }
+        self._generate_archive_with_active_prompt();
+    }
+
+    fn _generate_archive_with_active_prompt(&mut self) {
+        let (
+            current_profile_name_opt,
+            archive_path_opt,
+            snapshot_nodes_clone,
+            root_path_clone,
+            archive_options,
+            token_cache,
+        ) = {
             let data = self.app_session_data_ops.lock().unwrap();
             (
                 data.get_profile_name(),
                 data.get_archive_path(),
                 data.get_snapshot_nodes().to_vec(),
                 data.get_root_path_for_scan(),
+                data.get_archive_options(),
+                data.get_cached_file_token_details(),
             )
         };
 
@@ -1079,27 +1397,88 @@ impl MyAppLogic {
                 return;
             }
         };
+        let profile_name = current_profile_name_opt.clone().unwrap_or_default();
+        let archive_path = match self.resolve_archive_path_for_generation(
+            &profile_name,
+            &archive_path,
+            &root_path_clone,
+        ) {
+            Ok(resolved) => resolved,
+            Err(e) => {
+                app_error!(self, "Cannot save archive: {e}.");
+                return;
+            }
+        };
 
-        match self
-            .archiver
-            .create_content(&snapshot_nodes_clone, &root_path_clone)
-        {
-            Ok(content) => match self.archiver.save(&archive_path, &content) {
-                Ok(_) => {
+        let mut request = ArchiveRequest::new(&snapshot_nodes_clone, &root_path_clone);
+        request.profile_name = profile_name;
+        request.options = archive_options;
+        request.token_cache = token_cache;
+        request.record_path = self.archive_record_path(&request.profile_name);
+        request.history_dir = self.archive_history_dir(&request.profile_name);
+
+        match self.archiver.write_archive_to_file(&request, &archive_path) {
+            Ok(report) => {
+                log::debug!(
+                    "Archive written: {} files, {} bytes, history generation {:?}.",
+                    report.files_written,
+                    report.bytes_written,
+                    report.history_generation
+                );
+                if report.part_paths.is_empty() {
                     app_info!(self, "Archive saved to '{}'.", archive_path.display());
-                    self.update_current_archive_status();
+                } else {
+                    app_info!(
+                        self,
+                        "Archive saved as {} parts next to '{}'.",
+                        report.part_paths.len(),
+                        archive_path.display()
+                    );
                 }
-                Err(e) => {
-                    app_error!(
+                if let Some(transform_report) = &report.transform {
+                    app_info!(
                         self,
-                        "Failed to save archive content to '{}': {}",
-                        archive_path.display(),
-                        e
+                        "Content transforms reduced the selection from {} to {} tokens.",
+                        transform_report.raw_tokens,
+                        transform_report.transformed_tokens
                     );
                 }
-            },
+                if let Some(budget_report) = &report.budget {
+                    self.report_token_budget_outcome(budget_report, &root_path_clone);
+                }
+                if let Some(secret_report) = &report.secrets {
+                    self.report_secret_findings(secret_report);
+                }
+                self.report_skipped_files(&report.skipped);
+                self.update_current_archive_status();
+            }
+            Err(ArchiveError::SecretsFound(findings)) => {
+                app_error!(
+                    self,
+                    "Archive not written: possible secrets found in {}. Remove them, allowlist them or change the secret policy.",
+                    Self::describe_secret_findings(&findings)
+                );
+            }
+            Err(e @ ArchiveError::UnsupportedContent { .. }) => {
+                app_error!(
+                    self,
+                    "Archive not written: {e}. Deselect the file or change the policy under 'Binary and Large Files...'."
+                );
+            }
+            Err(ArchiveError::Output(e)) => {
+                app_error!(
+                    self,
+                    "Failed to save archive content to '{}': {}",
+                    archive_path.display(),
+                    e
+                );
+            }
             Err(e) => {
-                if e.kind() == io::ErrorKind::NotFound {
+                let source_missing = matches!(
+                    &e,
+                    ArchiveError::ReadSource { source, .. } if source.kind() == io::ErrorKind::NotFound
+                );
+                if source_missing {
                     log::error!("Failed to create archive content: {e}");
                     if let Some(ui_state_ref) = &self.ui_state {
                         self.synchronous_command_queue
@@ -1117,958 +1496,1216 @@ impl MyAppLogic {
         }
     }
 
-    fn handle_button_clicked(&mut self, window_id: WindowId, control_id: ControlId) {
-        match control_id {
-            ui_constants::FILTER_EXPAND_BUTTON_ID => {
-                self.handle_expand_filtered_all_click(window_id);
+    /*
+     * Writes only the files added or modified since the last full generation, plus the
+     * paths deleted since, to a delta archive next to the profile's archive.
+     * The full archive and its record are left untouched. [ArchiveGenDeltaV1]
+     */
+    fn _do_generate_delta_archive(&mut self) {
+        if self.ui_state.is_none() {
+            log::error!("Cannot generate delta archive: No UI state (main window).");
+            return;
+        }
+
+        let (
+            current_profile_name_opt,
+            archive_path_opt,
+            snapshot_nodes_clone,
+            root_path_clone,
+            archive_options,
+            token_cache,
+        ) = {
+            let data = self.app_session_data_ops.lock().unwrap();
+            (
+                data.get_profile_name(),
+                data.get_archive_path(),
+                data.get_snapshot_nodes().to_vec(),
+                data.get_root_path_for_scan(),
+                data.get_archive_options(),
+                data.get_cached_file_token_details(),
+            )
+        };
+
+        let Some(profile_name) = current_profile_name_opt else {
+            app_error!(self, "No profile loaded. Cannot save delta archive.");
+            return;
+        };
+        let Some(archive_path) = archive_path_opt else {
+            app_error!(
+                self,
+                "No archive path set for current profile. Cannot save delta archive."
+            );
+            return;
+        };
+        let Some(archive_path) =
+            self.latest_archive_path(&profile_name, &archive_path, &root_path_clone)
+        else {
+            app_error!(
+                self,
+                "The archive path of the current profile could not be resolved. Cannot save delta archive."
+            );
+            return;
+        };
+
+        let delta_path = core::delta_file_path(&archive_path);
+        let mut request = ArchiveRequest::new(&snapshot_nodes_clone, &root_path_clone);
+        request.profile_name = profile_name;
+        request.options = archive_options;
+        request.token_cache = token_cache;
+        request.record_path = self.archive_record_path(&request.profile_name);
+
+        match self
+            .archiver
+            .write_delta_archive_to_file(&request, &archive_path, &delta_path)
+        {
+            Ok(report) => {
+                match report.delta.as_ref() {
+                    Some(delta)
+                        if delta.added.is_empty()
+                            && delta.modified.is_empty()
+                            && delta.deleted.is_empty() =>
+                    {
+                        app_info!(
+                            self,
+                            "No changes since the last archive. Empty delta saved to '{}'.",
+                            delta_path.display()
+                        );
+                    }
+                    Some(delta) => {
+                        app_info!(
+                            self,
+                            "Delta archive saved to '{}': {} added, {} modified, {} deleted.",
+                            delta_path.display(),
+                            delta.added.len(),
+                            delta.modified.len(),
+                            delta.deleted.len()
+                        );
+                    }
+                    None => {
+                        app_info!(self, "Delta archive saved to '{}'.", delta_path.display());
+                    }
+                }
+                if let Some(budget_report) = &report.budget {
+                    self.report_token_budget_outcome(budget_report, &root_path_clone);
+                }
+                if let Some(secret_report) = &report.secrets {
+                    self.report_secret_findings(secret_report);
+                }
+                self.report_skipped_files(&report.skipped);
             }
-            ui_constants::FILTER_CLEAR_BUTTON_ID => {
-                self.handle_filter_clear_requested(window_id);
+            Err(ArchiveError::SecretsFound(findings)) => {
+                app_error!(
+                    self,
+                    "Delta archive not written: possible secrets found in {}.",
+                    Self::describe_secret_findings(&findings)
+                );
             }
-            ui_constants::SEARCH_MODE_TOGGLE_BUTTON_ID => {
-                self.handle_search_mode_toggle_clicked(window_id);
+            Err(e @ ArchiveError::UnsupportedContent { .. }) => {
+                app_error!(self, "Delta archive not written: {e}.");
             }
-            _ => {
-                log::debug!(
-                    "ButtonClicked for unhandled control_id {} on window {window_id:?}",
-                    control_id.raw()
+            Err(ArchiveError::NoDeltaBaseline(_)) => {
+                app_warn!(
+                    self,
+                    "No record of the last archive exists. Generate the full archive first."
                 );
             }
+            Err(e) => {
+                app_error!(self, "Failed to create delta archive: {}", e);
+            }
+        }
+    }
+
+    /* The archive history of the active profile; warns and returns `None` without one. */
+    fn active_archive_history(&mut self, action_description: &str) -> Option<ArchiveHistory> {
+        self.require_active_project(action_description)?;
+        let profile_name_opt = self.app_session_data_ops.lock().unwrap().get_profile_name();
+        let history_dir = profile_name_opt.and_then(|name| self.archive_history_dir(&name));
+        if history_dir.is_none() {
+            app_warn!(self, "Cannot {action_description}: No profile is active.");
         }
+        history_dir.map(ArchiveHistory::new)
     }
 
     /*
-     * Handles clicks on the search mode toggle button, updating internal state
-     * and keeping the button label in sync with the active mode.
+     * Handles "Generate All Archives": regenerates the archive of every profile of the
+     * project on a background worker, each profile loaded apart from the active session.
+     * [ArchiveBatchGenerateV1]
      */
-    fn handle_search_mode_toggle_clicked(&mut self, window_id: WindowId) {
-        let new_mode = {
-            let ui_state = match self
-                .ui_state
-                .as_mut()
-                .filter(|state| state.window_id() == window_id)
-            {
-                Some(state) => state,
-                None => {
-                    log::warn!(
-                        "SearchModeToggleClicked received but no matching UI state for window {window_id:?}"
-                    );
-                    return;
-                }
-            };
-            ui_state.toggle_search_mode()
+    fn handle_menu_generate_all_archives_clicked(&mut self) {
+        if self.batch_generation_driver.is_some() {
+            app_warn!(
+                self,
+                "Archives are already being generated for all profiles."
+            );
+            return;
+        }
+        let Some(project_ctx) = self.require_active_project("generate all archives") else {
+            return;
         };
+        let profile_names = match self
+            .profile_manager
+            .list_profiles(&project_ctx, APP_NAME_FOR_PROFILES)
+        {
+            Ok(names) => names,
+            Err(e) => {
+                app_error!(self, "Failed to list profiles: {}", e);
+                return;
+            }
+        };
+        if profile_names.is_empty() {
+            app_info!(
+                self,
+                "The project has no profiles to generate archives for."
+            );
+            return;
+        }
 
-        log::debug!("Search mode toggled to {new_mode:?}");
-        let button_text = match new_mode {
-            SearchMode::ByName => "Name",
-            SearchMode::ByContent => "Content",
+        let services = BatchServices {
+            profile_manager: Arc::clone(&self.profile_manager),
+            file_system_scanner: Arc::clone(&self.file_system_scanner),
+            state_manager: Arc::clone(&self.state_manager),
+            archiver: Arc::clone(&self.archiver),
+            token_counter: Arc::clone(&self.token_counter_manager),
         };
-        self.synchronous_command_queue
-            .push_back(PlatformCommand::SetControlText {
-                window_id,
-                control_id: ui_constants::SEARCH_MODE_TOGGLE_BUTTON_ID,
-                text: button_text.to_string(),
-            });
+        let channel = batch_generate::generate_all_async(
+            project_ctx,
+            profile_names,
+            APP_NAME_FOR_PROFILES.to_string(),
+            services,
+        );
+        app_info!(
+            self,
+            "Generating archives for {} profile(s)...",
+            channel.total_profiles
+        );
+        self.batch_generation_driver = Some(BatchGenerationDriver {
+            receiver: Mutex::new(channel.receiver),
+            worker_handle: channel.worker_handle,
+        });
+    }
 
-        if matches!(new_mode, SearchMode::ByName) {
-            self.cancel_content_search();
-            if let Some(ui_state) = self.ui_state.as_mut() {
-                ui_state.set_content_search_matches(None);
+    /*
+     * Drains the batch generation channel: reports each finished profile, and once the
+     * summary arrives shows it in the viewer and refreshes the active profile's archive
+     * status, whose archive may have been rewritten.
+     */
+    fn poll_batch_generation_progress(&mut self) {
+        let Some(mut driver) = self.batch_generation_driver.take() else {
+            return;
+        };
+        let mut summary = None;
+        let mut disconnected = false;
+        loop {
+            let recv_result = driver
+                .receiver
+                .lock()
+                .expect("Batch generation receiver mutex poisoned")
+                .try_recv();
+            match recv_result {
+                Ok(BatchProgress::Profile {
+                    completed,
+                    total,
+                    result,
+                }) => {
+                    app_info!(self, "Archive {completed}/{total}: {}", result.describe());
+                }
+                Ok(BatchProgress::Finished(batch_summary)) => {
+                    summary = Some(batch_summary);
+                    break;
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    disconnected = true;
+                    break;
+                }
             }
         }
-    }
+        if summary.is_none() && !disconnected {
+            self.batch_generation_driver = Some(driver);
+            return;
+        }
 
-    fn handle_expand_filtered_all_click(&mut self, window_id: WindowId) {
-        let ui_state_ref = match self.ui_state.as_ref() {
-            Some(s) if s.window_id() == window_id => s,
-            _ => {
-                log::warn!(
-                    "ExpandFilteredAllClick received but no matching UI state for window {window_id:?}"
-                );
-                return;
-            }
+        if let Some(handle) = driver.worker_handle.take()
+            && let Err(err) = handle.join()
+        {
+            log::warn!("Batch generation worker join failed: {err:?}");
+        }
+        let Some(summary) = summary else {
+            app_error!(self, "Generating all archives stopped unexpectedly.");
+            return;
         };
-
-        if ui_state_ref.filter_text().is_some() {
-            log::debug!("Expanding visible tree items (filtered view)");
+        if let Some(window_id) = self.ui_state.as_ref().map(|s| s.window_id()) {
             self.synchronous_command_queue
-                .push_back(PlatformCommand::ExpandVisibleTreeItems {
+                .push_back(PlatformCommand::SetViewerContent {
                     window_id,
-                    control_id: ui_constants::ID_TREEVIEW_CTRL,
+                    control_id: ui_constants::ID_VIEWER_EDIT_CTRL,
+                    text: Self::normalize_viewer_content(&summary.render()),
                 });
+        }
+        if summary.failed_count() > 0 {
+            app_warn!(
+                self,
+                "Generated {} archive(s); {} failed and {} skipped. See the viewer for details.",
+                summary.written_count(),
+                summary.failed_count(),
+                summary.skipped_count()
+            );
         } else {
-            log::debug!("Expanding all tree items");
-            self.synchronous_command_queue
-                .push_back(PlatformCommand::ExpandAllTreeItems {
-                    window_id,
-                    control_id: ui_constants::ID_TREEVIEW_CTRL,
-                });
+            app_info!(
+                self,
+                "Generated {} archive(s); {} skipped without an archive path.",
+                summary.written_count(),
+                summary.skipped_count()
+            );
         }
+        self.update_current_archive_status();
     }
 
-    fn handle_menu_load_profile_clicked(&mut self) {
-        log::debug!(
-            "Menu action {:?} received by AppLogic, initiating profile selection flow.",
-            ui_constants::MENU_ACTION_LOAD_PROFILE
-        );
-        let window_id = match self.ui_state.as_ref().map(|s| s.window_id()) {
-            Some(id) => id,
-            None => {
-                log::warn!("Cannot handle LoadProfile: No UI state (main window).");
+    /*
+     * Handles the "Archive History..." menu action: lists the archives kept for the active
+     * profile in the viewer, then asks which one to restore, which two to compare, or how
+     * many to keep. [ArchiveHistoryV1]
+     */
+    fn handle_menu_show_archive_history_clicked(&mut self) {
+        let Some(window_id) = self.ui_state.as_ref().map(|s| s.window_id()) else {
+            log::warn!("Cannot show archive history: No UI state (main window).");
+            return;
+        };
+        let Some(history) = self.active_archive_history("show the archive history") else {
+            return;
+        };
+        let generations = match history.list() {
+            Ok(generations) => generations,
+            Err(e) => {
+                app_error!(self, "Failed to read the archive history: {}", e);
                 return;
             }
         };
+        let retention = self
+            .app_session_data_ops
+            .lock()
+            .unwrap()
+            .get_archive_options()
+            .history
+            .retention;
 
-        // Reuse the exact same function that the startup sequence uses.
-        self.initiate_profile_selection_or_creation(window_id);
+        let listing = if generations.is_empty() {
+            "No archives have been generated since the history was enabled.\n".to_string()
+        } else {
+            archive_history::render_generations(&generations)
+        };
+        self.synchronous_command_queue
+            .push_back(PlatformCommand::SetViewerContent {
+                window_id,
+                control_id: ui_constants::ID_VIEWER_EDIT_CTRL,
+                text: Self::normalize_viewer_content(&listing),
+            });
+        self.synchronous_command_queue
+            .push_back(PlatformCommand::ShowInputDialog {
+                window_id,
+                title: "Archive History".to_string(),
+                prompt: format!(
+                    "Enter a generation to restore (e.g. '3'), two to compare (e.g. '2 5'), or 'keep N' to keep the last N archives (now {retention}, 0 turns the history off):"
+                ),
+                default_text: Some(format!("keep {retention}")),
+                context_tag: Some(ARCHIVE_HISTORY_CONTEXT_TAG.to_string()),
+            });
     }
 
-    fn handle_menu_open_folder_clicked(&mut self) {
-        log::debug!(
-            "Menu action {:?} received by AppLogic.",
-            ui_constants::MENU_ACTION_OPEN_FOLDER
-        );
-        let ui_state_mut = match self.ui_state.as_mut() {
-            Some(s) => s,
-            None => {
-                log::warn!("Cannot handle OpenFolder: No UI state (main window).");
+    fn _handle_input_dialog_for_archive_history(
+        &mut self,
+        window_id: WindowId,
+        command_input_opt: Option<String>,
+    ) {
+        let Some(command_text) = command_input_opt else {
+            log::debug!("Archive history input cancelled.");
+            return;
+        };
+        let command = match archive_history::parse_command(&command_text) {
+            Ok(command) => command,
+            Err(message) => {
+                app_warn!(self, "{}.", message);
+                self.synchronous_command_queue
+                    .push_back(PlatformCommand::ShowInputDialog {
+                        window_id,
+                        title: "Archive History".to_string(),
+                        prompt:
+                            "Invalid command. Enter '3' to restore, '2 5' to compare or 'keep 10':"
+                                .to_string(),
+                        default_text: Some(command_text),
+                        context_tag: Some(ARCHIVE_HISTORY_CONTEXT_TAG.to_string()),
+                    });
                 return;
             }
         };
 
-        let window_id = ui_state_mut.window_id();
-        let _ = ui_state_mut;
-        self.start_project_folder_prompt(window_id);
+        match command {
+            HistoryCommand::Keep(retention) => {
+                let mut options = self
+                    .app_session_data_ops
+                    .lock()
+                    .unwrap()
+                    .get_archive_options();
+                options.history.retention = retention;
+                if self.save_archive_options_to_active_profile(options) {
+                    if retention == 0 {
+                        app_info!(self, "Archive history turned off.");
+                    } else {
+                        app_info!(
+                            self,
+                            "Archive history keeps the last {} archive(s).",
+                            retention
+                        );
+                    }
+                }
+            }
+            HistoryCommand::Diff(from, to) => {
+                let Some(history) = self.active_archive_history("compare archives") else {
+                    return;
+                };
+                match history.diff(from, to) {
+                    Ok(changes) => {
+                        let root_path = self
+                            .app_session_data_ops
+                            .lock()
+                            .unwrap()
+                            .get_root_path_for_scan();
+                        self.synchronous_command_queue.push_back(
+                            PlatformCommand::SetViewerContent {
+                                window_id,
+                                control_id: ui_constants::ID_VIEWER_EDIT_CTRL,
+                                text: Self::normalize_viewer_content(
+                                    &archive_history::render_changes(
+                                        from, to, &changes, &root_path,
+                                    ),
+                                ),
+                            },
+                        );
+                        app_info!(
+                            self,
+                            "Generation #{} to #{}: {} added, {} modified, {} removed.",
+                            from,
+                            to,
+                            changes.added.len(),
+                            changes.modified.len(),
+                            changes.removed.len()
+                        );
+                    }
+                    Err(e) => app_error!(self, "Cannot compare archives: {}", e),
+                }
+            }
+            HistoryCommand::Restore(id) => {
+                let Some(history) = self.active_archive_history("restore an archive") else {
+                    return;
+                };
+                let profile_name_opt = self.app_session_data_ops.lock().unwrap().get_profile_name();
+                let record_path = profile_name_opt.and_then(|name| self.archive_record_path(&name));
+                match history.restore(id, record_path.as_deref()) {
+                    Ok(generation) => {
+                        app_info!(
+                            self,
+                            "Restored archive #{} of {} to '{}'.",
+                            generation.id,
+                            generation.created,
+                            generation.archive_path.display()
+                        );
+                        self.update_current_archive_status();
+                    }
+                    Err(e) => app_error!(self, "Cannot restore archive #{}: {}", id, e),
+                }
+            }
+        }
     }
 
     /*
-     * Starts the first step of the profile creation sequence when the user
-     * chooses "File/New Profile".
-     *
-     * An active main window is required so the dialogs for entering the profile
-     * name and selecting the root folder can be displayed.
+     * Handles the "Apply Edited Archive..." menu action by asking for the archive file
+     * that came back from the LLM. [ArchiveApplyEditedV1]
      */
-    fn handle_menu_new_profile_clicked(&mut self) {
-        let window_id = match self.ui_state.as_ref().map(|s| s.window_id()) {
-            Some(id) => id,
-            None => {
-                log::warn!("Cannot handle NewProfile: No UI state (main window).");
+    fn handle_menu_apply_edited_archive_clicked(&mut self) {
+        let Some(project_ctx) = self.require_active_project("apply an edited archive") else {
+            return;
+        };
+        let (profile_name_opt, archive_path_opt, scan_root) = {
+            let data = self.app_session_data_ops.lock().unwrap();
+            (
+                data.get_profile_name(),
+                data.get_archive_path(),
+                data.get_root_path_for_scan(),
+            )
+        };
+        let initial_dir = profile_name_opt
+            .zip(archive_path_opt)
+            .and_then(|(name, ap)| self.latest_archive_path(&name, &ap, &scan_root))
+            .and_then(|ap| ap.parent().map(PathBuf::from))
+            .or_else(|| Some(project_ctx.root_path().to_path_buf()));
+        let Some(ui_state_mut) = self.ui_state.as_mut() else {
+            log::warn!("Cannot apply edited archive: No UI state (main window).");
+            return;
+        };
+        ui_state_mut.set_pending_action(Some(PendingAction::ApplyingEditedArchive));
+        self.synchronous_command_queue
+            .push_back(PlatformCommand::ShowOpenFileDialog {
+                window_id: ui_state_mut.window_id(),
+                title: "Apply Edited Archive".to_string(),
+                filter_spec: "Text Files (*.txt)\0*.txt\0All Files (*.*)\0*.*\0\0".to_string(),
+                initial_dir,
+            });
+    }
+
+    /*
+     * Parses the chosen archive and shows the preview (status and unified diff of every
+     * file) in the viewer, then asks which files to write. Files changed on disk since the
+     * archive was generated are detected with the active profile's content record.
+     */
+    fn _handle_file_open_dialog_for_edited_archive(
+        &mut self,
+        window_id: WindowId,
+        result: Option<PathBuf>,
+    ) {
+        self.pending_apply_preview = None;
+        let Some(archive_file) = result else {
+            log::debug!("Apply edited archive cancelled.");
+            return;
+        };
+        let Some(project_ctx) = self.require_active_project("apply an edited archive") else {
+            return;
+        };
+        let archive_text = match fs::read_to_string(&archive_file) {
+            Ok(text) => text,
+            Err(e) => {
+                app_error!(self, "Failed to read '{}': {}", archive_file.display(), e);
                 return;
             }
         };
 
-        if self.active_project.is_none() {
-            app_warn!(self, "Cannot create a profile: No project folder is open.");
-            return;
+        let profile_name_opt = self.app_session_data_ops.lock().unwrap().get_profile_name();
+        let baseline = profile_name_opt
+            .and_then(|name| self.archive_record_path(&name))
+            .and_then(|record_path| ArchiveRecord::load(&record_path).ok());
+        if baseline.is_none() {
+            app_warn!(
+                self,
+                "No record of the generated archive exists, so changes made on disk since then cannot be detected."
+            );
         }
 
-        log::debug!(
-            "Menu action {:?} received by AppLogic.",
-            ui_constants::MENU_ACTION_NEW_PROFILE
-        );
-        self.start_new_profile_creation_flow(window_id);
-    }
+        // Files are decoded and written back the way the archiver reads them.
+        let options = self
+            .app_session_data_ops
+            .lock()
+            .unwrap()
+            .get_archive_options();
+        let encodings = EncodingRules::compile(&options.encodings).unwrap_or_else(|e| {
+            log::warn!("AppLogic: Ignoring encoding rules for applying an archive: {e}");
+            EncodingRules::default()
+        });
+        let preview =
+            match ApplyPreview::build(&project_ctx, &archive_text, baseline.as_ref(), &encodings) {
+                Ok(preview) => preview,
+                Err(e) => {
+                    app_error!(self, "Cannot apply '{}': {}", archive_file.display(), e);
+                    return;
+                }
+            };
+        self.synchronous_command_queue
+            .push_back(PlatformCommand::SetViewerContent {
+                window_id,
+                control_id: ui_constants::ID_VIEWER_EDIT_CTRL,
+                text: Self::normalize_viewer_content(&preview.render_text()),
+            });
 
-    fn handle_file_open_dialog_completed(&mut self, window_id: WindowId, result: Option<PathBuf>) {
-        if !self
-            .ui_state
-            .as_ref()
-            .is_some_and(|s| s.window_id() == window_id)
-        {
-            log::warn!(
-                "FileOpenProfileDialogCompleted for non-matching or non-existent UI state. Window ID: {window_id:?}. Ignoring."
+        let applicable = preview.applicable_indices().len();
+        if applicable == 0 {
+            app_info!(
+                self,
+                "None of the {} file(s) in the archive can be written; see the viewer.",
+                preview.files.len()
             );
             return;
         }
+        self.pending_apply_preview = Some(preview);
+        self.synchronous_command_queue
+            .push_back(PlatformCommand::ShowInputDialog {
+                window_id,
+                title: "Apply Edited Archive".to_string(),
+                prompt: format!(
+                    "{applicable} file(s) are new or changed (see the viewer). Enter 'all' or the numbers of the files to write, e.g. '1, 3':"
+                ),
+                default_text: Some("all".to_string()),
+                context_tag: Some(APPLY_EDITED_ARCHIVE_CONTEXT_TAG.to_string()),
+            });
+    }
 
-        let profile_file_path = match result {
-            Some(pfp) => pfp,
-            None => {
-                log::debug!("Load profile cancelled.");
+    fn _handle_input_dialog_for_apply_edited_archive(
+        &mut self,
+        window_id: WindowId,
+        selection_input_opt: Option<String>,
+    ) {
+        let Some(preview) = self.pending_apply_preview.take() else {
+            log::warn!("Apply edited archive input received without a pending preview.");
+            return;
+        };
+        let Some(selection_text) = selection_input_opt else {
+            app_info!(self, "Applying the edited archive was cancelled.");
+            return;
+        };
+
+        let accepted: Result<Vec<usize>, String> =
+            if selection_text.trim().eq_ignore_ascii_case("all") {
+                Ok(preview.applicable_indices())
+            } else {
+                selection_text
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|part| !part.is_empty())
+                    .map(|part| match part.parse::<usize>() {
+                        Ok(number) if (1..=preview.files.len()).contains(&number) => Ok(number - 1),
+                        _ => Err(part.to_string()),
+                    })
+                    .collect()
+            };
+        let accepted = match accepted {
+            Ok(accepted) => accepted,
+            Err(bad) => {
+                app_warn!(self, "'{}' is not a file number from the preview.", bad);
+                self.pending_apply_preview = Some(preview);
+                self.synchronous_command_queue
+                    .push_back(PlatformCommand::ShowInputDialog {
+                        window_id,
+                        title: "Apply Edited Archive".to_string(),
+                        prompt: "Invalid selection. Enter 'all' or file numbers, e.g. '1, 3':"
+                            .to_string(),
+                        default_text: Some(selection_text),
+                        context_tag: Some(APPLY_EDITED_ARCHIVE_CONTEXT_TAG.to_string()),
+                    });
                 return;
             }
         };
 
-        let Some(project_ctx) = self.require_active_project("load a profile from disk") else {
+        let report = preview.apply(&accepted);
+        self.report_apply_outcome(&report);
+        let profile_active = self
+            .app_session_data_ops
+            .lock()
+            .unwrap()
+            .get_profile_name()
+            .is_some();
+        if profile_active && !(report.written.is_empty() && report.created.is_empty()) {
+            self.handle_menu_refresh_file_list_clicked();
+        }
+    }
+
+    /*
+     * Handles the "Apply Patch..." menu action by asking for a text file (typically a saved
+     * model answer) that contains unified diffs. [ArchiveApplyPatchV1]
+     */
+    fn handle_menu_apply_patch_clicked(&mut self) {
+        let Some(project_ctx) = self.require_active_project("apply a patch") else {
             return;
         };
-
-        log::debug!("Profile selected for load: {profile_file_path:?}");
-        match self
-            .profile_manager
-            .load_profile_from_path(&profile_file_path)
-        {
-            Ok(loaded_profile) => {
-                let profile_name_clone = loaded_profile.name.clone();
-                log::debug!(
-                    "Successfully loaded profile '{profile_name_clone}' via manager from path."
-                );
-
-                self.persist_last_project_path(&project_ctx);
-                if let Ok(pn) = ProfileName::new(&profile_name_clone) {
-                    self.persist_last_profile_for_project(&project_ctx, &pn);
-                }
-                let status_msg = format!("Profile '{profile_name_clone}' loaded and scanned.");
-                self._activate_profile_and_show_window(window_id, loaded_profile, status_msg);
-            }
-            Err(e) => {
-                app_error!(
-                    self,
-                    "Failed to load profile from {:?} via manager: {:?}",
-                    profile_file_path,
-                    e
-                );
-                self.app_session_data_ops.lock().unwrap().clear();
-
-                if let Some(ui_state_mut) = self.ui_state.as_mut() {
-                    ui_state_mut.set_archive_status(None);
-                }
-            }
-        }
+        let Some(ui_state_mut) = self.ui_state.as_mut() else {
+            log::warn!("Cannot apply patch: No UI state (main window).");
+            return;
+        };
+        ui_state_mut.set_pending_action(Some(PendingAction::ApplyingPatch));
+        self.synchronous_command_queue
+            .push_back(PlatformCommand::ShowOpenFileDialog {
+                window_id: ui_state_mut.window_id(),
+                title: "Apply Patch".to_string(),
+                filter_spec: "Patch and Text Files (*.patch;*.diff;*.txt;*.md)\0*.patch;*.diff;*.txt;*.md\0All Files (*.*)\0*.*\0\0".to_string(),
+                initial_dir: Some(project_ctx.root_path().to_path_buf()),
+            });
     }
 
-    fn handle_menu_save_profile_as_clicked(&mut self) {
-        log::debug!(
-            "Menu action {:?} received by AppLogic.",
-            ui_constants::MENU_ACTION_SAVE_PROFILE_AS
-        );
-        let Some(project_ctx) = self.require_active_project("save the profile") else {
+    /*
+     * Applies the diffs in the chosen file to the scanned snapshot in memory and shows the
+     * dry run (per-hunk report and resulting content) in the viewer. Writing waits for the
+     * user's confirmation.
+     */
+    fn _handle_file_open_dialog_for_patch(&mut self, window_id: WindowId, result: Option<PathBuf>) {
+        self.pending_patch_plan = None;
+        let Some(patch_file) = result else {
+            log::debug!("Apply patch cancelled.");
             return;
         };
-
-        let ui_state_mut = match self.ui_state.as_mut() {
-            Some(s) => s,
-            None => {
-                log::warn!("Cannot handle SaveProfileAs: No UI state (main window).");
+        let Some(project_ctx) = self.require_active_project("apply a patch") else {
+            return;
+        };
+        let patch_text = match fs::read_to_string(&patch_file) {
+            Ok(text) => text,
+            Err(e) => {
+                app_error!(self, "Failed to read '{}': {}", patch_file.display(), e);
                 return;
             }
         };
-
-        let profile_dir_opt = self
-            .profile_manager
-            .get_profile_dir_path(&project_ctx, APP_NAME_FOR_PROFILES);
-        let base_name = self
+        let snapshot_nodes = self
             .app_session_data_ops
             .lock()
             .unwrap()
-            .get_profile_name()
-            .map_or_else(|| "new_profile".to_string(), |name| name.clone());
-        let sanitized_current_name = core::profiles::sanitize_profile_name(&base_name);
-        let default_filename = format!("{sanitized_current_name}.json");
+            .get_snapshot_nodes()
+            .clone();
+        let plan = PatchPlan::build(&project_ctx, &snapshot_nodes, &patch_text);
+        if plan.files.is_empty() {
+            app_warn!(
+                self,
+                "No unified diff was found in '{}'.",
+                patch_file.display()
+            );
+            return;
+        }
+        self.synchronous_command_queue
+            .push_back(PlatformCommand::SetViewerContent {
+                window_id,
+                control_id: ui_constants::ID_VIEWER_EDIT_CTRL,
+                text: Self::normalize_viewer_content(&plan.render_dry_run()),
+            });
 
-        ui_state_mut.set_pending_action(Some(PendingAction::SavingProfileAs));
+        let applicable = plan.applicable_count();
+        let not_applicable = plan.files.len() - applicable;
+        if applicable == 0 {
+            app_warn!(
+                self,
+                "None of the {} patched file(s) can be written; see the viewer.",
+                plan.files.len()
+            );
+            return;
+        }
+        self.pending_patch_plan = Some(plan);
         self.synchronous_command_queue
-            .push_back(PlatformCommand::ShowSaveFileDialog {
-                window_id: ui_state_mut.window_id(),
-                title: "Save Profile As".to_string(),
-                default_filename,
-                filter_spec: "Profile Files (*.json)\0*.json\0\0".to_string(),
-                initial_dir: profile_dir_opt,
+            .push_back(PlatformCommand::ShowInputDialog {
+                window_id,
+                title: "Apply Patch".to_string(),
+                prompt: format!(
+                    "{applicable} file(s) patched cleanly and {not_applicable} will be skipped (see the dry run in the viewer). Enter 'apply' to write them:"
+                ),
+                default_text: Some("apply".to_string()),
+                context_tag: Some(APPLY_PATCH_CONTEXT_TAG.to_string()),
             });
     }
 
-    /*
-     * Handles the outcome of a file save dialog. The behavior depends on the
-     * `pending_action` that was active when the dialog was initiated.
-     * This function delegates to specific handlers based on that action.
-     */
-    fn handle_file_save_dialog_completed(&mut self, window_id: WindowId, result: Option<PathBuf>) {
-        let ui_state_mut = match self
-            .ui_state
-            .as_mut()
-            .filter(|s| s.window_id() == window_id)
-        {
-            Some(s) => s,
-            None => {
-                log::warn!(
-                    "FileSaveDialogCompleted received for an unknown or non-main window (ID: {window_id:?}). Ignoring event."
-                );
-                return;
-            }
+    fn _handle_input_dialog_for_apply_patch(&mut self, confirmation_opt: Option<String>) {
+        let Some(plan) = self.pending_patch_plan.take() else {
+            log::warn!("Apply patch input received without a pending dry run.");
+            return;
         };
+        if !confirmation_opt.is_some_and(|text| text.trim().eq_ignore_ascii_case("apply")) {
+            app_info!(self, "The patch was not applied.");
+            return;
+        }
+        let report = plan.write();
+        self.report_patch_outcome(&report);
+        let profile_active = self
+            .app_session_data_ops
+            .lock()
+            .unwrap()
+            .get_profile_name()
+            .is_some();
+        let changed = report.written.len() + report.created.len() + report.deleted.len();
+        if profile_active && changed > 0 {
+            self.handle_menu_refresh_file_list_clicked();
+        }
+    }
 
-        let action = ui_state_mut.take_pending_action();
-        log::debug!(
-            "FileSaveDialogCompleted with pending action: {action:?}, for result: {result:?}"
+    fn report_patch_outcome(&mut self, report: &PatchWriteReport) {
+        app_info!(
+            self,
+            "Applied patch: {} file(s) updated, {} created, {} deleted.",
+            report.written.len(),
+            report.created.len(),
+            report.deleted.len()
         );
+        if !report.skipped.is_empty() {
+            app_warn!(
+                self,
+                "Skipped because a hunk failed or the path is not allowed: {}.",
+                report.skipped.join(", ")
+            );
+        }
+        if !report.refused.is_empty() {
+            app_warn!(
+                self,
+                "Not written because they changed on disk after the dry run: {}.",
+                report.refused.join(", ")
+            );
+        }
+        for (path, message) in &report.failed {
+            app_error!(self, "Failed to write '{}': {}", path, message);
+        }
+    }
 
-        match action {
-            Some(PendingAction::SettingArchivePath) => {
-                self._handle_file_save_dialog_for_setting_archive_path(window_id, result);
-            }
-            Some(PendingAction::SavingProfileAs) => {
-                self._handle_file_save_dialog_for_saving_profile_as(window_id, result);
-            }
-            Some(PendingAction::CreatingNewProfileGetName)
-            | Some(PendingAction::CreatingNewProfileGetRoot)
-            | Some(PendingAction::OpeningProjectFolder) => {
-                app_error!(
-                    self,
-                    "FileSaveDialogCompleted received, but was expecting dialog for {:?}. This is a logic error.",
-                    action
-                );
-            }
-            None => {
-                app_warn!(
-                    self,
-                    "FileSaveDialogCompleted received, but no pending action was set. Ignoring."
-                );
-            }
+    fn report_apply_outcome(&mut self, report: &ApplyReport) {
+        app_info!(
+            self,
+            "Applied edited archive: {} file(s) updated, {} created.",
+            report.written.len(),
+            report.created.len()
+        );
+        if !report.refused.is_empty() {
+            app_warn!(
+                self,
+                "Not written because they changed on disk since the archive was generated: {}.",
+                report.refused.join(", ")
+            );
+        }
+        for (path, message) in &report.failed {
+            app_error!(self, "Failed to write '{}': {}", path, message);
         }
     }
 
     /*
-     * Handles the outcome of a file save dialog initiated for setting a profile's archive path.
-     * If a path is selected, it updates the current profile's archive path in the
-     * application session data, saves the profile modifications, and refreshes relevant UI elements
-     * like the window title and archive status indicators.
+     * Tells the user exactly which files the token budget left out or truncated.
+     * Paths are shown relative to the project root.
      */
-    fn _handle_file_save_dialog_for_setting_archive_path(
-        &mut self,
-        window_id: WindowId,
-        result: Option<PathBuf>,
-    ) {
-        let path = match result {
-            Some(p) => p,
-            None => {
-                return;
-            }
-        };
-
-        log::debug!("User selected archive path: {path:?}");
-
-        let profile_to_save_opt = {
-            let mut profile_runtime_data = self.app_session_data_ops.lock().unwrap();
-            if profile_runtime_data.get_profile_name().is_none() {
-                app_error!(self, "No profile is active. Cannot set archive path.");
-                return;
-            }
-            profile_runtime_data.set_archive_path(Some(path.clone()));
-            Some(profile_runtime_data.create_profile_snapshot())
-        };
+    fn report_token_budget_outcome(&mut self, budget_report: &TokenBudgetReport, root: &Path) {
+        if !budget_report.omitted.is_empty() {
+            let omitted_list: Vec<String> = budget_report
+                .omitted
+                .iter()
+                .map(|omitted| {
+                    let mut shown = omitted.clone();
+                    if let Ok(relative) = omitted.path.strip_prefix(root) {
+                        shown.path = relative.to_path_buf();
+                    }
+                    shown.to_string()
+                })
+                .collect();
+            app_warn!(
+                self,
+                "Token budget of {} exceeded by the selection ({} tokens). Left out: {}.",
+                budget_report.budget,
+                budget_report.tokens_before,
+                omitted_list.join("; ")
+            );
+        }
+        if budget_report.is_over_budget() {
+            app_warn!(
+                self,
+                "Pinned files alone need {} tokens, more than the budget of {}.",
+                budget_report.tokens_after,
+                budget_report.budget
+            );
+        }
+    }
 
-        let profile_to_save = match profile_to_save_opt {
-            Some(p) => p,
-            None => {
-                log::error!(
-                    "_handle_file_save_dialog_for_setting_archive_path: profile_to_save was unexpectedly None despite an active profile check."
-                );
-                return;
-            }
-        };
+    /* Lists findings as "path:line (rule)"; the secret itself is never shown. */
+    fn describe_secret_findings(findings: &[SecretFinding]) -> String {
+        findings
+            .iter()
+            .map(SecretFinding::to_string)
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
 
-        let Some(project_ctx) = self.require_active_project("save the profile") else {
+    /* Tells the user where the archive may contain secrets. [ArchiveGenSecretScanV1] */
+    fn report_secret_findings(&mut self, secret_report: &SecretScanReport) {
+        if secret_report.findings.is_empty() {
             return;
+        }
+        let described = Self::describe_secret_findings(&secret_report.findings);
+        if secret_report.policy == SecretPolicy::Redact {
+            app_warn!(
+                self,
+                "Redacted {} possible secret(s): {}.",
+                secret_report.findings.len(),
+                described
+            );
+        } else {
+            app_warn!(
+                self,
+                "Possible secrets in the archive: {}. Review it before sharing.",
+                described
+            );
+        }
+    }
+
+    /* Lists the binary and oversize files left out of the archive or shown as placeholders. */
+    fn report_skipped_files(&mut self, skipped: &[SkippedFile]) {
+        let describe = |policy: ContentPolicy| {
+            skipped
+                .iter()
+                .filter(|file| file.policy == policy)
+                .map(SkippedFile::to_string)
+                .collect::<Vec<_>>()
         };
+        let left_out = describe(ContentPolicy::Skip);
+        if !left_out.is_empty() {
+            app_warn!(
+                self,
+                "Skipped {} binary or oversize file(s): {}.",
+                left_out.len(),
+                left_out.join("; ")
+            );
+        }
+        let replaced = describe(ContentPolicy::Placeholder);
+        if !replaced.is_empty() {
+            app_warn!(
+                self,
+                "Included {} binary or oversize file(s) as placeholders: {}.",
+                replaced.len(),
+                replaced.join("; ")
+            );
+        }
+    }
 
-        match self.profile_manager.save_profile(
-            &project_ctx,
-            &profile_to_save,
-            APP_NAME_FOR_PROFILES,
-        ) {
-            Ok(_) => {
-                app_info!(
-                    self,
-                    "Archive path set to '{}' for profile '{}' and profile saved.",
-                    path.display(),
-                    profile_to_save.name
-                );
-                self._update_window_title_with_profile_and_archive(window_id);
-                self.update_current_archive_status();
+    fn handle_button_clicked(&mut self, window_id: WindowId, control_id: ControlId) {
+        match control_id {
+            ui_constants::FILTER_EXPAND_BUTTON_ID => {
+                self.handle_expand_filtered_all_click(window_id);
             }
-            Err(e) => {
-                app_error!(
-                    self,
-                    "Failed to save profile '{}' after setting archive path: {}",
-                    profile_to_save.name,
-                    e
+            ui_constants::FILTER_CLEAR_BUTTON_ID => {
+                self.handle_filter_clear_requested(window_id);
+            }
+            ui_constants::SEARCH_MODE_TOGGLE_BUTTON_ID => {
+                self.handle_search_mode_toggle_clicked(window_id);
+            }
+            _ => {
+                log::debug!(
+                    "ButtonClicked for unhandled control_id {} on window {window_id:?}",
+                    control_id.raw()
                 );
             }
         }
     }
 
-    fn make_profile_name(path: Option<PathBuf>) -> Result<String, String> {
-        let profile_save_path =
-            path.ok_or_else(|| "User cancelled the 'Save Profile As' dialog.".to_string())?;
-
-        log::debug!("User selected path for 'Save Profile As': {profile_save_path:?}");
-
-        let profile_name_osstr = profile_save_path
-            .file_stem()
-            .ok_or_else(|| "Could not extract profile name from save path.".to_string())?;
+    /*
+     * Handles clicks on the search mode toggle button, updating internal state
+     * and keeping the button label in sync with the active mode.
+     */
+    fn handle_search_mode_toggle_clicked(&mut self, window_id: WindowId) {
+        let new_mode = {
+            let ui_state = match self
+                .ui_state
+                .as_mut()
+                .filter(|state| state.window_id() == window_id)
+            {
+                Some(state) => state,
+                None => {
+                    log::warn!(
+                        "SearchModeToggleClicked received but no matching UI state for window {window_id:?}"
+                    );
+                    return;
+                }
+            };
+            ui_state.toggle_search_mode()
+        };
 
-        let profile_name_str = profile_name_osstr
-            .to_str()
-            .ok_or_else(|| "Profile save filename stem not valid UTF-8.".to_string())?
-            .to_string();
+        log::debug!("Search mode toggled to {new_mode:?}");
+        let button_text = match new_mode {
+            SearchMode::ByName => "Name",
+            SearchMode::ByContent => "Content",
+        };
+        self.synchronous_command_queue
+            .push_back(PlatformCommand::SetControlText {
+                window_id,
+                control_id: ui_constants::SEARCH_MODE_TOGGLE_BUTTON_ID,
+                text: button_text.to_string(),
+            });
 
-        if profile_name_str.trim().is_empty()
-            || !profile_name_str
-                .chars()
-                .all(core::profiles::is_valid_profile_name_char)
-        {
-            return Err(format!(
-                "Invalid profile name extracted from path: '{profile_name_str}'"
-            ));
+        if matches!(new_mode, SearchMode::ByName) {
+            self.cancel_content_search();
+            if let Some(ui_state) = self.ui_state.as_mut() {
+                ui_state.set_content_search_matches(None);
+            }
         }
-
-        Ok(profile_name_str)
     }
 
-    /*
-     * Handles the outcome of a file save dialog initiated for saving the current profile under a new name or path.
-     * If a path is selected, it extracts the new profile name, updates the application session data
-     * to reflect this new profile (name, path, etc.), saves it through the profile manager,
-     * and refreshes relevant UI elements.
-     */
-    fn _handle_file_save_dialog_for_saving_profile_as(
-        &mut self,
-        window_id: WindowId,
-        result: Option<PathBuf>,
-    ) {
-        let profile_name_str = match Self::make_profile_name(result) {
-            Ok(pfn) => pfn,
-            Err(e) => {
-                app_error!(self, "{}", e);
+    fn handle_expand_filtered_all_click(&mut self, window_id: WindowId) {
+        let ui_state_ref = match self.ui_state.as_ref() {
+            Some(s) if s.window_id() == window_id => s,
+            _ => {
+                log::warn!(
+                    "ExpandFilteredAllClick received but no matching UI state for window {window_id:?}"
+                );
                 return;
             }
         };
 
-        let profile = {
-            let mut profile_runtime_data = self.app_session_data_ops.lock().unwrap();
-            profile_runtime_data.set_profile_name(Some(profile_name_str));
-            profile_runtime_data.set_archive_path(None);
-            profile_runtime_data.create_profile_snapshot()
-        };
-        let Some(project_ctx) = self.require_active_project("save the profile") else {
-            return;
-        };
-        if let Err(e) =
-            self.profile_manager
-                .save_profile(&project_ctx, &profile, APP_NAME_FOR_PROFILES)
-        {
-            app_error!(
-                self,
-                "Failed to save profile '{}' in 'Save Profile As': {}",
-                profile.name,
-                e
-            );
-            // If save fails, should we revert profile_name in app_session_data_ops?
-            // Current logic does not. For now, matching existing behavior.
+        if ui_state_ref.filter_text().is_some() {
+            log::debug!("Expanding visible tree items (filtered view)");
+            self.synchronous_command_queue
+                .push_back(PlatformCommand::ExpandVisibleTreeItems {
+                    window_id,
+                    control_id: ui_constants::ID_TREEVIEW_CTRL,
+                });
         } else {
-            // Only update persisted references if save was successful
-            self.persist_last_project_path(&project_ctx);
-            if let Ok(pn) = ProfileName::new(&profile.name) {
-                self.persist_last_profile_for_project(&project_ctx, &pn);
-            }
+            log::debug!("Expanding all tree items");
+            self.synchronous_command_queue
+                .push_back(PlatformCommand::ExpandAllTreeItems {
+                    window_id,
+                    control_id: ui_constants::ID_TREEVIEW_CTRL,
+                });
         }
-        // These UI updates happen regardless of save success in current logic.
-        self._update_window_title_with_profile_and_archive(window_id);
-        self.update_current_archive_status();
-    }
-
-    fn handle_window_resized(&mut self, _window_id: WindowId, _width: i32, _height: i32) {
-        log::debug!("Window resized: ID {_window_id:?}, W:{_width}, H:{_height}");
     }
 
-    fn handle_menu_refresh_file_list_clicked(&mut self) {
+    fn handle_menu_load_profile_clicked(&mut self) {
         log::debug!(
-            "Menu action {:?} received by AppLogic.",
-            ui_constants::MENU_ACTION_REFRESH_FILE_LIST
+            "Menu action {:?} received by AppLogic, initiating profile selection flow.",
+            ui_constants::MENU_ACTION_LOAD_PROFILE
         );
-        let main_window_id = match self.ui_state.as_ref().map(|s| s.window_id()) {
+        let window_id = match self.ui_state.as_ref().map(|s| s.window_id()) {
             Some(id) => id,
             None => {
-                log::error!("AppLogic: Refresh requested but no main window UI state. Ignoring.");
+                log::warn!("Cannot handle LoadProfile: No UI state (main window).");
                 return;
             }
         };
 
-        let (
-            current_profile_name_clone,
-            root_path_to_scan,
-            current_selection_paths_opt,
-            exclude_patterns,
-        ) = {
-            let data = self.app_session_data_ops.lock().unwrap();
-            let name = data.get_profile_name();
-            if name.is_none() {
-                app_warn!(self, "Refresh: No profile active.");
+        // Reuse the exact same function that the startup sequence uses.
+        self.initiate_profile_selection_or_creation(window_id);
+    }
+
+    fn handle_menu_open_folder_clicked(&mut self) {
+        log::debug!(
+            "Menu action {:?} received by AppLogic.",
+            ui_constants::MENU_ACTION_OPEN_FOLDER
+        );
+        let ui_state_mut = match self.ui_state.as_mut() {
+            Some(s) => s,
+            None => {
+                log::warn!("Cannot handle OpenFolder: No UI state (main window).");
                 return;
             }
-
-            let (selected, deselected) = data.get_current_selection_paths();
-            let exclude_patterns = data.get_exclude_patterns();
-
-            (
-                name,
-                data.get_root_path_for_scan(),
-                Some((selected, deselected)),
-                exclude_patterns,
-            )
         };
 
-        let current_profile_name = match current_profile_name_clone {
-            Some(n) => n,
-            None => return,
-        };
-        let (current_selected_paths, current_deselected_paths) = match current_selection_paths_opt {
-            Some(paths) => paths,
+        let window_id = ui_state_mut.window_id();
+        let _ = ui_state_mut;
+        self.start_project_folder_prompt(window_id);
+    }
+
+    /*
+     * Starts the first step of the profile creation sequence when the user
+     * chooses "File/New Profile".
+     *
+     * An active main window is required so the dialogs for entering the profile
+     * name and selecting the root folder can be displayed.
+     */
+    fn handle_menu_new_profile_clicked(&mut self) {
+        let window_id = match self.ui_state.as_ref().map(|s| s.window_id()) {
+            Some(id) => id,
             None => {
-                app_error!(
-                    self,
-                    "Refresh: Could not get current selection paths for active profile."
-                );
+                log::warn!("Cannot handle NewProfile: No UI state (main window).");
                 return;
             }
         };
 
+        if self.active_project.is_none() {
+            app_warn!(self, "Cannot create a profile: No project folder is open.");
+            return;
+        }
+
         log::debug!(
-            "Refreshing file list for profile '{current_profile_name}', root: {root_path_to_scan:?}"
+            "Menu action {:?} received by AppLogic.",
+            ui_constants::MENU_ACTION_NEW_PROFILE
         );
+        self.start_new_profile_creation_flow(window_id);
+    }
 
-        // TODO: Do we really need a full new scan_directory here?
-        match self
-            .file_system_scanner
-            .scan_directory(&root_path_to_scan, &exclude_patterns)
+    fn handle_file_open_dialog_completed(&mut self, window_id: WindowId, result: Option<PathBuf>) {
+        if !self
+            .ui_state
+            .as_ref()
+            .is_some_and(|s| s.window_id() == window_id)
         {
-            Ok(new_nodes) => {
-                {
-                    let mut data = self.app_session_data_ops.lock().unwrap();
-                    data.set_snapshot_nodes(new_nodes);
-                    log::debug!(
-                        "Scan successful, {} top-level nodes found.",
-                        data.get_snapshot_nodes().len()
-                    );
-
-                    data.apply_selection_states_to_snapshot(
-                        &*self.state_manager,
-                        &current_selected_paths,
-                        &current_deselected_paths,
-                    );
-                }
-
-                log::debug!(
-                    "Applied selections from profile '{current_profile_name}' to refreshed tree and updated token cache."
-                );
+            log::warn!(
+                "FileOpenProfileDialogCompleted for non-matching or non-existent UI state. Window ID: {window_id:?}. Ignoring."
+            );
+            return;
+        }
 
-                self.refresh_tree_view_from_cache(main_window_id);
-                self.update_current_archive_status();
-                self._update_token_count_and_request_display();
-                app_info!(
-                    self,
-                    "File list refreshed for profile '{}'.",
-                    current_profile_name
-                );
+        if self.ui_state.as_ref().and_then(|s| s.pending_action())
+            == Some(&PendingAction::ApplyingEditedArchive)
+        {
+            if let Some(ui_state_mut) = self.ui_state.as_mut() {
+                ui_state_mut.take_pending_action();
             }
-            Err(e) => {
-                app_error!(self, "Failed to refresh file list: {}", e);
+            self._handle_file_open_dialog_for_edited_archive(window_id, result);
+            return;
+        }
+        if self.ui_state.as_ref().and_then(|s| s.pending_action())
+            == Some(&PendingAction::ApplyingPatch)
+        {
+            if let Some(ui_state_mut) = self.ui_state.as_mut() {
+                ui_state_mut.take_pending_action();
             }
+            self._handle_file_open_dialog_for_patch(window_id, result);
+            return;
         }
-    }
 
-    /*
-     * Activates a given profile: sets it as current in `ProfileRuntimeDataOperations`, scans its root folder,
-     * applies its selection state, refreshes UI elements, and shows the window.
-     * Assumes `self.ui_state` is Some and `window_id` matches `self.ui_state.window_id`.
-     */
-    fn _activate_profile_and_show_window(
-        &mut self,
-        window_id: WindowId,
-        profile_to_activate: Profile,
-        initial_operation_status_message: String,
-    ) {
-        assert!(
-            self.ui_state
-                .as_ref()
-                .is_some_and(|s| s.window_id() == window_id),
-            "Mismatched window ID or no UI state for _activate_profile_and_show_window"
-        );
-
-        let profile_name_for_persist = profile_to_activate.name.clone();
-        let scan_result = {
-            let mut data = self.app_session_data_ops.lock().unwrap();
-            data.load_profile_into_session(
-                profile_to_activate,
-                &*self.file_system_scanner,
-                &*self.state_manager,
-                &*self.token_counter_manager,
-            )
-        };
-
-        let (scan_was_successful, final_status_message) = match scan_result {
-            Ok(_) => (true, initial_operation_status_message),
-            Err(scan_error_message) => (false, scan_error_message),
-        };
-
-        if let Some(project_ctx) = self.active_project.as_ref() {
-            self.persist_last_project_path(project_ctx);
-            if let Ok(pn) = ProfileName::new(&profile_name_for_persist) {
-                self.persist_last_profile_for_project(project_ctx, &pn);
+        let profile_file_path = match result {
+            Some(pfp) => pfp,
+            None => {
+                log::debug!("Load profile cancelled.");
+                return;
             }
-        }
-
-        self._update_window_title_with_profile_and_archive(window_id);
-
-        // Show the main window before populating the TreeView. This ensures that
-        // child controls like the TreeView have completed their visual setup
-        // (including attaching the state image list used for checkboxes) before
-        // we insert items with a checked state.
-        self.synchronous_command_queue
-            .push_back(PlatformCommand::ShowWindow { window_id });
-
-        self.repopulate_tree_view(window_id);
-
-        self.update_current_archive_status();
-        self._update_token_count_and_request_display();
-
-        if scan_was_successful {
-            app_info!(self, "{}", final_status_message);
-        } else {
-            app_error!(self, "{}", final_status_message);
-        }
-    }
-
-    pub(crate) fn initiate_profile_selection_or_creation(&mut self, window_id: WindowId) {
-        log::debug!("Initiating profile selection or creation flow.");
-        assert!(
-            self.ui_state
-                .as_ref()
-                .is_some_and(|s| s.window_id() == window_id),
-            "initiate_profile_selection_or_creation called with mismatching window ID or no UI state."
-        );
+        };
 
-        let Some(project_ctx) = self.require_active_project("list profiles") else {
+        let Some(project_ctx) = self.require_active_project("load a profile from disk") else {
             return;
         };
 
+        log::debug!("Profile selected for load: {profile_file_path:?}");
         match self
             .profile_manager
-            .list_profiles(&project_ctx, APP_NAME_FOR_PROFILES)
+            .load_profile_from_path(&profile_file_path)
         {
-            Ok(profile_names) => {
-                let available_profiles: Vec<String> = profile_names
-                    .iter()
-                    .map(|p| p.as_str().to_string())
-                    .collect();
-                let (title, prompt) = if available_profiles.is_empty() {
-                    (
-                        "Welcome to SourcePacker!".to_string(),
-                        "No profiles found. Please create a new profile to get started."
-                            .to_string(),
-                    )
-                } else {
-                    (
-                        "Select or Create Profile".to_string(),
-                        "Please select an existing profile, or create a new one.".to_string(),
-                    )
-                };
+            Ok(loaded_profile) => {
+                let profile_name_clone = loaded_profile.name.clone();
                 log::debug!(
-                    "Found {} available profiles. Dialog prompt: '{}'",
-                    available_profiles.len(),
-                    prompt
+                    "Successfully loaded profile '{profile_name_clone}' via manager from path."
                 );
 
-                self.define_styles();
-
-                self.synchronous_command_queue.push_back(
-                    PlatformCommand::ShowProfileSelectionDialog {
-                        window_id,
-                        available_profiles,
-                        title,
-                        prompt,
-                    },
-                );
+                self.persist_last_project_path(&project_ctx);
+                if let Ok(pn) = ProfileName::new(&profile_name_clone) {
+                    self.persist_last_profile_for_project(&project_ctx, &pn);
+                }
+                let status_msg = format!("Profile '{profile_name_clone}' loaded and scanned.");
+                self._activate_profile_and_show_window(window_id, loaded_profile, status_msg);
             }
             Err(e) => {
                 app_error!(
                     self,
-                    "Failed to list profiles: {:?}. Cannot proceed with profile selection.",
+                    "Failed to load profile from {:?} via manager: {:?}",
+                    profile_file_path,
                     e
                 );
+                self.app_session_data_ops.lock().unwrap().clear();
+
+                if let Some(ui_state_mut) = self.ui_state.as_mut() {
+                    ui_state_mut.set_archive_status(None);
+                }
             }
         }
     }
 
-    fn handle_profile_selection_dialog_completed(
-        &mut self,
-        window_id: WindowId,
-        chosen_profile_name: Option<String>,
-        create_new_requested: bool,
-        user_cancelled: bool,
-    ) {
-        if !self
-            .ui_state
-            .as_ref()
-            .is_some_and(|s| s.window_id() == window_id)
-        {
-            log::debug!(
-                "ProfileSelectionDialogCompleted for non-matching or non-existent UI state. Window ID: {window_id:?}. Ignoring."
-            );
+    fn handle_menu_save_profile_as_clicked(&mut self) {
+        log::debug!(
+            "Menu action {:?} received by AppLogic.",
+            ui_constants::MENU_ACTION_SAVE_PROFILE_AS
+        );
+        let Some(project_ctx) = self.require_active_project("save the profile") else {
             return;
-        }
+        };
 
-        let Some(project_ctx) = self.require_active_project("load profiles") else {
-            return;
+        let ui_state_mut = match self.ui_state.as_mut() {
+            Some(s) => s,
+            None => {
+                log::warn!("Cannot handle SaveProfileAs: No UI state (main window).");
+                return;
+            }
         };
 
-        log::debug!(
-            "ProfileSelectionDialogCompleted event received: chosen: {chosen_profile_name:?}, create_new: {create_new_requested}, cancelled: {user_cancelled}"
-        );
+        let profile_dir_opt = self
+            .profile_manager
+            .get_profile_dir_path(&project_ctx, APP_NAME_FOR_PROFILES);
+        let base_name = self
+            .app_session_data_ops
+            .lock()
+            .unwrap()
+            .get_profile_name()
+            .map_or_else(|| "new_profile".to_string(), |name| name.clone());
+        let sanitized_current_name = core::profiles::sanitize_profile_name(&base_name);
+        let default_filename = format!("{sanitized_current_name}.json");
 
-        if user_cancelled {
-            let should_quit = {
-                let data = self.app_session_data_ops.lock().unwrap();
-                data.get_profile_name().is_none()
-            };
+        ui_state_mut.set_pending_action(Some(PendingAction::SavingProfileAs));
+        self.synchronous_command_queue
+            .push_back(PlatformCommand::ShowSaveFileDialog {
+                window_id: ui_state_mut.window_id(),
+                title: "Save Profile As".to_string(),
+                default_filename,
+                filter_spec: "Profile Files (*.json)\0*.json\0\0".to_string(),
+                initial_dir: profile_dir_opt,
+            });
+    }
 
-            if should_quit {
-                log::debug!(
-                    "Profile selection was cancelled by user with no active profile. Quitting application."
-                );
-                self.synchronous_command_queue
-                    .push_back(PlatformCommand::QuitApplication);
-            } else {
-                log::debug!(
-                    "Profile selection was cancelled by user, retaining the currently active profile."
+    /*
+     * Handles the outcome of a file save dialog. The behavior depends on the
+     * `pending_action` that was active when the dialog was initiated.
+     * This function delegates to specific handlers based on that action.
+     */
+    fn handle_file_save_dialog_completed(&mut self, window_id: WindowId, result: Option<PathBuf>) {
+        let ui_state_mut = match self
+            .ui_state
+            .as_mut()
+            .filter(|s| s.window_id() == window_id)
+        {
+            Some(s) => s,
+            None => {
+                log::warn!(
+                    "FileSaveDialogCompleted received for an unknown or non-main window (ID: {window_id:?}). Ignoring event."
                 );
+                return;
             }
-            return;
-        }
+        };
 
-        if create_new_requested {
-            log::debug!("User requested to create a new profile.");
-            self.start_new_profile_creation_flow(window_id);
-            return;
-        }
+        let action = ui_state_mut.take_pending_action();
+        log::debug!(
+            "FileSaveDialogCompleted with pending action: {action:?}, for result: {result:?}"
+        );
 
-        let profile_name_to_load = match chosen_profile_name {
-            Some(name) => match ProfileName::new(&name) {
-                Ok(pn) => pn,
-                Err(_) => {
-                    app_error!(
-                        self,
-                        "Invalid profile name '{}'. Please choose a different name.",
-                        name
-                    );
-                    self.initiate_profile_selection_or_creation(window_id);
-                    return;
-                }
-            },
+        match action {
+            Some(PendingAction::SettingArchivePath) => {
+                self._handle_file_save_dialog_for_setting_archive_path(window_id, result);
+            }
+            Some(PendingAction::SavingProfileAs) => {
+                self._handle_file_save_dialog_for_saving_profile_as(window_id, result);
+            }
+            Some(PendingAction::CreatingNewProfileGetName)
+            | Some(PendingAction::CreatingNewProfileGetRoot)
+            | Some(PendingAction::OpeningProjectFolder)
+            | Some(PendingAction::ApplyingEditedArchive)
+            | Some(PendingAction::ApplyingPatch) => {
+                app_error!(
+                    self,
+                    "FileSaveDialogCompleted received, but was expecting dialog for {:?}. This is a logic error.",
+                    action
+                );
+            }
             None => {
                 app_warn!(
                     self,
-                    "ProfileSelectionDialogCompleted in unexpected state (no choice, not create, not cancelled). Re-initiating."
+                    "FileSaveDialogCompleted received, but no pending action was set. Ignoring."
                 );
-                self.initiate_profile_selection_or_creation(window_id);
-                return;
             }
-        };
+        }
+    }
 
-        log::debug!("User chose profile '{profile_name_to_load}'. Attempting to load.");
-        match self.profile_manager.load_profile(
-            &project_ctx,
-            &profile_name_to_load,
-            APP_NAME_FOR_PROFILES,
-        ) {
-            Ok(profile) => {
-                log::debug!("Successfully loaded chosen profile '{}'.", profile.name);
-                let operation_status_message = format!("Profile '{}' loaded.", profile.name);
-                self.persist_last_project_path(&project_ctx);
-                if let Ok(pn) = ProfileName::new(&profile.name) {
-                    self.persist_last_profile_for_project(&project_ctx, &pn);
-                }
-                self._activate_profile_and_show_window(
-                    window_id,
-                    profile,
-                    operation_status_message,
-                );
-            }
-            Err(e) => {
-                app_error!(
-                    self,
-                    "Could not load profile '{}': {:?}. Please try again or create a new one.",
-                    profile_name_to_load,
-                    e
-                );
-                self.initiate_profile_selection_or_creation(window_id);
-            }
-        }
-    }
-
-    fn start_new_profile_creation_flow(&mut self, window_id: WindowId) {
-        log::debug!("Starting new profile creation flow (Step 1: Get Name).");
-        let ui_state_mut = self
-            .ui_state
-            .as_mut()
-            .filter(|s| s.window_id() == window_id)
-            .expect("UI state must exist and match window_id for start_new_profile_creation_flow");
-
-        ui_state_mut.set_pending_action(Some(PendingAction::CreatingNewProfileGetName));
-        self.synchronous_command_queue
-            .push_back(PlatformCommand::ShowInputDialog {
-                window_id,
-                title: "New Profile (1/2): Name".to_string(),
-                prompt: "Enter a name for the new profile:".to_string(),
-                default_text: None,
-                context_tag: Some("NewProfileName".to_string()),
-            });
-    }
-
-    fn _handle_input_dialog_for_new_profile_name(
+    /*
+     * Handles the outcome of a file save dialog initiated for setting a profile's archive path.
+     * If a path is selected, it updates the current profile's archive path in the
+     * application session data, saves the profile modifications, and refreshes relevant UI elements
+     * like the window title and archive status indicators.
+     */
+    fn _handle_file_save_dialog_for_setting_archive_path(
         &mut self,
         window_id: WindowId,
-        profile_name_input_opt: Option<String>, // Renamed 'text' for clarity within this function
+        result: Option<PathBuf>,
     ) {
-        let profile_name_text = match profile_name_input_opt {
-            Some(t) => t,
+        let path = match result {
+            Some(p) => p,
             None => {
-                log::debug!("New profile name input cancelled. Returning to profile selection.");
-                // Ensure ui_state exists, though it should from the calling function's check
-                let ui_state_mut = self.ui_state.as_mut().expect(
-                "ui_state should exist when _handle_input_dialog_for_new_profile_name is called",
-            );
-                ui_state_mut.set_pending_action(None);
-                ui_state_mut.set_pending_new_profile_name(None);
-                self.initiate_profile_selection_or_creation(window_id);
                 return;
             }
         };
 
-        if profile_name_text.trim().is_empty()
-            || !profile_name_text
-                .chars()
-                .all(core::profiles::is_valid_profile_name_char)
-        {
-            app_warn!(
-                self,
-                "Invalid profile name. Please use only letters, numbers, spaces, underscores, or hyphens."
-            );
-            self.synchronous_command_queue
-                .push_back(PlatformCommand::ShowInputDialog {
-                    window_id,
-                    title: "New Profile (1/2): Name".to_string(),
-                    prompt: "Enter a name for the new profile (invalid previous attempt):"
-                        .to_string(),
-                    default_text: Some(profile_name_text),
-                    context_tag: Some("NewProfileName".to_string()),
-                });
-            return;
-        }
-
-        log::debug!(
-            "New profile name '{profile_name_text}' is valid. Proceeding to Step 2 (Get Root Folder)."
-        );
-        // Ensure ui_state exists
-        let ui_state_mut = self.ui_state.as_mut().expect(
-            "ui_state should exist when _handle_input_dialog_for_new_profile_name is called (valid name case)",
-        );
-        ui_state_mut.set_pending_new_profile_name(Some(profile_name_text));
-        ui_state_mut.set_pending_action(Some(PendingAction::CreatingNewProfileGetRoot));
-
-        self.synchronous_command_queue
-            .push_back(PlatformCommand::ShowFolderPickerDialog {
-                window_id,
-                title: "New Profile (2/2): Select Root Folder".to_string(),
-                initial_dir: None,
-            });
-    }
-
-    fn handle_input_dialog_completed(
-        &mut self,
-        window_id: WindowId,
-        text: Option<String>,
-        context_tag: Option<String>,
-    ) {
-        if !self
-            .ui_state
-            .as_ref()
-            .is_some_and(|s| s.window_id() == window_id)
-        {
-            log::warn!(
-                "InputDialogCompleted for an unknown or non-main window (ID: {window_id:?}). Ignoring."
-            );
-            return;
-        }
-
-        log::debug!("InputDialogCompleted: text: {text:?}, context_tag: {context_tag:?}");
+        log::debug!("User selected archive path: {path:?}");
 
-        match context_tag.as_deref() {
-            Some("NewProfileName") => {
-                // Call the new helper method
-                self._handle_input_dialog_for_new_profile_name(window_id, text);
-            }
-            _ => {
-                app_warn!(
-                    self,
-                    "InputDialogCompleted with unhandled context: {:?}",
-                    context_tag
-                );
-                // Ensure ui_state exists before modifying it, consistent with the guard at the start of the function
-                if let Some(ui_state_mut) = self.ui_state.as_mut() {
-                    ui_state_mut.set_pending_action(None);
-                }
+        let profile_to_save_opt = {
+            let mut profile_runtime_data = self.app_session_data_ops.lock().unwrap();
+            if profile_runtime_data.get_profile_name().is_none() {
+                app_error!(self, "No profile is active. Cannot set archive path.");
+                return;
             }
-        }
-    }
-
-    /*
-     * Handles completion of the exclude patterns dialog. When the user saves changes the updated
-     * patterns are persisted to disk, cached in the active session, and a refresh is triggered so
-     * the file tree immediately reflects the new rules.
-     */
-    fn handle_exclude_patterns_dialog_completed(
-        &mut self,
-        window_id: WindowId,
-        saved: bool,
-        patterns: String,
-    ) {
-        if !self
-            .ui_state
-            .as_ref()
-            .is_some_and(|s| s.window_id() == window_id)
-        {
-            log::warn!(
-                "ExcludePatternsDialogCompleted received for an unknown window {window_id:?}. Ignoring."
-            );
-            return;
-        }
-
-        log::debug!(
-            "Exclude patterns dialog completed for window {:?}, saved: {}, first line preview: {:?}",
-            window_id,
-            saved,
-            patterns.lines().next()
-        );
-
-        if !saved {
-            log::debug!("Exclude patterns dialog was cancelled; no action taken.");
-            return;
-        }
-
-        let parsed_patterns: Vec<String> = patterns
-            .lines()
-            .map(|line| line.trim())
-            .filter(|line| !line.is_empty())
-            .map(|line| line.to_string())
-            .collect();
+            profile_runtime_data.set_archive_path(Some(path.clone()));
+            Some(profile_runtime_data.create_profile_snapshot())
+        };
 
-        let (profile_to_save, profile_name) = {
-            let data = self.app_session_data_ops.lock().unwrap();
-            match data.get_profile_name() {
-                Some(name) if !name.is_empty() => {
-                    let mut snapshot = data.create_profile_snapshot();
-                    snapshot.exclude_patterns = parsed_patterns.clone();
-                    (snapshot, name)
-                }
-                _ => {
-                    app_warn!(
-                        self,
-                        "Cannot update exclude patterns: No profile is active."
-                    );
-                    return;
-                }
+        let profile_to_save = match profile_to_save_opt {
+            Some(p) => p,
+            None => {
+                log::error!(
+                    "_handle_file_save_dialog_for_setting_archive_path: profile_to_save was unexpectedly None despite an active profile check."
+                );
+                return;
             }
         };
 
@@ -2082,259 +2719,1990 @@ impl MyAppLogic {
             APP_NAME_FOR_PROFILES,
         ) {
             Ok(_) => {
-                {
-                    let mut data = self.app_session_data_ops.lock().unwrap();
-                    data.set_exclude_patterns(parsed_patterns.clone());
-                }
                 app_info!(
                     self,
-                    "Updated exclude patterns for profile '{}'.",
-                    profile_name
+                    "Archive path set to '{}' for profile '{}' and profile saved.",
+                    path.display(),
+                    profile_to_save.name
                 );
-                self.handle_menu_refresh_file_list_clicked();
+                self._update_window_title_with_profile_and_archive(window_id);
+                self.update_current_archive_status();
             }
             Err(e) => {
                 app_error!(
                     self,
-                    "Failed to save exclude patterns for profile '{}': {}",
-                    profile_name,
+                    "Failed to save profile '{}' after setting archive path: {}",
+                    profile_to_save.name,
                     e
                 );
             }
         }
     }
 
-    fn handle_folder_picker_dialog_completed(
-        &mut self,
-        window_id: WindowId,
-        path: Option<PathBuf>,
-    ) {
-        let ui_state_mut = match self
-            .ui_state
-            .as_mut()
-            .filter(|s| s.window_id() == window_id)
-        {
-            Some(s) => s,
-            None => {
-                log::warn!(
-                    "FolderPickerDialogCompleted for an unknown or non-main window (ID: {window_id:?}). Ignoring."
-                );
-                return;
-            }
-        };
+    fn make_profile_name(path: Option<PathBuf>) -> Result<String, String> {
+        let profile_save_path =
+            path.ok_or_else(|| "User cancelled the 'Save Profile As' dialog.".to_string())?;
 
-        let pending_action = ui_state_mut.take_pending_action();
-        let pending_new_profile_name = if matches!(
-            pending_action,
-            Some(PendingAction::CreatingNewProfileGetRoot)
-        ) {
-            ui_state_mut.take_pending_new_profile_name()
+        log::debug!("User selected path for 'Save Profile As': {profile_save_path:?}");
+
+        let profile_name_osstr = profile_save_path
+            .file_stem()
+            .ok_or_else(|| "Could not extract profile name from save path.".to_string())?;
+
+        let profile_name_str = profile_name_osstr
+            .to_str()
+            .ok_or_else(|| "Profile save filename stem not valid UTF-8.".to_string())?
+            .to_string();
+
+        if profile_name_str.trim().is_empty()
+            || !profile_name_str
+                .chars()
+                .all(core::profiles::is_valid_profile_name_char)
+        {
+            return Err(format!(
+                "Invalid profile name extracted from path: '{profile_name_str}'"
+            ));
+        }
+
+        Ok(profile_name_str)
+    }
+
+    /*
+     * Handles the outcome of a file save dialog initiated for saving the current profile under a new name or path.
+     * If a path is selected, it extracts the new profile name, updates the application session data
+     * to reflect this new profile (name, path, etc.), saves it through the profile manager,
+     * and refreshes relevant UI elements.
+     */
+    fn _handle_file_save_dialog_for_saving_profile_as(
+        &mut self,
+        window_id: WindowId,
+        result: Option<PathBuf>,
+    ) {
+        let profile_name_str = match Self::make_profile_name(result) {
+            Ok(pfn) => pfn,
+            Err(e) => {
+                app_error!(self, "{}", e);
+                return;
+            }
+        };
+
+        let profile = {
+            let mut profile_runtime_data = self.app_session_data_ops.lock().unwrap();
+            profile_runtime_data.set_profile_name(Some(profile_name_str));
+            profile_runtime_data.set_archive_path(None);
+            profile_runtime_data.create_profile_snapshot()
+        };
+        let Some(project_ctx) = self.require_active_project("save the profile") else {
+            return;
+        };
+        if let Err(e) =
+            self.profile_manager
+                .save_profile(&project_ctx, &profile, APP_NAME_FOR_PROFILES)
+        {
+            app_error!(
+                self,
+                "Failed to save profile '{}' in 'Save Profile As': {}",
+                profile.name,
+                e
+            );
+            // If save fails, should we revert profile_name in app_session_data_ops?
+            // Current logic does not. For now, matching existing behavior.
         } else {
-            None
+            // Only update persisted references if save was successful
+            self.persist_last_project_path(&project_ctx);
+            if let Ok(pn) = ProfileName::new(&profile.name) {
+                self.persist_last_profile_for_project(&project_ctx, &pn);
+            }
+        }
+        // These UI updates happen regardless of save success in current logic.
+        self._update_window_title_with_profile_and_archive(window_id);
+        self.update_current_archive_status();
+    }
+
+    fn handle_window_resized(&mut self, _window_id: WindowId, _width: i32, _height: i32) {
+        log::debug!("Window resized: ID {_window_id:?}, W:{_width}, H:{_height}");
+    }
+
+    fn handle_menu_refresh_file_list_clicked(&mut self) {
+        log::debug!(
+            "Menu action {:?} received by AppLogic.",
+            ui_constants::MENU_ACTION_REFRESH_FILE_LIST
+        );
+        let main_window_id = match self.ui_state.as_ref().map(|s| s.window_id()) {
+            Some(id) => id,
+            None => {
+                log::error!("AppLogic: Refresh requested but no main window UI state. Ignoring.");
+                return;
+            }
+        };
+
+        let (
+            current_profile_name_clone,
+            root_path_to_scan,
+            current_selection_paths_opt,
+            exclude_patterns,
+        ) = {
+            let data = self.app_session_data_ops.lock().unwrap();
+            let name = data.get_profile_name();
+            if name.is_none() {
+                app_warn!(self, "Refresh: No profile active.");
+                return;
+            }
+
+            let (selected, deselected, outline) = data.get_current_selection_paths();
+            let exclude_patterns = data.get_exclude_patterns();
+
+            (
+                name,
+                data.get_root_path_for_scan(),
+                Some((selected, deselected, outline)),
+                exclude_patterns,
+            )
+        };
+
+        let current_profile_name = match current_profile_name_clone {
+            Some(n) => n,
+            None => return,
         };
+        let (current_selected_paths, current_deselected_paths, current_outline_paths) =
+            match current_selection_paths_opt {
+                Some(paths) => paths,
+                None => {
+                    app_error!(
+                        self,
+                        "Refresh: Could not get current selection paths for active profile."
+                    );
+                    return;
+                }
+            };
+
         log::debug!(
-            "FolderPickerDialogCompleted: path: {path:?}, pending_action: {pending_action:?}"
+            "Refreshing file list for profile '{current_profile_name}', root: {root_path_to_scan:?}"
+        );
+
+        // TODO: Do we really need a full new scan_directory here?
+        match self
+            .file_system_scanner
+            .scan_directory(&root_path_to_scan, &exclude_patterns)
+        {
+            Ok(new_nodes) => {
+                {
+                    let mut data = self.app_session_data_ops.lock().unwrap();
+                    data.set_snapshot_nodes(new_nodes);
+                    log::debug!(
+                        "Scan successful, {} top-level nodes found.",
+                        data.get_snapshot_nodes().len()
+                    );
+
+                    data.apply_selection_states_to_snapshot(
+                        &*self.state_manager,
+                        &current_selected_paths,
+                        &current_deselected_paths,
+                        &current_outline_paths,
+                    );
+                }
+
+                log::debug!(
+                    "Applied selections from profile '{current_profile_name}' to refreshed tree and updated token cache."
+                );
+
+                self.refresh_tree_view_from_cache(main_window_id);
+                self.update_current_archive_status();
+                self._update_token_count_and_request_display();
+                self.restart_file_watcher(true);
+                app_info!(
+                    self,
+                    "File list refreshed for profile '{}'.",
+                    current_profile_name
+                );
+            }
+            Err(e) => {
+                app_error!(self, "Failed to refresh file list: {}", e);
+            }
+        }
+    }
+
+    /*
+     * Watches the root folder of the active profile, replacing the watch of the previous
+     * one. [FileSystemWatchV1] Called after activation and after each successful refresh.
+     * Without a successful scan there is no tree to keep up to date, so nothing is watched
+     * until the next successful refresh.
+     */
+    fn restart_file_watcher(&mut self, scan_was_successful: bool) {
+        self.file_watcher = None;
+        if !scan_was_successful {
+            return;
+        }
+        let root = self
+            .app_session_data_ops
+            .lock()
+            .unwrap()
+            .get_root_path_for_scan();
+        if !root.is_dir() {
+            return;
+        }
+        match self.file_system_scanner.watch_directory(&root) {
+            Ok(watcher) => self.file_watcher = watcher,
+            Err(e) => app_warn!(
+                self,
+                "Could not watch {:?} for changes ({}). Use Refresh to pick up changes.",
+                root,
+                e
+            ),
+        }
+    }
+
+    fn poll_file_watcher(&mut self) {
+        let Some(changes) = self.file_watcher.as_mut().and_then(FileWatcher::poll) else {
+            return;
+        };
+        self.apply_file_changes(&changes);
+    }
+
+    /*
+     * Applies changes reported by the file watcher to the tree without a full scan, then
+     * re-applies the profile's selection and updates the tree view, archive status and
+     * token count as a refresh would. Renamed paths keep their selection. Falls back to a
+     * full refresh when the watcher could not tell what changed.
+     */
+    pub(crate) fn apply_file_changes(&mut self, changes: &[FileChange]) {
+        let Some(main_window_id) = self.ui_state.as_ref().map(|s| s.window_id()) else {
+            return;
+        };
+        let (mut nodes, root, exclude_patterns, (mut selected, mut deselected, mut outline)) = {
+            let data = self.app_session_data_ops.lock().unwrap();
+            if data.get_profile_name().is_none() {
+                return;
+            }
+            (
+                data.get_snapshot_nodes().to_vec(),
+                data.get_root_path_for_scan(),
+                data.get_exclude_patterns(),
+                data.get_current_selection_paths(),
+            )
+        };
+
+        let update = file_watcher::apply_changes(&mut nodes, &root, &exclude_patterns, changes);
+        if update.needs_rescan {
+            log::debug!("AppLogic: File watcher requested a full rescan of {root:?}.");
+            self.handle_menu_refresh_file_list_clicked();
+            return;
+        }
+        if update.is_empty() {
+            return;
+        }
+        for (from, to) in &update.renamed {
+            for paths in [&mut selected, &mut deselected, &mut outline] {
+                let moved: Vec<PathBuf> = paths
+                    .iter()
+                    .filter(|path| path.starts_with(from))
+                    .cloned()
+                    .collect();
+                for path in moved {
+                    paths.remove(&path);
+                    let suffix = path.strip_prefix(from).unwrap_or(Path::new(""));
+                    paths.insert(to.join(suffix));
+                }
+            }
+        }
+
+        {
+            let mut data = self.app_session_data_ops.lock().unwrap();
+            data.set_snapshot_nodes(nodes);
+            data.apply_selection_states_to_snapshot(
+                &*self.state_manager,
+                &selected,
+                &deselected,
+                &outline,
+            );
+        }
+        self.refresh_tree_view_from_cache(main_window_id);
+        self.update_current_archive_status();
+        self._update_token_count_and_request_display();
+        let count = update.changed_paths.len() + update.renamed.len();
+        app_info!(
+            self,
+            "Picked up {} file system change{}.",
+            count,
+            if count == 1 { "" } else { "s" }
+        );
+    }
+
+    /*
+     * Activates a given profile: sets it as current in `ProfileRuntimeDataOperations`, scans its root folder,
+     * applies its selection state, refreshes UI elements, and shows the window.
+     * Assumes `self.ui_state` is Some and `window_id` matches `self.ui_state.window_id`.
+     */
+    fn _activate_profile_and_show_window(
+        &mut self,
+        window_id: WindowId,
+        profile_to_activate: Profile,
+        initial_operation_status_message: String,
+    ) {
+        assert!(
+            self.ui_state
+                .as_ref()
+                .is_some_and(|s| s.window_id() == window_id),
+            "Mismatched window ID or no UI state for _activate_profile_and_show_window"
+        );
+
+        let profile_name_for_persist = profile_to_activate.name.clone();
+        // Components of a composite profile are profiles of the same project.
+        let project_ctx = self
+            .active_project
+            .clone()
+            .unwrap_or_else(|| ProjectContext::new(profile_to_activate.root_folder.clone()));
+        let component_loader = ProjectComponentLoader::new(
+            &project_ctx,
+            &*self.profile_manager,
+            APP_NAME_FOR_PROFILES,
         );
+        let scan_result = {
+            let mut data = self.app_session_data_ops.lock().unwrap();
+            data.load_profile_into_session(
+                profile_to_activate,
+                &*self.file_system_scanner,
+                &*self.state_manager,
+                &*self.token_counter_manager,
+                &component_loader,
+            )
+        };
+
+        let (scan_was_successful, final_status_message) = match scan_result {
+            Ok(_) => (true, initial_operation_status_message),
+            Err(scan_error_message) => (false, scan_error_message),
+        };
+
+        if let Some(project_ctx) = self.active_project.as_ref() {
+            self.persist_last_project_path(project_ctx);
+            if let Ok(pn) = ProfileName::new(&profile_name_for_persist) {
+                self.persist_last_profile_for_project(project_ctx, &pn);
+            }
+        }
+
+        self._update_window_title_with_profile_and_archive(window_id);
+
+        // Show the main window before populating the TreeView. This ensures that
+        // child controls like the TreeView have completed their visual setup
+        // (including attaching the state image list used for checkboxes) before
+        // we insert items with a checked state.
+        self.synchronous_command_queue
+            .push_back(PlatformCommand::ShowWindow { window_id });
+
+        self.repopulate_tree_view(window_id);
+
+        self.update_current_archive_status();
+        self._update_token_count_and_request_display();
+        self.restart_file_watcher(scan_was_successful);
+
+        if scan_was_successful {
+            app_info!(self, "{}", final_status_message);
+        } else {
+            app_error!(self, "{}", final_status_message);
+        }
+    }
+
+    pub(crate) fn initiate_profile_selection_or_creation(&mut self, window_id: WindowId) {
+        log::debug!("Initiating profile selection or creation flow.");
+        assert!(
+            self.ui_state
+                .as_ref()
+                .is_some_and(|s| s.window_id() == window_id),
+            "initiate_profile_selection_or_creation called with mismatching window ID or no UI state."
+        );
+
+        let Some(project_ctx) = self.require_active_project("list profiles") else {
+            return;
+        };
+
+        match self
+            .profile_manager
+            .list_profiles(&project_ctx, APP_NAME_FOR_PROFILES)
+        {
+            Ok(profile_names) => {
+                let available_profiles: Vec<String> = profile_names
+                    .iter()
+                    .map(|p| p.as_str().to_string())
+                    .collect();
+                let (title, prompt) = if available_profiles.is_empty() {
+                    (
+                        "Welcome to SourcePacker!".to_string(),
+                        "No profiles found. Please create a new profile to get started."
+                            .to_string(),
+                    )
+                } else {
+                    (
+                        "Select or Create Profile".to_string(),
+                        "Please select an existing profile, or create a new one.".to_string(),
+                    )
+                };
+                log::debug!(
+                    "Found {} available profiles. Dialog prompt: '{}'",
+                    available_profiles.len(),
+                    prompt
+                );
+
+                self.define_styles();
+
+                self.synchronous_command_queue.push_back(
+                    PlatformCommand::ShowProfileSelectionDialog {
+                        window_id,
+                        available_profiles,
+                        title,
+                        prompt,
+                    },
+                );
+            }
+            Err(e) => {
+                app_error!(
+                    self,
+                    "Failed to list profiles: {:?}. Cannot proceed with profile selection.",
+                    e
+                );
+            }
+        }
+    }
+
+    fn handle_profile_selection_dialog_completed(
+        &mut self,
+        window_id: WindowId,
+        chosen_profile_name: Option<String>,
+        create_new_requested: bool,
+        user_cancelled: bool,
+    ) {
+        if !self
+            .ui_state
+            .as_ref()
+            .is_some_and(|s| s.window_id() == window_id)
+        {
+            log::debug!(
+                "ProfileSelectionDialogCompleted for non-matching or non-existent UI state. Window ID: {window_id:?}. Ignoring."
+            );
+            return;
+        }
+
+        let Some(project_ctx) = self.require_active_project("load profiles") else {
+            return;
+        };
+
+        log::debug!(
+            "ProfileSelectionDialogCompleted event received: chosen: {chosen_profile_name:?}, create_new: {create_new_requested}, cancelled: {user_cancelled}"
+        );
+
+        if user_cancelled {
+            let should_quit = {
+                let data = self.app_session_data_ops.lock().unwrap();
+                data.get_profile_name().is_none()
+            };
+
+            if should_quit {
+                log::debug!(
+                    "Profile selection was cancelled by user with no active profile. Quitting application."
+                );
+                self.synchronous_command_queue
+                    .push_back(PlatformCommand::QuitApplication);
+            } else {
+                log::debug!(
+                    "Profile selection was cancelled by user, retaining the currently active profile."
+                );
+            }
+            return;
+        }
+
+        if create_new_requested {
+            log::debug!("User requested to create a new profile.");
+            self.start_new_profile_creation_flow(window_id);
+            return;
+        }
+
+        let profile_name_to_load = match chosen_profile_name {
+            Some(name) => match ProfileName::new(&name) {
+                Ok(pn) => pn,
+                Err(_) => {
+                    app_error!(
+                        self,
+                        "Invalid profile name '{}'. Please choose a different name.",
+                        name
+                    );
+                    self.initiate_profile_selection_or_creation(window_id);
+                    return;
+                }
+            },
+            None => {
+                app_warn!(
+                    self,
+                    "ProfileSelectionDialogCompleted in unexpected state (no choice, not create, not cancelled). Re-initiating."
+                );
+                self.initiate_profile_selection_or_creation(window_id);
+                return;
+            }
+        };
+
+        log::debug!("User chose profile '{profile_name_to_load}'. Attempting to load.");
+        match self.profile_manager.load_profile(
+            &project_ctx,
+            &profile_name_to_load,
+            APP_NAME_FOR_PROFILES,
+        ) {
+            Ok(profile) => {
+                log::debug!("Successfully loaded chosen profile '{}'.", profile.name);
+                let operation_status_message = format!("Profile '{}' loaded.", profile.name);
+                self.persist_last_project_path(&project_ctx);
+                if let Ok(pn) = ProfileName::new(&profile.name) {
+                    self.persist_last_profile_for_project(&project_ctx, &pn);
+                }
+                self._activate_profile_and_show_window(
+                    window_id,
+                    profile,
+                    operation_status_message,
+                );
+            }
+            Err(e) => {
+                app_error!(
+                    self,
+                    "Could not load profile '{}': {:?}. Please try again or create a new one.",
+                    profile_name_to_load,
+                    e
+                );
+                self.initiate_profile_selection_or_creation(window_id);
+            }
+        }
+    }
+
+    fn start_new_profile_creation_flow(&mut self, window_id: WindowId) {
+        log::debug!("Starting new profile creation flow (Step 1: Get Name).");
+        let ui_state_mut = self
+            .ui_state
+            .as_mut()
+            .filter(|s| s.window_id() == window_id)
+            .expect("UI state must exist and match window_id for start_new_profile_creation_flow");
+
+        ui_state_mut.set_pending_action(Some(PendingAction::CreatingNewProfileGetName));
+        self.synchronous_command_queue
+            .push_back(PlatformCommand::ShowInputDialog {
+                window_id,
+                title: "New Profile (1/2): Name".to_string(),
+                prompt: "Enter a name for the new profile:".to_string(),
+                default_text: None,
+                context_tag: Some("NewProfileName".to_string()),
+            });
+    }
+
+    fn _handle_input_dialog_for_new_profile_name(
+        &mut self,
+        window_id: WindowId,
+        profile_name_input_opt: Option<String>, // Renamed 'text' for clarity within this function
+    ) {
+        let profile_name_text = match profile_name_input_opt {
+            Some(t) => t,
+            None => {
+                log::debug!("New profile name input cancelled. Returning to profile selection.");
+                // Ensure ui_state exists, though it should from the calling function's check
+                let ui_state_mut = self.ui_state.as_mut().expect(
+                "ui_state should exist when _handle_input_dialog_for_new_profile_name is called",
+            );
+                ui_state_mut.set_pending_action(None);
+                ui_state_mut.set_pending_new_profile_name(None);
+                self.initiate_profile_selection_or_creation(window_id);
+                return;
+            }
+        };
+
+        if profile_name_text.trim().is_empty()
+            || !profile_name_text
+                .chars()
+                .all(core::profiles::is_valid_profile_name_char)
+        {
+            app_warn!(
+                self,
+                "Invalid profile name. Please use only letters, numbers, spaces, underscores, or hyphens."
+            );
+            self.synchronous_command_queue
+                .push_back(PlatformCommand::ShowInputDialog {
+                    window_id,
+                    title: "New Profile (1/2): Name".to_string(),
+                    prompt: "Enter a name for the new profile (invalid previous attempt):"
+                        .to_string(),
+                    default_text: Some(profile_name_text),
+                    context_tag: Some("NewProfileName".to_string()),
+                });
+            return;
+        }
+
+        log::debug!(
+            "New profile name '{profile_name_text}' is valid. Proceeding to Step 2 (Get Root Folder)."
+        );
+        // Ensure ui_state exists
+        let ui_state_mut = self.ui_state.as_mut().expect(
+            "ui_state should exist when _handle_input_dialog_for_new_profile_name is called (valid name case)",
+        );
+        ui_state_mut.set_pending_new_profile_name(Some(profile_name_text));
+        ui_state_mut.set_pending_action(Some(PendingAction::CreatingNewProfileGetRoot));
+
+        self.synchronous_command_queue
+            .push_back(PlatformCommand::ShowFolderPickerDialog {
+                window_id,
+                title: "New Profile (2/2): Select Root Folder".to_string(),
+                initial_dir: None,
+            });
+    }
+
+    fn handle_input_dialog_completed(
+        &mut self,
+        window_id: WindowId,
+        text: Option<String>,
+        context_tag: Option<String>,
+    ) {
+        if !self
+            .ui_state
+            .as_ref()
+            .is_some_and(|s| s.window_id() == window_id)
+        {
+            log::warn!(
+                "InputDialogCompleted for an unknown or non-main window (ID: {window_id:?}). Ignoring."
+            );
+            return;
+        }
+
+        log::debug!("InputDialogCompleted: text: {text:?}, context_tag: {context_tag:?}");
+
+        match context_tag.as_deref() {
+            Some("NewProfileName") => {
+                // Call the new helper method
+                self._handle_input_dialog_for_new_profile_name(window_id, text);
+            }
+            Some(ARCHIVE_FORMAT_CONTEXT_TAG) => {
+                self._handle_input_dialog_for_archive_format(window_id, text);
+            }
+            Some(ARCHIVE_SPLIT_CONTEXT_TAG) => {
+                self._handle_input_dialog_for_archive_split(window_id, text);
+            }
+            Some(TOKEN_BUDGET_CONTEXT_TAG) => {
+                self._handle_input_dialog_for_token_budget(window_id, text);
+            }
+            Some(FILE_PRIORITY_CONTEXT_TAG) => {
+                self._handle_input_dialog_for_file_priority(window_id, text);
+            }
+            Some(CONTENT_TRANSFORMS_CONTEXT_TAG) => {
+                self._handle_input_dialog_for_content_transforms(window_id, text);
+            }
+            Some(DIRECTORY_TREE_CONTEXT_TAG) => {
+                self._handle_input_dialog_for_directory_tree(window_id, text);
+            }
+            Some(SECRET_SCAN_CONTEXT_TAG) => {
+                self._handle_input_dialog_for_secret_scan(window_id, text);
+            }
+            Some(CONTENT_LIMITS_CONTEXT_TAG) => {
+                self._handle_input_dialog_for_content_limits(window_id, text);
+            }
+            Some(ENCODINGS_CONTEXT_TAG) => {
+                self._handle_input_dialog_for_encodings(window_id, text);
+            }
+            Some(LINE_NUMBERS_CONTEXT_TAG) => {
+                self._handle_input_dialog_for_line_numbers(window_id, text);
+            }
+            Some(ARCHIVE_HISTORY_CONTEXT_TAG) => {
+                self._handle_input_dialog_for_archive_history(window_id, text);
+            }
+            Some(PROMPT_VARIANT_CONTEXT_TAG) => {
+                self._handle_input_dialog_for_prompt_variant(window_id, text);
+            }
+            Some(APPLY_EDITED_ARCHIVE_CONTEXT_TAG) => {
+                self._handle_input_dialog_for_apply_edited_archive(window_id, text);
+            }
+            Some(APPLY_PATCH_CONTEXT_TAG) => {
+                self._handle_input_dialog_for_apply_patch(text);
+            }
+            _ => {
+                app_warn!(
+                    self,
+                    "InputDialogCompleted with unhandled context: {:?}",
+                    context_tag
+                );
+                // Ensure ui_state exists before modifying it, consistent with the guard at the start of the function
+                if let Some(ui_state_mut) = self.ui_state.as_mut() {
+                    ui_state_mut.set_pending_action(None);
+                }
+            }
+        }
+    }
+
+    /*
+     * Handles completion of the exclude patterns dialog. When the user saves changes the updated
+     * patterns are persisted to disk, cached in the active session, and a refresh is triggered so
+     * the file tree immediately reflects the new rules.
+     */
+    fn handle_exclude_patterns_dialog_completed(
+        &mut self,
+        window_id: WindowId,
+        saved: bool,
+        patterns: String,
+    ) {
+        if !self
+            .ui_state
+            .as_ref()
+            .is_some_and(|s| s.window_id() == window_id)
+        {
+            log::warn!(
+                "ExcludePatternsDialogCompleted received for an unknown window {window_id:?}. Ignoring."
+            );
+            return;
+        }
+
+        log::debug!(
+            "Exclude patterns dialog completed for window {:?}, saved: {}, first line preview: {:?}",
+            window_id,
+            saved,
+            patterns.lines().next()
+        );
+
+        if !saved {
+            log::debug!("Exclude patterns dialog was cancelled; no action taken.");
+            return;
+        }
+
+        let parsed_patterns: Vec<String> = patterns
+            .lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty())
+            .map(|line| line.to_string())
+            .collect();
+
+        let (profile_to_save, profile_name) = {
+            let data = self.app_session_data_ops.lock().unwrap();
+            match data.get_profile_name() {
+                Some(name) if !name.is_empty() => {
+                    let mut snapshot = data.create_profile_snapshot();
+                    snapshot.exclude_patterns = parsed_patterns.clone();
+                    (snapshot, name)
+                }
+                _ => {
+                    app_warn!(
+                        self,
+                        "Cannot update exclude patterns: No profile is active."
+                    );
+                    return;
+                }
+            }
+        };
+
+        let Some(project_ctx) = self.require_active_project("save the profile") else {
+            return;
+        };
+
+        match self.profile_manager.save_profile(
+            &project_ctx,
+            &profile_to_save,
+            APP_NAME_FOR_PROFILES,
+        ) {
+            Ok(_) => {
+                {
+                    let mut data = self.app_session_data_ops.lock().unwrap();
+                    data.set_exclude_patterns(parsed_patterns.clone());
+                }
+                app_info!(
+                    self,
+                    "Updated exclude patterns for profile '{}'.",
+                    profile_name
+                );
+                self.handle_menu_refresh_file_list_clicked();
+            }
+            Err(e) => {
+                app_error!(
+                    self,
+                    "Failed to save exclude patterns for profile '{}': {}",
+                    profile_name,
+                    e
+                );
+            }
+        }
+    }
+
+    fn handle_folder_picker_dialog_completed(
+        &mut self,
+        window_id: WindowId,
+        path: Option<PathBuf>,
+    ) {
+        let ui_state_mut = match self
+            .ui_state
+            .as_mut()
+            .filter(|s| s.window_id() == window_id)
+        {
+            Some(s) => s,
+            None => {
+                log::warn!(
+                    "FolderPickerDialogCompleted for an unknown or non-main window (ID: {window_id:?}). Ignoring."
+                );
+                return;
+            }
+        };
+
+        let pending_action = ui_state_mut.take_pending_action();
+        let pending_new_profile_name = if matches!(
+            pending_action,
+            Some(PendingAction::CreatingNewProfileGetRoot)
+        ) {
+            ui_state_mut.take_pending_new_profile_name()
+        } else {
+            None
+        };
+        log::debug!(
+            "FolderPickerDialogCompleted: path: {path:?}, pending_action: {pending_action:?}"
+        );
+
+        let root_folder_path = match path {
+            Some(p) => p,
+            None => {
+                log::debug!("Folder selection cancelled.");
+                if matches!(
+                    pending_action,
+                    Some(PendingAction::CreatingNewProfileGetRoot)
+                ) {
+                    log::debug!("Root folder selection cancelled. Returning to profile selection.");
+                    ui_state_mut.set_pending_new_profile_name(None);
+                    self.initiate_profile_selection_or_creation(window_id);
+                }
+                return;
+            }
+        };
+
+        let _ = ui_state_mut;
+
+        match pending_action {
+            Some(PendingAction::OpeningProjectFolder) => {
+                let ctx = ProjectContext::new(root_folder_path.clone());
+                log::debug!("Opened project folder: {:?}", ctx.display_name());
+
+                self.persist_last_project_path(&ctx);
+                self.active_project = Some(ctx);
+
+                // Cancel any ongoing asynchronous work
+                self.cancel_token_recalculation();
+                self.cancel_content_search();
+
+                // Clear session data (Step 1.4: Replace current project and profile)
+                self.app_session_data_ops.lock().unwrap().clear();
+
+                // Update title
+                self._update_window_title_with_profile_and_archive(window_id);
+
+                // Update UI to reflect empty state
+                self.repopulate_tree_view(window_id);
+                self.update_current_archive_status();
+                self._update_token_count_and_request_display();
+
+                app_info!(self, "Opened project folder: {:?}", root_folder_path);
+
+                self.initiate_profile_selection_or_creation(window_id);
+            }
+            Some(PendingAction::CreatingNewProfileGetRoot) => {
+                let profile_name = match pending_new_profile_name {
+                    Some(name) => name,
+                    None => {
+                        app_warn!(
+                            self,
+                            "FolderPickerDialogCompleted but no pending profile name. Re-initiating profile selection."
+                        );
+                        self.initiate_profile_selection_or_creation(window_id);
+                        return;
+                    }
+                };
+
+                log::debug!(
+                    "Creating new profile '{profile_name}' with root folder {root_folder_path:?}."
+                );
+                let profile_name = match ProfileName::new(profile_name) {
+                    Ok(pn) => pn,
+                    Err(_) => {
+                        app_error!(
+                            self,
+                            "Invalid profile name. Please use letters, numbers, spaces, underscores, or hyphens."
+                        );
+                        return;
+                    }
+                };
+
+                let new_profile_dto = Profile::new(profile_name.clone(), root_folder_path.clone());
+
+                let Some(project_ctx) = self.require_active_project("create a profile") else {
+                    return;
+                };
+
+                match self.profile_manager.save_profile(
+                    &project_ctx,
+                    &new_profile_dto,
+                    APP_NAME_FOR_PROFILES,
+                ) {
+                    Ok(_) => {
+                        log::debug!("Successfully saved new profile '{}'.", new_profile_dto.name);
+                        let operation_status_message =
+                            format!("New profile '{}' created and loaded.", new_profile_dto.name);
+
+                        self.persist_last_project_path(&project_ctx);
+                        if let Ok(pn) = ProfileName::new(&new_profile_dto.name) {
+                            self.persist_last_profile_for_project(&project_ctx, &pn);
+                        }
+                        self._activate_profile_and_show_window(
+                            window_id,
+                            new_profile_dto,
+                            operation_status_message,
+                        );
+                    }
+                    Err(e) => {
+                        app_error!(
+                            self,
+                            "Failed to save new profile '{}': {:?}. Please try again.",
+                            profile_name,
+                            e
+                        );
+                        self.initiate_profile_selection_or_creation(window_id);
+                    }
+                }
+            }
+            _ => {
+                app_warn!(
+                    self,
+                    "FolderPickerDialogCompleted with unexpected pending action: {:?}",
+                    pending_action
+                );
+            }
+        }
+    }
+
+    fn _update_window_title_with_profile_and_archive(&mut self, window_id: WindowId) {
+        assert!(
+            self.ui_state
+                .as_ref()
+                .is_some_and(|s| s.window_id() == window_id),
+            "_update_window_title_with_profile_and_archive called with mismatching window ID or no UI state."
+        );
+
+        let app_data_ops_guard = self.app_session_data_ops.lock().unwrap();
+        let mut title = MainWindowUiState::compose_window_title(&*app_data_ops_guard);
+        drop(app_data_ops_guard);
+
+        if let Some(project_ctx) = &self.active_project {
+            title = format!("{} (Project: {})", title, project_ctx.display_name());
+        }
+
+        self.synchronous_command_queue
+            .push_back(PlatformCommand::SetWindowTitle { window_id, title });
+    }
+
+    fn handle_menu_set_archive_path_clicked(&mut self) {
+        if self.active_project.is_none() {
+            app_warn!(self, "Cannot set archive path: No project folder is open.");
+            return;
+        }
+
+        let ui_state_mut = match self.ui_state.as_mut() {
+            Some(s) => s,
+            None => {
+                log::warn!("Cannot handle SetArchivePath: No UI state (main window).");
+                return;
+            }
+        };
+
+        log::debug!(
+            "Menu action {:?} received by AppLogic.",
+            ui_constants::MENU_ACTION_SET_ARCHIVE_PATH
+        );
+        let (current_profile_name_opt, current_archive_path_opt, current_root_path) = {
+            let data = self.app_session_data_ops.lock().unwrap();
+            (
+                data.get_profile_name(),
+                data.get_archive_path(),
+                data.get_root_path_for_scan(),
+            )
+        };
+
+        if current_profile_name_opt.is_none() {
+            app_warn!(self, "Cannot set archive path: No profile is active.");
+            return;
+        }
+
+        ui_state_mut.set_pending_action(Some(PendingAction::SettingArchivePath));
+
+        let default_filename = current_archive_path_opt
+            .as_ref()
+            .and_then(|ap| ap.file_name())
+            .map(|os_name| os_name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| {
+                current_profile_name_opt
+                    .as_ref()
+                    .map(|p_name| core::profiles::sanitize_profile_name(p_name) + ".txt")
+                    .unwrap_or_else(|| "archive.txt".to_string())
+            });
+
+        let initial_dir_for_dialog = current_archive_path_opt
+            .as_ref()
+            .and_then(|ap| ap.parent().map(PathBuf::from))
+            .or(Some(current_root_path));
+
+        self.synchronous_command_queue
+            .push_back(PlatformCommand::ShowSaveFileDialog {
+                window_id: ui_state_mut.window_id(),
+                title: "Set Archive File Path".to_string(),
+                default_filename,
+                filter_spec: "Text Files (*.txt)\0*.txt\0All Files (*.*)\0*.*\0\0".to_string(),
+                initial_dir: initial_dir_for_dialog,
+            });
+    }
+
+    /*
+     * Handles the "Archive Format..." menu action by asking for one of the supported
+     * format names, pre-filled with the active profile's current format.
+     */
+    fn handle_menu_set_archive_format_clicked(&mut self) {
+        let Some(window_id) = self.ui_state.as_ref().map(|s| s.window_id()) else {
+            log::warn!("Cannot set archive format: No UI state (main window).");
+            return;
+        };
+
+        log::debug!(
+            "Menu action {:?} received by AppLogic.",
+            ui_constants::MENU_ACTION_SET_ARCHIVE_FORMAT
+        );
+        let (profile_name_opt, current_format) = {
+            let data = self.app_session_data_ops.lock().unwrap();
+            (data.get_profile_name(), data.get_archive_options().format)
+        };
+        if profile_name_opt.is_none() {
+            app_warn!(self, "Cannot set archive format: No profile is active.");
+            return;
+        }
+
+        let choices: Vec<&str> = ArchiveFormat::ALL
+            .iter()
+            .map(|format| format.display_name())
+            .collect();
+        self.synchronous_command_queue
+            .push_back(PlatformCommand::ShowInputDialog {
+                window_id,
+                title: "Archive Format".to_string(),
+                prompt: format!("Enter the archive format ({}):", choices.join(", ")),
+                default_text: Some(current_format.display_name().to_string()),
+                context_tag: Some(ARCHIVE_FORMAT_CONTEXT_TAG.to_string()),
+            });
+    }
+
+    fn _handle_input_dialog_for_archive_format(
+        &mut self,
+        window_id: WindowId,
+        format_input_opt: Option<String>,
+    ) {
+        let Some(format_text) = format_input_opt else {
+            log::debug!("Archive format input cancelled.");
+            return;
+        };
+
+        let Some(format) = ArchiveFormat::from_display_name(&format_text) else {
+            app_warn!(self, "Unknown archive format '{}'.", format_text.trim());
+            self.synchronous_command_queue
+                .push_back(PlatformCommand::ShowInputDialog {
+                    window_id,
+                    title: "Archive Format".to_string(),
+                    prompt: "Unknown format. Enter Plain, Markdown, XML, JSON or JSONL:"
+                        .to_string(),
+                    default_text: Some(format_text),
+                    context_tag: Some(ARCHIVE_FORMAT_CONTEXT_TAG.to_string()),
+                });
+            return;
+        };
+
+        let mut options = self
+            .app_session_data_ops
+            .lock()
+            .unwrap()
+            .get_archive_options();
+        options.format = format;
+        if self.save_archive_options_to_active_profile(options) {
+            app_info!(self, "Archive format set to {}.", format.display_name());
+        }
+    }
+
+    /*
+     * Handles the "Split Archive..." menu action. The setting is entered as text: a token
+     * budget per part, "dirs" (optionally followed by a budget) to split by top-level
+     * directory, or "off" to write a single archive file.
+     */
+    fn handle_menu_set_archive_split_clicked(&mut self) {
+        let Some(window_id) = self.ui_state.as_ref().map(|s| s.window_id()) else {
+            log::warn!("Cannot set archive split: No UI state (main window).");
+            return;
+        };
+
+        let (profile_name_opt, current_split) = {
+            let data = self.app_session_data_ops.lock().unwrap();
+            (data.get_profile_name(), data.get_archive_options().split)
+        };
+        if profile_name_opt.is_none() {
+            app_warn!(self, "Cannot set archive split: No profile is active.");
+            return;
+        }
+
+        let current_text = current_split
+            .map(|split| split.to_setting_text())
+            .unwrap_or_else(|| "off".to_string());
+        self.synchronous_command_queue
+            .push_back(PlatformCommand::ShowInputDialog {
+                window_id,
+                title: "Split Archive".to_string(),
+                prompt: "Enter the token budget per part, 'dirs [budget]' to split by top-level directory, or 'off':".to_string(),
+                default_text: Some(current_text),
+                context_tag: Some(ARCHIVE_SPLIT_CONTEXT_TAG.to_string()),
+            });
+    }
+
+    fn _handle_input_dialog_for_archive_split(
+        &mut self,
+        window_id: WindowId,
+        split_input_opt: Option<String>,
+    ) {
+        let Some(split_text) = split_input_opt else {
+            log::debug!("Archive split input cancelled.");
+            return;
+        };
+
+        let split = match ArchiveSplitOptions::parse_setting(&split_text) {
+            Ok(split) => split,
+            Err(message) => {
+                app_warn!(self, "{}.", message);
+                self.synchronous_command_queue
+                    .push_back(PlatformCommand::ShowInputDialog {
+                        window_id,
+                        title: "Split Archive".to_string(),
+                        prompt: "Invalid setting. Enter a token budget (e.g. 100000), 'dirs [budget]' or 'off':".to_string(),
+                        default_text: Some(split_text),
+                        context_tag: Some(ARCHIVE_SPLIT_CONTEXT_TAG.to_string()),
+                    });
+                return;
+            }
+        };
+
+        let mut options = self
+            .app_session_data_ops
+            .lock()
+            .unwrap()
+            .get_archive_options();
+        options.split = split.clone();
+        if self.save_archive_options_to_active_profile(options) {
+            match split {
+                Some(split) if split.max_tokens_per_part > 0 => app_info!(
+                    self,
+                    "Archive will be split by {} into parts of at most {} tokens.",
+                    split.strategy.display_name(),
+                    split.max_tokens_per_part
+                ),
+                Some(split) => app_info!(
+                    self,
+                    "Archive will be split by {}.",
+                    split.strategy.display_name()
+                ),
+                None => app_info!(self, "Archive will be written as a single file."),
+            }
+        }
+    }
+
+    /*
+     * Handles the "Token Budget..." menu action. Accepts a maximum token count for the
+     * selected files, or "off" to disable budget enforcement.
+     */
+    fn handle_menu_set_token_budget_clicked(&mut self) {
+        let Some(window_id) = self.ui_state.as_ref().map(|s| s.window_id()) else {
+            log::warn!("Cannot set token budget: No UI state (main window).");
+            return;
+        };
+
+        let (profile_name_opt, current_budget) = {
+            let data = self.app_session_data_ops.lock().unwrap();
+            (
+                data.get_profile_name(),
+                data.get_archive_options().token_budget,
+            )
+        };
+        if profile_name_opt.is_none() {
+            app_warn!(self, "Cannot set token budget: No profile is active.");
+            return;
+        }
+
+        self.synchronous_command_queue
+            .push_back(PlatformCommand::ShowInputDialog {
+                window_id,
+                title: "Token Budget".to_string(),
+                prompt: "Enter the maximum number of tokens for the archive, or 'off':".to_string(),
+                default_text: Some(
+                    current_budget
+                        .map(|budget| budget.to_string())
+                        .unwrap_or_else(|| "off".to_string()),
+                ),
+                context_tag: Some(TOKEN_BUDGET_CONTEXT_TAG.to_string()),
+            });
+    }
+
+    fn _handle_input_dialog_for_token_budget(
+        &mut self,
+        window_id: WindowId,
+        budget_input_opt: Option<String>,
+    ) {
+        let Some(budget_text) = budget_input_opt else {
+            log::debug!("Token budget input cancelled.");
+            return;
+        };
 
-        let root_folder_path = match path {
-            Some(p) => p,
-            None => {
-                log::debug!("Folder selection cancelled.");
-                if matches!(
-                    pending_action,
-                    Some(PendingAction::CreatingNewProfileGetRoot)
-                ) {
-                    log::debug!("Root folder selection cancelled. Returning to profile selection.");
-                    ui_state_mut.set_pending_new_profile_name(None);
-                    self.initiate_profile_selection_or_creation(window_id);
+        let trimmed = budget_text.trim();
+        let token_budget = if trimmed.is_empty() || trimmed.eq_ignore_ascii_case("off") {
+            None
+        } else {
+            match trimmed.parse::<usize>() {
+                Ok(budget) if budget > 0 => Some(budget),
+                _ => {
+                    app_warn!(self, "'{}' is not a valid token budget.", trimmed);
+                    self.synchronous_command_queue
+                        .push_back(PlatformCommand::ShowInputDialog {
+                            window_id,
+                            title: "Token Budget".to_string(),
+                            prompt: "Invalid budget. Enter a positive number of tokens, or 'off':"
+                                .to_string(),
+                            default_text: Some(budget_text),
+                            context_tag: Some(TOKEN_BUDGET_CONTEXT_TAG.to_string()),
+                        });
+                    return;
                 }
-                return;
             }
         };
 
-        let _ = ui_state_mut;
+        let mut options = self
+            .app_session_data_ops
+            .lock()
+            .unwrap()
+            .get_archive_options();
+        options.token_budget = token_budget;
+        if self.save_archive_options_to_active_profile(options) {
+            match token_budget {
+                Some(budget) => app_info!(self, "Token budget set to {} tokens.", budget),
+                None => app_info!(self, "Token budget disabled."),
+            }
+        }
+    }
 
-        match pending_action {
-            Some(PendingAction::OpeningProjectFolder) => {
-                let ctx = ProjectContext::new(root_folder_path.clone());
-                log::debug!("Opened project folder: {:?}", ctx.display_name());
+    /*
+     * Handles the "Content Transforms..." menu action. The transforms are entered as a
+     * comma-separated list, each optionally scoped by a glob, e.g.
+     * "strip-comments@*.rs, collapse-blank-lines"; "off" clears the list.
+     * [ArchiveGenContentTransformsV1]
+     */
+    fn handle_menu_set_content_transforms_clicked(&mut self) {
+        let Some(window_id) = self.ui_state.as_ref().map(|s| s.window_id()) else {
+            log::warn!("Cannot set content transforms: No UI state (main window).");
+            return;
+        };
 
-                self.persist_last_project_path(&ctx);
-                self.active_project = Some(ctx);
+        let (profile_name_opt, current_transforms) = {
+            let data = self.app_session_data_ops.lock().unwrap();
+            (
+                data.get_profile_name(),
+                data.get_archive_options().transforms,
+            )
+        };
+        if profile_name_opt.is_none() {
+            app_warn!(self, "Cannot set content transforms: No profile is active.");
+            return;
+        }
 
-                // Cancel any ongoing asynchronous work
-                self.cancel_token_recalculation();
-                self.cancel_content_search();
+        self.synchronous_command_queue
+            .push_back(PlatformCommand::ShowInputDialog {
+                window_id,
+                title: "Content Transforms".to_string(),
+                prompt: format!(
+                    "Enter transforms in order as 'name[@glob]', separated by commas, or 'off'. Available: {}:",
+                    ContentTransform::ALL_NAMES.join(", ")
+                ),
+                default_text: Some(content_transform::to_setting_text(&current_transforms)),
+                context_tag: Some(CONTENT_TRANSFORMS_CONTEXT_TAG.to_string()),
+            });
+    }
 
-                // Clear session data (Step 1.4: Replace current project and profile)
-                self.app_session_data_ops.lock().unwrap().clear();
+    fn _handle_input_dialog_for_content_transforms(
+        &mut self,
+        window_id: WindowId,
+        transforms_input_opt: Option<String>,
+    ) {
+        let Some(transforms_text) = transforms_input_opt else {
+            log::debug!("Content transforms input cancelled.");
+            return;
+        };
 
-                // Update title
-                self._update_window_title_with_profile_and_archive(window_id);
+        let transforms = match content_transform::parse_setting(&transforms_text) {
+            Ok(transforms) => transforms,
+            Err(message) => {
+                app_warn!(self, "{}.", message);
+                self.synchronous_command_queue
+                    .push_back(PlatformCommand::ShowInputDialog {
+                        window_id,
+                        title: "Content Transforms".to_string(),
+                        prompt: "Invalid setting. Enter e.g. 'strip-comments@*.rs, convert-tabs:4' or 'off':".to_string(),
+                        default_text: Some(transforms_text),
+                        context_tag: Some(CONTENT_TRANSFORMS_CONTEXT_TAG.to_string()),
+                    });
+                return;
+            }
+        };
 
-                // Update UI to reflect empty state
-                self.repopulate_tree_view(window_id);
-                self.update_current_archive_status();
-                self._update_token_count_and_request_display();
+        let mut options = self
+            .app_session_data_ops
+            .lock()
+            .unwrap()
+            .get_archive_options();
+        let count = transforms.len();
+        options.transforms = transforms;
+        if self.save_archive_options_to_active_profile(options) {
+            if count == 0 {
+                app_info!(self, "Content transforms disabled.");
+            } else {
+                app_info!(
+                    self,
+                    "{} content transform(s) will be applied to the archive.",
+                    count
+                );
+            }
+            // The estimate counts files as transformed, so it changes with the rules.
+            self.request_token_recalculation(true);
+        }
+    }
 
-                app_info!(self, "Opened project folder: {:?}", root_folder_path);
+    /*
+     * Handles the "Directory Tree..." menu action: asks whether the archive starts with an
+     * ASCII tree of the project, covering only the selected files or all scanned files,
+     * and how many directory levels to expand. [ArchiveGenDirectoryTreeV1]
+     */
+    fn handle_menu_set_directory_tree_clicked(&mut self) {
+        let Some(window_id) = self.ui_state.as_ref().map(|s| s.window_id()) else {
+            log::warn!("Cannot set directory tree: No UI state (main window).");
+            return;
+        };
 
-                self.initiate_profile_selection_or_creation(window_id);
+        let (profile_name_opt, current_tree) = {
+            let data = self.app_session_data_ops.lock().unwrap();
+            (
+                data.get_profile_name(),
+                data.get_archive_options().directory_tree,
+            )
+        };
+        if profile_name_opt.is_none() {
+            app_warn!(self, "Cannot set directory tree: No profile is active.");
+            return;
+        }
+
+        self.synchronous_command_queue
+            .push_back(PlatformCommand::ShowInputDialog {
+                window_id,
+                title: "Directory Tree".to_string(),
+                prompt: "Enter 'selected' or 'all' files, optionally with ':depth' (e.g. 'all:3'), or 'off':".to_string(),
+                default_text: Some(DirectoryTreeOptions::to_setting_text(current_tree.as_ref())),
+                context_tag: Some(DIRECTORY_TREE_CONTEXT_TAG.to_string()),
+            });
+    }
+
+    fn _handle_input_dialog_for_directory_tree(
+        &mut self,
+        window_id: WindowId,
+        tree_input_opt: Option<String>,
+    ) {
+        let Some(tree_text) = tree_input_opt else {
+            log::debug!("Directory tree input cancelled.");
+            return;
+        };
+
+        let directory_tree = match DirectoryTreeOptions::parse_setting(&tree_text) {
+            Ok(directory_tree) => directory_tree,
+            Err(message) => {
+                app_warn!(self, "{}", message);
+                self.synchronous_command_queue
+                    .push_back(PlatformCommand::ShowInputDialog {
+                        window_id,
+                        title: "Directory Tree".to_string(),
+                        prompt: "Invalid setting. Enter e.g. 'selected', 'all:3' or 'off':"
+                            .to_string(),
+                        default_text: Some(tree_text),
+                        context_tag: Some(DIRECTORY_TREE_CONTEXT_TAG.to_string()),
+                    });
+                return;
             }
-            Some(PendingAction::CreatingNewProfileGetRoot) => {
-                let profile_name = match pending_new_profile_name {
-                    Some(name) => name,
-                    None => {
-                        app_warn!(
-                            self,
-                            "FolderPickerDialogCompleted but no pending profile name. Re-initiating profile selection."
-                        );
-                        self.initiate_profile_selection_or_creation(window_id);
-                        return;
-                    }
-                };
+        };
 
-                log::debug!(
-                    "Creating new profile '{profile_name}' with root folder {root_folder_path:?}."
-                );
-                let profile_name = match ProfileName::new(profile_name) {
-                    Ok(pn) => pn,
-                    Err(_) => {
-                        app_error!(
-                            self,
-                            "Invalid profile name. Please use letters, numbers, spaces, underscores, or hyphens."
-                        );
-                        return;
-                    }
-                };
+        let mut options = self
+            .app_session_data_ops
+            .lock()
+            .unwrap()
+            .get_archive_options();
+        options.directory_tree = directory_tree;
+        if self.save_archive_options_to_active_profile(options) {
+            match directory_tree {
+                None => app_info!(self, "Directory tree disabled."),
+                Some(tree) if tree.include_unselected => app_info!(
+                    self,
+                    "The archive will start with a tree of all project files."
+                ),
+                Some(_) => app_info!(
+                    self,
+                    "The archive will start with a tree of the selected files."
+                ),
+            }
+        }
+    }
 
-                let new_profile_dto = Profile::new(profile_name.clone(), root_folder_path.clone());
+    /*
+     * Handles the "Secret Scanning..." menu action: the policy (off, warn, redact or abort)
+     * followed by optional custom rules and allowlist entries, separated by ';'.
+     * [ArchiveGenSecretScanV1]
+     */
+    fn handle_menu_set_secret_scan_clicked(&mut self) {
+        let Some(window_id) = self.ui_state.as_ref().map(|s| s.window_id()) else {
+            log::warn!("Cannot set secret scanning: No UI state (main window).");
+            return;
+        };
 
-                let Some(project_ctx) = self.require_active_project("create a profile") else {
-                    return;
-                };
+        let (profile_name_opt, current_scan) = {
+            let data = self.app_session_data_ops.lock().unwrap();
+            (
+                data.get_profile_name(),
+                data.get_archive_options().secret_scan,
+            )
+        };
+        if profile_name_opt.is_none() {
+            app_warn!(self, "Cannot set secret scanning: No profile is active.");
+            return;
+        }
 
-                match self.profile_manager.save_profile(
-                    &project_ctx,
-                    &new_profile_dto,
-                    APP_NAME_FOR_PROFILES,
-                ) {
-                    Ok(_) => {
-                        log::debug!("Successfully saved new profile '{}'.", new_profile_dto.name);
-                        let operation_status_message =
-                            format!("New profile '{}' created and loaded.", new_profile_dto.name);
+        self.synchronous_command_queue
+            .push_back(PlatformCommand::ShowInputDialog {
+                window_id,
+                title: "Secret Scanning".to_string(),
+                prompt: "Enter the policy (off, warn, redact or abort), optionally followed by '; rule:name=regex', '; allow:regex' or '; allow-path:glob':".to_string(),
+                default_text: Some(secret_scan::to_setting_text(&current_scan)),
+                context_tag: Some(SECRET_SCAN_CONTEXT_TAG.to_string()),
+            });
+    }
 
-                        self.persist_last_project_path(&project_ctx);
-                        if let Ok(pn) = ProfileName::new(&new_profile_dto.name) {
-                            self.persist_last_profile_for_project(&project_ctx, &pn);
-                        }
-                        self._activate_profile_and_show_window(
-                            window_id,
-                            new_profile_dto,
-                            operation_status_message,
-                        );
-                    }
-                    Err(e) => {
-                        app_error!(
-                            self,
-                            "Failed to save new profile '{}': {:?}. Please try again.",
-                            profile_name,
-                            e
-                        );
-                        self.initiate_profile_selection_or_creation(window_id);
-                    }
+    fn _handle_input_dialog_for_secret_scan(
+        &mut self,
+        window_id: WindowId,
+        scan_input_opt: Option<String>,
+    ) {
+        let Some(scan_text) = scan_input_opt else {
+            log::debug!("Secret scanning input cancelled.");
+            return;
+        };
+
+        let secret_scan = match secret_scan::parse_setting(&scan_text) {
+            Ok(secret_scan) => secret_scan,
+            Err(message) => {
+                app_warn!(self, "{}.", message);
+                self.synchronous_command_queue
+                    .push_back(PlatformCommand::ShowInputDialog {
+                        window_id,
+                        title: "Secret Scanning".to_string(),
+                        prompt: "Invalid setting. Enter e.g. 'redact; allow-path:*.pem' or 'warn':"
+                            .to_string(),
+                        default_text: Some(scan_text),
+                        context_tag: Some(SECRET_SCAN_CONTEXT_TAG.to_string()),
+                    });
+                return;
+            }
+        };
+
+        let mut options = self
+            .app_session_data_ops
+            .lock()
+            .unwrap()
+            .get_archive_options();
+        let policy = secret_scan.policy;
+        options.secret_scan = secret_scan;
+        if self.save_archive_options_to_active_profile(options) {
+            match policy {
+                SecretPolicy::Off => app_info!(self, "Secret scanning disabled."),
+                SecretPolicy::Warn => {
+                    app_info!(self, "Possible secrets will be reported when archiving.")
+                }
+                SecretPolicy::Redact => {
+                    app_info!(self, "Possible secrets will be redacted from the archive.")
+                }
+                SecretPolicy::Abort => {
+                    app_info!(self, "Archiving will stop when possible secrets are found.")
                 }
             }
-            _ => {
-                app_warn!(
-                    self,
-                    "FolderPickerDialogCompleted with unexpected pending action: {:?}",
-                    pending_action
-                );
+        }
+    }
+
+    /*
+     * Handles the "Binary and Large Files..." menu action: the maximum file size and what
+     * archive generation does with binary and oversize files. [ArchiveGenBinaryFilesV1]
+     */
+    fn handle_menu_set_content_limits_clicked(&mut self) {
+        let Some(window_id) = self.ui_state.as_ref().map(|s| s.window_id()) else {
+            log::warn!("Cannot set content limits: No UI state (main window).");
+            return;
+        };
+
+        let (profile_name_opt, current_limits) = {
+            let data = self.app_session_data_ops.lock().unwrap();
+            (
+                data.get_profile_name(),
+                data.get_archive_options().content_limits,
+            )
+        };
+        if profile_name_opt.is_none() {
+            app_warn!(self, "Cannot set content limits: No profile is active.");
+            return;
+        }
+
+        self.synchronous_command_queue
+            .push_back(PlatformCommand::ShowInputDialog {
+                window_id,
+                title: "Binary and Large Files".to_string(),
+                prompt: format!(
+                    "Enter 'binary:<policy>, oversize:<policy>, max:<size or none>' (policies: {}):",
+                    ContentPolicy::ALL_NAMES.join(", ")
+                ),
+                default_text: Some(content_class::to_setting_text(&current_limits)),
+                context_tag: Some(CONTENT_LIMITS_CONTEXT_TAG.to_string()),
+            });
+    }
+
+    fn _handle_input_dialog_for_content_limits(
+        &mut self,
+        window_id: WindowId,
+        limits_input_opt: Option<String>,
+    ) {
+        let Some(limits_text) = limits_input_opt else {
+            log::debug!("Content limits input cancelled.");
+            return;
+        };
+
+        let content_limits = match content_class::parse_setting(&limits_text) {
+            Ok(content_limits) => content_limits,
+            Err(message) => {
+                app_warn!(self, "{}.", message);
+                self.synchronous_command_queue
+                    .push_back(PlatformCommand::ShowInputDialog {
+                        window_id,
+                        title: "Binary and Large Files".to_string(),
+                        prompt: "Invalid setting. Enter e.g. 'binary:skip, oversize:placeholder, max:512KB':"
+                            .to_string(),
+                        default_text: Some(limits_text),
+                        context_tag: Some(CONTENT_LIMITS_CONTEXT_TAG.to_string()),
+                    });
+                return;
             }
+        };
+
+        let mut options = self
+            .app_session_data_ops
+            .lock()
+            .unwrap()
+            .get_archive_options();
+        options.content_limits = content_limits;
+        if self.save_archive_options_to_active_profile(options) {
+            app_info!(
+                self,
+                "Binary and large files: {}.",
+                content_class::to_setting_text(&content_limits)
+            );
         }
     }
 
-    fn _update_window_title_with_profile_and_archive(&mut self, window_id: WindowId) {
-        assert!(
-            self.ui_state
-                .as_ref()
-                .is_some_and(|s| s.window_id() == window_id),
-            "_update_window_title_with_profile_and_archive called with mismatching window ID or no UI state."
-        );
-
-        let app_data_ops_guard = self.app_session_data_ops.lock().unwrap();
-        let mut title = MainWindowUiState::compose_window_title(&*app_data_ops_guard);
-        drop(app_data_ops_guard);
+    /*
+     * Handles the "Source Encodings..." menu action: fallback encodings by glob for files
+     * without a byte order mark. [FutureEncodingSupportOtherV1]
+     */
+    fn handle_menu_set_encodings_clicked(&mut self) {
+        let Some(window_id) = self.ui_state.as_ref().map(|s| s.window_id()) else {
+            log::warn!("Cannot set source encodings: No UI state (main window).");
+            return;
+        };
 
-        if let Some(project_ctx) = &self.active_project {
-            title = format!("{} (Project: {})", title, project_ctx.display_name());
+        let (profile_name_opt, current_rules) = {
+            let data = self.app_session_data_ops.lock().unwrap();
+            (
+                data.get_profile_name(),
+                data.get_archive_options().encodings,
+            )
+        };
+        if profile_name_opt.is_none() {
+            app_warn!(self, "Cannot set source encodings: No profile is active.");
+            return;
         }
 
         self.synchronous_command_queue
-            .push_back(PlatformCommand::SetWindowTitle { window_id, title });
+            .push_back(PlatformCommand::ShowInputDialog {
+                window_id,
+                title: "Source Encodings".to_string(),
+                prompt: format!(
+                    "Enter comma-separated 'encoding@glob' fallbacks for files without a BOM (encodings: {}), or 'off':",
+                    text_encoding::TextEncoding::ALL_NAMES.join(", ")
+                ),
+                default_text: Some(text_encoding::to_setting_text(&current_rules)),
+                context_tag: Some(ENCODINGS_CONTEXT_TAG.to_string()),
+            });
     }
 
-    fn handle_menu_set_archive_path_clicked(&mut self) {
-        if self.active_project.is_none() {
-            app_warn!(self, "Cannot set archive path: No project folder is open.");
+    fn _handle_input_dialog_for_encodings(
+        &mut self,
+        window_id: WindowId,
+        encodings_input_opt: Option<String>,
+    ) {
+        let Some(encodings_text) = encodings_input_opt else {
+            log::debug!("Source encodings input cancelled.");
             return;
-        }
+        };
 
-        let ui_state_mut = match self.ui_state.as_mut() {
-            Some(s) => s,
-            None => {
-                log::warn!("Cannot handle SetArchivePath: No UI state (main window).");
+        let rules = match text_encoding::parse_setting(&encodings_text) {
+            Ok(rules) => rules,
+            Err(message) => {
+                app_warn!(self, "{}.", message);
+                self.synchronous_command_queue
+                    .push_back(PlatformCommand::ShowInputDialog {
+                        window_id,
+                        title: "Source Encodings".to_string(),
+                        prompt: "Invalid setting. Enter e.g. 'windows-1252@legacy*, utf-16le@*.rc' or 'off':"
+                            .to_string(),
+                        default_text: Some(encodings_text),
+                        context_tag: Some(ENCODINGS_CONTEXT_TAG.to_string()),
+                    });
                 return;
             }
         };
 
-        log::debug!(
-            "Menu action {:?} received by AppLogic.",
-            ui_constants::MENU_ACTION_SET_ARCHIVE_PATH
-        );
-        let (current_profile_name_opt, current_archive_path_opt, current_root_path) = {
+        let mut options = self
+            .app_session_data_ops
+            .lock()
+            .unwrap()
+            .get_archive_options();
+        let summary = text_encoding::to_setting_text(&rules);
+        options.encodings = rules;
+        if self.save_archive_options_to_active_profile(options) {
+            app_info!(self, "Source encoding fallbacks: {}.", summary);
+        }
+    }
+
+    /*
+     * Handles the "Line Numbers..." menu action: off, all files, or the files matching a
+     * list of globs get a line-number gutter in the archive. [ArchiveGenLineNumbersV1]
+     */
+    fn handle_menu_set_line_numbers_clicked(&mut self) {
+        let Some(window_id) = self.ui_state.as_ref().map(|s| s.window_id()) else {
+            log::warn!("Cannot set line numbers: No UI state (main window).");
+            return;
+        };
+
+        let (profile_name_opt, current_line_numbers) = {
             let data = self.app_session_data_ops.lock().unwrap();
             (
                 data.get_profile_name(),
-                data.get_archive_path(),
-                data.get_root_path_for_scan(),
+                data.get_archive_options().line_numbers,
             )
         };
+        if profile_name_opt.is_none() {
+            app_warn!(self, "Cannot set line numbers: No profile is active.");
+            return;
+        }
 
-        if current_profile_name_opt.is_none() {
-            app_warn!(self, "Cannot set archive path: No profile is active.");
+        self.synchronous_command_queue
+            .push_back(PlatformCommand::ShowInputDialog {
+                window_id,
+                title: "Line Numbers".to_string(),
+                prompt: "Number the lines of archived files: 'off', 'all', or comma-separated globs (e.g. '*.rs, *.toml'):".to_string(),
+                default_text: Some(line_numbers::to_setting_text(current_line_numbers.as_ref())),
+                context_tag: Some(LINE_NUMBERS_CONTEXT_TAG.to_string()),
+            });
+    }
+
+    fn _handle_input_dialog_for_line_numbers(
+        &mut self,
+        window_id: WindowId,
+        line_numbers_input_opt: Option<String>,
+    ) {
+        let Some(line_numbers_text) = line_numbers_input_opt else {
+            log::debug!("Line numbers input cancelled.");
             return;
+        };
+
+        let line_numbers = match line_numbers::parse_setting(&line_numbers_text) {
+            Ok(line_numbers) => line_numbers,
+            Err(message) => {
+                app_warn!(self, "{}.", message);
+                self.synchronous_command_queue
+                    .push_back(PlatformCommand::ShowInputDialog {
+                        window_id,
+                        title: "Line Numbers".to_string(),
+                        prompt: "Invalid setting. Enter 'off', 'all' or globs such as '*.rs':"
+                            .to_string(),
+                        default_text: Some(line_numbers_text),
+                        context_tag: Some(LINE_NUMBERS_CONTEXT_TAG.to_string()),
+                    });
+                return;
+            }
+        };
+
+        let mut options = self
+            .app_session_data_ops
+            .lock()
+            .unwrap()
+            .get_archive_options();
+        let summary = line_numbers::to_setting_text(line_numbers.as_ref());
+        options.line_numbers = line_numbers;
+        if self.save_archive_options_to_active_profile(options) {
+            app_info!(self, "Line numbers: {}.", summary);
+            // The estimate counts numbered files with their gutter.
+            self.request_token_recalculation(true);
         }
+    }
 
-        ui_state_mut.set_pending_action(Some(PendingAction::SettingArchivePath));
+    /*
+     * Handles the "Include as Outline" menu action: switches the file shown in the viewer
+     * between full inclusion and outline-only inclusion, where the archive holds just its
+     * signatures. [FileSelOutlineV1]
+     */
+    fn handle_menu_toggle_outline_clicked(&mut self) {
+        let Some(window_id) = self.ui_state.as_ref().map(|s| s.window_id()) else {
+            log::warn!("Cannot toggle outline: No UI state (main window).");
+            return;
+        };
+        let Some(file_path) = self.active_viewer_file_path() else {
+            app_warn!(
+                self,
+                "Select a file in the tree to include it as an outline."
+            );
+            return;
+        };
 
-        let default_filename = current_archive_path_opt
-            .as_ref()
-            .and_then(|ap| ap.file_name())
-            .map(|os_name| os_name.to_string_lossy().into_owned())
-            .unwrap_or_else(|| {
-                current_profile_name_opt
-                    .as_ref()
-                    .map(|p_name| core::profiles::sanitize_profile_name(p_name) + ".txt")
-                    .unwrap_or_else(|| "archive.txt".to_string())
-            });
+        let changes = {
+            let mut data = self.app_session_data_ops.lock().unwrap();
+            let new_state = match data.get_node_attributes_for_path(&file_path) {
+                Some((SelectionState::Outline, _)) => SelectionState::Selected,
+                _ => SelectionState::Outline,
+            };
+            data.update_node_state_and_collect_changes(&file_path, new_state, &*self.state_manager)
+        };
 
-        let initial_dir_for_dialog = current_archive_path_opt
-            .as_ref()
-            .and_then(|ap| ap.parent().map(PathBuf::from))
-            .or(Some(current_root_path));
+        for (changed_path, new_state) in &changes {
+            let Some(item_id) = self
+                .ui_state
+                .as_ref()
+                .and_then(|s| s.tree_item_id_for_path(changed_path))
+            else {
+                continue;
+            };
+            let text = self.build_tree_item_display_text(changed_path);
+            self.synchronous_command_queue
+                .push_back(PlatformCommand::UpdateTreeItemVisualState {
+                    window_id,
+                    control_id: ui_constants::ID_TREEVIEW_CTRL,
+                    item_id,
+                    new_state: match new_state {
+                        SelectionState::Selected | SelectionState::Outline => CheckState::Checked,
+                        _ => CheckState::Unchecked,
+                    },
+                });
+            self.synchronous_command_queue
+                .push_back(PlatformCommand::UpdateTreeItemText {
+                    window_id,
+                    control_id: ui_constants::ID_TREEVIEW_CTRL,
+                    item_id,
+                    text,
+                });
+        }
+
+        let file_name = file_path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        if changes
+            .iter()
+            .any(|(_, state)| *state == SelectionState::Outline)
+        {
+            app_info!(self, "'{}' will be included as an outline.", file_name);
+        } else {
+            app_info!(self, "'{}' will be included in full.", file_name);
+        }
+        self.update_current_archive_status();
+        self._update_token_count_and_request_display();
+    }
+
+    /*
+     * Toggles the table-of-contents manifest at the top of the archive for the active
+     * profile. [ArchiveGenManifestV1]
+     */
+    fn handle_menu_toggle_archive_manifest_clicked(&mut self) {
+        let (profile_name_opt, mut options) = {
+            let data = self.app_session_data_ops.lock().unwrap();
+            (data.get_profile_name(), data.get_archive_options())
+        };
+        if profile_name_opt.is_none() {
+            app_warn!(
+                self,
+                "Cannot change the archive manifest: No profile is active."
+            );
+            return;
+        }
+
+        options.include_manifest = !options.include_manifest;
+        let include_manifest = options.include_manifest;
+        if self.save_archive_options_to_active_profile(options) {
+            if include_manifest {
+                app_info!(self, "Archive manifest enabled.");
+            } else {
+                app_info!(self, "Archive manifest disabled.");
+            }
+        }
+    }
+
+    /*
+     * Returns the file currently shown in the viewer, which is the target of the
+     * "File Priority..." action. Directories are not supported.
+     */
+    fn active_viewer_file_path(&self) -> Option<PathBuf> {
+        let ui_state = self.ui_state.as_ref()?;
+        let path = ui_state.path_for_tree_item(ui_state.active_viewer_item_id()?)?;
+        let data = self.app_session_data_ops.lock().unwrap();
+        match data.get_node_attributes_for_path(&path) {
+            Some((_, false)) => Some(path),
+            _ => None,
+        }
+    }
+
+    /*
+     * Handles the "File Priority..." menu action for the file selected in the tree.
+     * The priority decides what the token budget drops first.
+     */
+    fn handle_menu_set_file_priority_clicked(&mut self) {
+        let Some(window_id) = self.ui_state.as_ref().map(|s| s.window_id()) else {
+            log::warn!("Cannot set file priority: No UI state (main window).");
+            return;
+        };
+        let Some(file_path) = self.active_viewer_file_path() else {
+            app_warn!(self, "Select a file in the tree to set its priority.");
+            return;
+        };
 
+        let current_priority = self
+            .app_session_data_ops
+            .lock()
+            .unwrap()
+            .get_archive_options()
+            .file_priorities
+            .get(&file_path)
+            .copied()
+            .unwrap_or_default();
+        let file_name = file_path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
         self.synchronous_command_queue
-            .push_back(PlatformCommand::ShowSaveFileDialog {
-                window_id: ui_state_mut.window_id(),
-                title: "Set Archive File Path".to_string(),
-                default_filename,
-                filter_spec: "Text Files (*.txt)\0*.txt\0All Files (*.*)\0*.*\0\0".to_string(),
-                initial_dir: initial_dir_for_dialog,
+            .push_back(PlatformCommand::ShowInputDialog {
+                window_id,
+                title: "File Priority".to_string(),
+                prompt: format!("Enter the priority of '{file_name}' (pinned, normal, optional):"),
+                default_text: Some(current_priority.display_name().to_string()),
+                context_tag: Some(FILE_PRIORITY_CONTEXT_TAG.to_string()),
             });
     }
 
+    fn _handle_input_dialog_for_file_priority(
+        &mut self,
+        window_id: WindowId,
+        priority_input_opt: Option<String>,
+    ) {
+        let Some(priority_text) = priority_input_opt else {
+            log::debug!("File priority input cancelled.");
+            return;
+        };
+        let Some(file_path) = self.active_viewer_file_path() else {
+            app_warn!(self, "Select a file in the tree to set its priority.");
+            return;
+        };
+        let Some(priority) = FilePriority::from_display_name(&priority_text) else {
+            app_warn!(self, "Unknown file priority '{}'.", priority_text.trim());
+            self.synchronous_command_queue
+                .push_back(PlatformCommand::ShowInputDialog {
+                    window_id,
+                    title: "File Priority".to_string(),
+                    prompt: "Unknown priority. Enter pinned, normal or optional:".to_string(),
+                    default_text: Some(priority_text),
+                    context_tag: Some(FILE_PRIORITY_CONTEXT_TAG.to_string()),
+                });
+            return;
+        };
+
+        let mut options = self
+            .app_session_data_ops
+            .lock()
+            .unwrap()
+            .get_archive_options();
+        if priority == FilePriority::Normal {
+            options.file_priorities.remove(&file_path);
+        } else {
+            options.file_priorities.insert(file_path.clone(), priority);
+        }
+        if self.save_archive_options_to_active_profile(options) {
+            app_info!(
+                self,
+                "Priority of '{}' set to {}.",
+                file_path.display(),
+                priority.display_name()
+            );
+        }
+    }
+
+    /*
+     * Persists updated archive options into the active profile on disk and, once the save
+     * succeeds, into the running session. Returns `true` if the options were saved.
+     */
+    fn save_archive_options_to_active_profile(&mut self, options: ArchiveOptions) -> bool {
+        let (profile_to_save, profile_name) = {
+            let data = self.app_session_data_ops.lock().unwrap();
+            match data.get_profile_name() {
+                Some(name) if !name.is_empty() => {
+                    let mut snapshot = data.create_profile_snapshot();
+                    snapshot.archive_options = options.clone();
+                    (snapshot, name)
+                }
+                _ => {
+                    app_warn!(self, "Cannot update archive options: No profile is active.");
+                    return false;
+                }
+            }
+        };
+
+        let Some(project_ctx) = self.require_active_project("save the profile") else {
+            return false;
+        };
+
+        match self.profile_manager.save_profile(
+            &project_ctx,
+            &profile_to_save,
+            APP_NAME_FOR_PROFILES,
+        ) {
+            Ok(_) => {
+                self.app_session_data_ops
+                    .lock()
+                    .unwrap()
+                    .set_archive_options(options);
+                true
+            }
+            Err(e) => {
+                app_error!(
+                    self,
+                    "Failed to save archive options for profile '{}': {}",
+                    profile_name,
+                    e
+                );
+                false
+            }
+        }
+    }
+
     /*
      * Handles the "Edit Exclude Patterns..." menu action by launching a modal dialog pre-populated
      * with the current profile's exclude patterns. When no profile is active the command is ignored
@@ -2648,6 +5016,8 @@ impl PlatformEventHandler for MyAppLogic {
     fn try_dequeue_command(&mut self) -> Option<PlatformCommand> {
         self.poll_token_recalc_progress();
         self.poll_content_search_progress();
+        self.poll_batch_generation_progress();
+        self.poll_file_watcher();
         self.synchronous_command_queue.pop_front()
     }
 
@@ -2693,6 +5063,53 @@ impl PlatformEventHandler for MyAppLogic {
                     self.handle_menu_refresh_file_list_clicked()
                 }
                 ui_constants::MENU_ACTION_GENERATE_ARCHIVE => self._do_generate_archive(),
+                ui_constants::MENU_ACTION_GENERATE_DELTA_ARCHIVE => {
+                    self._do_generate_delta_archive()
+                }
+                ui_constants::MENU_ACTION_SHOW_ARCHIVE_HISTORY => {
+                    self.handle_menu_show_archive_history_clicked()
+                }
+                ui_constants::MENU_ACTION_GENERATE_ALL_ARCHIVES => {
+                    self.handle_menu_generate_all_archives_clicked()
+                }
+                ui_constants::MENU_ACTION_APPLY_EDITED_ARCHIVE => {
+                    self.handle_menu_apply_edited_archive_clicked()
+                }
+                ui_constants::MENU_ACTION_APPLY_PATCH => self.handle_menu_apply_patch_clicked(),
+                ui_constants::MENU_ACTION_SET_ARCHIVE_FORMAT => {
+                    self.handle_menu_set_archive_format_clicked()
+                }
+                ui_constants::MENU_ACTION_SET_ARCHIVE_SPLIT => {
+                    self.handle_menu_set_archive_split_clicked()
+                }
+                ui_constants::MENU_ACTION_SET_TOKEN_BUDGET => {
+                    self.handle_menu_set_token_budget_clicked()
+                }
+                ui_constants::MENU_ACTION_SET_FILE_PRIORITY => {
+                    self.handle_menu_set_file_priority_clicked()
+                }
+                ui_constants::MENU_ACTION_TOGGLE_ARCHIVE_MANIFEST => {
+                    self.handle_menu_toggle_archive_manifest_clicked()
+                }
+                ui_constants::MENU_ACTION_SET_CONTENT_TRANSFORMS => {
+                    self.handle_menu_set_content_transforms_clicked()
+                }
+                ui_constants::MENU_ACTION_TOGGLE_OUTLINE => {
+                    self.handle_menu_toggle_outline_clicked()
+                }
+                ui_constants::MENU_ACTION_SET_DIRECTORY_TREE => {
+                    self.handle_menu_set_directory_tree_clicked()
+                }
+                ui_constants::MENU_ACTION_SET_SECRET_SCAN => {
+                    self.handle_menu_set_secret_scan_clicked()
+                }
+                ui_constants::MENU_ACTION_SET_CONTENT_LIMITS => {
+                    self.handle_menu_set_content_limits_clicked()
+                }
+                ui_constants::MENU_ACTION_SET_ENCODINGS => self.handle_menu_set_encodings_clicked(),
+                ui_constants::MENU_ACTION_SET_LINE_NUMBERS => {
+                    self.handle_menu_set_line_numbers_clicked()
+                }
                 _ => log::warn!("Received unhandled menu action ID: {action_id:?}"),
             },
             AppEvent::FileOpenProfileDialogCompleted { window_id, result } => {
@@ -2901,7 +5318,10 @@ impl MyAppLogic {
                 collected.push(cmd);
                 made_progress = true;
             }
-            if self.token_recalc_driver.is_none() && self.content_search_driver.is_none() {
+            if self.token_recalc_driver.is_none()
+                && self.content_search_driver.is_none()
+                && self.batch_generation_driver.is_none()
+            {
                 break;
             }
             if !made_progress {
@@ -2,14 +2,27 @@
 mod tests {
     use crate::app_logic::{SearchMode, handler::*, ui_constants};
 
+    use crate::core::archive_format::ArchiveDeltaHeader;
+    use crate::core::archive_history::ArchiveHistory;
+    use crate::core::archive_prompt::{PromptOptions, PromptVariant};
+    use crate::core::archive_record::ArchiveRecord;
+    use crate::core::archive_tree::DirectoryTreeOptions;
+    use crate::core::composite_profile::ComponentLoaderOperations;
+    use crate::core::content_transform::{ContentTransform, TransformRule};
+    use crate::core::file_node::ArchiveChanges;
+    use crate::core::file_watcher::{FileChange, FileWatcher};
     use crate::core::token_progress::TokenProgressEntry;
     use crate::core::{
-        ArchiveStatus, ArchiverOperations, ConfigError, ConfigManagerOperations,
-        ContentSearchProgress, ContentSearchResult, FileNode, FileSystemError,
-        FileSystemScannerOperations, NodeStateApplicatorOperations, Profile, ProfileError,
-        ProfileManagerOperations, ProfileName, ProfileRuntimeDataOperations, ProjectContext,
-        SelectionState, TokenCounterOperations, TokenProgress, TokenProgressChannel,
+        ArchiveError, ArchiveFormat, ArchiveOptions, ArchiveReport, ArchiveRequest,
+        ArchiveSplitOptions, ArchiveStatus, ArchiverOperations, ConfigError,
+        ConfigManagerOperations, ContentSearchProgress, ContentSearchResult, FileNode,
+        FilePriority, FileSystemError, FileSystemScannerOperations, NodeStateApplicatorOperations,
+        Profile, ProfileError, ProfileManagerOperations, ProfileName, ProfileRuntimeDataOperations,
+        ProjectContext, SelectionState, TokenBudgetReport, TokenCounterOperations, TokenProgress,
+        TokenProgressChannel,
         file_node::FileTokenDetails,
+        secret_scan::{SecretFinding, SecretPolicy, SecretScanReport},
+        token_budget::{BudgetAction, OmittedFile},
     };
     use crate::platform_layer::{
         AppEvent, CheckState, MessageSeverity, PlatformCommand, PlatformEventHandler, StyleId,
@@ -60,6 +73,7 @@ mod tests {
         cached_file_token_details: HashMap<PathBuf, FileTokenDetails>,
         cached_total_token_count: usize,
         exclude_patterns: Vec<String>,
+        archive_options: ArchiveOptions,
 
         // Call counters for &self methods using AtomicUsize
         get_profile_name_calls: AtomicUsize,
@@ -107,6 +121,7 @@ mod tests {
                 cached_file_token_details: HashMap::new(),
                 cached_total_token_count: 0,
                 exclude_patterns: Vec::new(),
+                archive_options: ArchiveOptions::default(),
 
                 get_profile_name_calls: AtomicUsize::new(0),
                 get_archive_path_calls: AtomicUsize::new(0),
@@ -286,6 +301,12 @@ mod tests {
         fn set_exclude_patterns(&mut self, patterns: Vec<String>) {
             self.exclude_patterns = patterns;
         }
+        fn get_archive_options(&self) -> ArchiveOptions {
+            self.archive_options.clone()
+        }
+        fn set_archive_options(&mut self, options: ArchiveOptions) {
+            self.archive_options = options;
+        }
         fn get_snapshot_nodes(&self) -> &Vec<FileNode> {
             self.get_snapshot_nodes_calls
                 .fetch_add(1, Ordering::Relaxed);
@@ -303,6 +324,7 @@ mod tests {
             _state_manager: &dyn NodeStateApplicatorOperations,
             selected_paths: &HashSet<PathBuf>,
             deselected_paths: &HashSet<PathBuf>,
+            outline_paths: &HashSet<PathBuf>,
         ) {
             self._apply_selection_states_to_snapshot_log
                 .lock()
@@ -313,9 +335,12 @@ mod tests {
                 nodes: &mut [FileNode],
                 selected: &HashSet<PathBuf>,
                 deselected: &HashSet<PathBuf>,
+                outline: &HashSet<PathBuf>,
             ) {
                 for node in nodes.iter_mut() {
-                    if selected.contains(node.path()) {
+                    if outline.contains(node.path()) {
+                        node.set_state(SelectionState::Outline);
+                    } else if selected.contains(node.path()) {
                         node.set_state(SelectionState::Selected);
                     } else if deselected.contains(node.path()) {
                         node.set_state(SelectionState::Deselected);
@@ -325,7 +350,12 @@ mod tests {
                         node.set_state(SelectionState::New);
                     }
                     if node.is_dir() {
-                        apply_recursive(node.children.as_mut_slice(), selected, deselected);
+                        apply_recursive(
+                            node.children.as_mut_slice(),
+                            selected,
+                            deselected,
+                            outline,
+                        );
                     }
                 }
             }
@@ -333,6 +363,7 @@ mod tests {
                 self.snapshot_nodes.as_mut_slice(),
                 selected_paths,
                 deselected_paths,
+                outline_paths,
             );
         }
         fn get_node_attributes_for_path(
@@ -548,6 +579,9 @@ mod tests {
                 total_files: if preset_total > 0 { 1 } else { 0 },
             })
         }
+        fn get_cached_file_token_details(&self) -> HashMap<PathBuf, FileTokenDetails> {
+            self.cached_file_token_details.clone()
+        }
         fn apply_token_progress(&mut self, progress: TokenProgress) -> usize {
             self.apply_token_progress_calls
                 .fetch_add(1, Ordering::Relaxed);
@@ -595,6 +629,7 @@ mod tests {
                 .store(0, Ordering::Relaxed);
             self.cached_file_token_details.clear();
             self.exclude_patterns.clear();
+            self.archive_options = ArchiveOptions::default();
         }
         fn create_profile_snapshot(&self) -> Profile {
             self.create_profile_snapshot_calls
@@ -631,6 +666,7 @@ mod tests {
                 &mut profile.deselected_paths,
             );
             profile.exclude_patterns = self.exclude_patterns.clone();
+            profile.archive_options = self.archive_options.clone();
             profile
         }
 
@@ -640,6 +676,7 @@ mod tests {
             file_system_scanner: &dyn FileSystemScannerOperations, // Added underscore as it's now used
             state_manager: &dyn NodeStateApplicatorOperations,     // Added underscore
             _token_counter: &dyn TokenCounterOperations,           // Added underscore
+            _component_loader: &dyn ComponentLoaderOperations,
         ) -> Result<(), String> {
             self._load_profile_into_session_log
                 .lock()
@@ -658,12 +695,14 @@ mod tests {
                     self.snapshot_nodes = scanned_nodes; // <<< KEY CHANGE: Populate snapshot_nodes from scanner
                     self.cached_file_token_details = loaded_profile.file_details.clone();
                     self.exclude_patterns = loaded_profile.exclude_patterns.clone();
+                    self.archive_options = loaded_profile.archive_options.clone();
 
                     // Simulate applying selection states (simplified for mock)
                     self.apply_selection_states_to_snapshot(
                         state_manager,
                         &loaded_profile.selected_paths,
                         &loaded_profile.deselected_paths,
+                        &loaded_profile.outline_paths,
                     );
 
                     // Respect the pre-set mock result if it was an error, otherwise Ok
@@ -692,33 +731,45 @@ mod tests {
             }
         }
 
-        fn get_current_selection_paths(&self) -> (HashSet<PathBuf>, HashSet<PathBuf>) {
+        fn get_current_selection_paths(
+            &self,
+        ) -> (HashSet<PathBuf>, HashSet<PathBuf>, HashSet<PathBuf>) {
             self._get_current_selection_paths_calls
                 .fetch_add(1, Ordering::Relaxed);
             let mut selected = HashSet::new();
             let mut deselected = HashSet::new();
+            let mut outline = HashSet::new();
             fn gather_paths_recursive(
                 nodes: &[FileNode],
                 selected: &mut HashSet<PathBuf>,
                 deselected: &mut HashSet<PathBuf>,
+                outline: &mut HashSet<PathBuf>,
             ) {
                 for node in nodes {
                     match node.state() {
                         SelectionState::Selected => {
                             selected.insert(node.path().to_path_buf());
                         }
+                        SelectionState::Outline => {
+                            outline.insert(node.path().to_path_buf());
+                        }
                         SelectionState::Deselected => {
                             deselected.insert(node.path().to_path_buf());
                         }
                         _ => {}
                     }
                     if node.is_dir() {
-                        gather_paths_recursive(&node.children, selected, deselected);
+                        gather_paths_recursive(&node.children, selected, deselected, outline);
                     }
                 }
             }
-            gather_paths_recursive(&self.snapshot_nodes, &mut selected, &mut deselected);
-            (selected, deselected)
+            gather_paths_recursive(
+                &self.snapshot_nodes,
+                &mut selected,
+                &mut deselected,
+                &mut outline,
+            );
+            (selected, deselected, outline)
         }
     }
     // --- End MockProfileRuntimeData ---
@@ -986,18 +1037,23 @@ mod tests {
             ProfileError::NoProjectDirectory => ProfileError::NoProjectDirectory,
             ProfileError::ProfileNotFound(s) => ProfileError::ProfileNotFound(s.clone()),
             ProfileError::InvalidProfileName(s) => ProfileError::InvalidProfileName(s.clone()),
+            ProfileError::InvalidArchiveOptions(s) => {
+                ProfileError::InvalidArchiveOptions(s.clone())
+            }
         }
     }
 
     struct MockFileSystemScanner {
         scan_directory_results: Mutex<HashMap<PathBuf, Result<Vec<FileNode>, FileSystemError>>>,
         scan_directory_calls: Mutex<Vec<PathBuf>>,
+        watch_directory_calls: Mutex<Vec<PathBuf>>,
     }
     impl MockFileSystemScanner {
         fn new() -> Self {
             MockFileSystemScanner {
                 scan_directory_results: Mutex::new(HashMap::new()),
                 scan_directory_calls: Mutex::new(Vec::new()),
+                watch_directory_calls: Mutex::new(Vec::new()),
             }
         }
         fn set_scan_directory_result(
@@ -1014,6 +1070,9 @@ mod tests {
         fn get_scan_directory_calls(&self) -> Vec<PathBuf> {
             self.scan_directory_calls.lock().unwrap().clone()
         }
+        fn get_watch_directory_calls(&self) -> Vec<PathBuf> {
+            self.watch_directory_calls.lock().unwrap().clone()
+        }
     }
     impl FileSystemScannerOperations for MockFileSystemScanner {
         fn scan_directory(
@@ -1033,6 +1092,17 @@ mod tests {
                 None => Ok(Vec::new()), // Default to empty vec if no result is set for path
             }
         }
+        fn watch_directory(
+            &self,
+            root_path: &Path,
+        ) -> Result<Option<FileWatcher>, FileSystemError> {
+            self.watch_directory_calls
+                .lock()
+                .unwrap()
+                .push(root_path.to_path_buf());
+            // No OS watcher in tests; the recorded call is what they check.
+            Ok(None)
+        }
     }
     fn clone_file_system_error(error: &FileSystemError) -> FileSystemError {
         match error {
@@ -1043,12 +1113,17 @@ mod tests {
                 FileSystemError::IgnoreError(ignore::Error::from(mock_io_err))
             }
             FileSystemError::InvalidPath(p) => FileSystemError::InvalidPath(p.clone()),
+            FileSystemError::Watch(e) => {
+                FileSystemError::Watch(notify::Error::generic(&e.to_string()))
+            }
         }
     }
 
+    type WriteArchiveCallLog = (Vec<FileNode>, PathBuf, PathBuf); // (nodes, root, destination)
+
     struct MockArchiver {
-        create_archive_content_result: Mutex<io::Result<String>>,
-        create_archive_content_calls: Mutex<Vec<(Vec<FileNode>, PathBuf)>>,
+        write_archive_result: Mutex<Result<ArchiveReport, ArchiveError>>,
+        write_archive_calls: Mutex<Vec<WriteArchiveCallLog>>,
         check_archive_status_result: Mutex<ArchiveStatus>,
         check_archive_status_calls: Mutex<Vec<(Option<PathBuf>, Vec<FileNode>)>>,
         save_archive_content_result: Mutex<io::Result<()>>,
@@ -1059,8 +1134,8 @@ mod tests {
     impl MockArchiver {
         fn new() -> Self {
             MockArchiver {
-                create_archive_content_result: Mutex::new(Ok("mocked_archive_content".to_string())),
-                create_archive_content_calls: Mutex::new(Vec::new()),
+                write_archive_result: Mutex::new(Ok(ArchiveReport::default())),
+                write_archive_calls: Mutex::new(Vec::new()),
                 check_archive_status_result: Mutex::new(ArchiveStatus::UpToDate),
                 check_archive_status_calls: Mutex::new(Vec::new()),
                 save_archive_content_result: Mutex::new(Ok(())),
@@ -1069,12 +1144,11 @@ mod tests {
                 get_file_timestamp_calls: Mutex::new(Vec::new()),
             }
         }
-        fn set_create_archive_content_result(&self, result: io::Result<String>) {
-            *self.create_archive_content_result.lock().unwrap() = result;
+        fn set_write_archive_result(&self, result: Result<ArchiveReport, ArchiveError>) {
+            *self.write_archive_result.lock().unwrap() = result;
         }
-        #[allow(dead_code)]
-        fn get_create_archive_content_calls(&self) -> Vec<(Vec<FileNode>, PathBuf)> {
-            self.create_archive_content_calls.lock().unwrap().clone()
+        fn get_write_archive_calls(&self) -> Vec<WriteArchiveCallLog> {
+            self.write_archive_calls.lock().unwrap().clone()
         }
         fn set_check_archive_status_result(&self, result: ArchiveStatus) {
             *self.check_archive_status_result.lock().unwrap() = result;
@@ -1083,9 +1157,11 @@ mod tests {
         fn get_check_archive_status_calls(&self) -> Vec<(Option<PathBuf>, Vec<FileNode>)> {
             self.check_archive_status_calls.lock().unwrap().clone()
         }
+        #[allow(dead_code)]
         fn set_save_archive_content_result(&self, result: io::Result<()>) {
             *self.save_archive_content_result.lock().unwrap() = result;
         }
+        #[allow(dead_code)]
         fn get_save_archive_content_calls(&self) -> Vec<(PathBuf, String)> {
             self.save_archive_content_calls.lock().unwrap().clone()
         }
@@ -1104,33 +1180,73 @@ mod tests {
     fn clone_io_error(error: &io::Error) -> io::Error {
         io::Error::new(error.kind(), format!("{error}"))
     }
+    fn clone_archive_error(error: &ArchiveError) -> ArchiveError {
+        match error {
+            ArchiveError::ReadSource { path, source } => ArchiveError::ReadSource {
+                path: path.clone(),
+                source: clone_io_error(source),
+            },
+            ArchiveError::Output(e) => ArchiveError::Output(clone_io_error(e)),
+            ArchiveError::InvalidOptions(e) => ArchiveError::InvalidOptions(e.clone()),
+            ArchiveError::InvalidTransform(e) => ArchiveError::InvalidTransform(e.clone()),
+            ArchiveError::NoDeltaBaseline(path) => ArchiveError::NoDeltaBaseline(path.clone()),
+            ArchiveError::InvalidSecretRule(e) => ArchiveError::InvalidSecretRule(e.clone()),
+            ArchiveError::InvalidEncodingRule(e) => ArchiveError::InvalidEncodingRule(e.clone()),
+            ArchiveError::InvalidLineNumbers(e) => ArchiveError::InvalidLineNumbers(e.clone()),
+            ArchiveError::SecretsFound(findings) => ArchiveError::SecretsFound(findings.clone()),
+            ArchiveError::UnsupportedContent { path, class, size } => {
+                ArchiveError::UnsupportedContent {
+                    path: path.clone(),
+                    class: *class,
+                    size: *size,
+                }
+            }
+        }
+    }
     impl ArchiverOperations for MockArchiver {
-        fn create_content(
+        fn write_archive(
             &self,
-            nodes: &[FileNode],
-            root_path_for_display: &Path,
-        ) -> io::Result<String> {
-            self.create_archive_content_calls
-                .lock()
-                .unwrap()
-                .push((nodes.to_vec(), root_path_for_display.to_path_buf()));
-            self.create_archive_content_result
+            request: &ArchiveRequest,
+            _sink: &mut dyn io::Write,
+        ) -> Result<ArchiveReport, ArchiveError> {
+            self.write_archive_to_file(request, Path::new(""))
+        }
+        fn write_archive_to_file(
+            &self,
+            request: &ArchiveRequest,
+            path: &Path,
+        ) -> Result<ArchiveReport, ArchiveError> {
+            self.write_archive_calls.lock().unwrap().push((
+                request.nodes.to_vec(),
+                request.root_path_for_display.to_path_buf(),
+                path.to_path_buf(),
+            ));
+            self.write_archive_result
                 .lock()
                 .unwrap()
                 .as_ref()
-                .map(|s| s.clone())
-                .map_err(clone_io_error)
+                .map(|report| report.clone())
+                .map_err(clone_archive_error)
+        }
+        fn write_delta_archive_to_file(
+            &self,
+            request: &ArchiveRequest,
+            _archive_path: &Path,
+            delta_path: &Path,
+        ) -> Result<ArchiveReport, ArchiveError> {
+            self.write_archive_to_file(request, delta_path)
         }
         fn check_status(
             &self,
             archive_path_opt: Option<&Path>,
             file_nodes_tree: &[FileNode],
+            _record_path: Option<&Path>,
         ) -> ArchiveStatus {
             self.check_archive_status_calls.lock().unwrap().push((
                 archive_path_opt.map(|p| p.to_path_buf()),
                 file_nodes_tree.to_vec(),
             ));
-            *self.check_archive_status_result.lock().unwrap()
+            self.check_archive_status_result.lock().unwrap().clone()
         }
         fn save(&self, path: &Path, content: &str) -> io::Result<()> {
             self.save_archive_content_calls
@@ -1190,6 +1306,7 @@ mod tests {
             tree: &mut Vec<FileNode>,
             selected_paths: &HashSet<PathBuf>,
             deselected_paths: &HashSet<PathBuf>,
+            outline_paths: &HashSet<PathBuf>,
         ) {
             self.apply_selection_states_to_nodes_calls
                 .lock()
@@ -1201,7 +1318,9 @@ mod tests {
                 ));
             // Basic simulation for mock
             for node in tree.iter_mut() {
-                if selected_paths.contains(node.path()) {
+                if outline_paths.contains(node.path()) {
+                    node.set_state(SelectionState::Outline);
+                } else if selected_paths.contains(node.path()) {
                     node.set_state(SelectionState::Selected);
                 } else if deselected_paths.contains(node.path()) {
                     node.set_state(SelectionState::Deselected);
@@ -1214,6 +1333,7 @@ mod tests {
                         &mut node.children,
                         selected_paths,
                         deselected_paths,
+                        outline_paths,
                     );
                 }
             }
@@ -1340,9 +1460,12 @@ mod tests {
             root_folder: startup_project_root.clone(),
             selected_paths: selected_paths_for_profile.clone(),
             deselected_paths: HashSet::new(),
+            outline_paths: HashSet::new(),
             archive_path: Some(startup_archive_path.clone()),
             file_details: HashMap::new(),
             exclude_patterns: Vec::new(),
+            archive_options: ArchiveOptions::default(),
+            components: Vec::new(),
         };
         mock_profile_manager.set_load_profile_result(
             last_profile_name_to_load,
@@ -1600,6 +1723,152 @@ mod tests {
         assert_eq!(scan_calls, vec![root_path]);
     }
 
+    #[test]
+    fn test_archive_format_dialog_updates_profile_and_session() {
+        // Arrange
+        let (
+            mut logic,
+            mock_app_session_mutexed,
+            _mock_config_manager,
+            mock_profile_manager_arc,
+            _mock_file_system_scanner_arc,
+            _mock_archiver_arc,
+            _mock_state_manager,
+            _mock_token_counter,
+        ) = setup_logic_with_mocks();
+        let main_window_id = WindowId::new(7);
+        logic.test_set_main_window_id_and_init_ui_state(main_window_id);
+        mock_app_session_mutexed
+            .lock()
+            .unwrap()
+            .set_profile_name_for_mock(Some("DemoProfile".to_string()));
+
+        // Act
+        logic.handle_event(AppEvent::GenericInputDialogCompleted {
+            window_id: main_window_id,
+            text: Some(" markdown ".to_string()),
+            context_tag: Some("ArchiveFormat".to_string()),
+        });
+
+        // Assert
+        let saved_profiles = mock_profile_manager_arc.get_save_profile_calls();
+        assert_eq!(saved_profiles.len(), 1);
+        assert_eq!(
+            saved_profiles[0].1.archive_options.format,
+            ArchiveFormat::Markdown
+        );
+        assert_eq!(
+            mock_app_session_mutexed
+                .lock()
+                .unwrap()
+                .get_archive_options()
+                .format,
+            ArchiveFormat::Markdown
+        );
+    }
+
+    #[test]
+    fn test_archive_format_dialog_rejects_unknown_format() {
+        // Arrange
+        let (
+            mut logic,
+            mock_app_session_mutexed,
+            _mock_config_manager,
+            mock_profile_manager_arc,
+            _mock_file_system_scanner_arc,
+            _mock_archiver_arc,
+            _mock_state_manager,
+            _mock_token_counter,
+        ) = setup_logic_with_mocks();
+        let main_window_id = WindowId::new(7);
+        logic.test_set_main_window_id_and_init_ui_state(main_window_id);
+        mock_app_session_mutexed
+            .lock()
+            .unwrap()
+            .set_profile_name_for_mock(Some("DemoProfile".to_string()));
+
+        // Act
+        logic.handle_event(AppEvent::GenericInputDialogCompleted {
+            window_id: main_window_id,
+            text: Some("yaml".to_string()),
+            context_tag: Some("ArchiveFormat".to_string()),
+        });
+
+        // Assert
+        assert!(mock_profile_manager_arc.get_save_profile_calls().is_empty());
+        let cmds = logic.test_drain_commands();
+        assert!(
+            find_command(&cmds, |cmd| matches!(
+                cmd,
+                PlatformCommand::ShowInputDialog { context_tag: Some(tag), .. } if tag == "ArchiveFormat"
+            ))
+            .is_some(),
+            "Expected the archive format dialog to be shown again"
+        );
+    }
+
+    #[test]
+    fn test_archive_split_dialog_updates_profile_and_session() {
+        // Arrange
+        let (
+            mut logic,
+            mock_app_session_mutexed,
+            _mock_config_manager,
+            mock_profile_manager_arc,
+            _mock_file_system_scanner_arc,
+            _mock_archiver_arc,
+            _mock_state_manager,
+            _mock_token_counter,
+        ) = setup_logic_with_mocks();
+        let main_window_id = WindowId::new(7);
+        logic.test_set_main_window_id_and_init_ui_state(main_window_id);
+        mock_app_session_mutexed
+            .lock()
+            .unwrap()
+            .set_profile_name_for_mock(Some("DemoProfile".to_string()));
+
+        // Act
+        logic.handle_event(AppEvent::GenericInputDialogCompleted {
+            window_id: main_window_id,
+            text: Some("dirs 50000".to_string()),
+            context_tag: Some("ArchiveSplit".to_string()),
+        });
+
+        // Assert
+        let expected = ArchiveSplitOptions::parse_setting("dirs 50000").unwrap();
+        let saved_profiles = mock_profile_manager_arc.get_save_profile_calls();
+        assert_eq!(saved_profiles.len(), 1);
+        assert_eq!(saved_profiles[0].1.archive_options.split, expected);
+        assert_eq!(
+            mock_app_session_mutexed
+                .lock()
+                .unwrap()
+                .get_archive_options()
+                .split,
+            expected
+        );
+
+        // Act - an invalid setting is rejected and the dialog is shown again
+        logic.test_drain_commands();
+        logic.handle_event(AppEvent::GenericInputDialogCompleted {
+            window_id: main_window_id,
+            text: Some("tokens 0".to_string()),
+            context_tag: Some("ArchiveSplit".to_string()),
+        });
+
+        // Assert
+        assert_eq!(mock_profile_manager_arc.get_save_profile_calls().len(), 1);
+        let cmds = logic.test_drain_commands();
+        assert!(
+            find_command(&cmds, |cmd| matches!(
+                cmd,
+                PlatformCommand::ShowInputDialog { context_tag: Some(tag), .. } if tag == "ArchiveSplit"
+            ))
+            .is_some(),
+            "Expected the archive split dialog to be shown again"
+        );
+    }
+
     #[test]
     fn test_profile_load_updates_archive_status_via_mock_archiver() {
         // Arrange
@@ -1627,9 +1896,12 @@ mod tests {
             root_folder: root_folder_for_profile.clone(),
             selected_paths: HashSet::new(),
             deselected_paths: HashSet::new(),
+            outline_paths: HashSet::new(),
             archive_path: Some(archive_file_for_profile.clone()),
             file_details: HashMap::new(),
             exclude_patterns: Vec::new(),
+            archive_options: ArchiveOptions::default(),
+            components: Vec::new(),
         };
         mock_profile_manager_arc.set_load_profile_from_path_result(
             &profile_json_path_from_dialog,
@@ -1648,7 +1920,7 @@ mod tests {
             .set_snapshot_nodes_for_mock(vec![]); // Ensure snapshot nodes are empty for this part
 
         let archive_error_status = ArchiveStatus::ErrorChecking(Some(io::ErrorKind::NotFound));
-        mock_archiver_arc.set_check_archive_status_result(archive_error_status);
+        mock_archiver_arc.set_check_archive_status_result(archive_error_status.clone());
 
         // Act
         let event = AppEvent::FileOpenProfileDialogCompleted {
@@ -1731,8 +2003,11 @@ mod tests {
             mock_app_session.set_archive_path_for_mock(Some(archive_path.clone()));
             mock_app_session.set_snapshot_nodes_for_mock(file_nodes.clone());
         }
-        mock_archiver.set_create_archive_content_result(Ok("Test Archive Content".to_string()));
-        mock_archiver.set_save_archive_content_result(Ok(()));
+        mock_archiver.set_write_archive_result(Ok(ArchiveReport {
+            files_written: 1,
+            bytes_written: 20,
+            ..ArchiveReport::default()
+        }));
         mock_archiver.set_check_archive_status_result(ArchiveStatus::UpToDate); // After successful save
 
         // Act
@@ -1742,15 +2017,11 @@ mod tests {
         let cmds = logic.test_drain_commands();
 
         // Assert
-        let create_calls = mock_archiver.get_create_archive_content_calls();
-        assert_eq!(create_calls.len(), 1);
-        assert_eq!(create_calls[0].0, file_nodes);
-        assert_eq!(create_calls[0].1, root_folder);
-
-        let save_calls = mock_archiver.get_save_archive_content_calls();
-        assert_eq!(save_calls.len(), 1);
-        assert_eq!(save_calls[0].0, archive_path);
-        assert_eq!(save_calls[0].1, "Test Archive Content");
+        let write_calls = mock_archiver.get_write_archive_calls();
+        assert_eq!(write_calls.len(), 1);
+        assert_eq!(write_calls[0].0, file_nodes);
+        assert_eq!(write_calls[0].1, root_folder);
+        assert_eq!(write_calls[0].2, archive_path);
 
         // Check status update after save
         let archiver_status_calls = mock_archiver.get_check_archive_status_calls();
@@ -1768,128 +2039,991 @@ mod tests {
     }
 
     #[test]
-    fn test_generate_archive_missing_file_shows_message_box() {
+    fn test_generate_archive_asks_for_prompt_variant_and_counts_its_tokens() {
         // Arrange
-        let (mut logic, mock_app_session_mutexed, _, _, _, mock_archiver, _, _) =
+        let (mut logic, mock_app_session_mutexed, _, mock_profile_manager, _, mock_archiver, _, _) =
             setup_logic_with_mocks();
-        let main_window_id = WindowId::new(99);
+        let main_window_id = WindowId::new(1);
         logic.test_set_main_window_id_and_init_ui_state(main_window_id);
-
-        let profile_name = "PopupProfile";
-        let archive_path = PathBuf::from("/popup/archive.txt");
-        let root_folder = PathBuf::from("/popup/root");
-        let file_nodes = vec![FileNode::new_test(
-            root_folder.join("missing.txt"),
-            "missing.txt".into(),
-            false,
-        )];
         {
             let mut mock_app_session = mock_app_session_mutexed.lock().unwrap();
-            mock_app_session.set_profile_name_for_mock(Some(profile_name.to_string()));
-            mock_app_session.set_root_path_for_scan_for_mock(root_folder.clone());
-            mock_app_session.set_archive_path_for_mock(Some(archive_path.clone()));
-            mock_app_session.set_snapshot_nodes_for_mock(file_nodes.clone());
+            mock_app_session.set_profile_name_for_mock(Some("PromptProfile".to_string()));
+            mock_app_session.set_root_path_for_scan_for_mock(PathBuf::from("/test/root"));
+            mock_app_session.set_archive_path_for_mock(Some(PathBuf::from("/test/archive.txt")));
+            let mut options = mock_app_session.get_archive_options();
+            options.prompts = PromptOptions {
+                variants: vec![PromptVariant {
+                    name: "Review".to_string(),
+                    preamble: "Review these {file_count} files.".to_string(),
+                    postamble: String::new(),
+                }],
+                active: None,
+            };
+            mock_app_session.set_archive_options(options);
         }
-        mock_archiver.set_create_archive_content_result(Err(io::Error::new(
-            io::ErrorKind::NotFound,
-            "mocked missing file",
-        )));
+        let prompt_dialog = |cmds: &[PlatformCommand]| {
+            find_command(cmds, |cmd| {
+                matches!(cmd, PlatformCommand::ShowInputDialog { context_tag: Some(tag), .. } if tag == "PromptVariant")
+            })
+            .cloned()
+        };
 
-        // Act
+        // Act - generating first asks for the variant
         logic.handle_event(AppEvent::MenuActionClicked {
             action_id: ui_constants::MENU_ACTION_GENERATE_ARCHIVE,
         });
         let cmds = logic.test_drain_commands();
 
         // Assert
-        let message_box_cmd = find_command(&cmds, |cmd| {
-            matches!(
-                cmd,
-                PlatformCommand::ShowMessageBox {
-                    window_id,
-                    title,
-                    message,
-                    severity,
-                } if *window_id == main_window_id
-                    && title == "Missing Source File"
-                    && message.contains("mocked missing file")
-                    && *severity == MessageSeverity::Error
-            )
+        assert!(mock_archiver.get_write_archive_calls().is_empty());
+        match prompt_dialog(&cmds) {
+            Some(PlatformCommand::ShowInputDialog {
+                prompt,
+                default_text,
+                ..
+            }) => {
+                assert!(prompt.contains("(Review)"), "{prompt}");
+                assert_eq!(default_text.as_deref(), Some("none"));
+            }
+            _ => panic!("Expected the prompt dialog. Got: {cmds:?}"),
+        }
+
+        // Act - an unknown variant asks again
+        logic.handle_event(AppEvent::GenericInputDialogCompleted {
+            window_id: main_window_id,
+            text: Some("Docs".to_string()),
+            context_tag: Some("PromptVariant".to_string()),
         });
-        assert!(
-            message_box_cmd.is_some(),
-            "Expected ShowMessageBox command. Got: {cmds:?}"
-        );
+        let cmds = logic.test_drain_commands();
+        assert!(prompt_dialog(&cmds).is_some());
+        assert!(mock_archiver.get_write_archive_calls().is_empty());
 
-        assert!(
-            find_command(&cmds, |cmd| matches!(
-                cmd,
-                PlatformCommand::UpdateLabelText {
-                    control_id,
-                    ..
-                } if *control_id == ui_constants::STATUS_LABEL_GENERAL_ID
-            ))
-            .is_none(),
-            "Unexpected status label update when missing file encountered. Got: {cmds:?}"
-        );
+        // Act - choosing a variant stores it, counts it and generates
+        logic.handle_event(AppEvent::GenericInputDialogCompleted {
+            window_id: main_window_id,
+            text: Some("review".to_string()),
+            context_tag: Some("PromptVariant".to_string()),
+        });
+        let cmds = logic.test_drain_commands();
 
+        // Assert
+        let saved_profiles = mock_profile_manager.get_save_profile_calls();
+        assert_eq!(saved_profiles.len(), 1);
+        assert_eq!(
+            saved_profiles[0]
+                .1
+                .archive_options
+                .prompts
+                .active
+                .as_deref(),
+            Some("Review")
+        );
+        assert_eq!(mock_archiver.get_write_archive_calls().len(), 1);
         assert!(
-            mock_archiver.get_save_archive_content_calls().is_empty(),
-            "Archive save should not be attempted when content generation fails"
+            find_command(&cmds, |cmd| matches!(cmd, PlatformCommand::UpdateLabelText { control_id, text, .. } if *control_id == ui_constants::STATUS_LABEL_TOKENS_ID && text == "Tokens: 1 (incl. 1 prompt)")).is_some(),
+            "Expected the token label to include the prompt. Got: {cmds:?}"
         );
     }
 
     #[test]
-    fn test_menu_action_generate_archive_no_profile_shows_error() {
+    fn test_templated_archive_path_is_resolved_and_status_uses_the_recorded_archive() {
         // Arrange
-        let (mut logic, mock_app_session_mutexed, _, _, _, _, _, _) = setup_logic_with_mocks();
+        let (mut logic, mock_app_session_mutexed, _, _, _, mock_archiver, _, _) =
+            setup_logic_with_mocks();
         let main_window_id = WindowId::new(1);
         logic.test_set_main_window_id_and_init_ui_state(main_window_id);
-        mock_app_session_mutexed
-            .lock()
-            .unwrap()
-            .set_profile_name_for_mock(None);
+        let dir = tempdir().unwrap();
+        let root = dir.path().to_path_buf();
+        logic.test_set_active_project_root(root.clone());
+        {
+            let mut mock_app_session = mock_app_session_mutexed.lock().unwrap();
+            mock_app_session.set_profile_name_for_mock(Some("Templated".to_string()));
+            mock_app_session.set_root_path_for_scan_for_mock(root.clone());
+            mock_app_session
+                .set_archive_path_for_mock(Some(PathBuf::from("out/{profile}-archive.txt")));
+        }
 
         // Act
         logic.handle_event(AppEvent::MenuActionClicked {
             action_id: ui_constants::MENU_ACTION_GENERATE_ARCHIVE,
         });
-        let cmds = logic.test_drain_commands();
+        let _cmds = logic.test_drain_commands();
+
+        // Assert - the template is resolved against the project root
+        let write_calls = mock_archiver.get_write_archive_calls();
+        assert_eq!(write_calls.len(), 1);
+        assert_eq!(
+            write_calls[0].2,
+            root.join("out").join("Templated-archive.txt")
+        );
+
+        // Arrange - an archive generated earlier under another resolved name
+        let earlier = root.join("out").join("Templated-earlier.txt");
+        let project = ProjectContext::new(root.clone());
+        ArchiveRecord::new(&earlier, Default::default())
+            .save(&project.resolve_archive_record_file(&ProfileName::new("Templated").unwrap()))
+            .unwrap();
+
+        // Act
+        logic.update_current_archive_status();
 
         // Assert
-        assert!(find_command(&cmds, |cmd| matches!(cmd, PlatformCommand::UpdateLabelText { control_id, text, severity, .. } if *control_id == ui_constants::STATUS_LABEL_GENERAL_ID && severity == &MessageSeverity::Error && text.contains("No profile loaded"))).is_some(), "Expected 'No profile loaded' error status. Got: {cmds:?}");
+        let status_calls = mock_archiver.get_check_archive_status_calls();
+        assert_eq!(
+            status_calls.last().and_then(|(path, _)| path.clone()),
+            Some(earlier)
+        );
     }
 
     #[test]
-    fn test_menu_action_generate_archive_no_archive_path_shows_error() {
+    fn test_file_changes_update_the_tree_and_keep_selection_of_renamed_files() {
         // Arrange
-        let (mut logic, mock_app_session_mutexed, _, _, _, _, _, _) = setup_logic_with_mocks();
+        let (mut logic, mock_app_session_mutexed, _, _, _, mock_archiver, _, _) =
+            setup_logic_with_mocks();
         let main_window_id = WindowId::new(1);
         logic.test_set_main_window_id_and_init_ui_state(main_window_id);
+        let dir = tempdir().unwrap();
+        let root = dir.path().to_path_buf();
+        fs::write(root.join("kept.rs"), "kept").unwrap();
+        fs::write(root.join("old.rs"), "old").unwrap();
+        let mut nodes = crate::core::CoreFileSystemScanner::new()
+            .scan_directory(&root, &[])
+            .unwrap();
+        for node in nodes.iter_mut() {
+            node.set_state(SelectionState::Selected);
+        }
         {
             let mut mock_app_session = mock_app_session_mutexed.lock().unwrap();
-            mock_app_session.set_profile_name_for_mock(Some("NoArchivePathProfile".to_string()));
-            mock_app_session.set_archive_path_for_mock(None);
+            mock_app_session.set_profile_name_for_mock(Some("Watched".to_string()));
+            mock_app_session.set_root_path_for_scan_for_mock(root.clone());
+            mock_app_session.set_snapshot_nodes_for_mock(nodes);
         }
+        fs::rename(root.join("old.rs"), root.join("renamed.rs")).unwrap();
+        fs::write(root.join("added.rs"), "added").unwrap();
 
         // Act
-        logic.handle_event(AppEvent::MenuActionClicked {
-            action_id: ui_constants::MENU_ACTION_GENERATE_ARCHIVE,
-        });
-        let cmds = logic.test_drain_commands();
+        logic.apply_file_changes(&[
+            FileChange::Renamed {
+                from: root.join("old.rs"),
+                to: root.join("renamed.rs"),
+            },
+            FileChange::Added(root.join("added.rs")),
+        ]);
+        let _cmds = logic.test_drain_commands();
 
         // Assert
-        assert!(find_command(&cmds, |cmd| matches!(cmd, PlatformCommand::UpdateLabelText { control_id, text, severity, .. } if *control_id == ui_constants::STATUS_LABEL_GENERAL_ID && severity == &MessageSeverity::Error && text.contains("No archive path set"))).is_some(), "Expected 'No archive path set' error status. Got: {cmds:?}");
+        let mock_app_session = mock_app_session_mutexed.lock().unwrap();
+        let states: Vec<(&str, SelectionState)> = mock_app_session
+            .get_snapshot_nodes()
+            .iter()
+            .map(|node| (node.name(), node.state()))
+            .collect();
+        assert_eq!(
+            states,
+            vec![
+                ("added.rs", SelectionState::New),
+                ("kept.rs", SelectionState::Selected),
+                ("renamed.rs", SelectionState::Selected),
+            ]
+        );
+        assert!(!mock_archiver.get_check_archive_status_calls().is_empty());
     }
 
     #[test]
-    fn test_profile_selection_cancel_with_active_profile_keeps_application_running() {
+    fn test_refresh_restarts_the_file_watcher() {
         // Arrange
-        let (mut logic, mock_app_session_mutexed, ..) = setup_logic_with_mocks();
-        let window_id = WindowId::new(1);
-        logic.test_set_main_window_id_and_init_ui_state(window_id);
+        let (mut logic, mock_app_session_mutexed, _, _, mock_file_system_scanner, _, _, _) =
+            setup_logic_with_mocks();
+        logic.test_set_main_window_id_and_init_ui_state(WindowId::new(1));
+        let dir = tempdir().unwrap();
+        let root = dir.path().to_path_buf();
         {
-            let mut data = mock_app_session_mutexed.lock().unwrap();
+            let mut mock_app_session = mock_app_session_mutexed.lock().unwrap();
+            mock_app_session.set_profile_name_for_mock(Some("Watched".to_string()));
+            mock_app_session.set_root_path_for_scan_for_mock(root.clone());
+        }
+
+        // Act
+        logic.handle_event(AppEvent::MenuActionClicked {
+            action_id: ui_constants::MENU_ACTION_REFRESH_FILE_LIST,
+        });
+        let _cmds = logic.test_drain_commands();
+
+        // Assert
+        assert_eq!(
+            mock_file_system_scanner.get_watch_directory_calls(),
+            vec![root]
+        );
+    }
+
+    #[test]
+    fn test_generate_all_archives_runs_each_profile_apart_from_the_session() {
+        // Arrange
+        let (
+            mut logic,
+            mock_app_session_mutexed,
+            _,
+            mock_profile_manager,
+            mock_file_system_scanner,
+            mock_archiver,
+            _,
+            _,
+        ) = setup_logic_with_mocks();
+        let main_window_id = WindowId::new(1);
+        logic.test_set_main_window_id_and_init_ui_state(main_window_id);
+        logic.test_set_active_project_root(PathBuf::from("/mock/project_root"));
+        mock_app_session_mutexed
+            .lock()
+            .unwrap()
+            .set_profile_name_for_mock(Some("Active".to_string()));
+        let core_root = PathBuf::from("/mock/project_root/core");
+        let mut core_profile = Profile::new(ProfileName::new("Core").unwrap(), core_root.clone());
+        core_profile.archive_path = Some(PathBuf::from("/out/core.txt"));
+        let draft_profile = Profile::new(ProfileName::new("Draft").unwrap(), core_root.clone());
+        mock_profile_manager.set_list_profiles_result(Ok(vec![
+            "Core".to_string(),
+            "Draft".to_string(),
+            "Missing".to_string(),
+        ]));
+        mock_profile_manager.set_load_profile_result("Core", Ok(core_profile));
+        mock_profile_manager.set_load_profile_result("Draft", Ok(draft_profile));
+        let core_nodes = vec![FileNode::new_test(
+            core_root.join("lib.rs"),
+            "lib.rs".into(),
+            false,
+        )];
+        mock_file_system_scanner.set_scan_directory_result(&core_root, Ok(core_nodes.clone()));
+        mock_archiver.set_write_archive_result(Ok(ArchiveReport {
+            files_written: 1,
+            ..ArchiveReport::default()
+        }));
+
+        // Act
+        logic.handle_event(AppEvent::MenuActionClicked {
+            action_id: ui_constants::MENU_ACTION_GENERATE_ALL_ARCHIVES,
+        });
+        let cmds = logic.test_collect_commands_until_idle();
+
+        // Assert
+        let write_calls = mock_archiver.get_write_archive_calls();
+        assert_eq!(write_calls.len(), 1);
+        assert_eq!(write_calls[0].0, core_nodes);
+        assert_eq!(write_calls[0].1, core_root);
+        assert_eq!(write_calls[0].2, PathBuf::from("/out/core.txt"));
+        assert_eq!(
+            mock_app_session_mutexed.lock().unwrap().get_profile_name(),
+            Some("Active".to_string()),
+            "The active session must not be replaced"
+        );
+        let summary = cmds
+            .iter()
+            .find_map(|cmd| match cmd {
+                PlatformCommand::SetViewerContent { text, .. } => Some(text.clone()),
+                _ => None,
+            })
+            .expect("Expected the summary in the viewer");
+        assert!(summary.starts_with("Generated 1 archive(s), 1 failed, 1 skipped."));
+        assert!(summary.contains("'Draft': skipped, no archive path set"));
+        assert!(summary.contains("'Missing': failed: could not load profile"));
+        assert!(
+            find_command(&cmds, |cmd| matches!(cmd, PlatformCommand::UpdateLabelText { text, severity, .. } if text.starts_with("Archive 1/3: 'Core'") && *severity == MessageSeverity::Information)).is_some(),
+            "Expected per-profile progress. Got: {cmds:?}"
+        );
+        assert!(
+            find_command(&cmds, |cmd| matches!(cmd, PlatformCommand::UpdateLabelText { text, severity, .. } if text.starts_with("Generated 1 archive(s); 1 failed and 1 skipped") && *severity == MessageSeverity::Warning)).is_some(),
+            "Expected a warning summary. Got: {cmds:?}"
+        );
+    }
+
+    #[test]
+    fn test_generate_delta_archive_writes_next_to_archive_and_reports_changes() {
+        // Arrange
+        let (mut logic, mock_app_session_mutexed, _, _, _, mock_archiver, _, _) =
+            setup_logic_with_mocks();
+        let main_window_id = WindowId::new(1);
+        logic.test_set_main_window_id_and_init_ui_state(main_window_id);
+        let archive_path = PathBuf::from("/test/archive.txt");
+        {
+            let mut mock_app_session = mock_app_session_mutexed.lock().unwrap();
+            mock_app_session.set_profile_name_for_mock(Some("DeltaProfile".to_string()));
+            mock_app_session.set_root_path_for_scan_for_mock(PathBuf::from("/test/root"));
+            mock_app_session.set_archive_path_for_mock(Some(archive_path.clone()));
+        }
+        mock_archiver.set_write_archive_result(Ok(ArchiveReport {
+            files_written: 1,
+            delta: Some(ArchiveDeltaHeader {
+                added: Vec::new(),
+                modified: vec!["src/lib.rs".to_string()],
+                deleted: vec!["src/old.rs".to_string()],
+            }),
+            ..ArchiveReport::default()
+        }));
+
+        // Act
+        logic.handle_event(AppEvent::MenuActionClicked {
+            action_id: ui_constants::MENU_ACTION_GENERATE_DELTA_ARCHIVE,
+        });
+        let cmds = logic.test_drain_commands();
+
+        // Assert
+        let write_calls = mock_archiver.get_write_archive_calls();
+        assert_eq!(write_calls.len(), 1);
+        assert_eq!(write_calls[0].2, PathBuf::from("/test/archive.delta.txt"));
+        assert!(
+            mock_archiver.get_check_archive_status_calls().is_empty(),
+            "A delta does not change the status of the full archive"
+        );
+        let expected =
+            "Delta archive saved to '/test/archive.delta.txt': 0 added, 1 modified, 1 deleted.";
+        assert!(
+            find_command(&cmds, |cmd| matches!(cmd, PlatformCommand::UpdateLabelText { text, .. } if text == expected)).is_some(),
+            "Expected delta summary. Got: {cmds:?}"
+        );
+
+        // Act - without a previous full generation
+        mock_archiver.set_write_archive_result(Err(ArchiveError::NoDeltaBaseline(archive_path)));
+        logic.handle_event(AppEvent::MenuActionClicked {
+            action_id: ui_constants::MENU_ACTION_GENERATE_DELTA_ARCHIVE,
+        });
+        let cmds = logic.test_drain_commands();
+
+        // Assert
+        assert!(
+            find_command(&cmds, |cmd| matches!(cmd, PlatformCommand::UpdateLabelText { text, severity, .. } if severity == &MessageSeverity::Warning && text.contains("Generate the full archive first"))).is_some(),
+            "Expected a hint to generate the full archive. Got: {cmds:?}"
+        );
+    }
+
+    #[test]
+    fn test_apply_edited_archive_previews_and_writes_selected_files() {
+        // Arrange
+        let (mut logic, _, _, _, _, _, _, _) = setup_logic_with_mocks();
+        let main_window_id = WindowId::new(1);
+        logic.test_set_main_window_id_and_init_ui_state(main_window_id);
+        let project_dir = tempdir().expect("temp dir creation");
+        let root = project_dir.path().to_path_buf();
+        logic.test_set_active_project_root(root.clone());
+        fs::write(root.join("a.txt"), "old\n").unwrap();
+        let edited_archive = root.join("edited.txt");
+        fs::write(
+            &edited_archive,
+            "// ===== File: a.txt =====\nnew\n// ===== File: b.txt =====\nadded\n",
+        )
+        .unwrap();
+
+        // Act - choose the edited archive
+        logic.handle_event(AppEvent::MenuActionClicked {
+            action_id: ui_constants::MENU_ACTION_APPLY_EDITED_ARCHIVE,
+        });
+        let cmds = logic.test_drain_commands();
+        assert!(
+            find_command(&cmds, |cmd| matches!(
+                cmd,
+                PlatformCommand::ShowOpenFileDialog { .. }
+            ))
+            .is_some()
+        );
+        logic.handle_event(AppEvent::FileOpenProfileDialogCompleted {
+            window_id: main_window_id,
+            result: Some(edited_archive),
+        });
+        let cmds = logic.test_drain_commands();
+
+        // Assert - the preview is shown and nothing is written yet
+        assert!(
+            find_command(&cmds, |cmd| matches!(cmd, PlatformCommand::SetViewerContent { text, .. } if text.contains("1. a.txt (changed)") && text.contains("+new"))).is_some(),
+            "Expected the preview in the viewer. Got: {cmds:?}"
+        );
+        assert!(
+            find_command(&cmds, |cmd| matches!(cmd, PlatformCommand::ShowInputDialog { context_tag, .. } if context_tag.as_deref() == Some("ApplyEditedArchive"))).is_some(),
+            "Expected the file selection dialog. Got: {cmds:?}"
+        );
+        assert_eq!(fs::read_to_string(root.join("a.txt")).unwrap(), "old\n");
+
+        // Act - accept only the new file
+        logic.handle_event(AppEvent::GenericInputDialogCompleted {
+            window_id: main_window_id,
+            text: Some("2".to_string()),
+            context_tag: Some("ApplyEditedArchive".to_string()),
+        });
+        let cmds = logic.test_drain_commands();
+
+        // Assert
+        assert_eq!(fs::read_to_string(root.join("a.txt")).unwrap(), "old\n");
+        assert_eq!(fs::read_to_string(root.join("b.txt")).unwrap(), "added\n");
+        assert!(
+            find_command(&cmds, |cmd| matches!(cmd, PlatformCommand::UpdateLabelText { text, .. } if text == "Applied edited archive: 0 file(s) updated, 1 created.")).is_some(),
+            "Expected the apply summary. Got: {cmds:?}"
+        );
+    }
+
+    #[test]
+    fn test_archive_history_lists_compares_and_restores_generations() {
+        // Arrange
+        let (mut logic, mock_app_session_mutexed, _, _, _, _, _, _) = setup_logic_with_mocks();
+        let main_window_id = WindowId::new(1);
+        logic.test_set_main_window_id_and_init_ui_state(main_window_id);
+        let project_dir = tempdir().expect("temp dir creation");
+        let root = project_dir.path().to_path_buf();
+        logic.test_set_active_project_root(root.clone());
+        {
+            let mut mock_app_session = mock_app_session_mutexed.lock().unwrap();
+            mock_app_session.set_profile_name_for_mock(Some("Demo".to_string()));
+            mock_app_session.set_root_path_for_scan_for_mock(root.clone());
+        }
+        let archive_path = root.join("out.txt");
+        let history = ArchiveHistory::new(root.join(".sourcepacker/history/Demo"));
+        for (content, checksum) in [("first", "1"), ("second", "2")] {
+            fs::write(&archive_path, content).unwrap();
+            let files = [(root.join("a.rs"), checksum.to_string())].into();
+            history
+                .record(
+                    &archive_path,
+                    std::slice::from_ref(&archive_path),
+                    files,
+                    format!("t{checksum}"),
+                    10,
+                )
+                .unwrap();
+        }
+        let viewer_text = |cmds: &[PlatformCommand]| {
+            cmds.iter().find_map(|cmd| match cmd {
+                PlatformCommand::SetViewerContent { text, .. } => Some(text.clone()),
+                _ => None,
+            })
+        };
+        let answer = |logic: &mut MyAppLogic, text: &str| {
+            logic.handle_event(AppEvent::GenericInputDialogCompleted {
+                window_id: main_window_id,
+                text: Some(text.to_string()),
+                context_tag: Some("ArchiveHistory".to_string()),
+            });
+            logic.test_drain_commands()
+        };
+
+        // Act - list
+        logic.handle_event(AppEvent::MenuActionClicked {
+            action_id: ui_constants::MENU_ACTION_SHOW_ARCHIVE_HISTORY,
+        });
+        let cmds = logic.test_drain_commands();
+
+        // Assert
+        let listing = viewer_text(&cmds).expect("history in the viewer");
+        assert!(listing.starts_with("#2 t2: 1 file(s)"), "{listing}");
+        assert!(listing.contains("0 added, 1 modified, 0 removed since #1"));
+        assert!(
+            find_command(&cmds, |cmd| matches!(cmd, PlatformCommand::ShowInputDialog { default_text, .. } if default_text.as_deref() == Some("keep 10"))).is_some(),
+            "Expected the history dialog. Got: {cmds:?}"
+        );
+
+        // Act & Assert - compare, then restore
+        let cmds = answer(&mut logic, "1 2");
+        assert_eq!(
+            viewer_text(&cmds).as_deref(),
+            Some("Changes from generation #1 to #2:\r\n~ a.rs\r\n")
+        );
+        answer(&mut logic, "1");
+        assert_eq!(fs::read_to_string(&archive_path).unwrap(), "first");
+        let record = ArchiveRecord::load(&root.join(".sourcepacker/archives/Demo.json")).unwrap();
+        assert_eq!(
+            record.files.get(&root.join("a.rs")).map(String::as_str),
+            Some("1")
+        );
+
+        // Act & Assert - retention
+        answer(&mut logic, "keep 3");
+        assert_eq!(
+            mock_app_session_mutexed
+                .lock()
+                .unwrap()
+                .get_archive_options()
+                .history
+                .retention,
+            3
+        );
+    }
+
+    #[test]
+    fn test_apply_patch_shows_dry_run_before_writing() {
+        // Arrange
+        let (mut logic, mock_app_session_mutexed, _, _, _, _, _, _) = setup_logic_with_mocks();
+        let main_window_id = WindowId::new(1);
+        logic.test_set_main_window_id_and_init_ui_state(main_window_id);
+        let project_dir = tempdir().expect("temp dir creation");
+        let root = project_dir.path().to_path_buf();
+        logic.test_set_active_project_root(root.clone());
+        fs::write(root.join("a.txt"), "one\ntwo\n").unwrap();
+        mock_app_session_mutexed
+            .lock()
+            .unwrap()
+            .set_snapshot_nodes_for_mock(vec![FileNode::new_test(
+                root.join("a.txt"),
+                "a.txt".into(),
+                false,
+            )]);
+        let patch_file = root.join("answer.md");
+        fs::write(
+            &patch_file,
+            "Change this:\n```diff\n--- a/a.txt\n+++ b/a.txt\n@@ -1,2 +1,2 @@\n one\n-two\n+TWO\n```\n",
+        )
+        .unwrap();
+
+        // Act - choose the patch
+        logic.handle_event(AppEvent::MenuActionClicked {
+            action_id: ui_constants::MENU_ACTION_APPLY_PATCH,
+        });
+        logic.test_drain_commands();
+        logic.handle_event(AppEvent::FileOpenProfileDialogCompleted {
+            window_id: main_window_id,
+            result: Some(patch_file),
+        });
+        let cmds = logic.test_drain_commands();
+
+        // Assert - dry run only
+        assert!(
+            find_command(&cmds, |cmd| matches!(cmd, PlatformCommand::SetViewerContent { text, .. } if text.contains("hunk 1: applied at line 1") && text.contains("TWO"))).is_some(),
+            "Expected the dry run in the viewer. Got: {cmds:?}"
+        );
+        assert_eq!(
+            fs::read_to_string(root.join("a.txt")).unwrap(),
+            "one\ntwo\n"
+        );
+
+        // Act - confirm
+        logic.handle_event(AppEvent::GenericInputDialogCompleted {
+            window_id: main_window_id,
+            text: Some("apply".to_string()),
+            context_tag: Some("ApplyPatch".to_string()),
+        });
+        let cmds = logic.test_drain_commands();
+
+        // Assert
+        assert_eq!(
+            fs::read_to_string(root.join("a.txt")).unwrap(),
+            "one\nTWO\n"
+        );
+        assert!(
+            find_command(&cmds, |cmd| matches!(cmd, PlatformCommand::UpdateLabelText { text, .. } if text == "Applied patch: 1 file(s) updated, 0 created, 0 deleted.")).is_some(),
+            "Expected the patch summary. Got: {cmds:?}"
+        );
+    }
+
+    #[test]
+    fn test_generate_archive_reports_secret_findings() {
+        // Arrange
+        let (mut logic, mock_app_session_mutexed, _, _, _, mock_archiver, _, _) =
+            setup_logic_with_mocks();
+        let main_window_id = WindowId::new(1);
+        logic.test_set_main_window_id_and_init_ui_state(main_window_id);
+        {
+            let mut mock_app_session = mock_app_session_mutexed.lock().unwrap();
+            mock_app_session.set_profile_name_for_mock(Some("SecretProfile".to_string()));
+            mock_app_session.set_root_path_for_scan_for_mock(PathBuf::from("/test/root"));
+            mock_app_session.set_archive_path_for_mock(Some(PathBuf::from("/test/archive.txt")));
+        }
+        let finding = SecretFinding {
+            relative_path: ".env".to_string(),
+            line: 2,
+            rule: "generic-assignment".to_string(),
+        };
+        mock_archiver.set_write_archive_result(Ok(ArchiveReport {
+            files_written: 1,
+            secrets: Some(SecretScanReport {
+                policy: SecretPolicy::Redact,
+                findings: vec![finding.clone()],
+            }),
+            ..ArchiveReport::default()
+        }));
+
+        // Act
+        logic.handle_event(AppEvent::MenuActionClicked {
+            action_id: ui_constants::MENU_ACTION_GENERATE_ARCHIVE,
+        });
+        let cmds = logic.test_drain_commands();
+
+        // Assert
+        let expected = "Redacted 1 possible secret(s): .env:2 (generic-assignment).";
+        assert!(
+            find_command(&cmds, |cmd| matches!(cmd, PlatformCommand::UpdateLabelText { text, severity, .. } if severity == &MessageSeverity::Warning && text == expected)).is_some(),
+            "Expected the redaction warning. Got: {cmds:?}"
+        );
+
+        // Act - the abort policy stops the generation
+        mock_archiver.set_write_archive_result(Err(ArchiveError::SecretsFound(vec![finding])));
+        logic.handle_event(AppEvent::MenuActionClicked {
+            action_id: ui_constants::MENU_ACTION_GENERATE_ARCHIVE,
+        });
+        let cmds = logic.test_drain_commands();
+
+        // Assert
+        assert!(
+            find_command(&cmds, |cmd| matches!(cmd, PlatformCommand::UpdateLabelText { text, severity, .. } if severity == &MessageSeverity::Error && text.starts_with("Archive not written: possible secrets found in .env:2 (generic-assignment)."))).is_some(),
+            "Expected the abort error. Got: {cmds:?}"
+        );
+    }
+
+    #[test]
+    fn test_generate_archive_missing_file_shows_message_box() {
+        // Arrange
+        let (mut logic, mock_app_session_mutexed, _, _, _, mock_archiver, _, _) =
+            setup_logic_with_mocks();
+        let main_window_id = WindowId::new(99);
+        logic.test_set_main_window_id_and_init_ui_state(main_window_id);
+
+        let profile_name = "PopupProfile";
+        let archive_path = PathBuf::from("/popup/archive.txt");
+        let root_folder = PathBuf::from("/popup/root");
+        let file_nodes = vec![FileNode::new_test(
+            root_folder.join("missing.txt"),
+            "missing.txt".into(),
+            false,
+        )];
+        {
+            let mut mock_app_session = mock_app_session_mutexed.lock().unwrap();
+            mock_app_session.set_profile_name_for_mock(Some(profile_name.to_string()));
+            mock_app_session.set_root_path_for_scan_for_mock(root_folder.clone());
+            mock_app_session.set_archive_path_for_mock(Some(archive_path.clone()));
+            mock_app_session.set_snapshot_nodes_for_mock(file_nodes.clone());
+        }
+        mock_archiver.set_write_archive_result(Err(ArchiveError::ReadSource {
+            path: root_folder.join("missing.txt"),
+            source: io::Error::new(io::ErrorKind::NotFound, "mocked missing file"),
+        }));
+
+        // Act
+        logic.handle_event(AppEvent::MenuActionClicked {
+            action_id: ui_constants::MENU_ACTION_GENERATE_ARCHIVE,
+        });
+        let cmds = logic.test_drain_commands();
+
+        // Assert
+        let message_box_cmd = find_command(&cmds, |cmd| {
+            matches!(
+                cmd,
+                PlatformCommand::ShowMessageBox {
+                    window_id,
+                    title,
+                    message,
+                    severity,
+                } if *window_id == main_window_id
+                    && title == "Missing Source File"
+                    && message.contains("mocked missing file")
+                    && *severity == MessageSeverity::Error
+            )
+        });
+        assert!(
+            message_box_cmd.is_some(),
+            "Expected ShowMessageBox command. Got: {cmds:?}"
+        );
+
+        assert!(
+            find_command(&cmds, |cmd| matches!(
+                cmd,
+                PlatformCommand::UpdateLabelText {
+                    control_id,
+                    ..
+                } if *control_id == ui_constants::STATUS_LABEL_GENERAL_ID
+            ))
+            .is_none(),
+            "Unexpected status label update when missing file encountered. Got: {cmds:?}"
+        );
+
+        assert_eq!(
+            mock_archiver.get_write_archive_calls().len(),
+            1,
+            "Archive generation should be attempted exactly once"
+        );
+    }
+
+    #[test]
+    fn test_generate_archive_reports_files_left_out_by_token_budget() {
+        // Arrange
+        let (mut logic, mock_app_session_mutexed, _, _, _, mock_archiver, _, _) =
+            setup_logic_with_mocks();
+        let main_window_id = WindowId::new(42);
+        logic.test_set_main_window_id_and_init_ui_state(main_window_id);
+        let root_folder = PathBuf::from("/budget/root");
+        {
+            let mut mock_app_session = mock_app_session_mutexed.lock().unwrap();
+            mock_app_session.set_profile_name_for_mock(Some("BudgetProfile".to_string()));
+            mock_app_session.set_root_path_for_scan_for_mock(root_folder.clone());
+            mock_app_session.set_archive_path_for_mock(Some(PathBuf::from("/budget/a.txt")));
+        }
+        mock_archiver.set_write_archive_result(Ok(ArchiveReport {
+            files_written: 1,
+            budget: Some(TokenBudgetReport {
+                budget: 100,
+                tokens_before: 160,
+                tokens_after: 100,
+                omitted: vec![OmittedFile {
+                    path: root_folder.join("docs").join("notes.md"),
+                    priority: FilePriority::Optional,
+                    original_tokens: 60,
+                    action: BudgetAction::Dropped,
+                }],
+            }),
+            ..ArchiveReport::default()
+        }));
+
+        // Act
+        logic.handle_event(AppEvent::MenuActionClicked {
+            action_id: ui_constants::MENU_ACTION_GENERATE_ARCHIVE,
+        });
+        let cmds = logic.test_drain_commands();
+
+        // Assert
+        let expected_path = PathBuf::from("docs").join("notes.md");
+        let expected_fragment = format!(
+            "Left out: {} (optional, 60 tokens dropped)",
+            expected_path.display()
+        );
+        assert!(
+            find_command(&cmds, |cmd| matches!(
+                cmd,
+                PlatformCommand::UpdateLabelText { control_id, text, severity, .. }
+                    if *control_id == ui_constants::STATUS_LABEL_GENERAL_ID
+                        && *severity == MessageSeverity::Warning
+                        && text.contains(&expected_fragment)
+            ))
+            .is_some(),
+            "Expected a warning listing the omitted file. Got: {cmds:?}"
+        );
+    }
+
+    #[test]
+    fn test_token_budget_dialog_updates_profile_and_session() {
+        // Arrange
+        let (
+            mut logic,
+            mock_app_session_mutexed,
+            _mock_config_manager,
+            mock_profile_manager_arc,
+            _mock_file_system_scanner_arc,
+            _mock_archiver_arc,
+            _mock_state_manager,
+            _mock_token_counter,
+        ) = setup_logic_with_mocks();
+        let main_window_id = WindowId::new(7);
+        logic.test_set_main_window_id_and_init_ui_state(main_window_id);
+        mock_app_session_mutexed
+            .lock()
+            .unwrap()
+            .set_profile_name_for_mock(Some("DemoProfile".to_string()));
+
+        // Act
+        logic.handle_event(AppEvent::GenericInputDialogCompleted {
+            window_id: main_window_id,
+            text: Some(" 120000 ".to_string()),
+            context_tag: Some("TokenBudget".to_string()),
+        });
+
+        // Assert
+        let saved_profiles = mock_profile_manager_arc.get_save_profile_calls();
+        assert_eq!(saved_profiles.len(), 1);
+        assert_eq!(
+            saved_profiles[0].1.archive_options.token_budget,
+            Some(120000)
+        );
+
+        // Act - "off" disables the budget again
+        logic.handle_event(AppEvent::GenericInputDialogCompleted {
+            window_id: main_window_id,
+            text: Some("off".to_string()),
+            context_tag: Some("TokenBudget".to_string()),
+        });
+
+        // Assert
+        assert_eq!(
+            mock_app_session_mutexed
+                .lock()
+                .unwrap()
+                .get_archive_options()
+                .token_budget,
+            None
+        );
+    }
+
+    #[test]
+    fn test_content_transforms_dialog_updates_profile() {
+        // Arrange
+        let (
+            mut logic,
+            mock_app_session_mutexed,
+            _mock_config_manager,
+            mock_profile_manager_arc,
+            _mock_file_system_scanner_arc,
+            _mock_archiver_arc,
+            _mock_state_manager,
+            _mock_token_counter,
+        ) = setup_logic_with_mocks();
+        let main_window_id = WindowId::new(9);
+        logic.test_set_main_window_id_and_init_ui_state(main_window_id);
+        mock_app_session_mutexed
+            .lock()
+            .unwrap()
+            .set_profile_name_for_mock(Some("DemoProfile".to_string()));
+
+        // Act
+        logic.handle_event(AppEvent::GenericInputDialogCompleted {
+            window_id: main_window_id,
+            text: Some("strip-comments@*.rs, collapse-blank-lines".to_string()),
+            context_tag: Some("ContentTransforms".to_string()),
+        });
+
+        // Assert
+        let saved_profiles = mock_profile_manager_arc.get_save_profile_calls();
+        assert_eq!(saved_profiles.len(), 1);
+        assert_eq!(
+            saved_profiles[0].1.archive_options.transforms,
+            vec![
+                TransformRule {
+                    transform: ContentTransform::StripComments,
+                    glob: Some("*.rs".to_string()),
+                },
+                TransformRule {
+                    transform: ContentTransform::CollapseBlankLines,
+                    glob: None,
+                },
+            ]
+        );
+
+        // Act - an unknown transform is rejected and the dialog is shown again
+        logic.test_drain_commands();
+        logic.handle_event(AppEvent::GenericInputDialogCompleted {
+            window_id: main_window_id,
+            text: Some("shrink-everything".to_string()),
+            context_tag: Some("ContentTransforms".to_string()),
+        });
+
+        // Assert
+        assert_eq!(mock_profile_manager_arc.get_save_profile_calls().len(), 1);
+        let cmds = logic.test_drain_commands();
+        assert!(
+            find_command(&cmds, |cmd| matches!(
+                cmd,
+                PlatformCommand::ShowInputDialog { context_tag: Some(tag), .. } if tag == "ContentTransforms"
+            ))
+            .is_some(),
+            "Expected the content transforms dialog to be shown again"
+        );
+    }
+
+    #[test]
+    fn test_directory_tree_dialog_updates_profile() {
+        // Arrange
+        let (mut logic, mock_app_session_mutexed, _, mock_profile_manager_arc, ..) =
+            setup_logic_with_mocks();
+        let main_window_id = WindowId::new(9);
+        logic.test_set_main_window_id_and_init_ui_state(main_window_id);
+        mock_app_session_mutexed
+            .lock()
+            .unwrap()
+            .set_profile_name_for_mock(Some("DemoProfile".to_string()));
+
+        // Act
+        logic.handle_event(AppEvent::GenericInputDialogCompleted {
+            window_id: main_window_id,
+            text: Some("all:2".to_string()),
+            context_tag: Some("DirectoryTree".to_string()),
+        });
+
+        // Assert
+        let saved_profiles = mock_profile_manager_arc.get_save_profile_calls();
+        assert_eq!(saved_profiles.len(), 1);
+        assert_eq!(
+            saved_profiles[0].1.archive_options.directory_tree,
+            Some(DirectoryTreeOptions {
+                include_unselected: true,
+                max_depth: Some(2),
+            })
+        );
+    }
+
+    #[test]
+    fn test_toggle_archive_manifest_flips_profile_option() {
+        // Arrange
+        let (
+            mut logic,
+            mock_app_session_mutexed,
+            _mock_config_manager,
+            mock_profile_manager_arc,
+            _mock_file_system_scanner_arc,
+            _mock_archiver_arc,
+            _mock_state_manager,
+            _mock_token_counter,
+        ) = setup_logic_with_mocks();
+        logic.test_set_main_window_id_and_init_ui_state(WindowId::new(8));
+        mock_app_session_mutexed
+            .lock()
+            .unwrap()
+            .set_profile_name_for_mock(Some("DemoProfile".to_string()));
+
+        // Act
+        logic.handle_event(AppEvent::MenuActionClicked {
+            action_id: ui_constants::MENU_ACTION_TOGGLE_ARCHIVE_MANIFEST,
+        });
+        logic.handle_event(AppEvent::MenuActionClicked {
+            action_id: ui_constants::MENU_ACTION_TOGGLE_ARCHIVE_MANIFEST,
+        });
+
+        // Assert
+        let saved_profiles = mock_profile_manager_arc.get_save_profile_calls();
+        assert_eq!(saved_profiles.len(), 2);
+        assert!(saved_profiles[0].1.archive_options.include_manifest);
+        assert!(!saved_profiles[1].1.archive_options.include_manifest);
+    }
+
+    #[test]
+    fn test_menu_action_generate_archive_no_profile_shows_error() {
+        // Arrange
+        let (mut logic, mock_app_session_mutexed, _, _, _, _, _, _) = setup_logic_with_mocks();
+        let main_window_id = WindowId::new(1);
+        logic.test_set_main_window_id_and_init_ui_state(main_window_id);
+        mock_app_session_mutexed
+            .lock()
+            .unwrap()
+            .set_profile_name_for_mock(None);
+
+        // Act
+        logic.handle_event(AppEvent::MenuActionClicked {
+            action_id: ui_constants::MENU_ACTION_GENERATE_ARCHIVE,
+        });
+        let cmds = logic.test_drain_commands();
+
+        // Assert
+        assert!(find_command(&cmds, |cmd| matches!(cmd, PlatformCommand::UpdateLabelText { control_id, text, severity, .. } if *control_id == ui_constants::STATUS_LABEL_GENERAL_ID && severity == &MessageSeverity::Error && text.contains("No profile loaded"))).is_some(), "Expected 'No profile loaded' error status. Got: {cmds:?}");
+    }
+
+    #[test]
+    fn test_menu_action_generate_archive_no_archive_path_shows_error() {
+        // Arrange
+        let (mut logic, mock_app_session_mutexed, _, _, _, _, _, _) = setup_logic_with_mocks();
+        let main_window_id = WindowId::new(1);
+        logic.test_set_main_window_id_and_init_ui_state(main_window_id);
+        {
+            let mut mock_app_session = mock_app_session_mutexed.lock().unwrap();
+            mock_app_session.set_profile_name_for_mock(Some("NoArchivePathProfile".to_string()));
+            mock_app_session.set_archive_path_for_mock(None);
+        }
+
+        // Act
+        logic.handle_event(AppEvent::MenuActionClicked {
+            action_id: ui_constants::MENU_ACTION_GENERATE_ARCHIVE,
+        });
+        let cmds = logic.test_drain_commands();
+
+        // Assert
+        assert!(find_command(&cmds, |cmd| matches!(cmd, PlatformCommand::UpdateLabelText { control_id, text, severity, .. } if *control_id == ui_constants::STATUS_LABEL_GENERAL_ID && severity == &MessageSeverity::Error && text.contains("No archive path set"))).is_some(), "Expected 'No archive path set' error status. Got: {cmds:?}");
+    }
+
+    #[test]
+    fn test_profile_selection_cancel_with_active_profile_keeps_application_running() {
+        // Arrange
+        let (mut logic, mock_app_session_mutexed, ..) = setup_logic_with_mocks();
+        let window_id = WindowId::new(1);
+        logic.test_set_main_window_id_and_init_ui_state(window_id);
+        {
+            let mut data = mock_app_session_mutexed.lock().unwrap();
             data.set_profile_name_for_mock(Some("ExistingProfile".to_string()));
         }
 
@@ -1938,6 +3072,33 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_outdated_archive_status_names_changed_files() {
+        let changes = ArchiveChanges {
+            added: vec![PathBuf::from("/p/new.rs")],
+            removed: Vec::new(),
+            modified: ["a.rs", "b.rs", "c.rs", "d.rs", "e.rs"]
+                .iter()
+                .map(|name| Path::new("/p/src").join(name))
+                .collect(),
+        };
+
+        let text = MyAppLogic::archive_status_to_plain_string(
+            &ArchiveStatus::OutdatedRequiresUpdate(Some(changes)),
+        );
+
+        assert_eq!(
+            text,
+            "Out of date: added new.rs; modified a.rs, b.rs, c.rs, and 2 more."
+        );
+        assert_eq!(
+            MyAppLogic::archive_status_to_plain_string(&ArchiveStatus::OutdatedRequiresUpdate(
+                None
+            )),
+            "Out of date."
+        );
+    }
+
     #[test]
     fn test_update_current_archive_status_routes_to_dedicated_label() {
         // Arrange
@@ -1966,7 +3127,7 @@ mod tests {
         // Case 1: ArchiveStatus is an error
         let error_status = ArchiveStatus::ErrorChecking(Some(io::ErrorKind::PermissionDenied));
         let expected_dedicated_error_text = "Archive: Error: PermissionDenied.".to_string();
-        mock_archiver.set_check_archive_status_result(error_status);
+        mock_archiver.set_check_archive_status_result(error_status.clone());
 
         // Act 1
         logic.update_current_archive_status();
@@ -2371,6 +3532,67 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_toggle_outline_switches_active_file_between_outline_and_full() {
+        // Arrange
+        let (mut logic, mock_app_session, ..) = setup_logic_with_mocks();
+        let window_id = WindowId::new(1);
+        logic.test_set_main_window_id_and_init_ui_state(window_id);
+        let tree_item_id = TreeItemId(42);
+        let path = PathBuf::from("/root/lib.rs");
+        logic.test_set_path_to_tree_item_id_mapping(path.clone(), tree_item_id);
+        mock_app_session
+            .lock()
+            .unwrap()
+            .set_snapshot_nodes_for_mock(vec![FileNode::new_full(
+                path.clone(),
+                "lib.rs".into(),
+                false,
+                SelectionState::Selected,
+                Vec::new(),
+                "".to_string(),
+            )]);
+        logic.handle_event(AppEvent::TreeViewItemSelectionChanged {
+            window_id,
+            item_id: tree_item_id,
+        });
+        logic.test_drain_commands();
+
+        // Act
+        logic.handle_event(AppEvent::MenuActionClicked {
+            action_id: ui_constants::MENU_ACTION_TOGGLE_OUTLINE,
+        });
+        let cmds = logic.test_drain_commands();
+
+        // Assert
+        let state = |session: &Arc<Mutex<MockProfileRuntimeData>>| {
+            session
+                .lock()
+                .unwrap()
+                .get_node_attributes_for_path(&path)
+                .map(|(state, _)| state)
+        };
+        assert_eq!(state(&mock_app_session), Some(SelectionState::Outline));
+        assert!(
+            find_command(&cmds, |cmd| matches!(
+                cmd,
+                PlatformCommand::UpdateTreeItemText { item_id, text, .. }
+                    if *item_id == tree_item_id
+                        && text.ends_with(ui_constants::OUTLINE_ITEM_INDICATOR_CHAR)
+            ))
+            .is_some(),
+            "Expected the outline indicator on the tree item. Got: {cmds:?}"
+        );
+
+        // Act - toggling again includes the file in full
+        logic.handle_event(AppEvent::MenuActionClicked {
+            action_id: ui_constants::MENU_ACTION_TOGGLE_OUTLINE,
+        });
+
+        // Assert
+        assert_eq!(state(&mock_app_session), Some(SelectionState::Selected));
+    }
+
     #[test]
     fn test_select_text_file_loads_content_into_viewer() {
         // Arrange
@@ -2424,6 +3646,48 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_select_binary_file_shows_placeholder_in_viewer() {
+        // Arrange
+        let (mut logic, mock_app_session, ..) = setup_logic_with_mocks();
+        let window_id = WindowId::new(1);
+        logic.test_set_main_window_id_and_init_ui_state(window_id);
+
+        let temp_dir = tempdir().expect("temp dir creation");
+        let file_path = temp_dir.path().join("logo.png");
+        let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+        png.resize(3000, 0);
+        fs::write(&file_path, &png).expect("write png file");
+
+        let tree_item_id = TreeItemId(102);
+        logic.test_set_path_to_tree_item_id_mapping(file_path.clone(), tree_item_id);
+        {
+            let mut app_data = mock_app_session.lock().unwrap();
+            app_data.set_snapshot_nodes_for_mock(vec![FileNode::new_full(
+                file_path.clone(),
+                "logo.png".into(),
+                false,
+                SelectionState::Selected,
+                Vec::new(),
+                "".to_string(),
+            )]);
+        }
+
+        // Act
+        logic.handle_event(AppEvent::TreeViewItemSelectionChanged {
+            window_id,
+            item_id: tree_item_id,
+        });
+        let cmds = logic.test_drain_commands();
+
+        // Assert
+        let viewer_text = cmds.iter().find_map(|cmd| match cmd {
+            PlatformCommand::SetViewerContent { text, .. } => Some(text.as_str()),
+            _ => None,
+        });
+        assert_eq!(viewer_text, Some("[binary file, 3 KB, image/png]"));
+    }
+
     // --- Tests for newly exposed private functions ---
 
     #[test]
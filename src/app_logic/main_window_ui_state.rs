@@ -380,9 +380,9 @@ impl MainWindowUiState {
 mod tests {
     use super::*;
     use crate::core::{
-        ContentSearchProgress, FileNode, FileSystemScannerOperations,
+        ArchiveOptions, ContentSearchProgress, FileNode, FileSystemScannerOperations,
         NodeStateApplicatorOperations, Profile, ProfileRuntimeDataOperations, SelectionState,
-        TokenCounterOperations,
+        TokenCounterOperations, composite_profile::ComponentLoaderOperations,
     };
     use crate::platform_layer::WindowId;
     use std::collections::HashSet;
@@ -396,6 +396,7 @@ mod tests {
         profile_name: Option<String>,
         archive_path: Option<PathBuf>,
         exclude_patterns: Vec<String>,
+        archive_options: ArchiveOptions,
         // We don't need to mock all methods, only those used by MainWindowUiState
     }
 
@@ -412,6 +413,12 @@ mod tests {
         fn set_exclude_patterns(&mut self, patterns: Vec<String>) {
             self.exclude_patterns = patterns;
         }
+        fn get_archive_options(&self) -> ArchiveOptions {
+            self.archive_options.clone()
+        }
+        fn set_archive_options(&mut self, options: ArchiveOptions) {
+            self.archive_options = options;
+        }
 
         // --- Unused methods for these specific tests, provide default/dummy implementations ---
         fn set_profile_name(&mut self, _name: Option<String>) {
@@ -434,6 +441,7 @@ mod tests {
             _state_manager: &dyn NodeStateApplicatorOperations,
             _selected_paths: &HashSet<PathBuf>,
             _deselected_paths: &HashSet<PathBuf>,
+            _outline_paths: &HashSet<PathBuf>,
         ) {
             unimplemented!("MockProfileRuntimeDataOps: apply_selection_states_to_snapshot")
         }
@@ -474,6 +482,11 @@ mod tests {
         fn apply_token_progress(&mut self, _progress: TokenProgress) -> usize {
             unimplemented!("MockProfileRuntimeDataOps: apply_token_progress")
         }
+        fn get_cached_file_token_details(
+            &self,
+        ) -> std::collections::HashMap<PathBuf, crate::core::file_node::FileTokenDetails> {
+            std::collections::HashMap::new()
+        }
         fn search_content_async(
             &self,
             _search_term: String,
@@ -492,10 +505,13 @@ mod tests {
             _file_system_scanner: &dyn FileSystemScannerOperations,
             _state_manager: &dyn NodeStateApplicatorOperations,
             _token_counter: &dyn TokenCounterOperations,
+            _component_loader: &dyn ComponentLoaderOperations,
         ) -> Result<(), String> {
             unimplemented!("MockProfileRuntimeDataOps: load_profile_into_session")
         }
-        fn get_current_selection_paths(&self) -> (HashSet<PathBuf>, HashSet<PathBuf>) {
+        fn get_current_selection_paths(
+            &self,
+        ) -> (HashSet<PathBuf>, HashSet<PathBuf>, HashSet<PathBuf>) {
             unimplemented!("MockProfileRuntimeDataOps: get_current_selection_paths")
         }
     }
@@ -32,6 +32,9 @@ pub const ID_VIEWER_EDIT_CTRL: ControlId = ControlId::new(1030);
 // Unicode filled circle appended to "New" tree items to make the state obvious. [FileSelStateNewV2]
 pub const NEW_ITEM_INDICATOR_CHAR: char = '●';
 
+// Appended to tree items that are included as an outline only. [FileSelOutlineV1]
+pub const OUTLINE_ITEM_INDICATOR_CHAR: char = '◇';
+
 // Logical ID for the panel that will contain filter input and buttons.
 pub const FILTER_PANEL_ID: ControlId = ControlId::new(1020);
 
@@ -56,3 +59,20 @@ pub const MENU_ACTION_EDIT_EXCLUDE_PATTERNS: MenuActionId = MenuActionId(5);
 pub const MENU_ACTION_REFRESH_FILE_LIST: MenuActionId = MenuActionId(6);
 pub const MENU_ACTION_GENERATE_ARCHIVE: MenuActionId = MenuActionId(7);
 pub const MENU_ACTION_OPEN_FOLDER: MenuActionId = MenuActionId(8);
+pub const MENU_ACTION_SET_ARCHIVE_FORMAT: MenuActionId = MenuActionId(9);
+pub const MENU_ACTION_SET_ARCHIVE_SPLIT: MenuActionId = MenuActionId(10);
+pub const MENU_ACTION_SET_TOKEN_BUDGET: MenuActionId = MenuActionId(11);
+pub const MENU_ACTION_SET_FILE_PRIORITY: MenuActionId = MenuActionId(12);
+pub const MENU_ACTION_TOGGLE_ARCHIVE_MANIFEST: MenuActionId = MenuActionId(13);
+pub const MENU_ACTION_SET_CONTENT_TRANSFORMS: MenuActionId = MenuActionId(14);
+pub const MENU_ACTION_TOGGLE_OUTLINE: MenuActionId = MenuActionId(15);
+pub const MENU_ACTION_SET_DIRECTORY_TREE: MenuActionId = MenuActionId(16);
+pub const MENU_ACTION_GENERATE_DELTA_ARCHIVE: MenuActionId = MenuActionId(17);
+pub const MENU_ACTION_APPLY_EDITED_ARCHIVE: MenuActionId = MenuActionId(18);
+pub const MENU_ACTION_APPLY_PATCH: MenuActionId = MenuActionId(19);
+pub const MENU_ACTION_SET_SECRET_SCAN: MenuActionId = MenuActionId(20);
+pub const MENU_ACTION_SET_CONTENT_LIMITS: MenuActionId = MenuActionId(21);
+pub const MENU_ACTION_SET_ENCODINGS: MenuActionId = MenuActionId(22);
+pub const MENU_ACTION_SET_LINE_NUMBERS: MenuActionId = MenuActionId(23);
+pub const MENU_ACTION_SHOW_ARCHIVE_HISTORY: MenuActionId = MenuActionId(24);
+pub const MENU_ACTION_GENERATE_ALL_ARCHIVES: MenuActionId = MenuActionId(25);
@@ -6,17 +6,42 @@
  * for file system operations, profile management, configuration, archiving, state management,
  * and session data handling. It also includes utilities for token estimation and path utilities.
  */
+pub mod archive_apply;
+pub mod archive_format;
+pub mod archive_history;
+pub mod archive_manifest;
+pub mod archive_path_template;
+pub mod archive_prompt;
+pub mod archive_record;
+pub mod archive_split;
+pub mod archive_template;
+pub mod archive_tree;
 pub mod archiver;
+pub mod atomic_write;
+pub mod batch_generate;
 pub mod checksum_utils;
+pub mod composite_profile;
 pub mod config;
+pub mod content_class;
 pub mod content_search_progress;
+pub mod content_transform;
+pub mod entry_spool;
 pub mod file_node;
 pub mod file_system;
+pub mod file_watcher;
+pub mod line_numbers;
 pub mod node_state_applicator;
+pub mod outline;
+pub mod patch_apply;
 pub mod path_utils;
 pub mod profile_runtime_data;
+pub mod profile_settings;
 pub mod profiles;
 mod project_context;
+pub mod scan_cache;
+pub mod secret_scan;
+pub mod text_encoding;
+pub mod token_budget;
 pub mod token_progress;
 pub mod tokenizer_utils;
 
@@ -40,7 +65,13 @@ pub use project_context::ProjectContext;
 pub use project_context::{ProfileName, ProjectRelativePath};
 
 // Re-export archiver related items
-pub use archiver::{ArchiverOperations, CoreArchiver};
+pub use archive_format::ArchiveFormat;
+pub use archive_split::ArchiveSplitOptions;
+pub use archiver::{
+    ArchiveError, ArchiveReport, ArchiveRequest, ArchiverOperations, CoreArchiver, delta_file_path,
+};
+pub use profile_settings::ArchiveOptions;
+pub use token_budget::{FilePriority, TokenBudgetReport};
 
 // Re-export config related items
 pub use config::{ConfigManagerOperations, CoreConfigManager as CoreConfigManagerForConfig};
@@ -0,0 +1,668 @@
+use super::archive_format::{
+    ArchiveEntry, PLAIN_FILE_HEADER_PREFIX, decode_header_path, parse_plain_file_header,
+    parse_plain_lines_line, remove_line_numbers,
+};
+use super::archive_record::ArchiveRecord;
+use super::atomic_write;
+use super::checksum_utils;
+use super::project_context::{
+    PROJECT_CONFIG_DIR_NAME, ProjectContext, ProjectRelativePath, ProjectRelativePathError,
+};
+use super::text_encoding::{self, DetectedEncoding, EncodingRules};
+use similar::TextDiff;
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+/*
+ * This module unpacks an archive that was edited outside SourcePacker (typically by an
+ * LLM that answered with whole files in the plain text layout) back into the project.
+ * [ArchiveApplyEditedV1] Applying happens in two steps: `ApplyPreview::build` parses the
+ * text strictly, confines every path to the project root and classifies each file as
+ * new, changed or identical with a unified diff; `ApplyPreview::apply` then writes the
+ * files the user accepted. A file whose content on disk no longer matches the record of
+ * the generated archive is refused, so edits made since then are never overwritten.
+ * Files are compared as decoded text and written back in the encoding they were read
+ * with (see `text_encoding`), so a file that is not UTF-8 stays in its encoding.
+ */
+
+/*
+ * Errors that reject the whole edited archive. Nothing is written when any file in the
+ * archive is malformed or points outside the project.
+ */
+#[derive(Debug)]
+pub enum ApplyError {
+    Parse { line: usize, message: String },
+    InvalidPath { path: String, reason: String },
+    ReadTarget { path: PathBuf, source: io::Error },
+}
+
+impl fmt::Display for ApplyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApplyError::Parse { line, message } => write!(f, "Line {line}: {message}"),
+            ApplyError::InvalidPath { path, reason } => {
+                write!(f, "Refusing path '{path}': {reason}")
+            }
+            ApplyError::ReadTarget { path, source } => {
+                write!(f, "Failed to read '{}': {source}", path.display())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ApplyError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ApplyError::ReadTarget { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/*
+ * Parses an edited plain text archive. Unlike `ArchiveFormat::parse`, this is strict about
+ * anything an LLM might get wrong: only blank lines and `//` comment lines may precede the
+ * first file header, a line starting like a header must be a complete header, paths must
+ * be non-empty and unique, and at least one file must be present. Quoted header paths are
+ * decoded (see `archive_format::encode_header_path`). The line-number gutter of an archive
+ * generated with line numbers is removed.
+ * A line inside a file's content that is shaped like a header is read as a header; the
+ * plain text layout cannot express such content.
+ */
+pub fn parse_edited_archive(text: &str) -> Result<Vec<ArchiveEntry>, ApplyError> {
+    let mut entries: Vec<ArchiveEntry> = Vec::new();
+    let mut seen = HashSet::new();
+    for (index, line) in text.split_inclusive('\n').enumerate() {
+        let line_number = index + 1;
+        let trimmed = line.trim_end_matches(['\n', '\r']);
+        if trimmed.starts_with(PLAIN_FILE_HEADER_PREFIX) {
+            let parse_error = |message: String| ApplyError::Parse {
+                line: line_number,
+                message,
+            };
+            let encoded = parse_plain_file_header(trimmed)
+                .ok_or_else(|| parse_error("Malformed file header".to_string()))?;
+            let path = decode_header_path(encoded).map_err(|e| parse_error(e.to_string()))?;
+            if path.is_empty() {
+                return Err(parse_error("File header without a path".to_string()));
+            }
+            if !seen.insert(path.clone()) {
+                return Err(parse_error(format!("'{path}' appears more than once")));
+            }
+            entries.push(ArchiveEntry {
+                relative_path: path,
+                content: String::new(),
+                lines: None,
+            });
+        } else if let Some(current) = entries.last_mut() {
+            if current.content.is_empty()
+                && current.lines.is_none()
+                && let Some(lines) = parse_plain_lines_line(trimmed)
+            {
+                current.lines = Some(lines);
+                continue;
+            }
+            current.content.push_str(line);
+        } else if !trimmed.trim().is_empty() && !trimmed.starts_with("//") {
+            return Err(ApplyError::Parse {
+                line: line_number,
+                message: format!("Unexpected text before the first file header: '{trimmed}'"),
+            });
+        }
+    }
+    if entries.is_empty() {
+        return Err(ApplyError::Parse {
+            line: text.lines().count().max(1),
+            message: "The archive contains no file headers".to_string(),
+        });
+    }
+    entries.iter_mut().for_each(remove_line_numbers);
+    Ok(entries)
+}
+
+/*
+ * Resolves a header path to an absolute path inside the project. The path must be
+ * relative, must not climb out with `..`, must not point into `.sourcepacker`, and the
+ * nearest existing ancestor must resolve (through symlinks) to a directory in the root.
+ */
+pub(crate) fn resolve_target(project: &ProjectContext, path: &str) -> Result<PathBuf, ApplyError> {
+    let invalid = |reason: &str| ApplyError::InvalidPath {
+        path: path.to_string(),
+        reason: reason.to_string(),
+    };
+    let relative = Path::new(path);
+    if relative
+        .components()
+        .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir))
+    {
+        return Err(invalid(
+            "only paths relative to the project root are allowed",
+        ));
+    }
+    if relative
+        .components()
+        .find(|c| matches!(c, Component::Normal(_)))
+        .is_some_and(|first| {
+            // Windows file names are case-insensitive, so `.SourcePacker` is the same folder.
+            first
+                .as_os_str()
+                .to_string_lossy()
+                .eq_ignore_ascii_case(PROJECT_CONFIG_DIR_NAME)
+        })
+    {
+        return Err(invalid("SourcePacker's own files cannot be replaced"));
+    }
+    let absolute = ProjectRelativePath::from_relative(relative)
+        .map_err(|_| invalid("only paths relative to the project root are allowed"))?
+        .to_absolute(project);
+    let existing_ancestor = absolute
+        .ancestors()
+        .find(|ancestor| ancestor.exists())
+        .unwrap_or(project.root_path());
+    match ProjectRelativePath::try_from_absolute(project, existing_ancestor) {
+        Ok(_) => Ok(absolute),
+        Err(
+            ProjectRelativePathError::OutsideRoot(_) | ProjectRelativePathError::NotRelative(_),
+        ) => Err(invalid("the path leads outside the project root")),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApplyFileStatus {
+    New,
+    Changed,
+    Identical,
+    /* The file on disk differs from the archived version; it is never written. */
+    ChangedOnDisk,
+}
+
+impl ApplyFileStatus {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ApplyFileStatus::New => "new",
+            ApplyFileStatus::Changed => "changed",
+            ApplyFileStatus::Identical => "identical",
+            ApplyFileStatus::ChangedOnDisk => "changed on disk since the archive was generated",
+        }
+    }
+}
+
+/*
+ * One file of the edited archive. `disk_checksum` is the SHA-256 of the file when the
+ * preview was built (`None` if it did not exist), so a change between preview and apply
+ * is detected as well. `encoding` is the one the file on disk was decoded with, and the
+ * one `content` is written in.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApplyFilePreview {
+    pub relative_path: String,
+    pub absolute_path: PathBuf,
+    pub status: ApplyFileStatus,
+    pub content: String,
+    pub diff: String,
+    disk_checksum: Option<String>,
+    encoding: DetectedEncoding,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ApplyPreview {
+    pub files: Vec<ApplyFilePreview>,
+}
+
+/* Outcome of `ApplyPreview::apply`, as relative paths. */
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ApplyReport {
+    pub written: Vec<String>,
+    pub created: Vec<String>,
+    pub refused: Vec<String>,
+    pub failed: Vec<(String, String)>,
+}
+
+/*
+ * The archive cannot express a file without a final newline, so a file that lacks one on
+ * disk keeps it that way when the edited content differs only by the appended newline.
+ */
+fn match_trailing_newline(content: String, on_disk: Option<&str>) -> String {
+    match on_disk {
+        Some(old) if !old.is_empty() && !old.ends_with('\n') => content
+            .strip_suffix('\n')
+            .map(str::to_string)
+            .unwrap_or(content),
+        _ => content,
+    }
+}
+
+pub(crate) fn read_if_exists(path: &Path) -> io::Result<Option<Vec<u8>>> {
+    match fs::read(path) {
+        Ok(bytes) => Ok(Some(bytes)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+impl ApplyPreview {
+    /*
+     * Parses `archive_text` and compares every file with the project on disk. `baseline`
+     * is the content record of the generated archive; files listed there whose checksum
+     * changed since are marked `ChangedOnDisk`. Files the record does not know about
+     * (and all files when there is no record) are compared with the disk only.
+     * `encodings` are the profile's fallback rules for decoding the files on disk.
+     */
+    pub fn build(
+        project: &ProjectContext,
+        archive_text: &str,
+        baseline: Option<&ArchiveRecord>,
+        encodings: &EncodingRules,
+    ) -> Result<Self, ApplyError> {
+        let entries = parse_edited_archive(archive_text)?;
+        let mut files = Vec::with_capacity(entries.len());
+        for entry in entries {
+            let absolute_path = resolve_target(project, &entry.relative_path)?;
+            let read_error = |source| ApplyError::ReadTarget {
+                path: absolute_path.clone(),
+                source,
+            };
+            let old_bytes = read_if_exists(&absolute_path).map_err(read_error)?;
+            let disk_checksum = old_bytes
+                .as_deref()
+                .map(checksum_utils::calculate_sha256_for_bytes);
+            // A new file is decoded from no bytes, which gives the encoding it is created in.
+            let fallback = encodings.fallback_for(&entry.relative_path);
+            let (decoded, encoding) =
+                text_encoding::decode_detected(old_bytes.clone().unwrap_or_default(), fallback);
+            let old_text = old_bytes.is_some().then_some(decoded);
+            let content = match_trailing_newline(entry.content, old_text.as_deref());
+
+            let recorded = baseline.and_then(|record| record.files.get(&absolute_path));
+            let status = match (&old_text, recorded) {
+                (_, Some(recorded)) if disk_checksum.as_ref() != Some(recorded) => {
+                    ApplyFileStatus::ChangedOnDisk
+                }
+                (None, _) => ApplyFileStatus::New,
+                (Some(old), _) if *old == content => ApplyFileStatus::Identical,
+                (Some(_), _) => ApplyFileStatus::Changed,
+            };
+            let diff = TextDiff::from_lines(old_text.as_deref().unwrap_or(""), content.as_str())
+                .unified_diff()
+                .context_radius(3)
+                .header(
+                    &format!("a/{}", entry.relative_path),
+                    &format!("b/{}", entry.relative_path),
+                )
+                .to_string();
+            files.push(ApplyFilePreview {
+                relative_path: entry.relative_path,
+                absolute_path,
+                status,
+                content,
+                diff,
+                disk_checksum,
+                encoding,
+            });
+        }
+        Ok(ApplyPreview { files })
+    }
+
+    /* Indices of the files that would change the project. */
+    pub fn applicable_indices(&self) -> Vec<usize> {
+        self.files
+            .iter()
+            .enumerate()
+            .filter(|(_, f)| matches!(f.status, ApplyFileStatus::New | ApplyFileStatus::Changed))
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /* A numbered summary followed by the diff of every file, for the viewer. */
+    pub fn render_text(&self) -> String {
+        let mut text = String::new();
+        for (index, file) in self.files.iter().enumerate() {
+            text.push_str(&format!(
+                "{}. {} ({})\n",
+                index + 1,
+                file.relative_path,
+                file.status.label()
+            ));
+        }
+        for file in &self.files {
+            if matches!(file.status, ApplyFileStatus::New | ApplyFileStatus::Changed) {
+                text.push('\n');
+                text.push_str(&file.diff);
+            }
+        }
+        text
+    }
+
+    /*
+     * Writes the accepted files (by index). Identical files are skipped. Files marked
+     * `ChangedOnDisk`, and files whose checksum changed after the preview was built, are
+     * refused. A file whose new content its encoding cannot represent fails. Each file is
+     * replaced atomically; parent directories are created as needed.
+     */
+    pub fn apply(&self, accepted: &[usize]) -> ApplyReport {
+        let mut report = ApplyReport::default();
+        for file in accepted.iter().filter_map(|&index| self.files.get(index)) {
+            match file.status {
+                ApplyFileStatus::Identical => continue,
+                ApplyFileStatus::ChangedOnDisk => {
+                    report.refused.push(file.relative_path.clone());
+                    continue;
+                }
+                ApplyFileStatus::New | ApplyFileStatus::Changed => {}
+            }
+            let current_checksum = match read_if_exists(&file.absolute_path) {
+                Ok(bytes) => bytes
+                    .as_deref()
+                    .map(checksum_utils::calculate_sha256_for_bytes),
+                Err(e) => {
+                    report
+                        .failed
+                        .push((file.relative_path.clone(), e.to_string()));
+                    continue;
+                }
+            };
+            if current_checksum != file.disk_checksum {
+                report.refused.push(file.relative_path.clone());
+                continue;
+            }
+            let bytes = match file.encoding.encode(&file.content) {
+                Ok(bytes) => bytes,
+                Err(c) => {
+                    report.failed.push((
+                        file.relative_path.clone(),
+                        format!(
+                            "'{c}' cannot be written in {}",
+                            file.encoding.encoding.setting_name()
+                        ),
+                    ));
+                    continue;
+                }
+            };
+            let result = file
+                .absolute_path
+                .parent()
+                .map_or(Ok(()), fs::create_dir_all)
+                .and_then(|_| {
+                    atomic_write::write_file_atomically(&file.absolute_path, |out| {
+                        out.write_all(&bytes)
+                    })
+                });
+            match result {
+                Ok(()) if file.status == ApplyFileStatus::New => {
+                    report.created.push(file.relative_path.clone())
+                }
+                Ok(()) => report.written.push(file.relative_path.clone()),
+                Err(e) => report
+                    .failed
+                    .push((file.relative_path.clone(), e.to_string())),
+            }
+        }
+        report
+    }
+}
+
+/* Renders entries in the default plain text layout; used to round-trip in tests. */
+#[cfg(test)]
+fn render_plain(entries: &[ArchiveEntry]) -> String {
+    use super::archive_format::{ArchiveFormat, ArchivePreamble, FormatterSettings};
+    let mut out = Vec::new();
+    let mut formatter = ArchiveFormat::PlainText.create_formatter(FormatterSettings::default());
+    formatter
+        .write_preamble(&mut out, &ArchivePreamble::new("/project"))
+        .unwrap();
+    for entry in entries {
+        formatter.write_entry(&mut out, entry).unwrap();
+    }
+    formatter.write_closing(&mut out, None).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use std::collections::BTreeMap;
+    use tempfile::tempdir;
+
+    fn entry(path: &str, content: &str) -> ArchiveEntry {
+        ArchiveEntry {
+            relative_path: path.to_string(),
+            content: content.to_string(),
+            lines: None,
+        }
+    }
+
+    #[test]
+    fn test_parse_is_strict() {
+        let ok = "// Combined files from /p\n\n// ===== File: a.rs =====\nfn a() {}\n";
+        assert_eq!(
+            parse_edited_archive(ok).unwrap(),
+            vec![entry("a.rs", "fn a() {}\n")]
+        );
+
+        for (text, line) in [
+            ("Here are your files:\n// ===== File: a.rs =====\n", 1),
+            ("// ===== File: a.rs ====\nx\n", 1),
+            ("// ===== File: a.rs =====\n// ===== File: a.rs =====\n", 2),
+            ("// ===== File: \"bad\\q\" =====\n", 1),
+            ("// ===== File:  =====\n", 1),
+            ("// only a preamble\n", 1),
+        ] {
+            match parse_edited_archive(text) {
+                Err(ApplyError::Parse { line: l, .. }) => assert_eq!(l, line, "{text:?}"),
+                other => panic!("Expected a parse error for {text:?}, got {other:?}"),
+            }
+        }
+    }
+
+    /*
+     * Fuzzes the parser with random archives: every rendered archive must parse back to
+     * its entries, and arbitrary mutations of it must never panic.
+     */
+    #[test]
+    fn test_parse_fuzz_round_trip_and_mutations() {
+        const ALPHABET: &[char] = &[
+            'a', 'b', 'Z', '0', '.', '/', ' ', '"', '\\', '=', '\n', '\r', '\t', 'é', '/',
+        ];
+        let mut rng = StdRng::seed_from_u64(0x5EED_F00D);
+        let random_text = |rng: &mut StdRng, max_len: usize| -> String {
+            let len = rng.random_range(0..=max_len);
+            (0..len)
+                .map(|_| ALPHABET[rng.random_range(0..ALPHABET.len())])
+                .collect()
+        };
+        for _ in 0..500 {
+            let count = rng.random_range(1..4);
+            let mut entries: Vec<ArchiveEntry> = Vec::new();
+            for i in 0..count {
+                let path = format!("{}{i}", random_text(&mut rng, 12));
+                let mut content = random_text(&mut rng, 40).replace("// =====", "//=====");
+                if !content.ends_with('\n') {
+                    content.push('\n');
+                }
+                entries.push(entry(&path, &content));
+            }
+            let rendered = render_plain(&entries);
+            assert_eq!(
+                parse_edited_archive(&rendered).unwrap(),
+                entries,
+                "{rendered:?}"
+            );
+
+            let mut mutated: Vec<char> = rendered.chars().collect();
+            for _ in 0..rng.random_range(1..6) {
+                let at = rng.random_range(0..=mutated.len());
+                match rng.random_range(0..3) {
+                    0 if at < mutated.len() => {
+                        mutated.remove(at);
+                    }
+                    1 if at < mutated.len() => {
+                        mutated[at] = ALPHABET[rng.random_range(0..ALPHABET.len())];
+                    }
+                    _ => mutated.insert(at, ALPHABET[rng.random_range(0..ALPHABET.len())]),
+                }
+            }
+            let _ = parse_edited_archive(&mutated.into_iter().collect::<String>());
+        }
+    }
+
+    #[test]
+    fn test_preview_classifies_files_and_refuses_paths_outside_root() {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        fs::write(root.join("same.txt"), "same\n").unwrap();
+        fs::write(root.join("old.txt"), "one\ntwo").unwrap();
+        let project = ProjectContext::new(root.to_path_buf());
+
+        let text = render_plain(&[
+            entry("same.txt", "same\n"),
+            entry("old.txt", "one\n2\n"),
+            entry("sub/new.txt", "fresh\n"),
+        ]);
+        let preview =
+            ApplyPreview::build(&project, &text, None, &EncodingRules::default()).unwrap();
+        let statuses: Vec<ApplyFileStatus> = preview.files.iter().map(|f| f.status).collect();
+        assert_eq!(
+            statuses,
+            [
+                ApplyFileStatus::Identical,
+                ApplyFileStatus::Changed,
+                ApplyFileStatus::New
+            ]
+        );
+        assert!(
+            preview.files[1]
+                .diff
+                .contains("-two\n\\ No newline at end of file\n+2")
+        );
+        assert_eq!(preview.applicable_indices(), [1, 2]);
+
+        for bad in [
+            "../escape.txt",
+            "/etc/passwd",
+            ".sourcepacker/profiles/p.json",
+            ".SourcePacker/profiles/x.json",
+            "./.SOURCEPACKER/config.json",
+        ] {
+            let text = render_plain(&[entry(bad, "x\n")]);
+            assert!(
+                matches!(
+                    ApplyPreview::build(&project, &text, None, &EncodingRules::default()),
+                    Err(ApplyError::InvalidPath { .. })
+                ),
+                "{bad} was accepted"
+            );
+        }
+    }
+
+    #[test]
+    fn test_apply_writes_accepted_files_and_refuses_files_changed_on_disk() {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        fs::write(root.join("a.txt"), "a\n").unwrap();
+        fs::write(root.join("b.txt"), "b\n").unwrap();
+        let project = ProjectContext::new(root.to_path_buf());
+        let record = ArchiveRecord::new(
+            &root.join("archive.txt"),
+            BTreeMap::from([
+                (
+                    root.join("a.txt"),
+                    checksum_utils::calculate_sha256_for_bytes(b"a\n"),
+                ),
+                (
+                    root.join("b.txt"),
+                    checksum_utils::calculate_sha256_for_bytes(b"b before\n"),
+                ),
+            ]),
+        );
+        let text = render_plain(&[
+            entry("a.txt", "a edited\n"),
+            entry("b.txt", "b edited\n"),
+            entry("c/new.txt", "c\n"),
+        ]);
+
+        let preview =
+            ApplyPreview::build(&project, &text, Some(&record), &EncodingRules::default()).unwrap();
+        assert_eq!(preview.files[1].status, ApplyFileStatus::ChangedOnDisk);
+        let report = preview.apply(&[0, 1, 2]);
+
+        assert_eq!(report.written, ["a.txt"], "{report:?}");
+        assert_eq!(report.created, ["c/new.txt"], "{report:?}");
+        assert_eq!(report.refused, ["b.txt"]);
+        assert!(report.failed.is_empty());
+        assert_eq!(
+            fs::read_to_string(root.join("a.txt")).unwrap(),
+            "a edited\n"
+        );
+        assert_eq!(fs::read_to_string(root.join("b.txt")).unwrap(), "b\n");
+        assert_eq!(fs::read_to_string(root.join("c/new.txt")).unwrap(), "c\n");
+
+        // A file modified between preview and apply is refused as well.
+        let preview = ApplyPreview::build(
+            &project,
+            &render_plain(&[entry("a.txt", "again\n")]),
+            None,
+            &EncodingRules::default(),
+        )
+        .unwrap();
+        fs::write(root.join("a.txt"), "edited meanwhile\n").unwrap();
+        assert_eq!(preview.apply(&[0]).refused, ["a.txt"]);
+    }
+
+    #[test]
+    fn test_files_are_compared_decoded_and_written_back_in_their_encoding() {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        let utf16 = |text: &str| {
+            let mut bytes = vec![0xFF, 0xFE];
+            bytes.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+            bytes
+        };
+        fs::write(root.join("app.rc"), utf16("Größe\n")).unwrap();
+        fs::write(root.join("same.rc"), utf16("same\n")).unwrap();
+        fs::write(root.join("legacy.txt"), b"caf\xe9\n").unwrap();
+        let project = ProjectContext::new(root.to_path_buf());
+        let encodings =
+            EncodingRules::compile(&text_encoding::parse_setting("windows-1252@legacy*").unwrap())
+                .unwrap();
+        let text = render_plain(&[
+            entry("app.rc", "Größe 2\n"),
+            entry("same.rc", "same\n"),
+            entry("legacy.txt", "café crème\n"),
+        ]);
+
+        let preview = ApplyPreview::build(&project, &text, None, &encodings).unwrap();
+        let statuses: Vec<ApplyFileStatus> = preview.files.iter().map(|f| f.status).collect();
+        assert_eq!(
+            statuses,
+            [
+                ApplyFileStatus::Changed,
+                ApplyFileStatus::Identical,
+                ApplyFileStatus::Changed
+            ]
+        );
+        assert!(preview.files[0].diff.contains("-Größe\n+Größe 2\n"));
+        let report = preview.apply(&[0, 1, 2]);
+
+        assert_eq!(report.written, ["app.rc", "legacy.txt"], "{report:?}");
+        assert_eq!(fs::read(root.join("app.rc")).unwrap(), utf16("Größe 2\n"));
+        assert_eq!(
+            fs::read(root.join("legacy.txt")).unwrap(),
+            b"caf\xe9 cr\xe8me\n"
+        );
+
+        // Text the file's encoding cannot hold is not written.
+        let text = render_plain(&[entry("legacy.txt", "café → crème\n")]);
+        let preview = ApplyPreview::build(&project, &text, None, &encodings).unwrap();
+        let report = preview.apply(&[0]);
+        assert_eq!(report.failed.len(), 1, "{report:?}");
+        assert_eq!(
+            fs::read(root.join("legacy.txt")).unwrap(),
+            b"caf\xe9 cr\xe8me\n"
+        );
+    }
+}
@@ -0,0 +1,1536 @@
+use super::archive_manifest::ArchiveManifest;
+use super::archive_template::{CompiledTemplates, ParsedTemplate, TemplatePlaceholder};
+use super::archive_tree::DirectoryTree;
+use super::checksum_utils;
+use super::line_numbers::{self, LineRange};
+use super::tokenizer_utils::TokenCounterOperations;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+/*
+ * This module defines the output layouts an archive can be written in.
+ * `ArchiveFormat` is the persisted choice (stored on the profile), and each variant is
+ * backed by an `ArchiveFormatter` that streams the archive preamble, the per-file entries
+ * and the closing section into any `io::Write` sink. The module also provides parsers
+ * for each format so that a generated archive can be read back into its entries.
+ */
+
+/*
+ * The layout used when generating an archive.
+ * `PlainText` is the original SourcePacker layout with `// ===== File: ... =====` headers.
+ * The other variants target LLMs that respond better to structured input, or tools
+ * that want to consume the archive programmatically. [ArchiveGenOutputFormatsV1]
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ArchiveFormat {
+    #[default]
+    PlainText,
+    /* Markdown headings with fenced code blocks tagged by language. */
+    Markdown,
+    /* Anthropic-style `<document><source>..</source><document_content>..</document_content></document>` blocks. */
+    XmlDocuments,
+    /* A single JSON object holding the root and an array of files. */
+    Json,
+    /* One JSON object per line, one line per file. */
+    JsonLines,
+}
+
+impl ArchiveFormat {
+    pub const ALL: [ArchiveFormat; 5] = [
+        ArchiveFormat::PlainText,
+        ArchiveFormat::Markdown,
+        ArchiveFormat::XmlDocuments,
+        ArchiveFormat::Json,
+        ArchiveFormat::JsonLines,
+    ];
+
+    /*
+     * Creates a fresh formatter for one archive generation.
+     * Formatters may carry state between entries (e.g. JSON separators), so a new
+     * instance must be used for every archive.
+     */
+    pub fn create_formatter(&self, settings: FormatterSettings) -> Box<dyn ArchiveFormatter> {
+        match self {
+            ArchiveFormat::PlainText => Box::new(PlainTextFormatter {
+                settings,
+                root_display: String::new(),
+            }),
+            ArchiveFormat::Markdown => Box::new(MarkdownFormatter),
+            ArchiveFormat::XmlDocuments => Box::new(XmlDocumentsFormatter { next_index: 1 }),
+            ArchiveFormat::Json => Box::new(JsonFormatter { wrote_any: false }),
+            ArchiveFormat::JsonLines => Box::new(JsonLinesFormatter),
+        }
+    }
+
+    /*
+     * Parses an archive previously generated in this format back into its file entries.
+     * Returns `io::ErrorKind::InvalidData` if the text does not follow the layout.
+     */
+    pub fn parse(&self, archive_text: &str) -> io::Result<Vec<ArchiveEntry>> {
+        let mut entries = match self {
+            ArchiveFormat::PlainText => parse_plain_text(archive_text),
+            ArchiveFormat::Markdown => parse_markdown(archive_text),
+            ArchiveFormat::XmlDocuments => parse_xml_documents(archive_text),
+            ArchiveFormat::Json => parse_json(archive_text),
+            ArchiveFormat::JsonLines => parse_json_lines(archive_text),
+        }?;
+        for entry in &mut entries {
+            remove_line_numbers(entry);
+        }
+        Ok(entries)
+    }
+
+    /*
+     * Returns a short, user-facing name for the format. `from_display_name` accepts
+     * the same names (case-insensitively) so they can be typed into input dialogs.
+     */
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            ArchiveFormat::PlainText => "Plain",
+            ArchiveFormat::Markdown => "Markdown",
+            ArchiveFormat::XmlDocuments => "XML",
+            ArchiveFormat::Json => "JSON",
+            ArchiveFormat::JsonLines => "JSONL",
+        }
+    }
+
+    pub fn from_display_name(name: &str) -> Option<ArchiveFormat> {
+        let trimmed = name.trim();
+        Self::ALL
+            .into_iter()
+            .find(|format| format.display_name().eq_ignore_ascii_case(trimmed))
+    }
+}
+
+/*
+ * One file as it appears inside an archive: its display path (relative to the
+ * project root) and its full text content. `lines` is set when the content carries a
+ * line-number gutter and gives the range of the file's lines it holds.
+ * [ArchiveGenLineNumbersV1]
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchiveEntry {
+    #[serde(rename = "path")]
+    pub relative_path: String,
+    pub content: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lines: Option<LineRange>,
+}
+
+/*
+ * Identifies one part of a split archive and lists which files ended up in the other
+ * parts, so a reader of a single part knows what else exists. [ArchiveGenSplitPartsV1]
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchivePartHeader {
+    pub number: usize,
+    pub count: usize,
+    pub other_parts: Vec<ArchivePartListing>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchivePartListing {
+    pub number: usize,
+    pub files: Vec<String>,
+}
+
+/*
+ * Heads a delta archive: the files added or modified since the previous generation,
+ * whose content follows, and the files deleted (or deselected) since. [ArchiveGenDeltaV1]
+ */
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchiveDeltaHeader {
+    pub added: Vec<String>,
+    pub modified: Vec<String>,
+    pub deleted: Vec<String>,
+}
+
+impl ArchiveDeltaHeader {
+    pub fn summary_line(&self) -> String {
+        format!(
+            "Changes since the previous archive: {} added, {} modified, {} deleted",
+            self.added.len(),
+            self.modified.len(),
+            self.deleted.len()
+        )
+    }
+
+    /* Every path with its kind of change, in the order added, modified, deleted. */
+    fn listed_paths(&self) -> impl Iterator<Item = (&'static str, &String)> {
+        let added = self.added.iter().map(|p| ("added", p));
+        let modified = self.modified.iter().map(|p| ("modified", p));
+        let deleted = self.deleted.iter().map(|p| ("deleted", p));
+        added.chain(modified).chain(deleted)
+    }
+}
+
+/*
+ * Generation-time inputs shared by the formatters. Only the plain text layout uses
+ * templates; the token counter is consulted only when a template asks for `{token_count}`.
+ */
+#[derive(Clone, Default)]
+pub struct FormatterSettings {
+    pub templates: CompiledTemplates,
+    pub profile_name: String,
+    pub token_counter: Option<Arc<dyn TokenCounterOperations>>,
+}
+
+/*
+ * Everything rendered before the first file: the project root, the part header of a
+ * split archive, the change list of a delta archive, the optional manifest (table of contents) [ArchiveGenManifestV1] and the
+ * optional directory tree of the project [ArchiveGenDirectoryTreeV1]. `prompt` is the
+ * rendered prompt preamble, which comes before all of it. [ArchiveGenPromptsV1]
+ */
+#[derive(Debug, Clone, Copy)]
+pub struct ArchivePreamble<'a> {
+    pub prompt: Option<&'a str>,
+    pub root_display: &'a str,
+    pub part: Option<&'a ArchivePartHeader>,
+    pub delta: Option<&'a ArchiveDeltaHeader>,
+    pub manifest: Option<&'a ArchiveManifest>,
+    pub tree: Option<&'a DirectoryTree>,
+}
+
+impl<'a> ArchivePreamble<'a> {
+    pub fn new(root_display: &'a str) -> Self {
+        ArchivePreamble {
+            prompt: None,
+            root_display,
+            part: None,
+            delta: None,
+            manifest: None,
+            tree: None,
+        }
+    }
+}
+
+/*
+ * Streams one archive in a specific layout.
+ * The archiver calls `write_preamble` once, `write_entry` for every selected file in
+ * traversal order, and `write_closing` once at the end, with the rendered prompt
+ * postamble if there is one. The prompt, part header, delta header, manifest and
+ * directory tree written with the preamble, and the postamble, are skipped again by
+ * `ArchiveFormat::parse`.
+ */
+pub trait ArchiveFormatter {
+    fn write_preamble(&mut self, out: &mut dyn Write, preamble: &ArchivePreamble)
+    -> io::Result<()>;
+    fn write_entry(&mut self, out: &mut dyn Write, entry: &ArchiveEntry) -> io::Result<()>;
+    fn write_closing(&mut self, out: &mut dyn Write, postamble: Option<&str>) -> io::Result<()>;
+}
+
+const PLAIN_PREAMBLE_PREFIX: &str = "// Combined files from ";
+const PLAIN_PROMPT_LINE: &str = "// ===== Prompt =====";
+const PLAIN_END_OF_FILES_LINE: &str = "// ===== End of files =====";
+const MARKDOWN_POSTAMBLE_RULE: &str = "---";
+pub const PLAIN_FILE_HEADER_PREFIX: &str = "// ===== File: ";
+const PLAIN_FILE_HEADER_SUFFIX: &str = " =====";
+const MARKDOWN_PREAMBLE_PREFIX: &str = "# Combined files from ";
+const MARKDOWN_FILE_HEADING_PREFIX: &str = "## File: ";
+const MARKDOWN_LINES_PREFIX: &str = "Lines ";
+pub const PLAIN_LINES_PREFIX: &str = "// Lines ";
+const MARKDOWN_PART_LINE_PREFIX: &str = "> ";
+const XML_PART_CLOSE: &str = "</part>\n";
+const XML_MANIFEST_CLOSE: &str = "</manifest>\n";
+const XML_DELTA_CLOSE: &str = "</delta>\n";
+const XML_TREE_CLOSE: &str = "</tree>\n";
+const MARKDOWN_MANIFEST_HEADING: &str = "## Manifest";
+const MARKDOWN_TREE_HEADING: &str = "## Project Tree";
+const TREE_TITLE: &str = "Project tree:";
+
+/* Strips the gutter from an entry that was written with line numbers. */
+pub fn remove_line_numbers(entry: &mut ArchiveEntry) {
+    if entry.lines.is_some() {
+        entry.content = line_numbers::strip_line_numbers(&entry.content);
+    }
+}
+
+fn write_content_with_trailing_newline(out: &mut dyn Write, content: &str) -> io::Result<()> {
+    out.write_all(content.as_bytes())?;
+    if !content.ends_with('\n') {
+        out.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+/*
+ * Writes the part header as plain lines behind `prefix`; shared by the text-based layouts.
+ */
+fn write_part_header_lines(
+    out: &mut dyn Write,
+    part: &ArchivePartHeader,
+    prefix: &str,
+) -> io::Result<()> {
+    writeln!(out, "{prefix}Part {} of {}", part.number, part.count)?;
+    if part.other_parts.is_empty() {
+        return Ok(());
+    }
+    writeln!(out, "{prefix}Files in other parts:")?;
+    for listing in &part.other_parts {
+        for file in &listing.files {
+            writeln!(out, "{prefix}  part {}: {file}", listing.number)?;
+        }
+    }
+    Ok(())
+}
+
+/*
+ * Writes the delta header as plain lines behind `prefix`; shared by the text-based layouts.
+ */
+fn write_delta_header_lines(
+    out: &mut dyn Write,
+    delta: &ArchiveDeltaHeader,
+    prefix: &str,
+) -> io::Result<()> {
+    writeln!(out, "{prefix}{}", delta.summary_line())?;
+    for (kind, path) in delta.listed_paths() {
+        writeln!(out, "{prefix}  {kind}: {path}")?;
+    }
+    Ok(())
+}
+
+/*
+ * Writes the manifest as a pipe-separated table behind `prefix`.
+ */
+fn write_manifest_lines(
+    out: &mut dyn Write,
+    manifest: &ArchiveManifest,
+    prefix: &str,
+) -> io::Result<()> {
+    writeln!(out, "{prefix}Manifest:")?;
+    writeln!(out, "{prefix}path | bytes | lines | tokens | sha256")?;
+    for file in &manifest.files {
+        writeln!(
+            out,
+            "{prefix}{} | {} | {} | {} | {}",
+            file.path, file.bytes, file.lines, file.tokens, file.sha256
+        )?;
+    }
+    writeln!(out, "{prefix}{}", manifest.totals_line())?;
+    writeln!(out, "{prefix}{}", manifest.origin_line())
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/*
+ * The original SourcePacker layout. The preamble, file header and file footer lines come
+ * from the profile's templates; the defaults reproduce the historic output exactly.
+ */
+struct PlainTextFormatter {
+    settings: FormatterSettings,
+    root_display: String,
+}
+
+impl PlainTextFormatter {
+    fn write_template_line(out: &mut dyn Write, rendered: Option<String>) -> io::Result<()> {
+        match rendered {
+            Some(line) => writeln!(out, "{line}"),
+            None => Ok(()),
+        }
+    }
+
+    /* Renders a per-file template; an empty template produces no line at all. */
+    fn render_for_entry(
+        &self,
+        template: &ParsedTemplate,
+        entry: &ArchiveEntry,
+        root_display: &str,
+        token_count: &mut Option<usize>,
+    ) -> Option<String> {
+        if template.is_empty() {
+            return None;
+        }
+        let path = Path::new(&entry.relative_path);
+        Some(template.render(|placeholder| {
+            match placeholder {
+                TemplatePlaceholder::RelativePath => {
+                    encode_header_path(&entry.relative_path).into_owned()
+                }
+                TemplatePlaceholder::FileName => path
+                    .file_name()
+                    .map(|n| n.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+                TemplatePlaceholder::Extension => path
+                    .extension()
+                    .map(|e| e.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+                TemplatePlaceholder::TokenCount => match &self.settings.token_counter {
+                    Some(counter) => token_count
+                        .get_or_insert_with(|| counter.count_tokens(&entry.content))
+                        .to_string(),
+                    None => "n/a".to_string(),
+                },
+                TemplatePlaceholder::LineCount => entry.content.lines().count().to_string(),
+                TemplatePlaceholder::Sha256 => {
+                    checksum_utils::calculate_sha256_for_bytes(entry.content.as_bytes())
+                }
+                TemplatePlaceholder::Profile => self.settings.profile_name.clone(),
+                TemplatePlaceholder::Root => root_display.to_string(),
+                // Rejected in file templates when they are parsed.
+                TemplatePlaceholder::Project
+                | TemplatePlaceholder::FileCount
+                | TemplatePlaceholder::Date
+                | TemplatePlaceholder::FileList => String::new(),
+            }
+        }))
+    }
+}
+
+impl ArchiveFormatter for PlainTextFormatter {
+    fn write_preamble(
+        &mut self,
+        out: &mut dyn Write,
+        preamble: &ArchivePreamble,
+    ) -> io::Result<()> {
+        self.root_display = preamble.root_display.to_string();
+        if let Some(prompt) = preamble.prompt {
+            writeln!(out, "{PLAIN_PROMPT_LINE}")?;
+            write_content_with_trailing_newline(out, prompt)?;
+        }
+        let template = &self.settings.templates.preamble;
+        let rendered = (!template.is_empty()).then(|| {
+            template.render(|placeholder| match placeholder {
+                TemplatePlaceholder::Profile => self.settings.profile_name.clone(),
+                _ => preamble.root_display.to_string(),
+            })
+        });
+        Self::write_template_line(out, rendered)?;
+        if let Some(part) = preamble.part {
+            write_part_header_lines(out, part, "// ")?;
+        }
+        if let Some(delta) = preamble.delta {
+            write_delta_header_lines(out, delta, "// ")?;
+        }
+        if let Some(manifest) = preamble.manifest {
+            write_manifest_lines(out, manifest, "// ")?;
+        }
+        if let Some(tree) = preamble.tree {
+            writeln!(out, "// {TREE_TITLE}")?;
+            for line in &tree.lines {
+                writeln!(out, "// {line}")?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_entry(&mut self, out: &mut dyn Write, entry: &ArchiveEntry) -> io::Result<()> {
+        let mut token_count = None;
+        let header = self.render_for_entry(
+            &self.settings.templates.file_header,
+            entry,
+            &self.root_display,
+            &mut token_count,
+        );
+        Self::write_template_line(out, header)?;
+        if let Some(lines) = entry.lines {
+            writeln!(out, "{PLAIN_LINES_PREFIX}{lines}")?;
+        }
+        write_content_with_trailing_newline(out, &entry.content)?;
+        let footer = self.render_for_entry(
+            &self.settings.templates.file_footer,
+            entry,
+            &self.root_display,
+            &mut token_count,
+        );
+        Self::write_template_line(out, footer)
+    }
+
+    fn write_closing(&mut self, out: &mut dyn Write, postamble: Option<&str>) -> io::Result<()> {
+        if let Some(postamble) = postamble {
+            writeln!(out, "{PLAIN_END_OF_FILES_LINE}")?;
+            write_content_with_trailing_newline(out, postamble)?;
+        }
+        Ok(())
+    }
+}
+
+/*
+ * Paths in plain text headers are written verbatim, which keeps the historic layout for
+ * ordinary names. A path that would be ambiguous on a header line (control characters
+ * such as newlines, leading or trailing whitespace, or a leading `"`) is written as a
+ * JSON string literal instead, so every file name round-trips.
+ */
+pub fn encode_header_path(path: &str) -> Cow<'_, str> {
+    let needs_quoting = path.is_empty()
+        || path.starts_with('"')
+        || path.starts_with(char::is_whitespace)
+        || path.ends_with(char::is_whitespace)
+        || path.chars().any(char::is_control);
+    if needs_quoting {
+        Cow::Owned(serde_json::Value::String(path.to_string()).to_string())
+    } else {
+        Cow::Borrowed(path)
+    }
+}
+
+/* The inverse of `encode_header_path`. Rejects malformed quoted paths. */
+pub fn decode_header_path(text: &str) -> io::Result<String> {
+    if !text.starts_with('"') {
+        return Ok(text.to_string());
+    }
+    serde_json::from_str::<String>(text)
+        .map_err(|e| invalid_data(format!("Malformed quoted path {text}: {e}")))
+}
+
+/* Returns the range of a `// Lines 1-120 of 120` line written after a file header. */
+pub fn parse_plain_lines_line(line: &str) -> Option<LineRange> {
+    LineRange::parse(line.strip_prefix(PLAIN_LINES_PREFIX)?)
+}
+
+/* Returns the still encoded path of a `// ===== File: ... =====` line. */
+pub fn parse_plain_file_header(line: &str) -> Option<&str> {
+    line.strip_prefix(PLAIN_FILE_HEADER_PREFIX)?
+        .strip_suffix(PLAIN_FILE_HEADER_SUFFIX)
+}
+
+/*
+ * Plain text archives carry no escaping, so a file whose content itself contains a
+ * line shaped like a file header cannot be told apart from a real header.
+ * Only archives written with the default templates can be parsed. Comment lines before
+ * the first file header (such as a part header or the manifest) are skipped.
+ */
+fn parse_plain_text(archive_text: &str) -> io::Result<Vec<ArchiveEntry>> {
+    let mut lines = archive_text.split_inclusive('\n');
+    let mut first = lines.next();
+    if first.is_some_and(|line| line.trim_end_matches(['\n', '\r']) == PLAIN_PROMPT_LINE) {
+        first = lines.find(|line| line.starts_with(PLAIN_PREAMBLE_PREFIX));
+    }
+    match first {
+        Some(first) if first.starts_with(PLAIN_PREAMBLE_PREFIX) => {}
+        _ => {
+            return Err(invalid_data(
+                "Plain text archive does not start with the expected preamble".to_string(),
+            ));
+        }
+    }
+
+    let mut entries: Vec<ArchiveEntry> = Vec::new();
+    for line in lines {
+        let trimmed = line.trim_end_matches(['\n', '\r']);
+        if trimmed == PLAIN_END_OF_FILES_LINE {
+            break;
+        }
+        if let Some(path) = parse_plain_file_header(trimmed) {
+            entries.push(ArchiveEntry {
+                relative_path: decode_header_path(path)?,
+                content: String::new(),
+                lines: None,
+            });
+        } else if let Some(current) = entries.last_mut() {
+            if current.content.is_empty()
+                && current.lines.is_none()
+                && let Some(lines) = parse_plain_lines_line(trimmed)
+            {
+                current.lines = Some(lines);
+                continue;
+            }
+            current.content.push_str(line);
+        } else if !trimmed.is_empty() && !trimmed.starts_with("//") {
+            return Err(invalid_data(format!(
+                "Unexpected text before the first file header: '{trimmed}'"
+            )));
+        }
+    }
+    Ok(entries)
+}
+
+/* Markdown headings followed by a fenced code block per file. */
+struct MarkdownFormatter;
+
+/*
+ * Maps a file extension to the language tag used on Markdown code fences.
+ * Unknown extensions fall back to the extension itself, which most renderers ignore gracefully.
+ */
+pub fn markdown_language_tag(relative_path: &str) -> String {
+    let extension = std::path::Path::new(relative_path)
+        .extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    let tag = match extension.as_str() {
+        "rs" => "rust",
+        "py" => "python",
+        "js" | "mjs" | "cjs" => "javascript",
+        "ts" => "typescript",
+        "tsx" => "tsx",
+        "jsx" => "jsx",
+        "c" => "c",
+        "h" | "hpp" | "hh" | "cpp" | "cc" | "cxx" => "cpp",
+        "cs" => "csharp",
+        "go" => "go",
+        "java" => "java",
+        "kt" => "kotlin",
+        "rb" => "ruby",
+        "sh" | "bash" => "bash",
+        "ps1" => "powershell",
+        "md" => "markdown",
+        "yml" | "yaml" => "yaml",
+        "txt" => "text",
+        other => other,
+    };
+    tag.to_string()
+}
+
+/*
+ * Chooses a backtick fence that is longer than any run of backticks in the content,
+ * so the content can never terminate the block early.
+ */
+fn markdown_fence_for(content: &str) -> String {
+    let mut longest_run = 0;
+    let mut current_run = 0;
+    for ch in content.chars() {
+        if ch == '`' {
+            current_run += 1;
+            longest_run = longest_run.max(current_run);
+        } else {
+            current_run = 0;
+        }
+    }
+    "`".repeat((longest_run + 1).max(3))
+}
+
+impl ArchiveFormatter for MarkdownFormatter {
+    fn write_preamble(
+        &mut self,
+        out: &mut dyn Write,
+        preamble: &ArchivePreamble,
+    ) -> io::Result<()> {
+        if let Some(prompt) = preamble.prompt {
+            write_content_with_trailing_newline(out, prompt)?;
+            writeln!(out)?;
+        }
+        writeln!(out, "{MARKDOWN_PREAMBLE_PREFIX}{}", preamble.root_display)?;
+        if let Some(part) = preamble.part {
+            writeln!(out)?;
+            write_part_header_lines(out, part, MARKDOWN_PART_LINE_PREFIX)?;
+        }
+        if let Some(delta) = preamble.delta {
+            writeln!(out)?;
+            write_delta_header_lines(out, delta, MARKDOWN_PART_LINE_PREFIX)?;
+        }
+        if let Some(manifest) = preamble.manifest {
+            writeln!(out)?;
+            writeln!(out, "{MARKDOWN_MANIFEST_HEADING}")?;
+            writeln!(out)?;
+            writeln!(out, "| Path | Bytes | Lines | Tokens | SHA-256 |")?;
+            writeln!(out, "| --- | ---: | ---: | ---: | --- |")?;
+            for file in &manifest.files {
+                writeln!(
+                    out,
+                    "| `{}` | {} | {} | {} | `{}` |",
+                    file.path.replace('|', "\\|"),
+                    file.bytes,
+                    file.lines,
+                    file.tokens,
+                    file.sha256
+                )?;
+            }
+            writeln!(out)?;
+            writeln!(out, "{}", manifest.totals_line())?;
+            writeln!(out, "{}", manifest.origin_line())?;
+        }
+        if let Some(tree) = preamble.tree {
+            let text = tree.to_text();
+            let fence = markdown_fence_for(&text);
+            writeln!(out)?;
+            writeln!(out, "{MARKDOWN_TREE_HEADING}")?;
+            writeln!(out)?;
+            writeln!(out, "{fence}text")?;
+            out.write_all(text.as_bytes())?;
+            writeln!(out, "{fence}")?;
+        }
+        Ok(())
+    }
+
+    fn write_entry(&mut self, out: &mut dyn Write, entry: &ArchiveEntry) -> io::Result<()> {
+        let fence = markdown_fence_for(&entry.content);
+        let language = markdown_language_tag(&entry.relative_path);
+        writeln!(out)?;
+        writeln!(out, "{MARKDOWN_FILE_HEADING_PREFIX}{}", entry.relative_path)?;
+        writeln!(out)?;
+        if let Some(lines) = entry.lines {
+            writeln!(out, "{MARKDOWN_LINES_PREFIX}{lines}")?;
+            writeln!(out)?;
+        }
+        writeln!(out, "{fence}{language}")?;
+        write_content_with_trailing_newline(out, &entry.content)?;
+        writeln!(out, "{fence}")
+    }
+
+    fn write_closing(&mut self, out: &mut dyn Write, postamble: Option<&str>) -> io::Result<()> {
+        if let Some(postamble) = postamble {
+            writeln!(out)?;
+            writeln!(out, "{MARKDOWN_POSTAMBLE_RULE}")?;
+            writeln!(out)?;
+            write_content_with_trailing_newline(out, postamble)?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_markdown(archive_text: &str) -> io::Result<Vec<ArchiveEntry>> {
+    let mut lines = archive_text.split_inclusive('\n');
+    /* A prompt preamble may precede the heading. */
+    match lines.find(|line| line.starts_with(MARKDOWN_PREAMBLE_PREFIX)) {
+        Some(first) if first.starts_with(MARKDOWN_PREAMBLE_PREFIX) => {}
+        _ => {
+            return Err(invalid_data(
+                "Markdown archive does not start with the expected heading".to_string(),
+            ));
+        }
+    }
+
+    let mut entries = Vec::new();
+    let mut in_preamble = true;
+    while let Some(line) = lines.next() {
+        let trimmed = line.trim_end_matches(['\n', '\r']);
+        /* The part and delta headers, manifest and tree sit before the first file heading. */
+        in_preamble = in_preamble && !trimmed.starts_with(MARKDOWN_FILE_HEADING_PREFIX);
+        if trimmed.is_empty() || in_preamble {
+            continue;
+        }
+        if trimmed == MARKDOWN_POSTAMBLE_RULE {
+            break;
+        }
+        let Some(path) = trimmed.strip_prefix(MARKDOWN_FILE_HEADING_PREFIX) else {
+            return Err(invalid_data(format!(
+                "Expected a '{MARKDOWN_FILE_HEADING_PREFIX}' heading, found '{trimmed}'"
+            )));
+        };
+
+        let mut next_non_empty = || {
+            lines
+                .by_ref()
+                .map(|l| l.trim_end_matches(['\n', '\r']))
+                .find(|l| !l.is_empty())
+                .ok_or_else(|| invalid_data(format!("Missing code fence for '{path}'")))
+        };
+        let mut fence_line = next_non_empty()?;
+        let line_range = fence_line
+            .strip_prefix(MARKDOWN_LINES_PREFIX)
+            .and_then(LineRange::parse);
+        if line_range.is_some() {
+            fence_line = next_non_empty()?;
+        }
+        let fence_len = fence_line.chars().take_while(|c| *c == '`').count();
+        if fence_len < 3 {
+            return Err(invalid_data(format!(
+                "Expected a code fence for '{path}', found '{fence_line}'"
+            )));
+        }
+        let fence = &fence_line[..fence_len];
+
+        let mut content = String::new();
+        let mut closed = false;
+        for content_line in lines.by_ref() {
+            if content_line.trim_end_matches(['\n', '\r']) == fence {
+                closed = true;
+                break;
+            }
+            content.push_str(content_line);
+        }
+        if !closed {
+            return Err(invalid_data(format!(
+                "Unterminated code fence for '{path}'"
+            )));
+        }
+        entries.push(ArchiveEntry {
+            relative_path: path.to_string(),
+            content,
+            lines: line_range,
+        });
+    }
+    Ok(entries)
+}
+
+/*
+ * Anthropic-style document blocks. Only the source path is XML-escaped; the content is
+ * emitted verbatim, as recommended for long-context prompting, so a file that itself
+ * contains `</document_content>` followed by `</document>` cannot be parsed back.
+ */
+struct XmlDocumentsFormatter {
+    next_index: usize,
+}
+
+const XML_CONTENT_CLOSE: &str = "</document_content>\n</document>\n";
+
+fn escape_xml_text(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn escape_xml_attribute(text: &str) -> String {
+    escape_xml_text(text).replace('"', "&quot;")
+}
+
+fn unescape_xml_text(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+impl ArchiveFormatter for XmlDocumentsFormatter {
+    fn write_preamble(
+        &mut self,
+        out: &mut dyn Write,
+        preamble: &ArchivePreamble,
+    ) -> io::Result<()> {
+        if let Some(prompt) = preamble.prompt {
+            write_content_with_trailing_newline(out, prompt)?;
+            writeln!(out)?;
+        }
+        writeln!(out, "<documents>")?;
+        if let Some(part) = preamble.part {
+            writeln!(
+                out,
+                "<part index=\"{}\" count=\"{}\">",
+                part.number, part.count
+            )?;
+            for listing in &part.other_parts {
+                writeln!(out, "<other_part index=\"{}\">", listing.number)?;
+                for file in &listing.files {
+                    writeln!(out, "<source>{}</source>", escape_xml_text(file))?;
+                }
+                writeln!(out, "</other_part>")?;
+            }
+            out.write_all(XML_PART_CLOSE.as_bytes())?;
+        }
+        if let Some(delta) = preamble.delta {
+            writeln!(out, "<delta>")?;
+            for (kind, path) in delta.listed_paths() {
+                writeln!(out, "<{kind}>{}</{kind}>", escape_xml_text(path))?;
+            }
+            out.write_all(XML_DELTA_CLOSE.as_bytes())?;
+        }
+        if let Some(manifest) = preamble.manifest {
+            writeln!(out, "<manifest>")?;
+            for file in &manifest.files {
+                writeln!(
+                    out,
+                    "<file path=\"{}\" bytes=\"{}\" lines=\"{}\" tokens=\"{}\" sha256=\"{}\"/>",
+                    escape_xml_attribute(&file.path),
+                    file.bytes,
+                    file.lines,
+                    file.tokens,
+                    file.sha256
+                )?;
+            }
+            writeln!(
+                out,
+                "<totals files=\"{}\" bytes=\"{}\" lines=\"{}\" tokens=\"{}\"/>",
+                manifest.files.len(),
+                manifest.total_bytes,
+                manifest.total_lines,
+                manifest.total_tokens
+            )?;
+            writeln!(
+                out,
+                "<generated timestamp=\"{}\" profile=\"{}\" tool_version=\"{}\"/>",
+                escape_xml_attribute(&manifest.generated_at),
+                escape_xml_attribute(&manifest.profile),
+                escape_xml_attribute(&manifest.tool_version)
+            )?;
+            out.write_all(XML_MANIFEST_CLOSE.as_bytes())?;
+        }
+        if let Some(tree) = preamble.tree {
+            writeln!(out, "<tree>")?;
+            out.write_all(escape_xml_text(&tree.to_text()).as_bytes())?;
+            out.write_all(XML_TREE_CLOSE.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    fn write_entry(&mut self, out: &mut dyn Write, entry: &ArchiveEntry) -> io::Result<()> {
+        match entry.lines {
+            Some(lines) => writeln!(
+                out,
+                "<document index=\"{}\" lines=\"{lines}\">",
+                self.next_index
+            )?,
+            None => writeln!(out, "<document index=\"{}\">", self.next_index)?,
+        }
+        self.next_index += 1;
+        writeln!(
+            out,
+            "<source>{}</source>",
+            escape_xml_text(&entry.relative_path)
+        )?;
+        writeln!(out, "<document_content>")?;
+        write_content_with_trailing_newline(out, &entry.content)?;
+        out.write_all(XML_CONTENT_CLOSE.as_bytes())
+    }
+
+    fn write_closing(&mut self, out: &mut dyn Write, postamble: Option<&str>) -> io::Result<()> {
+        writeln!(out, "</documents>")?;
+        if let Some(postamble) = postamble {
+            writeln!(out)?;
+            write_content_with_trailing_newline(out, postamble)?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_xml_documents(archive_text: &str) -> io::Result<Vec<ArchiveEntry>> {
+    let normalized;
+    let text = if archive_text.contains("\r\n") {
+        normalized = archive_text.replace("\r\n", "\n");
+        normalized.as_str()
+    } else {
+        archive_text
+    };
+
+    /* A prompt preamble may precede `<documents>`, and a postamble follow `</documents>`. */
+    let mut rest = text
+        .trim_start()
+        .strip_prefix("<documents>\n")
+        .or_else(|| text.split_once("\n<documents>\n").map(|(_, r)| r))
+        .ok_or_else(|| invalid_data("XML archive does not start with <documents>".to_string()))?;
+    if rest.starts_with("<part ") {
+        rest = rest
+            .split_once(XML_PART_CLOSE)
+            .map(|(_, r)| r)
+            .ok_or_else(|| invalid_data("Unterminated <part> element".to_string()))?;
+    }
+    if rest.starts_with("<delta>") {
+        rest = rest
+            .split_once(XML_DELTA_CLOSE)
+            .map(|(_, r)| r)
+            .ok_or_else(|| invalid_data("Unterminated <delta> element".to_string()))?;
+    }
+    if rest.starts_with("<manifest>") {
+        rest = rest
+            .split_once(XML_MANIFEST_CLOSE)
+            .map(|(_, r)| r)
+            .ok_or_else(|| invalid_data("Unterminated <manifest> element".to_string()))?;
+    }
+    if rest.starts_with("<tree>") {
+        rest = rest
+            .split_once(XML_TREE_CLOSE)
+            .map(|(_, r)| r)
+            .ok_or_else(|| invalid_data("Unterminated <tree> element".to_string()))?;
+    }
+
+    let mut entries = Vec::new();
+    loop {
+        if rest.trim_start().starts_with("</documents>\n") || rest.trim() == "</documents>" {
+            return Ok(entries);
+        }
+        let (attributes, after_open) = rest
+            .strip_prefix("<document index=\"")
+            .and_then(|r| r.split_once("\">\n"))
+            .ok_or_else(|| invalid_data("Expected a <document> element".to_string()))?;
+        let line_range = attributes
+            .split_once("\" lines=\"")
+            .and_then(|(_, range)| LineRange::parse(range));
+        let (source, after_source) = after_open
+            .strip_prefix("<source>")
+            .and_then(|r| r.split_once("</source>\n"))
+            .ok_or_else(|| invalid_data("Expected a <source> element".to_string()))?;
+        let body = after_source
+            .strip_prefix("<document_content>\n")
+            .ok_or_else(|| invalid_data("Expected a <document_content> element".to_string()))?;
+        let (content, after_document) = body.split_once(XML_CONTENT_CLOSE).ok_or_else(|| {
+            invalid_data(format!("Unterminated <document_content> for '{source}'"))
+        })?;
+        entries.push(ArchiveEntry {
+            relative_path: unescape_xml_text(source),
+            content: content.to_string(),
+            lines: line_range,
+        });
+        rest = after_document;
+    }
+}
+
+/*
+ * A single JSON document: `{"root": "...", "files": [{"path": "...", "content": "..."}, ...]}`.
+ * A prompt is written as a leading `prompt` and a trailing `postamble` member.
+ */
+struct JsonFormatter {
+    wrote_any: bool,
+}
+
+#[derive(Deserialize)]
+struct JsonArchive {
+    #[allow(dead_code)]
+    root: String,
+    files: Vec<ArchiveEntry>,
+}
+
+impl ArchiveFormatter for JsonFormatter {
+    fn write_preamble(
+        &mut self,
+        out: &mut dyn Write,
+        preamble: &ArchivePreamble,
+    ) -> io::Result<()> {
+        write!(out, "{{")?;
+        if let Some(prompt) = preamble.prompt {
+            write!(out, "\"prompt\":")?;
+            serde_json::to_writer(&mut *out, prompt)?;
+            write!(out, ",")?;
+        }
+        write!(out, "\"root\":")?;
+        serde_json::to_writer(&mut *out, preamble.root_display)?;
+        if let Some(part) = preamble.part {
+            write!(out, ",\"part\":")?;
+            serde_json::to_writer(&mut *out, part)?;
+        }
+        if let Some(delta) = preamble.delta {
+            write!(out, ",\"delta\":")?;
+            serde_json::to_writer(&mut *out, delta)?;
+        }
+        if let Some(manifest) = preamble.manifest {
+            write!(out, ",\"manifest\":")?;
+            serde_json::to_writer(&mut *out, manifest)?;
+        }
+        if let Some(tree) = preamble.tree {
+            write!(out, ",\"tree\":")?;
+            serde_json::to_writer(&mut *out, &tree.to_text())?;
+        }
+        write!(out, ",\"files\":[")
+    }
+
+    fn write_entry(&mut self, out: &mut dyn Write, entry: &ArchiveEntry) -> io::Result<()> {
+        if self.wrote_any {
+            write!(out, ",")?;
+        }
+        self.wrote_any = true;
+        writeln!(out)?;
+        serde_json::to_writer(&mut *out, entry)?;
+        Ok(())
+    }
+
+    fn write_closing(&mut self, out: &mut dyn Write, postamble: Option<&str>) -> io::Result<()> {
+        write!(out, "\n]")?;
+        if let Some(postamble) = postamble {
+            write!(out, ",\"postamble\":")?;
+            serde_json::to_writer(&mut *out, postamble)?;
+        }
+        writeln!(out, "}}")
+    }
+}
+
+fn parse_json(archive_text: &str) -> io::Result<Vec<ArchiveEntry>> {
+    let archive: JsonArchive = serde_json::from_str(archive_text)?;
+    Ok(archive.files)
+}
+
+/*
+ * JSON Lines: one self-contained `{"path": "...", "content": "..."}` object per line.
+ * A split archive starts with one `{"part": {...}}` line and a delta archive with one
+ * `{"delta": {...}}` line, followed by one `{"manifest": {...}}` line when the manifest is enabled and one `{"tree": "..."}` line
+ * when the directory tree is enabled. A prompt adds a `{"prompt": "..."}` line before all of
+ * them and a `{"postamble": "..."}` line after the last file.
+ */
+struct JsonLinesFormatter;
+
+#[derive(Serialize, Deserialize)]
+struct JsonLinesPartLine {
+    part: ArchivePartHeader,
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonLinesDeltaLine {
+    delta: ArchiveDeltaHeader,
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonLinesManifestLine {
+    manifest: ArchiveManifest,
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonLinesTreeLine {
+    tree: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonLinesPromptLine {
+    prompt: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonLinesPostambleLine {
+    postamble: String,
+}
+
+impl ArchiveFormatter for JsonLinesFormatter {
+    fn write_preamble(
+        &mut self,
+        out: &mut dyn Write,
+        preamble: &ArchivePreamble,
+    ) -> io::Result<()> {
+        if let Some(prompt) = preamble.prompt {
+            let line = JsonLinesPromptLine {
+                prompt: prompt.to_string(),
+            };
+            serde_json::to_writer(&mut *out, &line)?;
+            writeln!(out)?;
+        }
+        if let Some(part) = preamble.part {
+            serde_json::to_writer(&mut *out, &JsonLinesPartLine { part: part.clone() })?;
+            writeln!(out)?;
+        }
+        if let Some(delta) = preamble.delta {
+            let line = JsonLinesDeltaLine {
+                delta: delta.clone(),
+            };
+            serde_json::to_writer(&mut *out, &line)?;
+            writeln!(out)?;
+        }
+        if let Some(manifest) = preamble.manifest {
+            let line = JsonLinesManifestLine {
+                manifest: manifest.clone(),
+            };
+            serde_json::to_writer(&mut *out, &line)?;
+            writeln!(out)?;
+        }
+        if let Some(tree) = preamble.tree {
+            let line = JsonLinesTreeLine {
+                tree: tree.to_text(),
+            };
+            serde_json::to_writer(&mut *out, &line)?;
+            writeln!(out)?;
+        }
+        Ok(())
+    }
+
+    fn write_entry(&mut self, out: &mut dyn Write, entry: &ArchiveEntry) -> io::Result<()> {
+        serde_json::to_writer(&mut *out, entry)?;
+        writeln!(out)
+    }
+
+    fn write_closing(&mut self, out: &mut dyn Write, postamble: Option<&str>) -> io::Result<()> {
+        if let Some(postamble) = postamble {
+            let line = JsonLinesPostambleLine {
+                postamble: postamble.to_string(),
+            };
+            serde_json::to_writer(&mut *out, &line)?;
+            writeln!(out)?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_json_lines(archive_text: &str) -> io::Result<Vec<ArchiveEntry>> {
+    archive_text
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter(|line| serde_json::from_str::<JsonLinesPartLine>(line).is_err())
+        .filter(|line| serde_json::from_str::<JsonLinesDeltaLine>(line).is_err())
+        .filter(|line| serde_json::from_str::<JsonLinesManifestLine>(line).is_err())
+        .filter(|line| serde_json::from_str::<JsonLinesTreeLine>(line).is_err())
+        .filter(|line| serde_json::from_str::<JsonLinesPromptLine>(line).is_err())
+        .filter(|line| serde_json::from_str::<JsonLinesPostambleLine>(line).is_err())
+        .map(|line| serde_json::from_str::<ArchiveEntry>(line).map_err(io::Error::from))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::archive_manifest::ManifestEntry;
+
+    fn sample_entries() -> Vec<ArchiveEntry> {
+        vec![
+            ArchiveEntry {
+                relative_path: "src/main.rs".to_string(),
+                content: "fn main() {\n    println!(\"<hi> & \\\"bye\\\"\");\n}\n".to_string(),
+                lines: None,
+            },
+            ArchiveEntry {
+                relative_path: "docs/notes & <draft>.md".to_string(),
+                content: "```rust\nlet x = 1;\n```\n// ===== not a header\n".to_string(),
+                lines: None,
+            },
+            ArchiveEntry {
+                relative_path: "empty.txt".to_string(),
+                content: String::new(),
+                lines: None,
+            },
+        ]
+    }
+
+    fn render(format: ArchiveFormat, entries: &[ArchiveEntry]) -> String {
+        render_with_preamble(format, entries, &ArchivePreamble::new("/project"))
+    }
+
+    fn render_with_preamble(
+        format: ArchiveFormat,
+        entries: &[ArchiveEntry],
+        preamble: &ArchivePreamble,
+    ) -> String {
+        let mut out = Vec::new();
+        let mut formatter = format.create_formatter(FormatterSettings::default());
+        formatter.write_preamble(&mut out, preamble).unwrap();
+        for entry in entries {
+            formatter.write_entry(&mut out, entry).unwrap();
+        }
+        formatter.write_closing(&mut out, None).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    /* Text layouts always terminate content with a newline; the JSON formats store it verbatim. */
+    fn expected_after_round_trip(format: ArchiveFormat, entry: &ArchiveEntry) -> ArchiveEntry {
+        let mut content = entry.content.clone();
+        let structured = matches!(format, ArchiveFormat::Json | ArchiveFormat::JsonLines);
+        if !structured && !content.ends_with('\n') {
+            content.push('\n');
+        }
+        ArchiveEntry {
+            relative_path: entry.relative_path.clone(),
+            content,
+            lines: entry.lines,
+        }
+    }
+
+    fn assert_round_trip(format: ArchiveFormat, entries: &[ArchiveEntry]) {
+        let rendered = render(format, entries);
+        let parsed = format
+            .parse(&rendered)
+            .unwrap_or_else(|e| panic!("{format:?} failed to parse: {e}\n{rendered}"));
+        let expected: Vec<ArchiveEntry> = entries
+            .iter()
+            .map(|e| expected_after_round_trip(format, e))
+            .collect();
+        assert_eq!(parsed, expected, "Round trip mismatch for {format:?}");
+    }
+
+    #[test]
+    fn test_plain_text_round_trip() {
+        // The plain layout cannot escape header-like lines, so avoid them here.
+        let entries = vec![sample_entries().remove(0), sample_entries().remove(2)];
+        assert_round_trip(ArchiveFormat::PlainText, &entries);
+    }
+
+    #[test]
+    fn test_line_numbered_entries_show_range_and_parse_back_without_gutter() {
+        let source = "fn main() {\n    run();\n}\n";
+        let (numbered, total) = line_numbers::number_lines(source);
+        let lines = LineRange::whole(total);
+        let entries = vec![ArchiveEntry {
+            relative_path: "src/main.rs".to_string(),
+            content: numbered,
+            lines,
+        }];
+        for format in ArchiveFormat::ALL {
+            let rendered = render(format, &entries);
+            assert!(
+                rendered.contains("1 | fn main() {"),
+                "{format:?}: {rendered}"
+            );
+            let range_text = match format {
+                ArchiveFormat::Json | ArchiveFormat::JsonLines => {
+                    "\"lines\":{\"first\":1,\"last\":3,\"total\":3}"
+                }
+                _ => "1-3 of 3",
+            };
+            assert!(rendered.contains(range_text), "{format:?}: {rendered}");
+            let parsed = format.parse(&rendered).unwrap();
+            assert_eq!(parsed.len(), 1, "{format:?}");
+            assert_eq!(parsed[0].content, source, "{format:?}");
+            assert_eq!(parsed[0].lines, lines, "{format:?}");
+        }
+    }
+
+    #[test]
+    fn test_markdown_round_trip() {
+        assert_round_trip(ArchiveFormat::Markdown, &sample_entries());
+    }
+
+    #[test]
+    fn test_xml_documents_round_trip() {
+        assert_round_trip(ArchiveFormat::XmlDocuments, &sample_entries());
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        assert_round_trip(ArchiveFormat::Json, &sample_entries());
+    }
+
+    #[test]
+    fn test_json_lines_round_trip() {
+        assert_round_trip(ArchiveFormat::JsonLines, &sample_entries());
+    }
+
+    #[test]
+    fn test_round_trip_with_no_entries() {
+        for format in ArchiveFormat::ALL {
+            assert_round_trip(format, &[]);
+        }
+    }
+
+    #[test]
+    fn test_part_header_is_rendered_and_skipped_by_parsers() {
+        let part = ArchivePartHeader {
+            number: 2,
+            count: 3,
+            other_parts: vec![
+                ArchivePartListing {
+                    number: 1,
+                    files: vec!["a <1>.rs".to_string()],
+                },
+                ArchivePartListing {
+                    number: 3,
+                    files: vec!["c.rs".to_string(), "d.rs".to_string()],
+                },
+            ],
+        };
+        let entries = vec![sample_entries().remove(0)];
+        let preamble = ArchivePreamble {
+            part: Some(&part),
+            ..ArchivePreamble::new("/project")
+        };
+        for format in ArchiveFormat::ALL {
+            let rendered = render_with_preamble(format, &entries, &preamble);
+            let parsed = format
+                .parse(&rendered)
+                .unwrap_or_else(|e| panic!("{format:?} failed to parse: {e}\n{rendered}"));
+            assert_eq!(
+                parsed,
+                vec![expected_after_round_trip(format, &entries[0])],
+                "{format:?}"
+            );
+            assert!(rendered.contains("d.rs"), "{format:?}: {rendered}");
+        }
+
+        let plain = render_with_preamble(ArchiveFormat::PlainText, &entries, &preamble);
+        assert!(plain.starts_with(
+            "// Combined files from /project\n\
+             // Part 2 of 3\n\
+             // Files in other parts:\n\
+             //   part 1: a <1>.rs\n\
+             //   part 3: c.rs\n\
+             //   part 3: d.rs\n\
+             // ===== File: src/main.rs =====\n"
+        ));
+    }
+
+    #[test]
+    fn test_manifest_is_rendered_and_skipped_by_parsers() {
+        let entries = sample_entries();
+        let files = entries
+            .iter()
+            .map(|e| {
+                ManifestEntry::describe(
+                    e.relative_path.clone(),
+                    &e.content,
+                    |_| None,
+                    |text| text.split_whitespace().count(),
+                )
+            })
+            .collect();
+        let manifest = ArchiveManifest::new(
+            files,
+            "Demo \"profile\"",
+            "2026-01-02T03:04:05Z".to_string(),
+        );
+        let preamble = ArchivePreamble {
+            manifest: Some(&manifest),
+            ..ArchivePreamble::new("/project")
+        };
+        for format in ArchiveFormat::ALL {
+            let rendered = render_with_preamble(format, &entries, &preamble);
+            let parsed = format
+                .parse(&rendered)
+                .unwrap_or_else(|e| panic!("{format:?} failed to parse: {e}\n{rendered}"));
+            assert_eq!(parsed.len(), entries.len(), "{format:?}");
+            assert!(rendered.contains(&manifest.files[0].sha256), "{format:?}");
+            assert!(rendered.contains("2026-01-02T03:04:05Z"), "{format:?}");
+        }
+
+        let plain = render_with_preamble(ArchiveFormat::PlainText, &entries, &preamble);
+        let first = &manifest.files[0];
+        assert!(plain.contains(&format!(
+            "// src/main.rs | {} | 3 | {} | {}\n",
+            first.bytes, first.tokens, first.sha256
+        )));
+        assert!(plain.contains("// Total: 3 files, "), "{plain}");
+        let xml = render_with_preamble(ArchiveFormat::XmlDocuments, &entries, &preamble);
+        assert!(
+            xml.contains("profile=\"Demo &quot;profile&quot;\""),
+            "{xml}"
+        );
+    }
+
+    #[test]
+    fn test_delta_header_is_rendered_and_skipped_by_parsers() {
+        let entries = sample_entries();
+        let delta = ArchiveDeltaHeader {
+            added: vec!["src/new.rs".to_string()],
+            modified: vec!["src/main.rs".to_string()],
+            deleted: vec!["src/<old>.rs".to_string()],
+        };
+        let preamble = ArchivePreamble {
+            delta: Some(&delta),
+            ..ArchivePreamble::new("/project")
+        };
+        for format in ArchiveFormat::ALL {
+            let rendered = render_with_preamble(format, &entries, &preamble);
+            let parsed = format
+                .parse(&rendered)
+                .unwrap_or_else(|e| panic!("{format:?} failed to parse: {e}\n{rendered}"));
+            assert_eq!(parsed.len(), entries.len(), "{format:?}");
+            assert!(rendered.contains("src/new.rs"), "{format:?}: {rendered}");
+        }
+
+        let plain = render_with_preamble(ArchiveFormat::PlainText, &entries, &preamble);
+        assert!(plain.starts_with(
+            "// Combined files from /project\n\
+             // Changes since the previous archive: 1 added, 1 modified, 1 deleted\n\
+             //   added: src/new.rs\n\
+             //   modified: src/main.rs\n\
+             //   deleted: src/<old>.rs\n"
+        ));
+        let xml = render_with_preamble(ArchiveFormat::XmlDocuments, &entries, &preamble);
+        assert!(
+            xml.contains("<deleted>src/&lt;old&gt;.rs</deleted>"),
+            "{xml}"
+        );
+    }
+
+    #[test]
+    fn test_directory_tree_is_rendered_and_skipped_by_parsers() {
+        let entries = sample_entries();
+        let tree = DirectoryTree {
+            lines: vec![
+                ".".to_string(),
+                "├── [x] <main>.rs".to_string(),
+                "└── [ ] ```notes```.md".to_string(),
+            ],
+        };
+        let preamble = ArchivePreamble {
+            tree: Some(&tree),
+            ..ArchivePreamble::new("/project")
+        };
+        for format in ArchiveFormat::ALL {
+            let rendered = render_with_preamble(format, &entries, &preamble);
+            let parsed = format
+                .parse(&rendered)
+                .unwrap_or_else(|e| panic!("{format:?} failed to parse: {e}\n{rendered}"));
+            assert_eq!(parsed.len(), entries.len(), "{format:?}");
+            assert!(rendered.contains("notes```.md"), "{format:?}: {rendered}");
+        }
+
+        let plain = render_with_preamble(ArchiveFormat::PlainText, &entries, &preamble);
+        assert!(plain.contains("// Project tree:\n// .\n// ├── [x] <main>.rs\n"));
+        let markdown = render_with_preamble(ArchiveFormat::Markdown, &entries, &preamble);
+        assert!(
+            markdown.contains("## Project Tree\n\n````text\n.\n"),
+            "{markdown}"
+        );
+    }
+
+    #[test]
+    fn test_prompt_surrounds_archive_and_is_skipped_by_parsers() {
+        let entries = sample_entries();
+        let preamble = ArchivePreamble {
+            prompt: Some("Review these files.\n---\n- Be brief"),
+            ..ArchivePreamble::new("/project")
+        };
+        for format in ArchiveFormat::ALL {
+            let mut out = Vec::new();
+            let mut formatter = format.create_formatter(FormatterSettings::default());
+            formatter.write_preamble(&mut out, &preamble).unwrap();
+            for entry in &entries {
+                formatter.write_entry(&mut out, entry).unwrap();
+            }
+            formatter
+                .write_closing(&mut out, Some("Answer with a patch."))
+                .unwrap();
+            let rendered = String::from_utf8(out).unwrap();
+            let parsed = format
+                .parse(&rendered)
+                .unwrap_or_else(|e| panic!("{format:?} failed to parse: {e}\n{rendered}"));
+            let expected: Vec<ArchiveEntry> = entries
+                .iter()
+                .map(|e| expected_after_round_trip(format, e))
+                .collect();
+            assert_eq!(parsed, expected, "{format:?}");
+            assert!(rendered.contains("Be brief"), "{format:?}: {rendered}");
+            assert!(rendered.contains("Answer with a patch."), "{format:?}");
+        }
+
+        let markdown = render_with_preamble(ArchiveFormat::Markdown, &entries, &preamble);
+        assert!(markdown.starts_with("Review these files.\n---\n- Be brief\n\n"));
+    }
+
+    #[test]
+    fn test_plain_text_header_paths_round_trip_unusual_names() {
+        for name in [
+            "src/main.rs",
+            "dir with spaces/a =====.rs",
+            " leading.rs",
+            "trailing.rs\t",
+            "line\nbreak.rs",
+            "\"quoted\".rs",
+            "back\\slash\\path.rs",
+        ] {
+            let entries = vec![ArchiveEntry {
+                relative_path: name.to_string(),
+                content: "x\n".to_string(),
+                lines: None,
+            }];
+            let rendered = render(ArchiveFormat::PlainText, &entries);
+            assert_eq!(
+                ArchiveFormat::PlainText.parse(&rendered).unwrap(),
+                entries,
+                "{rendered}"
+            );
+        }
+        assert_eq!(encode_header_path("src\\main.rs"), "src\\main.rs");
+        assert_eq!(encode_header_path("a\nb"), "\"a\\nb\"");
+        assert!(decode_header_path("\"unterminated").is_err());
+    }
+
+    #[test]
+    fn test_plain_text_layout_is_unchanged() {
+        let rendered = render(ArchiveFormat::PlainText, &sample_entries()[..1]);
+        assert_eq!(
+            rendered,
+            "// Combined files from /project\n\
+             // ===== File: src/main.rs =====\n\
+             fn main() {\n    println!(\"<hi> & \\\"bye\\\"\");\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_markdown_fence_outgrows_backtick_runs_and_tags_language() {
+        let rendered = render(ArchiveFormat::Markdown, &sample_entries()[1..2]);
+        assert!(rendered.contains("\n````markdown\n"), "{rendered}");
+        let rendered = render(ArchiveFormat::Markdown, &sample_entries()[..1]);
+        assert!(rendered.contains("\n```rust\n"), "{rendered}");
+    }
+
+    #[test]
+    fn test_xml_escapes_source_but_not_content() {
+        let rendered = render(ArchiveFormat::XmlDocuments, &sample_entries()[..2]);
+        assert!(rendered.contains("<source>docs/notes &amp; &lt;draft&gt;.md</source>"));
+        assert!(rendered.contains("println!(\"<hi> & "));
+        assert!(rendered.contains("<document index=\"2\">"));
+    }
+
+    #[test]
+    fn test_parse_rejects_malformed_input() {
+        for format in ArchiveFormat::ALL {
+            let result = format.parse("this is not an archive\n");
+            assert!(result.is_err(), "{format:?} accepted malformed input");
+            assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn test_format_display_names_round_trip() {
+        for format in ArchiveFormat::ALL {
+            assert_eq!(
+                ArchiveFormat::from_display_name(&format.display_name().to_lowercase()),
+                Some(format)
+            );
+        }
+        assert_eq!(ArchiveFormat::from_display_name("yaml"), None);
+    }
+}
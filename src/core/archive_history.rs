@@ -0,0 +1,513 @@
+use super::archive_record::ArchiveRecord;
+use super::archive_split;
+use super::atomic_write;
+use super::checksum_utils;
+use super::content_class;
+use super::file_node::ArchiveChanges;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/*
+ * This module keeps the history of a profile's generated archives, so it can be answered
+ * what was sent to the model at an earlier time. [ArchiveHistoryV1]
+ * Each profile has a directory `.sourcepacker/history/<profile>/` with a `manifest.json`
+ * listing the generations, newest last, and a `blobs/` directory holding the archive
+ * files by SHA-256. Identical archives (or parts) are therefore stored once. A generation
+ * also records the checksum of every selected source file, which gives the file-level
+ * difference between any two generations. The oldest generations are dropped beyond the
+ * profile's retention limit, together with blobs no longer referenced.
+ */
+
+/* Bumped when the layout changes; a manifest with another version is not used. */
+pub const ARCHIVE_HISTORY_VERSION: u32 = 1;
+pub const DEFAULT_HISTORY_RETENTION: usize = 10;
+
+const MANIFEST_FILE_NAME: &str = "manifest.json";
+const BLOBS_SUBFOLDER_NAME: &str = "blobs";
+
+/* Per-profile history settings. A retention of 0 turns the history off. */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchiveHistoryOptions {
+    #[serde(default = "default_retention")]
+    pub retention: usize,
+}
+
+fn default_retention() -> usize {
+    DEFAULT_HISTORY_RETENTION
+}
+
+impl Default for ArchiveHistoryOptions {
+    fn default() -> Self {
+        ArchiveHistoryOptions {
+            retention: DEFAULT_HISTORY_RETENTION,
+        }
+    }
+}
+
+/*
+ * One file of a stored archive: where it was written, its blob, and its timestamp, which
+ * is put back on restore so timestamp-based status checks see the original generation.
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoryArchiveFile {
+    pub path: PathBuf,
+    pub sha256: String,
+    pub size: u64,
+    #[serde(default)]
+    pub modified: Option<SystemTime>,
+}
+
+/*
+ * One generation. `files` holds the SHA-256 of each selected source file by absolute path,
+ * in the same form as the archive's content record.
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoryGeneration {
+    pub id: u64,
+    pub created: String,
+    pub archive_path: PathBuf,
+    pub archive_files: Vec<HistoryArchiveFile>,
+    pub files: BTreeMap<PathBuf, String>,
+}
+
+impl HistoryGeneration {
+    pub fn archive_size(&self) -> u64 {
+        self.archive_files.iter().map(|file| file.size).sum()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct HistoryManifest {
+    version: u32,
+    generations: Vec<HistoryGeneration>,
+}
+
+/* The history directory of one profile. */
+#[derive(Debug, Clone)]
+pub struct ArchiveHistory {
+    dir: PathBuf,
+}
+
+impl ArchiveHistory {
+    pub fn new(dir: PathBuf) -> Self {
+        ArchiveHistory { dir }
+    }
+
+    /* All stored generations, oldest first. A missing history is empty. */
+    pub fn list(&self) -> io::Result<Vec<HistoryGeneration>> {
+        Ok(self.load_manifest()?.generations)
+    }
+
+    pub fn generation(&self, id: u64) -> io::Result<HistoryGeneration> {
+        self.list()?
+            .into_iter()
+            .find(|generation| generation.id == id)
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("No archive generation {id} in {:?}", self.dir),
+                )
+            })
+    }
+
+    /*
+     * Stores the archive files just written for `archive_path` as a new generation and
+     * applies the retention limit. Returns the new generation.
+     */
+    pub fn record(
+        &self,
+        archive_path: &Path,
+        archive_files: &[PathBuf],
+        files: BTreeMap<PathBuf, String>,
+        created: String,
+        retention: usize,
+    ) -> io::Result<HistoryGeneration> {
+        let mut manifest = self.load_manifest()?;
+        let mut stored = Vec::with_capacity(archive_files.len());
+        for path in archive_files {
+            let content = fs::read(path)?;
+            let sha256 = checksum_utils::calculate_sha256_for_bytes(&content);
+            let blob_path = self.blob_path(&sha256);
+            if !blob_path.is_file() {
+                fs::create_dir_all(self.dir.join(BLOBS_SUBFOLDER_NAME))?;
+                atomic_write::write_file_atomically(&blob_path, |out| out.write_all(&content))?;
+            }
+            stored.push(HistoryArchiveFile {
+                path: path.clone(),
+                sha256,
+                size: content.len() as u64,
+                modified: fs::metadata(path).and_then(|m| m.modified()).ok(),
+            });
+        }
+
+        let generation = HistoryGeneration {
+            id: manifest.generations.last().map_or(1, |last| last.id + 1),
+            created,
+            archive_path: archive_path.to_path_buf(),
+            archive_files: stored,
+            files,
+        };
+        manifest.generations.push(generation.clone());
+        let excess = manifest.generations.len().saturating_sub(retention.max(1));
+        manifest.generations.drain(..excess);
+        self.save_manifest(&manifest)?;
+        self.remove_unreferenced_blobs(&manifest);
+        Ok(generation)
+    }
+
+    /*
+     * The source files that differ between generation `from` and generation `to`: added
+     * and modified files are those of `to`, removed files those only in `from`.
+     */
+    pub fn diff(&self, from: u64, to: u64) -> io::Result<ArchiveChanges> {
+        Ok(changes_between(
+            &self.generation(from)?,
+            &self.generation(to)?,
+        ))
+    }
+
+    /*
+     * Writes the archive files of generation `id` back to where they were generated, and,
+     * with `record_path`, the content record that matches them, so the archive status
+     * compares the selection with the restored archive.
+     */
+    pub fn restore(&self, id: u64, record_path: Option<&Path>) -> io::Result<HistoryGeneration> {
+        let generation = self.generation(id)?;
+        for file in &generation.archive_files {
+            let content = fs::read(self.blob_path(&file.sha256))?;
+            if checksum_utils::calculate_sha256_for_bytes(&content) != file.sha256 {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("History blob {} is corrupt", file.sha256),
+                ));
+            }
+            if let Some(parent) = file.path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            atomic_write::write_file_atomically(&file.path, |out| out.write_all(&content))?;
+            if let Some(modified) = file.modified {
+                fs::File::options()
+                    .write(true)
+                    .open(&file.path)?
+                    .set_modified(modified)?;
+            }
+        }
+        let restored: HashSet<&Path> = generation
+            .archive_files
+            .iter()
+            .map(|file| file.path.as_path())
+            .collect();
+        for stale in archive_split::existing_part_files(&generation.archive_path) {
+            if !restored.contains(stale.as_path())
+                && let Err(e) = fs::remove_file(&stale)
+            {
+                log::warn!("ArchiveHistory: Failed to remove stale archive part {stale:?}: {e}");
+            }
+        }
+        if let Some(record_path) = record_path {
+            ArchiveRecord::new(&generation.archive_path, generation.files.clone())
+                .save(record_path)?;
+        }
+        Ok(generation)
+    }
+
+    fn blob_path(&self, sha256: &str) -> PathBuf {
+        self.dir.join(BLOBS_SUBFOLDER_NAME).join(sha256)
+    }
+
+    fn load_manifest(&self) -> io::Result<HistoryManifest> {
+        let path = self.dir.join(MANIFEST_FILE_NAME);
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                return Ok(HistoryManifest {
+                    version: ARCHIVE_HISTORY_VERSION,
+                    generations: Vec::new(),
+                });
+            }
+            Err(e) => return Err(e),
+        };
+        let manifest: HistoryManifest = serde_json::from_str(&text)?;
+        if manifest.version != ARCHIVE_HISTORY_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Archive history {path:?} has version {}, expected {ARCHIVE_HISTORY_VERSION}",
+                    manifest.version
+                ),
+            ));
+        }
+        Ok(manifest)
+    }
+
+    fn save_manifest(&self, manifest: &HistoryManifest) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        atomic_write::write_file_atomically(&self.dir.join(MANIFEST_FILE_NAME), |out| {
+            serde_json::to_writer_pretty(&mut *out, manifest)?;
+            out.write_all(b"\n")
+        })
+    }
+
+    /* Failures only leave unused blobs behind, so they are logged and otherwise ignored. */
+    fn remove_unreferenced_blobs(&self, manifest: &HistoryManifest) {
+        let referenced: HashSet<&str> = manifest
+            .generations
+            .iter()
+            .flat_map(|generation| &generation.archive_files)
+            .map(|file| file.sha256.as_str())
+            .collect();
+        let Ok(entries) = fs::read_dir(self.dir.join(BLOBS_SUBFOLDER_NAME)) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let name = entry.file_name();
+            if !referenced.contains(name.to_string_lossy().as_ref())
+                && let Err(e) = fs::remove_file(entry.path())
+            {
+                log::warn!(
+                    "ArchiveHistory: Failed to remove blob {:?}: {e}",
+                    entry.path()
+                );
+            }
+        }
+    }
+}
+
+fn changes_between(from: &HistoryGeneration, to: &HistoryGeneration) -> ArchiveChanges {
+    let mut changes = ArchiveChanges::default();
+    for (path, checksum) in &to.files {
+        match from.files.get(path) {
+            None => changes.added.push(path.clone()),
+            Some(previous) if previous != checksum => changes.modified.push(path.clone()),
+            Some(_) => {}
+        }
+    }
+    changes.removed = from
+        .files
+        .keys()
+        .filter(|path| !to.files.contains_key(*path))
+        .cloned()
+        .collect();
+    changes
+}
+
+/* What the user asked for in the "Archive History..." dialog. */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HistoryCommand {
+    Restore(u64),
+    Diff(u64, u64),
+    Keep(usize),
+}
+
+/* Parses `3` (restore), `2 5` (compare) or `keep 20` (retention). */
+pub fn parse_command(text: &str) -> Result<HistoryCommand, String> {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let number = |word: &str| {
+        word.trim_start_matches('#')
+            .parse::<u64>()
+            .map_err(|_| format!("'{word}' is not a generation number"))
+    };
+    match words.as_slice() {
+        [keep, count] if keep.eq_ignore_ascii_case("keep") => count
+            .parse()
+            .map(HistoryCommand::Keep)
+            .map_err(|_| format!("'{count}' is not a number of generations")),
+        [id] => Ok(HistoryCommand::Restore(number(id)?)),
+        [from, to] => Ok(HistoryCommand::Diff(number(from)?, number(to)?)),
+        _ => Err(format!("'{}' is not a history command", text.trim())),
+    }
+}
+
+/*
+ * Lists the generations for the viewer, newest first, each with the files that changed
+ * since the generation before it.
+ */
+pub fn render_generations(generations: &[HistoryGeneration]) -> String {
+    let mut text = String::new();
+    for (index, generation) in generations.iter().enumerate().rev() {
+        text.push_str(&format!(
+            "#{} {}: {} file(s), {} in '{}'\n",
+            generation.id,
+            generation.created,
+            generation.files.len(),
+            content_class::format_size(generation.archive_size()),
+            generation.archive_path.display()
+        ));
+        if let Some(previous) = index.checked_sub(1).map(|i| &generations[i]) {
+            let changes = changes_between(previous, generation);
+            text.push_str(&format!(
+                "    {} added, {} modified, {} removed since #{}\n",
+                changes.added.len(),
+                changes.modified.len(),
+                changes.removed.len(),
+                previous.id
+            ));
+        }
+    }
+    text
+}
+
+/* Lists the changed files between two generations, relative to `root`. */
+pub fn render_changes(from: u64, to: u64, changes: &ArchiveChanges, root: &Path) -> String {
+    let mut text = format!("Changes from generation #{from} to #{to}:\n");
+    if changes.is_empty() {
+        text.push_str("(no file changes)\n");
+    }
+    for (marker, paths) in [
+        ('+', &changes.added),
+        ('~', &changes.modified),
+        ('-', &changes.removed),
+    ] {
+        for path in paths {
+            let relative = path.strip_prefix(root).unwrap_or(path);
+            text.push_str(&format!("{marker} {}\n", relative.display()));
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn checksums(files: &[(&str, &str)]) -> BTreeMap<PathBuf, String> {
+        files
+            .iter()
+            .map(|(path, sha)| (PathBuf::from(path), sha.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_record_deduplicates_blobs_and_applies_retention() {
+        let dir = tempdir().unwrap();
+        let archive = dir.path().join("out.txt");
+        let history = ArchiveHistory::new(dir.path().join("history").join("Demo"));
+        let blob_count = || {
+            fs::read_dir(dir.path().join("history/Demo/blobs"))
+                .unwrap()
+                .count()
+        };
+
+        for (index, content) in ["one", "one", "two", "three"].iter().enumerate() {
+            fs::write(&archive, content).unwrap();
+            let generation = history
+                .record(
+                    &archive,
+                    std::slice::from_ref(&archive),
+                    BTreeMap::new(),
+                    format!("t{index}"),
+                    3,
+                )
+                .unwrap();
+            assert_eq!(generation.id, index as u64 + 1);
+            if index == 1 {
+                assert_eq!(blob_count(), 1, "identical archives share a blob");
+            }
+        }
+
+        let ids: Vec<u64> = history.list().unwrap().iter().map(|g| g.id).collect();
+        assert_eq!(ids, vec![2, 3, 4]);
+        assert_eq!(blob_count(), 3);
+        assert!(history.generation(1).is_err());
+        assert!(
+            ArchiveHistory::new(dir.path().join("none"))
+                .list()
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_diff_and_restore_generations() {
+        let dir = tempdir().unwrap();
+        let archive = dir.path().join("out.txt");
+        let record_path = dir.path().join("archives").join("Demo.json");
+        let history = ArchiveHistory::new(dir.path().join("history").join("Demo"));
+
+        fs::write(&archive, "first archive").unwrap();
+        let first = checksums(&[("/p/a.rs", "1"), ("/p/b.rs", "2")]);
+        history
+            .record(
+                &archive,
+                std::slice::from_ref(&archive),
+                first.clone(),
+                "t1".into(),
+                5,
+            )
+            .unwrap();
+        fs::write(&archive, "second archive").unwrap();
+        let second = checksums(&[("/p/a.rs", "1"), ("/p/b.rs", "3"), ("/p/c.rs", "4")]);
+        history
+            .record(
+                &archive,
+                std::slice::from_ref(&archive),
+                second,
+                "t2".into(),
+                5,
+            )
+            .unwrap();
+
+        let changes = history.diff(1, 2).unwrap();
+        assert_eq!(changes.added, vec![PathBuf::from("/p/c.rs")]);
+        assert_eq!(changes.modified, vec![PathBuf::from("/p/b.rs")]);
+        assert!(changes.removed.is_empty());
+        assert_eq!(
+            history.diff(2, 1).unwrap().removed,
+            vec![PathBuf::from("/p/c.rs")]
+        );
+
+        let restored = history.restore(1, Some(&record_path)).unwrap();
+        assert_eq!(restored.created, "t1");
+        assert_eq!(fs::read_to_string(&archive).unwrap(), "first archive");
+        let record = ArchiveRecord::load(&record_path).unwrap();
+        assert_eq!(record.archive_path, archive);
+        assert_eq!(record.files, first);
+        assert_eq!(
+            history.restore(9, None).unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+    }
+
+    #[test]
+    fn test_commands_and_rendering() {
+        assert_eq!(parse_command(" 3 "), Ok(HistoryCommand::Restore(3)));
+        assert_eq!(parse_command("#2 5"), Ok(HistoryCommand::Diff(2, 5)));
+        assert_eq!(parse_command("Keep 20"), Ok(HistoryCommand::Keep(20)));
+        assert!(parse_command("latest").is_err());
+        assert!(parse_command("1 2 3").is_err());
+
+        let generation = |id: u64, files: &[(&str, &str)]| HistoryGeneration {
+            id,
+            created: format!("t{id}"),
+            archive_path: PathBuf::from("/p/out.txt"),
+            archive_files: vec![HistoryArchiveFile {
+                path: PathBuf::from("/p/out.txt"),
+                sha256: id.to_string(),
+                size: 2048,
+                modified: None,
+            }],
+            files: checksums(files),
+        };
+        let generations = [
+            generation(4, &[("/p/a.rs", "1")]),
+            generation(5, &[("/p/a.rs", "2"), ("/p/b.rs", "3")]),
+        ];
+        let listing = render_generations(&generations);
+        assert!(
+            listing.starts_with(
+                "#5 t5: 2 file(s), 2 KB in '/p/out.txt'\n    1 added, 1 modified, 0 removed since #4\n#4 t4:"
+            ),
+            "{listing}"
+        );
+        let changes = changes_between(&generations[1], &generations[0]);
+        assert_eq!(
+            render_changes(5, 4, &changes, Path::new("/p")),
+            "Changes from generation #5 to #4:\n~ a.rs\n- b.rs\n"
+        );
+    }
+}
@@ -0,0 +1,142 @@
+use super::checksum_utils;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc3339;
+
+/*
+ * This module describes the optional table of contents written at the top of an archive.
+ * [ArchiveGenManifestV1] The manifest lists every included file with its size, line count,
+ * token count and SHA-256, followed by totals and where the archive came from. It helps
+ * models navigate large archives and serves as an audit record of what was sent.
+ * The values describe the content as written to the archive, so a file truncated by the
+ * token budget is listed with its truncated size and checksum.
+ */
+
+pub const TOOL_NAME: &str = "SourcePacker";
+pub const TOOL_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub path: String,
+    pub bytes: u64,
+    pub lines: usize,
+    pub tokens: usize,
+    pub sha256: String,
+}
+
+impl ManifestEntry {
+    /*
+     * Describes `content` as it appears in the archive. `cached_tokens` is returned by the
+     * caller's token cache for a given checksum, so files that were already counted are
+     * not counted again; `count_tokens` is only used on a cache miss.
+     */
+    pub fn describe(
+        path: String,
+        content: &str,
+        cached_tokens: impl FnOnce(&str) -> Option<usize>,
+        count_tokens: impl FnOnce(&str) -> usize,
+    ) -> Self {
+        let sha256 = checksum_utils::calculate_sha256_for_bytes(content.as_bytes());
+        let tokens = cached_tokens(&sha256).unwrap_or_else(|| count_tokens(content));
+        ManifestEntry {
+            path,
+            bytes: content.len() as u64,
+            lines: content.lines().count(),
+            tokens,
+            sha256,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchiveManifest {
+    pub files: Vec<ManifestEntry>,
+    pub total_bytes: u64,
+    pub total_lines: usize,
+    pub total_tokens: usize,
+    /* RFC 3339 timestamp in UTC. */
+    pub generated_at: String,
+    pub profile: String,
+    pub tool_version: String,
+}
+
+impl ArchiveManifest {
+    pub fn new(files: Vec<ManifestEntry>, profile: &str, generated_at: String) -> Self {
+        ArchiveManifest {
+            total_bytes: files.iter().map(|f| f.bytes).sum(),
+            total_lines: files.iter().map(|f| f.lines).sum(),
+            total_tokens: files.iter().map(|f| f.tokens).sum(),
+            files,
+            generated_at,
+            profile: profile.to_string(),
+            tool_version: TOOL_VERSION.to_string(),
+        }
+    }
+
+    /* A one-line summary of the totals, shared by the text-based layouts. */
+    pub fn totals_line(&self) -> String {
+        format!(
+            "Total: {} files, {} bytes, {} lines, {} tokens",
+            self.files.len(),
+            self.total_bytes,
+            self.total_lines,
+            self.total_tokens
+        )
+    }
+
+    /* A one-line description of the archive's origin, shared by the text-based layouts. */
+    pub fn origin_line(&self) -> String {
+        format!(
+            "Generated {} by {TOOL_NAME} {} for profile '{}'",
+            self.generated_at, self.tool_version, self.profile
+        )
+    }
+}
+
+/* Returns the current time as used in `ArchiveManifest::generated_at`. */
+pub fn current_timestamp() -> String {
+    OffsetDateTime::now_utc()
+        .format(&Rfc3339)
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest_totals_and_cached_tokens() {
+        let first = ManifestEntry::describe(
+            "a.rs".to_string(),
+            "fn a() {}\nfn b() {}\n",
+            |_| Some(99),
+            |_| panic!("Cached count must be used"),
+        );
+        let second = ManifestEntry::describe("b.rs".to_string(), "one two", |_| None, |_| 2);
+
+        assert_eq!(first.bytes, 20);
+        assert_eq!(first.lines, 2);
+        assert_eq!(first.tokens, 99);
+        assert_eq!(
+            first.sha256,
+            checksum_utils::calculate_sha256_for_bytes(b"fn a() {}\nfn b() {}\n")
+        );
+
+        let manifest = ArchiveManifest::new(vec![first, second], "Demo", "now".to_string());
+        assert_eq!(manifest.total_bytes, 27);
+        assert_eq!(manifest.total_lines, 3);
+        assert_eq!(manifest.total_tokens, 101);
+        assert_eq!(
+            manifest.totals_line(),
+            "Total: 2 files, 27 bytes, 3 lines, 101 tokens"
+        );
+        assert!(manifest.origin_line().contains("for profile 'Demo'"));
+    }
+
+    #[test]
+    fn test_current_timestamp_is_rfc3339() {
+        let timestamp = current_timestamp();
+        assert!(timestamp.ends_with('Z'), "{timestamp}");
+        assert_eq!(timestamp.as_bytes()[10], b'T', "{timestamp}");
+    }
+}
@@ -0,0 +1,309 @@
+use super::archive_record::ArchiveRecord;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use time::OffsetDateTime;
+
+/*
+ * This module resolves templated archive paths. [ArchivePathTemplateV1] A profile's
+ * `archive_path` may contain the placeholders `{project_root}`, `{profile}`, `{git_branch}`
+ * and `{date}` (`{date:FORMAT}` with the strftime codes `%Y %y %m %d %H %M %S %%`, default
+ * `%Y-%m-%d`, in local time), e.g. `{project_root}/out/{profile}-{date:%Y%m%d}.txt`. A
+ * literal brace is written as `{{` or `}}`. The path is resolved when the archive is
+ * generated; a path that is still relative afterwards is taken relative to the project
+ * root, so profiles can be shared between machines. The resolved path is kept in the profile's archive record,
+ * which is how the status check finds the latest archive of a templated path.
+ */
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArchivePathError {
+    UnknownPlaceholder(String),
+    UnsupportedDateFormat(String),
+    UnterminatedPlaceholder,
+    UnmatchedClosingBrace,
+    NoGitBranch(PathBuf),
+}
+
+impl fmt::Display for ArchivePathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArchivePathError::UnknownPlaceholder(name) => {
+                write!(f, "Unknown placeholder '{{{name}}}' in archive path")
+            }
+            ArchivePathError::UnsupportedDateFormat(code) => write!(
+                f,
+                "Unsupported date format code '{code}' in archive path (use %Y, %y, %m, %d, %H, %M, %S or %%)"
+            ),
+            ArchivePathError::UnterminatedPlaceholder => {
+                write!(f, "Unterminated '{{' in archive path")
+            }
+            ArchivePathError::UnmatchedClosingBrace => write!(
+                f,
+                "Unmatched '}}' in archive path (use '}}}}' for a literal brace)"
+            ),
+            ArchivePathError::NoGitBranch(root) => write!(
+                f,
+                "Archive path uses {{git_branch}}, but {root:?} is not in a git repository"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ArchivePathError {}
+
+/* The values an archive path is resolved with. */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchivePathContext {
+    pub project_root: PathBuf,
+    pub profile: String,
+    pub now: OffsetDateTime,
+    pub git_branch: Option<String>,
+}
+
+impl ArchivePathContext {
+    /*
+     * A context for the current local time, with the branch checked out in `project_root`.
+     * Falls back to UTC where the local offset cannot be determined (some platforms refuse
+     * to read it once the process has several threads).
+     */
+    pub fn new(project_root: &Path, profile: &str) -> Self {
+        ArchivePathContext {
+            project_root: project_root.to_path_buf(),
+            profile: profile.to_string(),
+            now: OffsetDateTime::now_local().unwrap_or_else(|e| {
+                log::debug!("ArchivePathContext: Using UTC for {{date}}: {e}");
+                OffsetDateTime::now_utc()
+            }),
+            git_branch: current_git_branch(project_root),
+        }
+    }
+}
+
+/* Whether `path` contains placeholders, and so may resolve differently on each generation. */
+pub fn is_templated(path: &Path) -> bool {
+    path.to_string_lossy().contains(['{', '}'])
+}
+
+/* Resolves `template`, joining a relative result onto the project root. */
+pub fn resolve_archive_path(
+    template: &Path,
+    context: &ArchivePathContext,
+) -> Result<PathBuf, ArchivePathError> {
+    let text = template.to_string_lossy();
+    let mut resolved = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                resolved.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                resolved.push('}');
+            }
+            '}' => return Err(ArchivePathError::UnmatchedClosingBrace),
+            '{' => {
+                let mut placeholder = String::new();
+                let mut terminated = false;
+                for inner in chars.by_ref() {
+                    if inner == '}' {
+                        terminated = true;
+                        break;
+                    }
+                    placeholder.push(inner);
+                }
+                if !terminated {
+                    return Err(ArchivePathError::UnterminatedPlaceholder);
+                }
+                resolved.push_str(&placeholder_value(placeholder.trim(), context)?);
+            }
+            other => resolved.push(other),
+        }
+    }
+    let resolved = PathBuf::from(resolved);
+    Ok(if resolved.is_relative() {
+        context.project_root.join(resolved)
+    } else {
+        resolved
+    })
+}
+
+fn placeholder_value(
+    placeholder: &str,
+    context: &ArchivePathContext,
+) -> Result<String, ArchivePathError> {
+    let (name, argument) = match placeholder.split_once(':') {
+        Some((name, argument)) => (name.trim(), Some(argument)),
+        None => (placeholder, None),
+    };
+    match (name, argument) {
+        ("project_root", None) => Ok(context.project_root.to_string_lossy().into_owned()),
+        ("profile", None) => Ok(path_component(&context.profile)),
+        ("git_branch", None) => context
+            .git_branch
+            .as_deref()
+            .map(path_component)
+            .ok_or_else(|| ArchivePathError::NoGitBranch(context.project_root.clone())),
+        ("date", format) => format_date(context.now, format.unwrap_or("%Y-%m-%d")),
+        _ => Err(ArchivePathError::UnknownPlaceholder(
+            placeholder.to_string(),
+        )),
+    }
+}
+
+/* Keeps a value inside a single path component; `feature/login` becomes `feature-login`. */
+fn path_component(value: &str) -> String {
+    value.replace(['/', '\\'], "-")
+}
+
+fn format_date(now: OffsetDateTime, format: &str) -> Result<String, ArchivePathError> {
+    let mut formatted = String::new();
+    let mut chars = format.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '%' {
+            formatted.push(ch);
+            continue;
+        }
+        match chars.next() {
+            Some('Y') => formatted.push_str(&format!("{:04}", now.year())),
+            Some('y') => formatted.push_str(&format!("{:02}", now.year().rem_euclid(100))),
+            Some('m') => formatted.push_str(&format!("{:02}", u8::from(now.month()))),
+            Some('d') => formatted.push_str(&format!("{:02}", now.day())),
+            Some('H') => formatted.push_str(&format!("{:02}", now.hour())),
+            Some('M') => formatted.push_str(&format!("{:02}", now.minute())),
+            Some('S') => formatted.push_str(&format!("{:02}", now.second())),
+            Some('%') => formatted.push('%'),
+            other => {
+                return Err(ArchivePathError::UnsupportedDateFormat(format!(
+                    "%{}",
+                    other.map(String::from).unwrap_or_default()
+                )));
+            }
+        }
+    }
+    Ok(formatted)
+}
+
+/*
+ * The branch checked out in the repository containing `root`, read from `.git/HEAD`
+ * without running git. A detached HEAD yields the abbreviated commit hash. Worktrees,
+ * whose `.git` is a file pointing at the real git directory, are followed.
+ */
+pub fn current_git_branch(root: &Path) -> Option<String> {
+    let dot_git = root
+        .ancestors()
+        .map(|dir| dir.join(".git"))
+        .find(|p| p.exists())?;
+    let git_dir = if dot_git.is_file() {
+        let pointer = fs::read_to_string(&dot_git).ok()?;
+        let target = PathBuf::from(pointer.trim().strip_prefix("gitdir:")?.trim());
+        dot_git.parent()?.join(target)
+    } else {
+        dot_git
+    };
+    let head = fs::read_to_string(git_dir.join("HEAD")).ok()?;
+    let head = head.trim();
+    match head.strip_prefix("ref:") {
+        Some(reference) => {
+            let reference = reference.trim();
+            Some(
+                reference
+                    .strip_prefix("refs/heads/")
+                    .unwrap_or(reference)
+                    .to_string(),
+            )
+        }
+        None => head.get(..7).map(str::to_string),
+    }
+}
+
+/*
+ * The archive the profile was last generated to, as kept in its archive record, or `None`
+ * when it has not been generated yet.
+ */
+pub fn last_resolved_archive_path(record_path: &Path) -> Option<PathBuf> {
+    ArchiveRecord::load(record_path)
+        .ok()
+        .map(|record| record.archive_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn context(root: &Path, git_branch: Option<&str>) -> ArchivePathContext {
+        ArchivePathContext {
+            project_root: root.to_path_buf(),
+            profile: "Core".to_string(),
+            now: OffsetDateTime::from_unix_timestamp(1_772_892_309).unwrap(), // 2026-03-07 14:05:09 UTC
+            git_branch: git_branch.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_placeholders_resolve_and_relative_paths_join_the_project_root() {
+        let root = Path::new("/work/demo");
+        let ctx = context(root, Some("feature/login"));
+
+        assert_eq!(
+            resolve_archive_path(
+                Path::new("{project_root}/out/{profile}-{date:%Y%m%d}.txt"),
+                &ctx
+            ),
+            Ok(PathBuf::from("/work/demo/out/Core-20260307.txt"))
+        );
+        assert_eq!(
+            resolve_archive_path(Path::new("out/{git_branch}_{date}_{{x}}.txt"), &ctx),
+            Ok(root.join("out/feature-login_2026-03-07_{x}.txt"))
+        );
+        assert_eq!(
+            resolve_archive_path(Path::new("/abs/{date:%H%M%S %%}.txt"), &ctx),
+            Ok(PathBuf::from("/abs/140509 %.txt"))
+        );
+        assert!(is_templated(Path::new("out/{profile}.txt")));
+        assert!(!is_templated(Path::new("/abs/archive.txt")));
+    }
+
+    #[test]
+    fn test_invalid_templates_are_rejected() {
+        let ctx = context(Path::new("/work/demo"), None);
+        let resolve = |template: &str| resolve_archive_path(Path::new(template), &ctx);
+
+        assert_eq!(
+            resolve("{branch}.txt"),
+            Err(ArchivePathError::UnknownPlaceholder("branch".to_string()))
+        );
+        assert_eq!(
+            resolve("{date:%Q}.txt"),
+            Err(ArchivePathError::UnsupportedDateFormat("%Q".to_string()))
+        );
+        assert_eq!(
+            resolve("{profile.txt"),
+            Err(ArchivePathError::UnterminatedPlaceholder)
+        );
+        assert_eq!(
+            resolve("{git_branch}.txt"),
+            Err(ArchivePathError::NoGitBranch(PathBuf::from("/work/demo")))
+        );
+    }
+
+    #[test]
+    fn test_git_branch_is_read_from_head() {
+        let dir = tempdir().unwrap();
+        let git_dir = dir.path().join(".git");
+        fs::create_dir_all(&git_dir).unwrap();
+        let nested = dir.path().join("src");
+        fs::create_dir_all(&nested).unwrap();
+
+        fs::write(git_dir.join("HEAD"), "ref: refs/heads/feature/login\n").unwrap();
+        assert_eq!(
+            current_git_branch(&nested).as_deref(),
+            Some("feature/login")
+        );
+
+        fs::write(git_dir.join("HEAD"), "0123456789abcdef\n").unwrap();
+        assert_eq!(current_git_branch(dir.path()).as_deref(), Some("0123456"));
+    }
+}
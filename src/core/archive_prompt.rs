@@ -0,0 +1,213 @@
+use super::archive_template::{ParsedTemplate, TemplateError, TemplatePlaceholder};
+use super::tokenizer_utils::TokenCounterOperations;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use time::OffsetDateTime;
+
+/*
+ * This module implements the prompt text a profile wraps around its archive.
+ * [ArchiveGenPromptsV1] A profile holds named variants, each with a preamble written
+ * before the archive and a postamble written after it, so the instructions that go with
+ * the files no longer have to be pasted by hand. The variant is picked when the archive is
+ * generated and remembered as the profile's active one. Prompts use the template syntax of
+ * `archive_template` with the placeholders `{project}`, `{profile}`, `{file_count}`,
+ * `{token_count}` (the tokens of the archived files, without the prompt), `{date}` and
+ * `{file_list}` (one relative path per line).
+ */
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PromptVariant {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub preamble: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub postamble: String,
+}
+
+/* The variants stored on a profile, and the name of the one in use (`None` for no prompt). */
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct PromptOptions {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub variants: Vec<PromptVariant>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub active: Option<String>,
+}
+
+impl PromptOptions {
+    /* Finds a variant by name, ignoring case. */
+    pub fn variant(&self, name: &str) -> Option<&PromptVariant> {
+        let name = name.trim();
+        self.variants
+            .iter()
+            .find(|variant| variant.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn active_variant(&self) -> Option<&PromptVariant> {
+        self.active.as_deref().and_then(|name| self.variant(name))
+    }
+
+    pub fn variant_names(&self) -> Vec<&str> {
+        self.variants.iter().map(|v| v.name.as_str()).collect()
+    }
+
+    /* Compiles the active variant; `Ok(None)` when no prompt is in use. */
+    pub fn compile_active(&self) -> Result<Option<CompiledPrompt>, TemplateError> {
+        self.active_variant()
+            .map(CompiledPrompt::compile)
+            .transpose()
+    }
+
+    pub fn validate(&self) -> Result<(), TemplateError> {
+        for variant in &self.variants {
+            CompiledPrompt::compile(variant)?;
+        }
+        Ok(())
+    }
+}
+
+/* The values a prompt is rendered with. */
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PromptContext {
+    pub project: String,
+    pub profile: String,
+    pub token_count: usize,
+    pub date: String,
+    pub file_list: Vec<String>,
+}
+
+impl PromptContext {
+    /* A context for today's date, with the project named after its root folder. */
+    pub fn new(root: &Path, profile: &str, file_list: Vec<String>, token_count: usize) -> Self {
+        PromptContext {
+            project: root
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_else(|| root.to_string_lossy().into_owned()),
+            profile: profile.to_string(),
+            token_count,
+            date: OffsetDateTime::now_utc().date().to_string(),
+            file_list,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompiledPrompt {
+    preamble: ParsedTemplate,
+    postamble: ParsedTemplate,
+}
+
+/* A rendered prompt; an empty preamble or postamble is `None` and writes nothing. */
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RenderedPrompt {
+    pub preamble: Option<String>,
+    pub postamble: Option<String>,
+}
+
+impl RenderedPrompt {
+    pub fn count_tokens(&self, counter: &dyn TokenCounterOperations) -> usize {
+        [&self.preamble, &self.postamble]
+            .into_iter()
+            .flatten()
+            .map(|text| counter.count_tokens(text))
+            .sum()
+    }
+}
+
+impl CompiledPrompt {
+    pub fn compile(variant: &PromptVariant) -> Result<Self, TemplateError> {
+        let scope = &TemplatePlaceholder::PROMPT_SCOPE;
+        Ok(CompiledPrompt {
+            preamble: ParsedTemplate::parse(&variant.preamble, scope, "prompt preamble")?,
+            postamble: ParsedTemplate::parse(&variant.postamble, scope, "prompt postamble")?,
+        })
+    }
+
+    /* Whether rendering needs the token count, which costs a pass over the files. */
+    pub fn uses_token_count(&self) -> bool {
+        self.preamble.uses(TemplatePlaceholder::TokenCount)
+            || self.postamble.uses(TemplatePlaceholder::TokenCount)
+    }
+
+    pub fn render(&self, context: &PromptContext) -> RenderedPrompt {
+        let render = |template: &ParsedTemplate| {
+            (!template.is_empty()).then(|| {
+                template.render(|placeholder| match placeholder {
+                    TemplatePlaceholder::Project => context.project.clone(),
+                    TemplatePlaceholder::FileCount => context.file_list.len().to_string(),
+                    TemplatePlaceholder::TokenCount => context.token_count.to_string(),
+                    TemplatePlaceholder::Date => context.date.clone(),
+                    TemplatePlaceholder::FileList => context.file_list.join("\n"),
+                    _ => context.profile.clone(),
+                })
+            })
+        };
+        RenderedPrompt {
+            preamble: render(&self.preamble),
+            postamble: render(&self.postamble),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_active_variant_renders_all_placeholders() {
+        let options = PromptOptions {
+            variants: vec![
+                PromptVariant {
+                    name: "Review".to_string(),
+                    preamble: "Review {project}/{profile} ({file_count} files, {token_count} tokens, {date}):\n{file_list}".to_string(),
+                    postamble: "List bugs first.".to_string(),
+                },
+                PromptVariant {
+                    name: "Docs".to_string(),
+                    preamble: String::new(),
+                    postamble: "Write docs.".to_string(),
+                },
+            ],
+            active: Some("review".to_string()),
+        };
+        let prompt = options.compile_active().unwrap().unwrap();
+        assert!(prompt.uses_token_count());
+        let context = PromptContext {
+            date: "2026-01-02".to_string(),
+            ..PromptContext::new(
+                Path::new("/work/demo"),
+                "Core",
+                vec!["src/a.rs".to_string(), "src/b.rs".to_string()],
+                1200,
+            )
+        };
+
+        let rendered = prompt.render(&context);
+        assert_eq!(
+            rendered.preamble.as_deref(),
+            Some("Review demo/Core (2 files, 1200 tokens, 2026-01-02):\nsrc/a.rs\nsrc/b.rs")
+        );
+        assert_eq!(rendered.postamble.as_deref(), Some("List bugs first."));
+
+        let docs = CompiledPrompt::compile(options.variant("DOCS").unwrap()).unwrap();
+        assert_eq!(docs.render(&context).preamble, None);
+        assert_eq!(options.variant_names(), ["Review", "Docs"]);
+    }
+
+    #[test]
+    fn test_prompt_rejects_file_placeholders() {
+        let options = PromptOptions {
+            variants: vec![PromptVariant {
+                name: "Bad".to_string(),
+                preamble: "{relative_path}".to_string(),
+                postamble: String::new(),
+            }],
+            active: None,
+        };
+        assert!(matches!(
+            options.validate(),
+            Err(TemplateError::PlaceholderNotAllowed { .. })
+        ));
+        assert_eq!(options.compile_active(), Ok(None));
+    }
+}
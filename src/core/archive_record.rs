@@ -0,0 +1,208 @@
+use super::atomic_write;
+use super::file_node::ArchiveChanges;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/*
+ * This module records what went into an archive, so that staleness can be decided by
+ * content instead of timestamps. [ArchiveSyncNotifyUserV1] [ArchiveSyncContentRecordV1]
+ * The record is a small JSON sidecar under `.sourcepacker/archives/`, holding the archive
+ * path and the SHA-256 of every selected source file at generation time. Comparing it with
+ * the current selection tells which files were added, removed or modified, and a file
+ * whose timestamp changed without a content change (e.g. after `git checkout`) no longer
+ * marks the archive as outdated. The size and modification time of each file as it was
+ * read are kept only to skip re-hashing files that were not touched since.
+ */
+
+/* Bumped when the layout changes; records with another version are ignored. */
+pub const ARCHIVE_RECORD_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchiveRecord {
+    pub version: u32,
+    /* The archive the record describes. A record for another path is not used. */
+    pub archive_path: PathBuf,
+    /* SHA-256 of each selected source file, keyed by absolute path. */
+    pub files: BTreeMap<PathBuf, String>,
+    /* Stamps of the files in `files` as they were read; files without one are re-hashed. */
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub stamps: BTreeMap<PathBuf, FileStamp>,
+}
+
+/*
+ * The size and modification time of a source file. A file whose current stamp equals the
+ * recorded one is taken to be unchanged without hashing it.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileStamp {
+    pub size: u64,
+    pub modified: SystemTime,
+}
+
+/* File systems with coarse timestamps (e.g. FAT) store modification times in 2 s steps. */
+const TIMESTAMP_GRANULARITY: Duration = Duration::from_secs(2);
+
+impl FileStamp {
+    pub fn current(path: &Path) -> io::Result<Self> {
+        let metadata = fs::metadata(path)?;
+        Ok(FileStamp {
+            size: metadata.len(),
+            modified: metadata.modified()?,
+        })
+    }
+
+    /*
+     * The stamp to record for a file that is about to be read, or `None` if it was
+     * modified so recently that a change right after the read could keep the same
+     * timestamp. Such files are re-hashed on every comparison instead.
+     */
+    pub fn before_read(path: &Path) -> io::Result<Option<Self>> {
+        let stamp = Self::current(path)?;
+        let settled = SystemTime::now()
+            .duration_since(stamp.modified)
+            .is_ok_and(|age| age > TIMESTAMP_GRANULARITY);
+        Ok(settled.then_some(stamp))
+    }
+}
+
+impl ArchiveRecord {
+    pub fn new(archive_path: &Path, files: BTreeMap<PathBuf, String>) -> Self {
+        ArchiveRecord {
+            version: ARCHIVE_RECORD_VERSION,
+            archive_path: archive_path.to_path_buf(),
+            files,
+            stamps: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_stamps(mut self, stamps: BTreeMap<PathBuf, FileStamp>) -> Self {
+        self.stamps = stamps;
+        self
+    }
+
+    /*
+     * Loads the record at `path`. A record written by another version is reported as
+     * `InvalidData` so callers fall back to timestamp comparison.
+     */
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        let record: ArchiveRecord = serde_json::from_str(&text)?;
+        if record.version != ARCHIVE_RECORD_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Archive record {path:?} has version {}, expected {ARCHIVE_RECORD_VERSION}",
+                    record.version
+                ),
+            ));
+        }
+        Ok(record)
+    }
+
+    /* Writes the record atomically, creating the parent directory if needed. */
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        atomic_write::write_file_atomically(path, |out| {
+            serde_json::to_writer_pretty(&mut *out, self)?;
+            out.write_all(b"\n")
+        })
+    }
+
+    /*
+     * Compares the record with the current selection. `current_checksum` is only asked
+     * for files present in both, and may return `Ok(None)` to signal "unchanged" without
+     * hashing (e.g. when the file's stamp matches the recorded one).
+     */
+    pub fn diff(
+        &self,
+        selected: &[PathBuf],
+        mut current_checksum: impl FnMut(&Path, &str) -> io::Result<Option<String>>,
+    ) -> io::Result<ArchiveChanges> {
+        let mut changes = ArchiveChanges::default();
+        let still_selected: HashSet<&Path> = selected.iter().map(PathBuf::as_path).collect();
+        for path in selected {
+            match self.files.get(path) {
+                None => changes.added.push(path.clone()),
+                Some(recorded) => {
+                    if let Some(current) = current_checksum(path, recorded)?
+                        && current != *recorded
+                    {
+                        changes.modified.push(path.clone());
+                    }
+                }
+            }
+        }
+        changes.removed = self
+            .files
+            .keys()
+            .filter(|path| !still_selected.contains(path.as_path()))
+            .cloned()
+            .collect();
+        Ok(changes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn record_with(files: &[(&str, &str)]) -> ArchiveRecord {
+        ArchiveRecord::new(
+            Path::new("/out/archive.txt"),
+            files
+                .iter()
+                .map(|(path, sha)| (PathBuf::from(path), sha.to_string()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_diff_reports_added_removed_and_modified() {
+        let record = record_with(&[("/p/a.rs", "1"), ("/p/b.rs", "2"), ("/p/c.rs", "3")]);
+        let selected = vec![
+            PathBuf::from("/p/a.rs"),
+            PathBuf::from("/p/b.rs"),
+            PathBuf::from("/p/d.rs"),
+        ];
+
+        let changes = record
+            .diff(&selected, |path, _| {
+                Ok(match path.to_str().unwrap() {
+                    "/p/b.rs" => Some("changed".to_string()),
+                    /* Same content despite a newer timestamp. */
+                    _ => Some("1".to_string()),
+                })
+            })
+            .unwrap();
+
+        assert_eq!(changes.added, vec![PathBuf::from("/p/d.rs")]);
+        assert_eq!(changes.removed, vec![PathBuf::from("/p/c.rs")]);
+        assert_eq!(changes.modified, vec![PathBuf::from("/p/b.rs")]);
+        assert!(!changes.is_empty());
+    }
+
+    #[test]
+    fn test_save_and_load_round_trip_and_version_check() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("archives").join("Demo.json");
+        let record = record_with(&[("/p/a.rs", "1")]);
+
+        record.save(&path).unwrap();
+        assert_eq!(ArchiveRecord::load(&path).unwrap(), record);
+
+        let mut outdated = record.clone();
+        outdated.version = ARCHIVE_RECORD_VERSION + 1;
+        outdated.save(&path).unwrap();
+        assert_eq!(
+            ArchiveRecord::load(&path).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
+}
@@ -0,0 +1,398 @@
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+/*
+ * This module plans how an archive is divided into several part files, so that each
+ * part fits into the context window of the model it is pasted into. [ArchiveGenSplitPartsV1]
+ * Planning is pure: the archiver measures every selected file first, hands the
+ * measurements to `plan_parts`, and then streams each planned part into its own file.
+ * A file is never cut in the middle unless that file alone exceeds the budget, in which
+ * case it is divided on line boundaries.
+ */
+
+/*
+ * How the selected files are grouped into parts.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ArchiveSplitStrategy {
+    /* Fill each part with files in archive order until the next file would not fit. */
+    #[default]
+    TokenBudget,
+    /* Start a new part for every top-level directory; files in the root share one group.
+     * A group that is larger than the budget continues in further parts. */
+    TopLevelDirectory,
+}
+
+impl ArchiveSplitStrategy {
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            ArchiveSplitStrategy::TokenBudget => "tokens",
+            ArchiveSplitStrategy::TopLevelDirectory => "directories",
+        }
+    }
+}
+
+/*
+ * Per-profile split settings. A budget of 0 means "no limit", which is only useful
+ * together with `TopLevelDirectory`.
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct ArchiveSplitOptions {
+    #[serde(default)]
+    pub strategy: ArchiveSplitStrategy,
+    #[serde(default)]
+    pub max_tokens_per_part: usize,
+}
+
+impl ArchiveSplitOptions {
+    /*
+     * Parses the text entered in the "Split Archive..." dialog: either a token budget
+     * ("100000"), a strategy ("dirs"), or a strategy followed by a budget ("dirs 100000").
+     * Returns `Ok(None)` for "off" (or an empty input), which disables splitting.
+     */
+    pub fn parse_setting(text: &str) -> Result<Option<ArchiveSplitOptions>, String> {
+        let words: Vec<&str> = text.split_whitespace().collect();
+        let (strategy_word, budget_word) = match words.as_slice() {
+            [] => return Ok(None),
+            [word] if word.eq_ignore_ascii_case("off") => return Ok(None),
+            [word] if word.chars().all(|c| c.is_ascii_digit()) => (None, Some(*word)),
+            [word] => (Some(*word), None),
+            [strategy, budget] => (Some(*strategy), Some(*budget)),
+            _ => return Err(format!("Unrecognized split setting '{}'", text.trim())),
+        };
+
+        let strategy = match strategy_word.map(|w| w.to_ascii_lowercase()) {
+            None => ArchiveSplitStrategy::TokenBudget,
+            Some(w) if w == "tokens" => ArchiveSplitStrategy::TokenBudget,
+            Some(w) if w == "dirs" || w == "directories" => ArchiveSplitStrategy::TopLevelDirectory,
+            Some(w) => return Err(format!("Unknown split strategy '{w}'")),
+        };
+        let max_tokens_per_part = match budget_word {
+            Some(b) => b
+                .parse::<usize>()
+                .map_err(|_| format!("'{b}' is not a valid token budget"))?,
+            None => 0,
+        };
+        if strategy == ArchiveSplitStrategy::TokenBudget && max_tokens_per_part == 0 {
+            return Err("Splitting by tokens requires a token budget above 0".to_string());
+        }
+        Ok(Some(ArchiveSplitOptions {
+            strategy,
+            max_tokens_per_part,
+        }))
+    }
+
+    /* Formats the options in the syntax accepted by `parse_setting`. */
+    pub fn to_setting_text(&self) -> String {
+        match (self.strategy, self.max_tokens_per_part) {
+            (ArchiveSplitStrategy::TokenBudget, budget) => budget.to_string(),
+            (ArchiveSplitStrategy::TopLevelDirectory, 0) => "dirs".to_string(),
+            (ArchiveSplitStrategy::TopLevelDirectory, budget) => format!("dirs {budget}"),
+        }
+    }
+}
+
+/*
+ * The measurements the planner needs for one selected file, in archive order.
+ * `cost` is the number of tokens the file's entry adds to a part (content plus framing).
+ * `line_costs` is only filled in for files whose cost alone exceeds the budget left for
+ * entries; it holds the token count of every line so the file can be divided on line
+ * boundaries.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SplitCandidate {
+    pub relative_path: String,
+    pub cost: usize,
+    pub line_costs: Option<Vec<usize>>,
+}
+
+/*
+ * One entry of a planned part: a whole file, or a range of its lines (0-based, end exclusive).
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartItem {
+    pub file_index: usize,
+    pub lines: Option<Range<usize>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct PlannedPart {
+    pub items: Vec<PartItem>,
+    pub cost: usize,
+}
+
+/*
+ * Returns the name used in part headers for an item, e.g. `src/big.rs (lines 1-400)`.
+ */
+pub fn part_item_label(candidates: &[SplitCandidate], item: &PartItem) -> String {
+    let path = &candidates[item.file_index].relative_path;
+    match &item.lines {
+        Some(lines) => format!("{path} (lines {}-{})", lines.start + 1, lines.end),
+        None => path.clone(),
+    }
+}
+
+fn top_level_group(relative_path: &str) -> &str {
+    let mut components = relative_path.split(['/', '\\']);
+    let first = components.next().unwrap_or_default();
+    if components.next().is_some() {
+        first
+    } else {
+        ""
+    }
+}
+
+/*
+ * Divides the candidates into parts whose cost stays within `budget` (0 = unlimited).
+ * Files keep their archive order within a part. With `TopLevelDirectory`, files are first
+ * grouped by their top-level directory (groups ordered by first appearance) and every
+ * group starts a new part.
+ */
+pub fn plan_parts(
+    candidates: &[SplitCandidate],
+    strategy: ArchiveSplitStrategy,
+    budget: usize,
+) -> Vec<PlannedPart> {
+    let groups: Vec<Vec<usize>> = match strategy {
+        ArchiveSplitStrategy::TokenBudget => vec![(0..candidates.len()).collect()],
+        ArchiveSplitStrategy::TopLevelDirectory => {
+            let mut groups: Vec<(&str, Vec<usize>)> = Vec::new();
+            for (index, candidate) in candidates.iter().enumerate() {
+                let key = top_level_group(&candidate.relative_path);
+                match groups.iter_mut().find(|(k, _)| *k == key) {
+                    Some((_, members)) => members.push(index),
+                    None => groups.push((key, vec![index])),
+                }
+            }
+            groups.into_iter().map(|(_, members)| members).collect()
+        }
+    };
+
+    let mut parts = Vec::new();
+    for group in groups {
+        pack_group(candidates, &group, budget, &mut parts);
+    }
+    parts
+}
+
+fn pack_group(
+    candidates: &[SplitCandidate],
+    group: &[usize],
+    budget: usize,
+    parts: &mut Vec<PlannedPart>,
+) {
+    let fits = |used: usize, cost: usize| budget == 0 || used + cost <= budget;
+    let mut current = PlannedPart::default();
+
+    for &file_index in group {
+        let candidate = &candidates[file_index];
+        if fits(current.cost, candidate.cost) {
+            current.cost += candidate.cost;
+            current.items.push(PartItem {
+                file_index,
+                lines: None,
+            });
+            continue;
+        }
+        if !current.items.is_empty() {
+            parts.push(std::mem::take(&mut current));
+        }
+        match &candidate.line_costs {
+            Some(line_costs) if candidate.cost > budget => {
+                /* The framing cost (header line etc.) is repeated for every slice. */
+                let content_cost: usize = line_costs.iter().sum();
+                let framing = candidate.cost.saturating_sub(content_cost);
+                let mut start = 0;
+                let mut slice_cost = framing;
+                for (line, line_cost) in line_costs.iter().enumerate() {
+                    if line > start && slice_cost + line_cost > budget {
+                        parts.push(PlannedPart {
+                            items: vec![PartItem {
+                                file_index,
+                                lines: Some(start..line),
+                            }],
+                            cost: slice_cost,
+                        });
+                        start = line;
+                        slice_cost = framing;
+                    }
+                    slice_cost += line_cost;
+                }
+                /* The last slice stays open so that following files can share its part. */
+                current = PlannedPart {
+                    items: vec![PartItem {
+                        file_index,
+                        lines: Some(start..line_costs.len()),
+                    }],
+                    cost: slice_cost,
+                };
+            }
+            _ => {
+                current.cost = candidate.cost;
+                current.items.push(PartItem {
+                    file_index,
+                    lines: None,
+                });
+            }
+        }
+    }
+    if !current.items.is_empty() {
+        parts.push(current);
+    }
+}
+
+/*
+ * Returns the path of part `number` (1-based): `archive.txt` becomes `archive.part1.txt`.
+ */
+pub fn part_file_path(archive_path: &Path, number: usize) -> PathBuf {
+    let stem = archive_path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let file_name = match archive_path.extension() {
+        Some(ext) => format!("{stem}.part{number}.{}", ext.to_string_lossy()),
+        None => format!("{stem}.part{number}"),
+    };
+    archive_path.with_file_name(file_name)
+}
+
+/*
+ * Lists the part files of `archive_path` that exist on disk, stopping at the first gap.
+ */
+pub fn existing_part_files(archive_path: &Path) -> Vec<PathBuf> {
+    (1..)
+        .map(|number| part_file_path(archive_path, number))
+        .take_while(|path| path.is_file())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(path: &str, cost: usize) -> SplitCandidate {
+        SplitCandidate {
+            relative_path: path.to_string(),
+            cost,
+            line_costs: None,
+        }
+    }
+
+    fn whole(file_index: usize) -> PartItem {
+        PartItem {
+            file_index,
+            lines: None,
+        }
+    }
+
+    fn item_indices(parts: &[PlannedPart]) -> Vec<Vec<usize>> {
+        parts
+            .iter()
+            .map(|p| p.items.iter().map(|i| i.file_index).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_plan_parts_by_token_budget_keeps_files_whole() {
+        let candidates = vec![
+            candidate("a.rs", 40),
+            candidate("b.rs", 50),
+            candidate("c.rs", 30),
+            candidate("d.rs", 100),
+        ];
+
+        let parts = plan_parts(&candidates, ArchiveSplitStrategy::TokenBudget, 100);
+
+        assert_eq!(item_indices(&parts), vec![vec![0, 1], vec![2], vec![3]]);
+        assert!(parts.iter().all(|p| p.cost <= 100));
+        assert_eq!(parts[0].items, vec![whole(0), whole(1)]);
+    }
+
+    #[test]
+    fn test_plan_parts_divides_oversized_file_on_line_boundaries() {
+        let candidates = vec![
+            candidate("small.rs", 10),
+            SplitCandidate {
+                relative_path: "big.rs".to_string(),
+                cost: 5 + 6 * 20,
+                line_costs: Some(vec![20; 6]),
+            },
+            candidate("tail.rs", 10),
+        ];
+
+        let parts = plan_parts(&candidates, ArchiveSplitStrategy::TokenBudget, 50);
+
+        let slices: Vec<Option<Range<usize>>> = parts
+            .iter()
+            .flat_map(|p| p.items.iter())
+            .filter(|i| i.file_index == 1)
+            .map(|i| i.lines.clone())
+            .collect();
+        assert_eq!(slices, vec![Some(0..2), Some(2..4), Some(4..6)]);
+        // The tail file does not fit next to the last slice (45 + 10 > 50).
+        assert_eq!(
+            item_indices(&parts),
+            vec![vec![0], vec![1], vec![1], vec![1], vec![2]]
+        );
+        assert!(parts.iter().all(|p| p.cost <= 50));
+        assert_eq!(
+            part_item_label(&candidates, &parts[2].items[0]),
+            "big.rs (lines 3-4)"
+        );
+    }
+
+    #[test]
+    fn test_plan_parts_by_top_level_directory() {
+        let candidates = vec![
+            candidate("README.md", 5),
+            candidate("src/main.rs", 10),
+            candidate("docs/guide.md", 10),
+            candidate("src/lib.rs", 10),
+            candidate("Cargo.toml", 5),
+        ];
+
+        let unlimited = plan_parts(&candidates, ArchiveSplitStrategy::TopLevelDirectory, 0);
+        assert_eq!(
+            item_indices(&unlimited),
+            vec![vec![0, 4], vec![1, 3], vec![2]]
+        );
+
+        let bounded = plan_parts(&candidates, ArchiveSplitStrategy::TopLevelDirectory, 15);
+        assert_eq!(
+            item_indices(&bounded),
+            vec![vec![0, 4], vec![1], vec![3], vec![2]]
+        );
+    }
+
+    #[test]
+    fn test_parse_split_setting() {
+        assert_eq!(ArchiveSplitOptions::parse_setting("off"), Ok(None));
+        assert_eq!(
+            ArchiveSplitOptions::parse_setting(" 8000 "),
+            Ok(Some(ArchiveSplitOptions {
+                strategy: ArchiveSplitStrategy::TokenBudget,
+                max_tokens_per_part: 8000
+            }))
+        );
+        let dirs = ArchiveSplitOptions::parse_setting("dirs 500")
+            .unwrap()
+            .unwrap();
+        assert_eq!(dirs.strategy, ArchiveSplitStrategy::TopLevelDirectory);
+        assert_eq!(dirs.to_setting_text(), "dirs 500");
+        assert!(ArchiveSplitOptions::parse_setting("tokens").is_err());
+        assert!(ArchiveSplitOptions::parse_setting("lines 20").is_err());
+    }
+
+    #[test]
+    fn test_part_file_path_inserts_part_number() {
+        assert_eq!(
+            part_file_path(Path::new("/out/archive.txt"), 2),
+            PathBuf::from("/out/archive.part2.txt")
+        );
+        assert_eq!(
+            part_file_path(Path::new("/out/archive"), 1),
+            PathBuf::from("/out/archive.part1")
+        );
+    }
+}
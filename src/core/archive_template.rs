@@ -0,0 +1,367 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/*
+ * This module implements the user-defined text templates used by the plain text archive
+ * layout: the global preamble and the per-file header and footer.
+ * [ArchiveGenFileHeaderFooterV1] [FutureArchiveHeaderFormatConfigurableV1]
+ * Templates are plain strings containing `{placeholder}` tokens; a literal brace is
+ * written as `{{` or `}}`. Templates are parsed once, validated against the placeholders
+ * allowed for their position, and then rendered per file with a value lookup.
+ */
+
+/*
+ * The values a template may reference. File-specific placeholders are only allowed in the
+ * per-file header and footer; the preamble is rendered once and only knows the profile and root.
+ * Prompt templates (see `archive_prompt`) describe the archive as a whole.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TemplatePlaceholder {
+    RelativePath,
+    FileName,
+    Extension,
+    TokenCount,
+    LineCount,
+    Sha256,
+    Profile,
+    Root,
+    Project,
+    FileCount,
+    Date,
+    FileList,
+}
+
+impl TemplatePlaceholder {
+    pub const FILE_SCOPE: [TemplatePlaceholder; 8] = [
+        TemplatePlaceholder::RelativePath,
+        TemplatePlaceholder::FileName,
+        TemplatePlaceholder::Extension,
+        TemplatePlaceholder::TokenCount,
+        TemplatePlaceholder::LineCount,
+        TemplatePlaceholder::Sha256,
+        TemplatePlaceholder::Profile,
+        TemplatePlaceholder::Root,
+    ];
+    pub const ARCHIVE_SCOPE: [TemplatePlaceholder; 2] =
+        [TemplatePlaceholder::Profile, TemplatePlaceholder::Root];
+    pub const PROMPT_SCOPE: [TemplatePlaceholder; 6] = [
+        TemplatePlaceholder::Project,
+        TemplatePlaceholder::Profile,
+        TemplatePlaceholder::FileCount,
+        TemplatePlaceholder::TokenCount,
+        TemplatePlaceholder::Date,
+        TemplatePlaceholder::FileList,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TemplatePlaceholder::RelativePath => "relative_path",
+            TemplatePlaceholder::FileName => "file_name",
+            TemplatePlaceholder::Extension => "extension",
+            TemplatePlaceholder::TokenCount => "token_count",
+            TemplatePlaceholder::LineCount => "line_count",
+            TemplatePlaceholder::Sha256 => "sha256",
+            TemplatePlaceholder::Profile => "profile",
+            TemplatePlaceholder::Root => "root",
+            TemplatePlaceholder::Project => "project",
+            TemplatePlaceholder::FileCount => "file_count",
+            TemplatePlaceholder::Date => "date",
+            TemplatePlaceholder::FileList => "file_list",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<TemplatePlaceholder> {
+        Self::FILE_SCOPE
+            .into_iter()
+            .chain(Self::PROMPT_SCOPE)
+            .find(|p| p.name() == name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TemplateError {
+    UnknownPlaceholder {
+        template: &'static str,
+        name: String,
+    },
+    PlaceholderNotAllowed {
+        template: &'static str,
+        name: String,
+    },
+    UnterminatedPlaceholder {
+        template: &'static str,
+    },
+    UnmatchedClosingBrace {
+        template: &'static str,
+    },
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TemplateError::UnknownPlaceholder { template, name } => {
+                write!(f, "Unknown placeholder '{{{name}}}' in {template} template")
+            }
+            TemplateError::PlaceholderNotAllowed { template, name } => write!(
+                f,
+                "Placeholder '{{{name}}}' cannot be used in the {template} template"
+            ),
+            TemplateError::UnterminatedPlaceholder { template } => {
+                write!(f, "Unterminated '{{' in {template} template")
+            }
+            TemplateError::UnmatchedClosingBrace { template } => write!(
+                f,
+                "Unmatched '}}' in {template} template (use '}}}}' for a literal brace)"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TemplateSegment {
+    Literal(String),
+    Placeholder(TemplatePlaceholder),
+}
+
+/*
+ * A validated template, ready to be rendered.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsedTemplate {
+    segments: Vec<TemplateSegment>,
+}
+
+impl ParsedTemplate {
+    /*
+     * Parses `text`, accepting only placeholders listed in `allowed`.
+     * `template` names the template in error messages (e.g. "file header").
+     */
+    pub fn parse(
+        text: &str,
+        allowed: &[TemplatePlaceholder],
+        template: &'static str,
+    ) -> Result<Self, TemplateError> {
+        let mut segments = Vec::new();
+        let mut literal = String::new();
+        let mut chars = text.chars().peekable();
+
+        while let Some(ch) = chars.next() {
+            match ch {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '}' => return Err(TemplateError::UnmatchedClosingBrace { template }),
+                '{' => {
+                    let mut name = String::new();
+                    let mut terminated = false;
+                    for inner in chars.by_ref() {
+                        if inner == '}' {
+                            terminated = true;
+                            break;
+                        }
+                        name.push(inner);
+                    }
+                    if !terminated {
+                        return Err(TemplateError::UnterminatedPlaceholder { template });
+                    }
+                    let placeholder =
+                        TemplatePlaceholder::from_name(name.trim()).ok_or_else(|| {
+                            TemplateError::UnknownPlaceholder {
+                                template,
+                                name: name.clone(),
+                            }
+                        })?;
+                    if !allowed.contains(&placeholder) {
+                        return Err(TemplateError::PlaceholderNotAllowed { template, name });
+                    }
+                    if !literal.is_empty() {
+                        segments.push(TemplateSegment::Literal(std::mem::take(&mut literal)));
+                    }
+                    segments.push(TemplateSegment::Placeholder(placeholder));
+                }
+                other => literal.push(other),
+            }
+        }
+        if !literal.is_empty() {
+            segments.push(TemplateSegment::Literal(literal));
+        }
+        Ok(ParsedTemplate { segments })
+    }
+
+    pub fn uses(&self, placeholder: TemplatePlaceholder) -> bool {
+        self.segments
+            .contains(&TemplateSegment::Placeholder(placeholder))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    /*
+     * Renders the template, asking `lookup` for the value of each placeholder occurrence.
+     */
+    pub fn render(&self, mut lookup: impl FnMut(TemplatePlaceholder) -> String) -> String {
+        let mut rendered = String::new();
+        for segment in &self.segments {
+            match segment {
+                TemplateSegment::Literal(text) => rendered.push_str(text),
+                TemplateSegment::Placeholder(p) => rendered.push_str(&lookup(*p)),
+            }
+        }
+        rendered
+    }
+}
+
+pub const DEFAULT_PREAMBLE_TEMPLATE: &str = "// Combined files from {root}";
+pub const DEFAULT_FILE_HEADER_TEMPLATE: &str = "// ===== File: {relative_path} =====";
+
+/*
+ * The templates stored on a profile. `None` keeps the built-in layout; an empty string
+ * suppresses that line entirely. Templates only affect the plain text format, since the
+ * structured formats define their own framing.
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct ArchiveTemplates {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preamble: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_header: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_footer: Option<String>,
+}
+
+/*
+ * The parsed form of `ArchiveTemplates`, with defaults filled in.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompiledTemplates {
+    pub preamble: ParsedTemplate,
+    pub file_header: ParsedTemplate,
+    pub file_footer: ParsedTemplate,
+}
+
+impl ArchiveTemplates {
+    pub fn compile(&self) -> Result<CompiledTemplates, TemplateError> {
+        Ok(CompiledTemplates {
+            preamble: ParsedTemplate::parse(
+                self.preamble
+                    .as_deref()
+                    .unwrap_or(DEFAULT_PREAMBLE_TEMPLATE),
+                &TemplatePlaceholder::ARCHIVE_SCOPE,
+                "preamble",
+            )?,
+            file_header: ParsedTemplate::parse(
+                self.file_header
+                    .as_deref()
+                    .unwrap_or(DEFAULT_FILE_HEADER_TEMPLATE),
+                &TemplatePlaceholder::FILE_SCOPE,
+                "file header",
+            )?,
+            file_footer: ParsedTemplate::parse(
+                self.file_footer.as_deref().unwrap_or(""),
+                &TemplatePlaceholder::FILE_SCOPE,
+                "file footer",
+            )?,
+        })
+    }
+
+    pub fn validate(&self) -> Result<(), TemplateError> {
+        self.compile().map(|_| ())
+    }
+}
+
+impl Default for CompiledTemplates {
+    fn default() -> Self {
+        ArchiveTemplates::default()
+            .compile()
+            .expect("Built-in archive templates must be valid")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render_with(template: &ParsedTemplate) -> String {
+        template.render(|p| match p {
+            TemplatePlaceholder::RelativePath => "src/lib.rs".to_string(),
+            TemplatePlaceholder::TokenCount => "42".to_string(),
+            other => format!("<{}>", other.name()),
+        })
+    }
+
+    #[test]
+    fn test_parse_and_render_placeholders_and_escaped_braces() {
+        let template = ParsedTemplate::parse(
+            "{{ {relative_path} }} has {token_count} tokens",
+            &TemplatePlaceholder::FILE_SCOPE,
+            "file header",
+        )
+        .unwrap();
+        assert_eq!(render_with(&template), "{ src/lib.rs } has 42 tokens");
+        assert!(template.uses(TemplatePlaceholder::TokenCount));
+        assert!(!template.uses(TemplatePlaceholder::Sha256));
+    }
+
+    #[test]
+    fn test_parse_rejects_unknown_placeholder() {
+        let result = ParsedTemplate::parse(
+            "// {relative_path} {author}",
+            &TemplatePlaceholder::FILE_SCOPE,
+            "file header",
+        );
+        assert_eq!(
+            result,
+            Err(TemplateError::UnknownPlaceholder {
+                template: "file header",
+                name: "author".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_file_placeholder_in_preamble() {
+        let templates = ArchiveTemplates {
+            preamble: Some("Files for {relative_path}".to_string()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            templates.validate(),
+            Err(TemplateError::PlaceholderNotAllowed { .. })
+        ));
+    }
+
+    #[test]
+    fn test_parse_rejects_unbalanced_braces() {
+        let allowed = TemplatePlaceholder::FILE_SCOPE;
+        assert_eq!(
+            ParsedTemplate::parse("{relative_path", &allowed, "file footer"),
+            Err(TemplateError::UnterminatedPlaceholder {
+                template: "file footer"
+            })
+        );
+        assert_eq!(
+            ParsedTemplate::parse("end }", &allowed, "file footer"),
+            Err(TemplateError::UnmatchedClosingBrace {
+                template: "file footer"
+            })
+        );
+    }
+
+    #[test]
+    fn test_default_templates_reproduce_builtin_layout() {
+        let compiled = CompiledTemplates::default();
+        assert_eq!(
+            render_with(&compiled.file_header),
+            "// ===== File: src/lib.rs ====="
+        );
+        assert!(compiled.file_footer.is_empty());
+    }
+}
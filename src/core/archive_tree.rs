@@ -0,0 +1,269 @@
+use super::file_node::{FileNode, SelectionState};
+use serde::{Deserialize, Serialize};
+
+/*
+ * This module renders the optional directory tree written at the top of an archive.
+ * [ArchiveGenDirectoryTreeV1] The tree is drawn from the scanned snapshot, so it already
+ * respects the profile's exclude patterns and never shows `.sourcepacker`. It tells the
+ * model what else exists in the project, with a marker on every file showing whether the
+ * file is in the archive. Directories below the depth limit are collapsed into a count.
+ */
+
+pub const INCLUDED_MARKER: &str = "[x]";
+pub const OUTLINE_MARKER: &str = "[o]";
+pub const EXCLUDED_MARKER: &str = "[ ]";
+
+/*
+ * Settings for the directory tree. `include_unselected` also lists files that are not in
+ * the archive; otherwise only selected files and the directories leading to them are shown.
+ * `max_depth` is the number of directory levels that are expanded; `None` expands all.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct DirectoryTreeOptions {
+    #[serde(default)]
+    pub include_unselected: bool,
+    #[serde(default)]
+    pub max_depth: Option<usize>,
+}
+
+impl DirectoryTreeOptions {
+    /*
+     * Parses the text entered in the "Directory Tree..." dialog: `off`, `selected` or
+     * `all`, optionally followed by `:<depth>` (e.g. `all:3`). `off` yields `None`.
+     */
+    pub fn parse_setting(text: &str) -> Result<Option<Self>, String> {
+        let text = text.trim();
+        if text.is_empty() || text.eq_ignore_ascii_case("off") {
+            return Ok(None);
+        }
+        let (mode, depth) = match text.split_once(':') {
+            Some((mode, depth)) => (mode.trim(), Some(depth.trim())),
+            None => (text, None),
+        };
+        let include_unselected = match mode.to_ascii_lowercase().as_str() {
+            "selected" => false,
+            "all" => true,
+            other => {
+                return Err(format!(
+                    "Unknown tree mode '{other}'. Use 'off', 'selected' or 'all'."
+                ));
+            }
+        };
+        let max_depth = match depth {
+            None => None,
+            Some(depth) => match depth.parse::<usize>() {
+                Ok(value) if value > 0 => Some(value),
+                _ => {
+                    return Err(format!(
+                        "Invalid tree depth '{depth}'. Use a whole number of at least 1."
+                    ));
+                }
+            },
+        };
+        Ok(Some(DirectoryTreeOptions {
+            include_unselected,
+            max_depth,
+        }))
+    }
+
+    /* The inverse of `parse_setting`, used to prefill the dialog. */
+    pub fn to_setting_text(options: Option<&Self>) -> String {
+        match options {
+            None => "off".to_string(),
+            Some(options) => {
+                let mode = if options.include_unselected {
+                    "all"
+                } else {
+                    "selected"
+                };
+                match options.max_depth {
+                    Some(depth) => format!("{mode}:{depth}"),
+                    None => mode.to_string(),
+                }
+            }
+        }
+    }
+}
+
+/* The rendered tree, one line per entry, starting with a legend and the root. */
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DirectoryTree {
+    pub lines: Vec<String>,
+}
+
+impl DirectoryTree {
+    pub fn render(nodes: &[FileNode], options: &DirectoryTreeOptions) -> Self {
+        let mut lines = vec![
+            format!(
+                "Legend: {INCLUDED_MARKER} included, {OUTLINE_MARKER} outline, {EXCLUDED_MARKER} not included"
+            ),
+            ".".to_string(),
+        ];
+        render_children(nodes, "", 1, options, &mut lines);
+        DirectoryTree { lines }
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = self.lines.join("\n");
+        text.push('\n');
+        text
+    }
+}
+
+fn file_marker(node: &FileNode) -> &'static str {
+    match node.state() {
+        SelectionState::Selected => INCLUDED_MARKER,
+        SelectionState::Outline => OUTLINE_MARKER,
+        SelectionState::Deselected | SelectionState::New => EXCLUDED_MARKER,
+    }
+}
+
+/* Counts the files below `node` as (total, included). */
+fn count_files(node: &FileNode) -> (usize, usize) {
+    if !node.is_dir() {
+        return (1, usize::from(node.is_selected()));
+    }
+    node.children
+        .iter()
+        .map(count_files)
+        .fold((0, 0), |(total, included), (t, i)| {
+            (total + t, included + i)
+        })
+}
+
+fn is_visible(node: &FileNode, options: &DirectoryTreeOptions) -> bool {
+    options.include_unselected || count_files(node).1 > 0
+}
+
+fn render_children(
+    nodes: &[FileNode],
+    prefix: &str,
+    depth: usize,
+    options: &DirectoryTreeOptions,
+    lines: &mut Vec<String>,
+) {
+    let visible: Vec<&FileNode> = nodes.iter().filter(|n| is_visible(n, options)).collect();
+    for (index, node) in visible.iter().enumerate() {
+        let is_last = index + 1 == visible.len();
+        let connector = if is_last { "└── " } else { "├── " };
+        if !node.is_dir() {
+            lines.push(format!(
+                "{prefix}{connector}{} {}",
+                file_marker(node),
+                node.name()
+            ));
+            continue;
+        }
+        if options.max_depth.is_some_and(|max| depth >= max) && !node.children.is_empty() {
+            let (total, included) = count_files(node);
+            lines.push(format!(
+                "{prefix}{connector}{}/ ({included} of {total} files included)",
+                node.name()
+            ));
+            continue;
+        }
+        lines.push(format!("{prefix}{connector}{}/", node.name()));
+        let child_prefix = format!("{prefix}{}", if is_last { "    " } else { "│   " });
+        render_children(&node.children, &child_prefix, depth + 1, options, lines);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn file(path: &str, state: SelectionState) -> FileNode {
+        let path = PathBuf::from(path);
+        let name = path.file_name().unwrap().to_string_lossy().into_owned();
+        FileNode::new_full(path, name, false, state, Vec::new(), String::new())
+    }
+
+    fn dir(path: &str, children: Vec<FileNode>) -> FileNode {
+        let path = PathBuf::from(path);
+        let name = path.file_name().unwrap().to_string_lossy().into_owned();
+        FileNode::new_full(
+            path,
+            name,
+            true,
+            SelectionState::New,
+            children,
+            String::new(),
+        )
+    }
+
+    fn sample_nodes() -> Vec<FileNode> {
+        vec![
+            dir(
+                "/p/src",
+                vec![
+                    dir(
+                        "/p/src/core",
+                        vec![file("/p/src/core/a.rs", SelectionState::Selected)],
+                    ),
+                    file("/p/src/main.rs", SelectionState::Outline),
+                    file("/p/src/notes.txt", SelectionState::Deselected),
+                ],
+            ),
+            dir(
+                "/p/docs",
+                vec![file("/p/docs/guide.md", SelectionState::New)],
+            ),
+            file("/p/Cargo.toml", SelectionState::Selected),
+        ]
+    }
+
+    #[test]
+    fn test_render_marks_included_files_and_optionally_hides_unselected() {
+        let all = DirectoryTreeOptions {
+            include_unselected: true,
+            max_depth: None,
+        };
+        assert_eq!(
+            DirectoryTree::render(&sample_nodes(), &all).lines[1..],
+            [
+                ".",
+                "├── src/",
+                "│   ├── core/",
+                "│   │   └── [x] a.rs",
+                "│   ├── [o] main.rs",
+                "│   └── [ ] notes.txt",
+                "├── docs/",
+                "│   └── [ ] guide.md",
+                "└── [x] Cargo.toml",
+            ]
+        );
+
+        let selected_only = DirectoryTreeOptions {
+            include_unselected: false,
+            max_depth: Some(1),
+        };
+        assert_eq!(
+            DirectoryTree::render(&sample_nodes(), &selected_only).lines[1..],
+            [
+                ".",
+                "├── src/ (2 of 3 files included)",
+                "└── [x] Cargo.toml",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_setting_round_trips() {
+        assert_eq!(DirectoryTreeOptions::parse_setting("off"), Ok(None));
+        let parsed = DirectoryTreeOptions::parse_setting(" all : 3 ").unwrap();
+        assert_eq!(
+            parsed,
+            Some(DirectoryTreeOptions {
+                include_unselected: true,
+                max_depth: Some(3),
+            })
+        );
+        assert_eq!(
+            DirectoryTreeOptions::to_setting_text(parsed.as_ref()),
+            "all:3"
+        );
+        assert!(DirectoryTreeOptions::parse_setting("selected:0").is_err());
+        assert!(DirectoryTreeOptions::parse_setting("everything").is_err());
+    }
+}
@@ -1,17 +1,394 @@
-use super::file_node::{ArchiveStatus, FileNode};
+use super::archive_format::{
+    ArchiveDeltaHeader, ArchiveEntry, ArchiveFormatter, ArchivePartHeader, ArchivePartListing,
+    ArchivePreamble, FormatterSettings,
+};
+use super::archive_history::ArchiveHistory;
+use super::archive_manifest::{self, ArchiveManifest, ManifestEntry};
+use super::archive_prompt::{PromptContext, RenderedPrompt};
+use super::archive_record::{ArchiveRecord, FileStamp};
+use super::archive_split::{self, ArchiveSplitOptions, PartItem, PlannedPart, SplitCandidate};
+use super::archive_template::TemplateError;
+use super::archive_tree::DirectoryTree;
+use super::atomic_write::{self, AtomicFileSet};
+use super::checksum_utils;
+use super::content_class::{
+    self, ContentClass, ContentPolicy, ContentReader, FileContent, SkippedFile,
+};
+use super::content_transform::{TransformError, TransformPipeline, TransformTokenReport};
+use super::entry_spool::{EntrySpool, SpooledText};
+use super::file_node::{ArchiveStatus, FileNode, FileTokenDetails, SelectionState};
+use super::line_numbers::{self, LineNumberError, LineNumbering, LineRange};
+use super::outline::{OutlineExtractor, OutlineExtractors};
+use super::profile_settings::ArchiveOptions;
+use super::secret_scan::{
+    SecretFinding, SecretPolicy, SecretScanError, SecretScanReport, SecretScanner,
+};
+use super::text_encoding::{EncodingError, EncodingRules};
+use super::token_budget::{self, BudgetAction, BudgetCandidate, TokenBudgetReport};
+use super::tokenizer_utils::TokenCounterOperations;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
 use std::fs;
-use std::io;
-use std::path::Path;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::time::SystemTime;
 
 /*
  * This module handles operations related to creating and managing the state of archives.
- * It includes functions to generate archive content from selected files,
- * retrieve file timestamps, check the synchronization status of an archive,
- * and save archive content to disk. It defines a trait `ArchiverOperations`
- * for abstracting these operations and a concrete implementation `CoreArchiver`.
+ * It defines a trait `ArchiverOperations` for generating archives from the selected files,
+ * checking their synchronization status and saving them, and a concrete implementation
+ * `CoreArchiver`. Each selected file is read and prepared once per generation, and every
+ * later step works from that prepared entry. Archives are streamed file by file into a
+ * `Write` sink, and archive files on disk are replaced atomically, so memory stays flat
+ * and readers never observe a partial archive.
  */
 
+/*
+ * Describes one archive generation: the node tree to traverse, the root used to
+ * relativize paths, the name of the profile being archived, and its options.
+ * `token_cache` holds the session's cached per-file token counts; entries whose checksum
+ * still matches the node are used for budget enforcement instead of re-counting.
+ * When `record_path` is set, `write_archive_to_file` stores the content record of the
+ * selection there for `check_status`, and with `history_dir` the written archive is added
+ * to the profile's archive history. `delta` is set for a delta archive and is written
+ * with the preamble.
+ */
+#[derive(Debug, Clone)]
+pub struct ArchiveRequest<'a> {
+    pub nodes: &'a [FileNode],
+    pub root_path_for_display: &'a Path,
+    pub profile_name: String,
+    pub options: ArchiveOptions,
+    pub token_cache: HashMap<PathBuf, FileTokenDetails>,
+    pub record_path: Option<PathBuf>,
+    pub history_dir: Option<PathBuf>,
+    pub delta: Option<ArchiveDeltaHeader>,
+}
+
+impl<'a> ArchiveRequest<'a> {
+    pub fn new(nodes: &'a [FileNode], root_path_for_display: &'a Path) -> Self {
+        ArchiveRequest {
+            nodes,
+            root_path_for_display,
+            profile_name: String::new(),
+            options: ArchiveOptions::default(),
+            token_cache: HashMap::new(),
+            record_path: None,
+            history_dir: None,
+            delta: None,
+        }
+    }
+}
+
+/*
+ * Summary of a completed archive generation. `part_paths` lists the files written for a
+ * split archive and is empty when the archive was written as a single file. `budget` is
+ * set when a token budget was enforced and lists every file that was dropped or truncated.
+ * `transform` is set when content transforms ran, with the selection's token count
+ * before and after them. `delta` lists the changes written to a delta archive. `secrets`
+ * is set unless secret scanning is off, with the findings and the policy applied to them.
+ * `skipped` lists the binary and oversize files that were left out or replaced by a
+ * placeholder. `history_generation` is the id under which the archive was added to the
+ * profile's history. `checksums` holds the SHA-256 of every selected file as it was read
+ * for the archive, including files left out by the budget or the content policy, and
+ * `stamps` the size and modification time of those files as far as they can be trusted
+ * (see `FileStamp::before_read`).
+ */
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ArchiveReport {
+    pub files_written: usize,
+    pub bytes_written: u64,
+    pub part_paths: Vec<PathBuf>,
+    pub budget: Option<TokenBudgetReport>,
+    pub transform: Option<TransformTokenReport>,
+    pub delta: Option<ArchiveDeltaHeader>,
+    pub secrets: Option<SecretScanReport>,
+    pub skipped: Vec<SkippedFile>,
+    pub history_generation: Option<u64>,
+    pub checksums: BTreeMap<PathBuf, String>,
+    pub stamps: BTreeMap<PathBuf, FileStamp>,
+}
+
+/*
+ * A selected file prepared for the archive. Only its metadata is kept in memory; the
+ * content, exactly as it will be written, lies in the selection's spool. `findings` are
+ * the possible secrets found in it before redaction, and `kept_tokens` is set when the
+ * token budget truncates it.
+ */
+struct PreparedFile<'a> {
+    node: &'a FileNode,
+    relative_path: String,
+    content: SpooledText,
+    lines: Option<LineRange>,
+    findings: Vec<SecretFinding>,
+    kept_tokens: Option<usize>,
+}
+
+/*
+ * The prepared files that go into an archive after the token budget has been applied,
+ * with the reports of the budget, the transforms and the secret scan (`None` when
+ * scanning is off).
+ */
+struct ArchiveSelection<'a> {
+    files: Vec<PreparedFile<'a>>,
+    spool: EntrySpool,
+    budget: Option<TokenBudgetReport>,
+    transform_report: Option<TransformTokenReport>,
+    secrets: Option<SecretScanReport>,
+    skipped: Vec<SkippedFile>,
+    /* The SHA-256 and stamp of every selected file as read, for the content record. */
+    checksums: BTreeMap<PathBuf, String>,
+    stamps: BTreeMap<PathBuf, FileStamp>,
+}
+
+/*
+ * The plan and shared values needed to write one part of a split archive.
+ */
+struct PartInput<'a> {
+    part: &'a PlannedPart,
+    header: &'a ArchivePartHeader,
+    generated_at: &'a str,
+    prompt: &'a RenderedPrompt,
+}
+
+/*
+ * Errors raised while generating an archive. Source read failures are kept apart from
+ * output failures so callers can tell "a selected file vanished" from "the archive
+ * could not be written".
+ */
+#[derive(Debug)]
+pub enum ArchiveError {
+    ReadSource {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Output(io::Error),
+    InvalidOptions(TemplateError),
+    InvalidTransform(TransformError),
+    /* A delta archive needs the content record of a previous full generation. */
+    NoDeltaBaseline(PathBuf),
+    InvalidSecretRule(SecretScanError),
+    InvalidEncodingRule(EncodingError),
+    InvalidLineNumbers(LineNumberError),
+    /* The secret policy is `Abort` and the selection contains possible secrets. */
+    SecretsFound(Vec<SecretFinding>),
+    /* A binary or oversize file whose policy is `Fail`. */
+    UnsupportedContent {
+        path: PathBuf,
+        class: ContentClass,
+        size: u64,
+    },
+}
+
+impl fmt::Display for ArchiveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArchiveError::ReadSource { path, source } => {
+                write!(f, "Failed to read file '{}': {}", path.display(), source)
+            }
+            ArchiveError::Output(e) => write!(f, "Failed to write archive: {e}"),
+            ArchiveError::InvalidOptions(e) => write!(f, "Invalid archive settings: {e}"),
+            ArchiveError::InvalidTransform(e) => write!(f, "Invalid archive settings: {e}"),
+            ArchiveError::NoDeltaBaseline(path) => write!(
+                f,
+                "No record of a previous generation of '{}' exists",
+                path.display()
+            ),
+            ArchiveError::InvalidSecretRule(e) => write!(f, "Invalid archive settings: {e}"),
+            ArchiveError::InvalidEncodingRule(e) => write!(f, "Invalid archive settings: {e}"),
+            ArchiveError::InvalidLineNumbers(e) => write!(f, "Invalid archive settings: {e}"),
+            ArchiveError::SecretsFound(findings) => write!(
+                f,
+                "{} possible secret(s) found: {}",
+                findings.len(),
+                findings
+                    .iter()
+                    .map(SecretFinding::to_string)
+                    .collect::<Vec<_>>()
+                    .join("; ")
+            ),
+            ArchiveError::UnsupportedContent { path, class, size } => write!(
+                f,
+                "Cannot archive '{}' as text ({class}, {})",
+                path.display(),
+                content_class::format_size(*size)
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ArchiveError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ArchiveError::ReadSource { source, .. } => Some(source),
+            ArchiveError::Output(e) => Some(e),
+            ArchiveError::InvalidOptions(e) => Some(e),
+            ArchiveError::InvalidTransform(e) => Some(e),
+            ArchiveError::InvalidSecretRule(e) => Some(e),
+            ArchiveError::InvalidEncodingRule(e) => Some(e),
+            ArchiveError::InvalidLineNumbers(e) => Some(e),
+            ArchiveError::NoDeltaBaseline(_)
+            | ArchiveError::SecretsFound(_)
+            | ArchiveError::UnsupportedContent { .. } => None,
+        }
+    }
+}
+
+impl From<io::Error> for ArchiveError {
+    fn from(err: io::Error) -> Self {
+        ArchiveError::Output(err)
+    }
+}
+
+impl From<ArchiveError> for io::Error {
+    fn from(err: ArchiveError) -> Self {
+        match err {
+            ArchiveError::ReadSource { ref source, .. } => {
+                io::Error::new(source.kind(), err.to_string())
+            }
+            ArchiveError::Output(e) => e,
+            ArchiveError::InvalidOptions(e) => io::Error::new(io::ErrorKind::InvalidInput, e),
+            ArchiveError::InvalidTransform(e) => io::Error::new(io::ErrorKind::InvalidInput, e),
+            ArchiveError::NoDeltaBaseline(_) => {
+                io::Error::new(io::ErrorKind::NotFound, err.to_string())
+            }
+            ArchiveError::InvalidSecretRule(e) => io::Error::new(io::ErrorKind::InvalidInput, e),
+            ArchiveError::InvalidEncodingRule(e) => io::Error::new(io::ErrorKind::InvalidInput, e),
+            ArchiveError::InvalidLineNumbers(e) => io::Error::new(io::ErrorKind::InvalidInput, e),
+            ArchiveError::SecretsFound(_) | ArchiveError::UnsupportedContent { .. } => {
+                io::Error::new(io::ErrorKind::InvalidData, err.to_string())
+            }
+        }
+    }
+}
+
+/*
+ * Wraps a sink and counts the bytes passed through it.
+ */
+struct CountingWriter<'a> {
+    inner: &'a mut dyn Write,
+    bytes_written: u64,
+}
+
+impl Write for CountingWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.bytes_written += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/* Tokens assumed for the per-file framing (header delimiters, fences) besides the path. */
+const ENTRY_FRAMING_TOKENS: usize = 4;
+/* Part headers depend on the plan and vice versa; a few rounds are enough to settle. */
+const MAX_SPLIT_PLANNING_ROUNDS: usize = 4;
+
+fn relative_display_path(node: &FileNode, root_path_for_display: &Path) -> String {
+    node.path()
+        .strip_prefix(root_path_for_display)
+        .unwrap_or(node.path())
+        .to_string_lossy()
+        .into_owned()
+}
+
+/*
+ * Returns the path of the delta archive written next to `archive_path`,
+ * e.g. `archive.txt` -> `archive.delta.txt`. [ArchiveGenDeltaV1]
+ */
+pub fn delta_file_path(archive_path: &Path) -> PathBuf {
+    let stem = archive_path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let file_name = match archive_path.extension() {
+        Some(ext) => format!("{stem}.delta.{}", ext.to_string_lossy()),
+        None => format!("{stem}.delta"),
+    };
+    archive_path.with_file_name(file_name)
+}
+
+/* Deselects every selected file that is not in `changed`, keeping outline files as outlines. */
+fn deselect_unchanged_files(nodes: &mut [FileNode], changed: &HashSet<&Path>) {
+    for node in nodes {
+        if node.is_dir() {
+            deselect_unchanged_files(&mut node.children, changed);
+        } else if node.is_selected() && !changed.contains(node.path()) {
+            node.set_state(SelectionState::Deselected);
+        }
+    }
+}
+
+/* Renders the project tree for the preamble when the profile asks for it. */
+fn directory_tree_for(request: &ArchiveRequest) -> Option<DirectoryTree> {
+    request
+        .options
+        .directory_tree
+        .map(|options| DirectoryTree::render(request.nodes, &options))
+}
+
+fn read_source(
+    node: &FileNode,
+    reader: &ContentReader,
+) -> Result<(FileContent, String), ArchiveError> {
+    reader
+        .read_with_checksum(node.path())
+        .map_err(|e| ArchiveError::ReadSource {
+            path: node.path().to_path_buf(),
+            source: e,
+        })
+}
+
+/*
+ * Builds the header of part `index` (0-based): its number and the files of every other part.
+ */
+fn part_header_for(
+    parts: &[PlannedPart],
+    candidates: &[SplitCandidate],
+    index: usize,
+) -> ArchivePartHeader {
+    ArchivePartHeader {
+        number: index + 1,
+        count: parts.len(),
+        other_parts: parts
+            .iter()
+            .enumerate()
+            .filter(|(other, _)| *other != index)
+            .map(|(other, part)| ArchivePartListing {
+                number: other + 1,
+                files: part
+                    .items
+                    .iter()
+                    .map(|item| archive_split::part_item_label(candidates, item))
+                    .collect(),
+            })
+            .collect(),
+    }
+}
+
+/*
+ * Returns the selected files of the tree in archive order (depth-first, in tree order).
+ * A path that occurs more than once, e.g. in a tree assembled from several composite
+ * profile components, is only returned the first time.
+ */
+pub fn collect_selected_files(nodes: &[FileNode]) -> Vec<&FileNode> {
+    let mut selected = Vec::new();
+    let mut seen = HashSet::new();
+    let mut buffer: Vec<&FileNode> = nodes.iter().rev().collect();
+    while let Some(node) = buffer.pop() {
+        if node.is_dir() {
+            buffer.extend(node.children.iter().rev());
+        } else if node.is_selected() && seen.insert(node.path()) {
+            selected.push(node);
+        }
+    }
+    selected
+}
+
 /*
  * Defines the operations for managing archives.
  * This trait abstracts the specific mechanisms for creating archive content,
@@ -20,32 +397,80 @@ use std::time::SystemTime;
  */
 pub trait ArchiverOperations: Send + Sync {
     /*
-     * Creates a concatenated string of content from selected files in the tree.
-     * Traverses the provided `nodes`. For each `FileNode` that is a file and
-     * has `FileState::Selected`, it reads its content and prepends/appends headers.
+     * Streams the archive into `sink`.
+     * Traverses the request's `nodes`. For each `FileNode` that is a file and
+     * has `FileState::Selected`, it reads its content and emits it in the layout
+     * given by `request.options.format`. Each source file is read once per generation,
+     * and only one file's content is held in memory at a time.
      * The `root_path_for_display` is used to relativize paths in headers.
+     * The profile's active prompt variant is written before and after the archive, and an
+     * optional directory tree of the project precedes the files.
+     */
+    fn write_archive(
+        &self,
+        request: &ArchiveRequest,
+        sink: &mut dyn Write,
+    ) -> Result<ArchiveReport, ArchiveError>;
+
+    /*
+     * Streams the archive into a temporary file next to `path` and renames it into place
+     * only once everything has been written. On failure the previous archive is left untouched.
+     * If `request.options.split` is set, the archive is written as numbered part files
+     * next to `path` instead (see `archive_split::part_file_path`).
+     * With `request.history_dir` set, the written archive is also kept in the profile's
+     * archive history. [ArchiveHistoryV1]
+     */
+    fn write_archive_to_file(
+        &self,
+        request: &ArchiveRequest,
+        path: &Path,
+    ) -> Result<ArchiveReport, ArchiveError>;
+
+    /*
+     * Writes a delta archive to `delta_path`: only the selected files that were added or
+     * modified since the archive at `archive_path` was generated, headed by the files
+     * deleted or deselected since. The comparison uses the content record at
+     * `request.record_path`, which is left unchanged, so successive deltas accumulate
+     * until the next full generation. The delta is never split and carries no directory
+     * tree. [ArchiveGenDeltaV1]
      */
-    fn create_content(
+    fn write_delta_archive_to_file(
         &self,
-        nodes: &[FileNode],
-        root_path_for_display: &Path,
-    ) -> io::Result<String>;
+        request: &ArchiveRequest,
+        archive_path: &Path,
+        delta_path: &Path,
+    ) -> Result<ArchiveReport, ArchiveError>;
+
+    /*
+     * Creates the archive as an in-memory string. Convenient for previews and tests;
+     * prefer `write_archive_to_file` for real output.
+     */
+    fn create_content(&self, request: &ArchiveRequest) -> io::Result<String> {
+        let mut archive_bytes = Vec::new();
+        self.write_archive(request, &mut archive_bytes)?;
+        String::from_utf8(archive_bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
 
     /*
      * Checks the synchronization status of an archive file.
-     * Compares the archive's timestamp (if `archive_path` is Some and exists)
-     * against the newest timestamp among the selected source files within `file_nodes_tree`.
+     * If a content record for the archive exists at `record_path`, the selected files in
+     * `file_nodes_tree` are compared with it by path and checksum; only files whose size
+     * or modification time changed since they were archived are re-hashed. Without a
+     * usable record, the archive's timestamp is compared against the newest timestamp
+     * among the selected source files.
+     * If only the part files of a split archive exist, the oldest part is used.
      * TODO: Does the path have to be an Option?
      */
     fn check_status(
         &self,
         archive_path: Option<&Path>,
         file_nodes_tree: &[FileNode],
+        record_path: Option<&Path>,
     ) -> ArchiveStatus;
 
     /*
      * Saves the provided archive `content` string to the specified `path`.
-     * Implementations should handle overwriting the file if it exists.
+     * Implementations should replace an existing file atomically.
      */
     fn save(&self, path: &Path, content: &str) -> io::Result<()>;
 
@@ -59,238 +484,1135 @@ pub trait ArchiverOperations: Send + Sync {
 /*
  * The core implementation of `ArchiverOperations`.
  * This struct handles the actual file system interactions for creating, checking,
- * and saving archives.
+ * and saving archives. The token counter is used for the `{token_count}` template placeholder.
+ * The outline extractors produce the content of files included as an outline.
  */
-pub struct CoreArchiver {}
+pub struct CoreArchiver {
+    token_counter: Arc<dyn TokenCounterOperations>,
+    outline_extractors: OutlineExtractors,
+}
 
 impl CoreArchiver {
     /*
      * Creates a new instance of `CoreArchiver`.
-     * This constructor doesn't require any parameters.
      * TODO: We should move the path to this structure.
      */
-    pub fn new() -> Self {
-        CoreArchiver {}
+    pub fn new(token_counter: Arc<dyn TokenCounterOperations>) -> Self {
+        CoreArchiver {
+            token_counter,
+            outline_extractors: OutlineExtractors::new(),
+        }
     }
-}
 
-impl Default for CoreArchiver {
-    fn default() -> Self {
-        Self::new()
+    /* Adds an outline extractor for another language. [FileSelOutlineV1] */
+    #[allow(dead_code)]
+    pub fn with_outline_extractor(mut self, extractor: Arc<dyn OutlineExtractor>) -> Self {
+        self.outline_extractors.register(extractor);
+        self
     }
-}
 
-impl ArchiverOperations for CoreArchiver {
-    fn create_content(
+    fn create_formatter(
         &self,
-        nodes: &[FileNode],
-        root_path_for_display: &Path,
-    ) -> io::Result<String> {
-        // Initialize archive_content with the new global header line.
-        let mut archive_content = format!(
-            "// Combined files from {}\n",
-            root_path_for_display.display()
-        );
-        let mut buffer = Vec::new();
+        request: &ArchiveRequest,
+    ) -> Result<Box<dyn ArchiveFormatter>, ArchiveError> {
+        let templates = request
+            .options
+            .templates
+            .compile()
+            .map_err(ArchiveError::InvalidOptions)?;
+        Ok(request.options.format.create_formatter(FormatterSettings {
+            templates,
+            profile_name: request.profile_name.clone(),
+            token_counter: Some(Arc::clone(&self.token_counter)),
+        }))
+    }
 
-        for node in nodes.iter().rev() {
-            buffer.push(node);
+    /*
+     * Renders the profile's active prompt variant for the files that go into the archive.
+     * `{token_count}` counts every entry as it will be written, so the entries are only
+     * counted when the prompt uses that placeholder.
+     */
+    fn render_prompt(
+        &self,
+        request: &ArchiveRequest,
+        selection: &ArchiveSelection,
+    ) -> Result<RenderedPrompt, ArchiveError> {
+        let Some(prompt) = request
+            .options
+            .prompts
+            .compile_active()
+            .map_err(ArchiveError::InvalidOptions)?
+        else {
+            return Ok(RenderedPrompt::default());
+        };
+        let mut token_count = 0;
+        if prompt.uses_token_count() {
+            for file in &selection.files {
+                let entry = self.load_entry(selection, file)?;
+                token_count += self.token_counter.count_tokens(&entry.content);
+            }
         }
+        let file_list = selection
+            .files
+            .iter()
+            .map(|file| file.relative_path.clone())
+            .collect();
+        Ok(prompt.render(&PromptContext::new(
+            request.root_path_for_display,
+            &request.profile_name,
+            file_list,
+            token_count,
+        )))
+    }
+
+    /*
+     * Reads and prepares every selected file once. Files are decoded by their BOM or the
+     * profile's fallback encoding for their path, and binary and oversize files are
+     * skipped, replaced by a placeholder line or fail the generation, per the profile's
+     * content limits. Secrets are found in the decoded text (and redacted if the policy
+     * asks for it), outlines are reduced to their signatures, content transforms run and
+     * lines are numbered. Each prepared entry is appended to the spool, keeping only its
+     * metadata in memory. If the profile has a token budget, the prepared entries are
+     * measured to decide which low-priority files to drop or truncate; the session's
+     * cached token count is used instead when a file is archived as it is on disk and its
+     * checksum still matches. Finally the secret policy is applied to the files that
+     * remain, so secrets are reported, redacted or abort the generation before anything
+     * is written.
+     */
+    fn select_files<'a>(
+        &self,
+        request: &ArchiveRequest<'a>,
+    ) -> Result<ArchiveSelection<'a>, ArchiveError> {
+        let encodings = EncodingRules::compile(&request.options.encodings)
+            .map_err(ArchiveError::InvalidEncodingRule)?;
+        let reader = ContentReader::new(
+            request.root_path_for_display,
+            request.options.content_limits.max_file_size,
+            encodings,
+        );
+        let transforms = TransformPipeline::compile(&request.options.transforms)
+            .map_err(ArchiveError::InvalidTransform)?;
+        let scanner = SecretScanner::compile(&request.options.secret_scan)
+            .map_err(ArchiveError::InvalidSecretRule)?;
+        let line_numbering = LineNumbering::compile(request.options.line_numbers.as_ref())
+            .map_err(ArchiveError::InvalidLineNumbers)?;
+
+        let mut transform_report = (!transforms.is_empty()).then(TransformTokenReport::default);
+        let mut spool = EntrySpool::create()?;
+        let mut files = Vec::new();
+        let mut skipped = Vec::new();
+        let mut budget_candidates = Vec::new();
+        let mut checksums = BTreeMap::new();
+        let mut stamps = BTreeMap::new();
+        for node in collect_selected_files(request.nodes) {
+            let relative_path = relative_display_path(node, request.root_path_for_display);
+            // The token count of the entry while it is still the file as it is on disk.
+            let mut known_tokens = request
+                .token_cache
+                .get(node.path())
+                .filter(|details| {
+                    node.state() != SelectionState::Outline && node.checksum_match(Some(details))
+                })
+                .map(|details| details.token_count);
+            // Taken before the read, so a change during the read shows as a new stamp.
+            let stamp =
+                FileStamp::before_read(node.path()).map_err(|e| ArchiveError::ReadSource {
+                    path: node.path().to_path_buf(),
+                    source: e,
+                })?;
+            let (mut source, checksum) = read_source(node, &reader)?;
+            checksums.insert(node.path().to_path_buf(), checksum);
+            if let Some(stamp) = stamp {
+                stamps.insert(node.path().to_path_buf(), stamp);
+            }
 
-        while let Some(node) = buffer.pop() {
-            if node.is_dir() {
-                for child in node.children.iter().rev() {
-                    buffer.push(child);
+            // Scanned before outline and transforms, so findings give lines of the file on
+            // disk; a secret that an outline or transform would leave out is still reported.
+            let mut findings = Vec::new();
+            if let (FileContent::Text(text), Some(scanner)) = (&mut source, &scanner) {
+                let matches = scanner.scan(&relative_path, text);
+                findings = matches
+                    .iter()
+                    .map(|secret| SecretFinding {
+                        relative_path: relative_path.clone(),
+                        line: secret.line,
+                        rule: secret.rule.clone(),
+                    })
+                    .collect();
+                if scanner.policy() == SecretPolicy::Redact && !matches.is_empty() {
+                    *text = SecretScanner::redact(text, &matches);
+                    known_tokens = None;
                 }
-            } else if node.is_selected() {
-                let display_path = node
-                    .path()
-                    .strip_prefix(root_path_for_display)
-                    .unwrap_or(node.path())
-                    .to_string_lossy();
-
-                archive_content.push_str(&format!("// ===== File: {display_path} =====\n"));
-
-                match fs::read_to_string(node.path()) {
-                    Ok(content) => {
-                        archive_content.push_str(&content);
-                        if !content.ends_with('\n') {
-                            archive_content.push('\n');
-                        }
+            }
+
+            let mut content = match source {
+                FileContent::Text(text) if node.state() == SelectionState::Outline => self
+                    .outline_extractors
+                    .extract(&relative_path, &text)
+                    .unwrap_or_else(|| {
+                        log::debug!(
+                            "Archiver: No outline extractor for {relative_path}; including it in full."
+                        );
+                        text
+                    }),
+                FileContent::Text(text) => text,
+                FileContent::NotText { class, size } => {
+                    // [ArchiveGenBinaryFilesV1]
+                    let policy = request.options.content_limits.policy_for(class);
+                    if policy == ContentPolicy::Fail {
+                        return Err(ArchiveError::UnsupportedContent {
+                            path: node.path().to_path_buf(),
+                            class,
+                            size,
+                        });
                     }
-                    Err(e) => {
-                        // Return an io::Error with a custom message including the path
-                        return Err(io::Error::new(
-                            e.kind(),
-                            format!("Failed to read file '{}': {}", node.path().display(), e),
-                        ));
+                    skipped.push(SkippedFile {
+                        relative_path: relative_path.clone(),
+                        class,
+                        size,
+                        policy,
+                    });
+                    if policy == ContentPolicy::Skip {
+                        continue;
                     }
+                    known_tokens = None;
+                    content_class::placeholder(class, size)
                 }
+            };
+
+            if let Some(report) = transform_report.as_mut() {
+                report.raw_tokens +=
+                    known_tokens.unwrap_or_else(|| self.token_counter.count_tokens(&content));
+                content = transforms.apply(&relative_path, content);
+                let tokens = self.token_counter.count_tokens(&content);
+                report.transformed_tokens += tokens;
+                known_tokens = Some(tokens);
             }
-        }
-        Ok(archive_content)
-    }
-
-    fn get_file_timestamp(&self, path: &Path) -> io::Result<SystemTime> {
-        fs::metadata(path)?.modified()
-    }
 
-    fn check_status(
-        &self,
-        archive_file_path_opt: Option<&Path>,
-        file_nodes_tree: &[FileNode],
-    ) -> ArchiveStatus {
-        let current_archive_path = match archive_file_path_opt {
-            Some(p) => p,
-            None => {
-                log::debug!(
-                    "Archiver: check_archive_status - No archive path provided, status is NotYetGenerated."
-                );
-                return ArchiveStatus::NotYetGenerated;
+            let mut lines = None;
+            if line_numbering.applies_to(&relative_path) {
+                let (numbered, total) = line_numbers::number_lines(&content);
+                content = numbered;
+                lines = LineRange::whole(total);
+                known_tokens = None;
             }
-        };
 
-        let archive_timestamp = match self.get_file_timestamp(current_archive_path) {
-            Ok(ts) => ts,
-            Err(e) if e.kind() == io::ErrorKind::NotFound => {
-                log::debug!(
-                    "Archiver: check_archive_status - Archive file {current_archive_path:?} missing."
-                );
-                return ArchiveStatus::ArchiveFileMissing;
-            }
-            Err(e) => {
-                log::error!(
-                    "Archiver: Error getting archive timestamp for {current_archive_path:?}: {e}"
-                );
-                return ArchiveStatus::ErrorChecking(Some(e.kind()));
+            if request.options.token_budget.is_some() {
+                budget_candidates.push(BudgetCandidate {
+                    path: node.path().to_path_buf(),
+                    tokens: known_tokens
+                        .unwrap_or_else(|| self.token_counter.count_tokens(&content)),
+                    priority: request
+                        .options
+                        .file_priorities
+                        .get(node.path())
+                        .copied()
+                        .unwrap_or_default(),
+                });
             }
-        };
+            files.push(PreparedFile {
+                node,
+                relative_path,
+                content: spool.push(&content)?,
+                lines,
+                findings,
+                kept_tokens: None,
+            });
+        }
+        if let Some(report) = &transform_report {
+            log::debug!(
+                "Archiver: Content transforms: {} -> {} tokens.",
+                report.raw_tokens,
+                report.transformed_tokens
+            );
+        }
 
-        let mut newest_selected_file_timestamp: Option<SystemTime> = None;
-        let mut has_selected_files = false;
-
-        /* Helper recursive function to iterate through nodes and find selected files.
-         * It now needs to take `&dyn ArchiverOperations` to call `get_file_timestamp`. */
-        fn find_newest_selected(
-            archiver: &dyn ArchiverOperations,
-            nodes: &[FileNode],
-            current_newest: &mut Option<SystemTime>,
-            has_any_selected: &mut bool,
-        ) -> io::Result<()> {
-            for node in nodes {
-                if node.is_dir() {
-                    find_newest_selected(
-                        archiver,
-                        &node.children,
-                        current_newest,
-                        has_any_selected,
-                    )?;
-                } else if node.is_selected() {
-                    *has_any_selected = true;
-                    let file_ts = archiver.get_file_timestamp(node.path())?;
-                    if let Some(newest) = current_newest {
-                        if file_ts > *newest {
-                            *current_newest = Some(file_ts);
-                        }
-                    } else {
-                        *current_newest = Some(file_ts);
-                    }
+        let budget = request.options.token_budget.map(|budget| {
+            let report = token_budget::plan_token_budget(&budget_candidates, budget);
+            log::debug!(
+                "Archiver: Token budget {budget}: {} -> {} tokens, {} files omitted or truncated.",
+                report.tokens_before,
+                report.tokens_after,
+                report.omitted.len()
+            );
+            report
+        });
+        if let Some(report) = &budget {
+            let actions = report.actions();
+            files.retain_mut(|file| match actions.get(file.node.path()) {
+                Some(BudgetAction::Dropped) => false,
+                Some(BudgetAction::Truncated { kept_tokens }) => {
+                    file.kept_tokens = Some(*kept_tokens);
+                    true
                 }
-            }
-            Ok(())
+                None => true,
+            });
         }
 
-        if let Err(e) = find_newest_selected(
-            self,
-            file_nodes_tree,
-            &mut newest_selected_file_timestamp,
-            &mut has_selected_files,
-        ) {
-            log::error!("Archiver: Error checking source file timestamps: {e}");
-            return ArchiveStatus::ErrorChecking(Some(e.kind()));
+        let secrets = match &scanner {
+            Some(scanner) => Some(Self::apply_secret_policy(scanner, &files)?),
+            None => None,
+        };
+        Ok(ArchiveSelection {
+            files,
+            spool,
+            budget,
+            transform_report,
+            secrets,
+            skipped,
+            checksums,
+            stamps,
+        })
+    }
+
+    /* Reads the entry of a prepared file back from the spool, truncated if the budget says so. */
+    fn load_entry(
+        &self,
+        selection: &ArchiveSelection,
+        file: &PreparedFile,
+    ) -> Result<ArchiveEntry, ArchiveError> {
+        let mut entry = ArchiveEntry {
+            relative_path: file.relative_path.clone(),
+            content: selection.spool.read(file.content)?,
+            lines: file.lines,
+        };
+        if let Some(kept_tokens) = file.kept_tokens {
+            self.truncate_entry(&mut entry, kept_tokens);
         }
+        Ok(entry)
+    }
 
-        if !has_selected_files {
-            log::debug!("Archiver: check_archive_status - No files selected.");
-            return ArchiveStatus::NoFilesSelected;
+    /*
+     * Cuts an entry down to `kept_tokens`, keeping its line range in step. The kept lines
+     * are followed by a truncation marker, whose tokens count against `kept_tokens`.
+     */
+    fn truncate_entry(&self, entry: &mut ArchiveEntry, kept_tokens: usize) {
+        let total_lines = entry.content.split_inclusive('\n').count();
+        // Measured for the longest possible count, so the marker fits what was reserved.
+        let marker_tokens = self
+            .token_counter
+            .count_tokens(&token_budget::truncation_marker(total_lines));
+        entry.content = token_budget::truncate_to_tokens(
+            &entry.content,
+            kept_tokens.saturating_sub(marker_tokens),
+            |text| self.token_counter.count_tokens(text),
+        );
+        let kept_lines = entry.content.split_inclusive('\n').count();
+        if kept_lines < total_lines {
+            entry
+                .content
+                .push_str(&token_budget::truncation_marker(total_lines - kept_lines));
         }
+        entry.lines = entry
+            .lines
+            .filter(|_| kept_lines > 0)
+            .map(|range| LineRange {
+                last: kept_lines,
+                ..range
+            });
+    }
 
-        match newest_selected_file_timestamp {
-            Some(newest_src_ts) => {
-                if newest_src_ts > archive_timestamp {
-                    log::debug!(
-                        "Archiver: check_archive_status - Archive {current_archive_path:?} is OUTDATED."
-                    );
-                    ArchiveStatus::OutdatedRequiresUpdate
-                } else {
-                    log::debug!(
-                        "Archiver: check_archive_status - Archive {current_archive_path:?} is UP TO DATE."
+    /*
+     * Reports the secrets found in the files that go into the archive. With the `Abort`
+     * policy any finding fails the generation before anything is written.
+     * [ArchiveGenSecretScanV1]
+     */
+    fn apply_secret_policy(
+        scanner: &SecretScanner,
+        files: &[PreparedFile],
+    ) -> Result<SecretScanReport, ArchiveError> {
+        let findings: Vec<SecretFinding> = files
+            .iter()
+            .flat_map(|file| file.findings.iter().cloned())
+            .collect();
+        if !findings.is_empty() {
+            log::debug!("Archiver: {} possible secret(s) found.", findings.len());
+        }
+        if scanner.policy() == SecretPolicy::Abort && !findings.is_empty() {
+            return Err(ArchiveError::SecretsFound(findings));
+        }
+        Ok(SecretScanReport {
+            policy: scanner.policy(),
+            findings,
+        })
+    }
+
+    /*
+     * Measures the tokens the entry of every selected file costs. Per-line costs are added
+     * by `plan_split` for the files that cannot fit into a part on their own.
+     */
+    fn measure_split_candidates(
+        &self,
+        selection: &ArchiveSelection,
+        include_manifest: bool,
+    ) -> Result<Vec<SplitCandidate>, ArchiveError> {
+        selection
+            .files
+            .iter()
+            .map(|file| {
+                let entry = self.load_entry(selection, file)?;
+                let content_tokens = self.token_counter.count_tokens(&entry.content);
+                let mut cost = content_tokens
+                    + self.token_counter.count_tokens(&entry.relative_path)
+                    + ENTRY_FRAMING_TOKENS;
+                if let Some(lines) = entry.lines {
+                    cost += self.token_counter.count_tokens(&lines.to_string());
+                }
+                if include_manifest {
+                    let row = ManifestEntry::describe(
+                        entry.relative_path.clone(),
+                        &entry.content,
+                        |_| Some(content_tokens),
+                        |_| content_tokens,
                     );
-                    ArchiveStatus::UpToDate
+                    cost += self.manifest_row_tokens(&row);
                 }
+                Ok(SplitCandidate {
+                    relative_path: entry.relative_path,
+                    cost,
+                    line_costs: None,
+                })
+            })
+            .collect()
+    }
+
+    /* Fills in the per-line costs of the files whose entry alone exceeds `budget`. */
+    fn measure_line_costs(
+        &self,
+        selection: &ArchiveSelection,
+        candidates: &mut [SplitCandidate],
+        budget: usize,
+    ) -> Result<(), ArchiveError> {
+        for (file, candidate) in selection.files.iter().zip(candidates.iter_mut()) {
+            if budget == 0 || candidate.cost <= budget || candidate.line_costs.is_some() {
+                continue;
             }
-            None => {
-                /* This case should ideally not be reached if has_selected_files is true,
-                 * but implies an issue like a selected file not having a timestamp.
-                 * find_newest_selected would usually return Err in such a case. */
-                log::warn!(
-                    "Archiver: check_archive_status - Files selected, but newest_selected_file_timestamp is None for archive {current_archive_path:?}. Defaulting to ErrorChecking."
-                );
-                ArchiveStatus::ErrorChecking(None)
-            }
+            let entry = self.load_entry(selection, file)?;
+            candidate.line_costs = Some(
+                entry
+                    .content
+                    .split_inclusive('\n')
+                    .map(|line| self.token_counter.count_tokens(line))
+                    .collect(),
+            );
         }
+        Ok(())
     }
 
-    fn save(&self, path: &Path, content: &str) -> io::Result<()> {
-        fs::write(path, content)
+    /*
+     * Counts the tokens of a part without any entries: preamble, part header, the fixed
+     * lines of the manifest, the directory tree and closing. Manifest rows are charged to
+     * the entries instead.
+     */
+    fn part_overhead_tokens(
+        &self,
+        request: &ArchiveRequest,
+        header: &ArchivePartHeader,
+    ) -> Result<usize, ArchiveError> {
+        let mut formatter = self.create_formatter(request)?;
+        let mut buffer = Vec::new();
+        let root_display = request.root_path_for_display.display().to_string();
+        let empty_manifest = request.options.include_manifest.then(|| {
+            ArchiveManifest::new(
+                Vec::new(),
+                &request.profile_name,
+                archive_manifest::current_timestamp(),
+            )
+        });
+        let tree = directory_tree_for(request);
+        let preamble = ArchivePreamble {
+            part: Some(header),
+            manifest: empty_manifest.as_ref(),
+            tree: tree.as_ref(),
+            ..ArchivePreamble::new(&root_display)
+        };
+        formatter.write_preamble(&mut buffer, &preamble)?;
+        formatter.write_closing(&mut buffer, None)?;
+        Ok(self
+            .token_counter
+            .count_tokens(&String::from_utf8_lossy(&buffer)))
     }
-}
 
-#[cfg(test)]
-mod archiver_tests {
-    use super::*;
-    use crate::core::file_node::{FileNode, SelectionState};
-    use std::fs::File;
-    use std::io::Write;
-    use std::path::{Path, PathBuf};
-    use std::thread;
-    use std::time::Duration;
-    use tempfile::tempdir;
+    /*
+     * Plans the parts so that entries plus part overhead stay within the budget. The
+     * overhead is only known once the plan is, so the plan is repeated with the largest
+     * observed overhead reserved until it no longer grows. Every round measures the lines
+     * of the files that no longer fit into the reduced budget, so they are divided as well.
+     */
+    fn plan_split(
+        &self,
+        request: &ArchiveRequest,
+        split: &ArchiveSplitOptions,
+        selection: &ArchiveSelection,
+        candidates: &mut [SplitCandidate],
+    ) -> Result<Vec<PlannedPart>, ArchiveError> {
+        let budget = split.max_tokens_per_part;
+        let mut reserved = 0;
+        self.measure_line_costs(selection, candidates, budget)?;
+        let mut parts = archive_split::plan_parts(candidates, split.strategy, budget);
+        for _ in 0..MAX_SPLIT_PLANNING_ROUNDS {
+            if budget == 0 {
+                break;
+            }
+            let mut largest_overhead = 0;
+            for index in 0..parts.len() {
+                let header = part_header_for(&parts, candidates, index);
+                largest_overhead =
+                    largest_overhead.max(self.part_overhead_tokens(request, &header)?);
+            }
+            if largest_overhead <= reserved {
+                break;
+            }
+            reserved = largest_overhead;
+            let entry_budget = budget.saturating_sub(reserved).max(1);
+            self.measure_line_costs(selection, candidates, entry_budget)?;
+            parts = archive_split::plan_parts(candidates, split.strategy, entry_budget);
+        }
+        if parts.is_empty() {
+            parts.push(PlannedPart::default());
+        }
+        Ok(parts)
+    }
 
-    // Helper to create a FileNode for testing
-    fn new_test_file_node(
-        base_path: &Path,
-        name: &str,
-        is_dir: bool,
-        state: SelectionState,
-        children: Vec<FileNode>,
-    ) -> FileNode {
-        FileNode::new_full(
-            base_path.join(name),
-            name.to_string(),
-            is_dir,
-            state,
-            children,
-            "".to_string(),
-        )
+    /* The entry of one part item, restricted to its line range for a slice. */
+    fn part_item_entry(
+        &self,
+        selection: &ArchiveSelection,
+        item: &PartItem,
+    ) -> Result<ArchiveEntry, ArchiveError> {
+        let entry = self.load_entry(selection, &selection.files[item.file_index])?;
+        let (content, lines) = match &item.lines {
+            Some(slice) => {
+                let content: String = entry
+                    .content
+                    .split_inclusive('\n')
+                    .skip(slice.start)
+                    .take(slice.len())
+                    .collect();
+                let sliced_lines = content.split_inclusive('\n').count();
+                let lines = entry
+                    .lines
+                    .filter(|_| sliced_lines > 0)
+                    .map(|range| LineRange {
+                        first: range.first + slice.start,
+                        last: range.first + slice.start + sliced_lines - 1,
+                        total: range.total,
+                    });
+                (content, lines)
+            }
+            None => (entry.content, entry.lines),
+        };
+        Ok(ArchiveEntry {
+            relative_path: entry.relative_path,
+            content,
+            lines,
+        })
     }
 
-    // Test helper for ArchiverOperations using CoreArchiver
-    fn test_with_archiver<F, R>(test_fn: F) -> R
-    where
-        F: FnOnce(&dyn ArchiverOperations) -> R,
-    {
-        crate::initialize_logging();
-        let archiver = CoreArchiver::new();
-        test_fn(&archiver)
+    /*
+     * Writes one part. With a manifest, each part's manifest lists the files (or slices)
+     * contained in that part. The entries are read back from the spool one at a time.
+     */
+    fn write_part(
+        &self,
+        request: &ArchiveRequest,
+        selection: &ArchiveSelection,
+        input: &PartInput,
+        sink: &mut dyn Write,
+    ) -> Result<u64, ArchiveError> {
+        let mut formatter = self.create_formatter(request)?;
+        let mut out = CountingWriter {
+            inner: sink,
+            bytes_written: 0,
+        };
+        let root_display = request.root_path_for_display.display().to_string();
+        let manifest = if request.options.include_manifest {
+            let mut files = Vec::new();
+            for item in &input.part.items {
+                let entry = self.part_item_entry(selection, item)?;
+                let node = selection.files[item.file_index].node;
+                files.push(self.describe_for_manifest(request, node, &entry));
+            }
+            Some(ArchiveManifest::new(
+                files,
+                &request.profile_name,
+                input.generated_at.to_string(),
+            ))
+        } else {
+            None
+        };
+        let tree = directory_tree_for(request);
+        // The prompt wraps the archive as a whole: its preamble opens the first part and
+        // its postamble closes the last.
+        let is_first = input.header.number == 1;
+        let is_last = input.header.number == input.header.count;
+        let preamble = ArchivePreamble {
+            prompt: input.prompt.preamble.as_deref().filter(|_| is_first),
+            part: Some(input.header),
+            manifest: manifest.as_ref(),
+            tree: tree.as_ref(),
+            ..ArchivePreamble::new(&root_display)
+        };
+        formatter.write_preamble(&mut out, &preamble)?;
+        for item in &input.part.items {
+            let entry = self.part_item_entry(selection, item)?;
+            formatter.write_entry(&mut out, &entry)?;
+        }
+        formatter.write_closing(
+            &mut out,
+            input.prompt.postamble.as_deref().filter(|_| is_last),
+        )?;
+        out.flush()?;
+        Ok(out.bytes_written)
     }
 
-    #[test]
-    fn test_core_archiver_create_archive_from_selected_files() -> io::Result<()> {
-        test_with_archiver(|archiver| {
-            // Arrange
-            let dir = tempdir()?;
-            let base_path = dir.path();
+    /*
+     * Writes a split archive: measures the selected files, plans the parts, and writes the
+     * parts as one atomic set, so a failure on any part leaves the previous parts untouched.
+     * Part files left over from an earlier, larger split and a previous single-file archive
+     * at `path` are removed afterwards, so only the new set remains.
+     */
+    fn write_split_archive_to_files(
+        &self,
+        request: &ArchiveRequest,
+        split: &ArchiveSplitOptions,
+        path: &Path,
+    ) -> Result<ArchiveReport, ArchiveError> {
+        let selection = self.select_files(request)?;
+        let mut candidates =
+            self.measure_split_candidates(&selection, request.options.include_manifest)?;
+        let parts = self.plan_split(request, split, &selection, &mut candidates)?;
+        let generated_at = archive_manifest::current_timestamp();
+        let prompt = self.render_prompt(request, &selection)?;
+
+        let mut report = ArchiveReport {
+            files_written: selection.files.len(),
+            budget: selection.budget.clone(),
+            transform: selection.transform_report,
+            secrets: selection.secrets.clone(),
+            skipped: selection.skipped.clone(),
+            checksums: selection.checksums.clone(),
+            stamps: selection.stamps.clone(),
+            ..ArchiveReport::default()
+        };
+        let mut part_files = AtomicFileSet::new();
+        for (index, part) in parts.iter().enumerate() {
+            let header = part_header_for(&parts, &candidates, index);
+            let part_path = archive_split::part_file_path(path, index + 1);
+            report.bytes_written += part_files.write(&part_path, |sink| {
+                let part_input = PartInput {
+                    part,
+                    header: &header,
+                    generated_at: &generated_at,
+                    prompt: &prompt,
+                };
+                self.write_part(request, &selection, &part_input, sink)
+            })?;
+            report.part_paths.push(part_path);
+        }
+        part_files.commit()?;
+
+        let stale_parts = (parts.len() + 1..)
+            .map(|number| archive_split::part_file_path(path, number))
+            .take_while(|p| p.is_file());
+        for stale in stale_parts.chain(path.is_file().then(|| path.to_path_buf())) {
+            if let Err(e) = fs::remove_file(&stale) {
+                log::warn!("Archiver: Failed to remove stale archive file {stale:?}: {e}");
+            }
+        }
+        Ok(report)
+    }
+
+    /*
+     * Describes an entry for the manifest. The session's cached token count is reused
+     * when its checksum matches the content as written (i.e. the file was not truncated).
+     */
+    fn describe_for_manifest(
+        &self,
+        request: &ArchiveRequest,
+        node: &FileNode,
+        entry: &ArchiveEntry,
+    ) -> ManifestEntry {
+        ManifestEntry::describe(
+            entry.relative_path.clone(),
+            &entry.content,
+            |sha| {
+                request
+                    .token_cache
+                    .get(node.path())
+                    .filter(|details| details.checksum == sha)
+                    .map(|details| details.token_count)
+            },
+            |text| self.token_counter.count_tokens(text),
+        )
+    }
+
+    /* Counts the tokens of the manifest row for one file, charged to its entry when planning parts. */
+    fn manifest_row_tokens(&self, row: &ManifestEntry) -> usize {
+        self.token_counter.count_tokens(&format!(
+            "{} | {} | {} | {} | {}\n",
+            row.path, row.bytes, row.lines, row.tokens, row.sha256
+        ))
+    }
+
+    /* Builds the manifest of a single-file archive from the prepared entries. */
+    fn build_manifest(
+        &self,
+        request: &ArchiveRequest,
+        selection: &ArchiveSelection,
+    ) -> Result<ArchiveManifest, ArchiveError> {
+        let mut files = Vec::new();
+        for file in &selection.files {
+            let entry = self.load_entry(selection, file)?;
+            files.push(self.describe_for_manifest(request, file.node, &entry));
+        }
+        Ok(ArchiveManifest::new(
+            files,
+            &request.profile_name,
+            archive_manifest::current_timestamp(),
+        ))
+    }
+
+    /*
+     * Adds the archive just written to the profile's history. As with the content record,
+     * a failure is only logged. Returns the id of the new generation.
+     */
+    fn save_history_generation(
+        &self,
+        request: &ArchiveRequest,
+        archive_path: &Path,
+        report: &ArchiveReport,
+        history_dir: &Path,
+    ) -> Option<u64> {
+        let archive_files = if report.part_paths.is_empty() {
+            vec![archive_path.to_path_buf()]
+        } else {
+            report.part_paths.clone()
+        };
+        let history = ArchiveHistory::new(history_dir.to_path_buf());
+        match history.record(
+            archive_path,
+            &archive_files,
+            report.checksums.clone(),
+            archive_manifest::current_timestamp(),
+            request.options.history.retention,
+        ) {
+            Ok(generation) => Some(generation.id),
+            Err(e) => {
+                log::warn!("Archiver: Failed to add archive to history {history_dir:?}: {e}");
+                None
+            }
+        }
+    }
+
+    /*
+     * Compares the selection with the archive's content record. Returns `None` if there is
+     * no usable record for `archive_path`, so the caller can fall back to timestamps.
+     * A file is only re-hashed when its size or modification time differs from the stamp
+     * recorded when it was read, or when no stamp was recorded for it.
+     */
+    fn status_from_record(
+        &self,
+        archive_path: &Path,
+        selected: &[PathBuf],
+        record_path: &Path,
+    ) -> Option<ArchiveStatus> {
+        let record = match ArchiveRecord::load(record_path) {
+            Ok(record) if record.archive_path == archive_path => record,
+            Ok(record) => {
+                log::debug!(
+                    "Archiver: Record {record_path:?} describes {:?}, not {archive_path:?}.",
+                    record.archive_path
+                );
+                return None;
+            }
+            Err(e) => {
+                if e.kind() != io::ErrorKind::NotFound {
+                    log::warn!("Archiver: Ignoring unreadable archive record {record_path:?}: {e}");
+                }
+                return None;
+            }
+        };
+
+        let diff = record.diff(selected, |path, _recorded| {
+            if let Some(stamp) = record.stamps.get(path)
+                && FileStamp::current(path)? == *stamp
+            {
+                return Ok(None);
+            }
+            checksum_utils::calculate_sha256_checksum(path).map(Some)
+        });
+        Some(match diff {
+            Ok(changes) if changes.is_empty() => ArchiveStatus::UpToDate,
+            Ok(changes) => {
+                log::debug!("Archiver: Archive {archive_path:?} is OUTDATED: {changes:?}");
+                ArchiveStatus::OutdatedRequiresUpdate(Some(changes))
+            }
+            Err(e) => {
+                log::error!("Archiver: Error comparing sources with archive record: {e}");
+                ArchiveStatus::ErrorChecking(Some(e.kind()))
+            }
+        })
+    }
+
+    /*
+     * Returns the timestamp of the archive at `path`, or of the oldest part if the archive
+     * was split. Reports `NotFound` if neither exists.
+     */
+    fn archive_timestamp(&self, path: &Path) -> io::Result<SystemTime> {
+        match self.get_file_timestamp(path) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                let parts = archive_split::existing_part_files(path);
+                if parts.is_empty() {
+                    return Err(e);
+                }
+                let mut oldest: Option<SystemTime> = None;
+                for part in parts {
+                    let ts = self.get_file_timestamp(&part)?;
+                    oldest = Some(oldest.map_or(ts, |o| o.min(ts)));
+                }
+                oldest.ok_or(e)
+            }
+            other => other,
+        }
+    }
+}
+
+impl ArchiverOperations for CoreArchiver {
+    fn write_archive(
+        &self,
+        request: &ArchiveRequest,
+        sink: &mut dyn Write,
+    ) -> Result<ArchiveReport, ArchiveError> {
+        let root_path_for_display = request.root_path_for_display;
+        let mut formatter = self.create_formatter(request)?;
+        let selection = self.select_files(request)?;
+        let mut out = CountingWriter {
+            inner: sink,
+            bytes_written: 0,
+        };
+        let manifest = if request.options.include_manifest {
+            Some(self.build_manifest(request, &selection)?)
+        } else {
+            None
+        };
+        let tree = directory_tree_for(request);
+        let root_display = root_path_for_display.display().to_string();
+        let prompt = self.render_prompt(request, &selection)?;
+        let preamble = ArchivePreamble {
+            prompt: prompt.preamble.as_deref(),
+            delta: request.delta.as_ref(),
+            manifest: manifest.as_ref(),
+            tree: tree.as_ref(),
+            ..ArchivePreamble::new(&root_display)
+        };
+        formatter.write_preamble(&mut out, &preamble)?;
+
+        let mut files_written = 0;
+        for file in &selection.files {
+            let entry = self.load_entry(&selection, file)?;
+            formatter.write_entry(&mut out, &entry)?;
+            files_written += 1;
+        }
+        formatter.write_closing(&mut out, prompt.postamble.as_deref())?;
+        out.flush()?;
+
+        Ok(ArchiveReport {
+            files_written,
+            bytes_written: out.bytes_written,
+            part_paths: Vec::new(),
+            budget: selection.budget,
+            transform: selection.transform_report,
+            delta: request.delta.clone(),
+            secrets: selection.secrets,
+            skipped: selection.skipped,
+            history_generation: None,
+            checksums: selection.checksums,
+            stamps: selection.stamps,
+        })
+    }
+
+    fn write_archive_to_file(
+        &self,
+        request: &ArchiveRequest,
+        path: &Path,
+    ) -> Result<ArchiveReport, ArchiveError> {
+        /* A templated path may lead into a folder that does not exist yet. [ArchivePathTemplateV1] */
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        let mut report = match &request.options.split {
+            Some(split) => self.write_split_archive_to_files(request, split, path)?,
+            None => {
+                let report = atomic_write::write_file_atomically(path, |sink| {
+                    self.write_archive(request, sink)
+                })?;
+                for stale in archive_split::existing_part_files(path) {
+                    if let Err(e) = fs::remove_file(&stale) {
+                        log::warn!("Archiver: Failed to remove stale archive part {stale:?}: {e}");
+                    }
+                }
+                report
+            }
+        };
+        let history_dir = request
+            .history_dir
+            .as_deref()
+            .filter(|_| request.options.history.retention > 0);
+        if let Some(record_path) = &request.record_path {
+            let record = ArchiveRecord::new(path, report.checksums.clone())
+                .with_stamps(report.stamps.clone());
+            if let Err(e) = record.save(record_path) {
+                log::warn!("Archiver: Failed to save archive record {record_path:?}: {e}");
+            }
+        }
+        if let Some(history_dir) = history_dir {
+            report.history_generation =
+                self.save_history_generation(request, path, &report, history_dir);
+        }
+        Ok(report)
+    }
+
+    fn write_delta_archive_to_file(
+        &self,
+        request: &ArchiveRequest,
+        archive_path: &Path,
+        delta_path: &Path,
+    ) -> Result<ArchiveReport, ArchiveError> {
+        let no_baseline = || ArchiveError::NoDeltaBaseline(archive_path.to_path_buf());
+        let record_path = request.record_path.as_deref().ok_or_else(no_baseline)?;
+        let record = match ArchiveRecord::load(record_path) {
+            Ok(record) if record.archive_path == archive_path => record,
+            Ok(_) => return Err(no_baseline()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Err(no_baseline()),
+            Err(e) => {
+                log::warn!("Archiver: Ignoring unreadable archive record {record_path:?}: {e}");
+                return Err(no_baseline());
+            }
+        };
+
+        let selected: Vec<PathBuf> = collect_selected_files(request.nodes)
+            .iter()
+            .map(|node| node.path().to_path_buf())
+            .collect();
+        let mut last_hashed = PathBuf::new();
+        let changes = record
+            .diff(&selected, |path, _recorded| {
+                last_hashed = path.to_path_buf();
+                checksum_utils::calculate_sha256_checksum(path).map(Some)
+            })
+            .map_err(|source| ArchiveError::ReadSource {
+                path: last_hashed.clone(),
+                source,
+            })?;
+
+        let changed: HashSet<&Path> = changes
+            .added
+            .iter()
+            .chain(&changes.modified)
+            .map(PathBuf::as_path)
+            .collect();
+        let mut nodes = request.nodes.to_vec();
+        deselect_unchanged_files(&mut nodes, &changed);
+
+        let root = request.root_path_for_display;
+        let relative = |paths: &[PathBuf]| -> Vec<String> {
+            paths
+                .iter()
+                .map(|path| {
+                    path.strip_prefix(root)
+                        .unwrap_or(path)
+                        .to_string_lossy()
+                        .into_owned()
+                })
+                .collect()
+        };
+        let delta_request = ArchiveRequest {
+            nodes: &nodes,
+            root_path_for_display: root,
+            profile_name: request.profile_name.clone(),
+            options: ArchiveOptions {
+                split: None,
+                directory_tree: None,
+                ..request.options.clone()
+            },
+            token_cache: request.token_cache.clone(),
+            record_path: None,
+            history_dir: None,
+            delta: Some(ArchiveDeltaHeader {
+                added: relative(&changes.added),
+                modified: relative(&changes.modified),
+                deleted: relative(&changes.removed),
+            }),
+        };
+        atomic_write::write_file_atomically(delta_path, |sink| {
+            self.write_archive(&delta_request, sink)
+        })
+    }
+
+    fn get_file_timestamp(&self, path: &Path) -> io::Result<SystemTime> {
+        fs::metadata(path)?.modified()
+    }
+
+    fn check_status(
+        &self,
+        archive_file_path_opt: Option<&Path>,
+        file_nodes_tree: &[FileNode],
+        record_path: Option<&Path>,
+    ) -> ArchiveStatus {
+        let current_archive_path = match archive_file_path_opt {
+            Some(p) => p,
+            None => {
+                log::debug!(
+                    "Archiver: check_archive_status - No archive path provided, status is NotYetGenerated."
+                );
+                return ArchiveStatus::NotYetGenerated;
+            }
+        };
+
+        let archive_timestamp = match self.archive_timestamp(current_archive_path) {
+            Ok(ts) => ts,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                log::debug!(
+                    "Archiver: check_archive_status - Archive file {current_archive_path:?} missing."
+                );
+                return ArchiveStatus::ArchiveFileMissing;
+            }
+            Err(e) => {
+                log::error!(
+                    "Archiver: Error getting archive timestamp for {current_archive_path:?}: {e}"
+                );
+                return ArchiveStatus::ErrorChecking(Some(e.kind()));
+            }
+        };
+
+        let selected: Vec<PathBuf> = collect_selected_files(file_nodes_tree)
+            .into_iter()
+            .map(|node| node.path().to_path_buf())
+            .collect();
+        if selected.is_empty() {
+            log::debug!("Archiver: check_archive_status - No files selected.");
+            return ArchiveStatus::NoFilesSelected;
+        }
+        if let Some(status) = record_path.and_then(|record_path| {
+            self.status_from_record(current_archive_path, &selected, record_path)
+        }) {
+            return status;
+        }
+
+        let mut newest_selected_file_timestamp = SystemTime::UNIX_EPOCH;
+        for path in &selected {
+            match self.get_file_timestamp(path) {
+                Ok(file_ts) => {
+                    newest_selected_file_timestamp = newest_selected_file_timestamp.max(file_ts)
+                }
+                Err(e) => {
+                    log::error!("Archiver: Error checking source file timestamps: {e}");
+                    return ArchiveStatus::ErrorChecking(Some(e.kind()));
+                }
+            }
+        }
+
+        if newest_selected_file_timestamp > archive_timestamp {
+            log::debug!(
+                "Archiver: check_archive_status - Archive {current_archive_path:?} is OUTDATED."
+            );
+            ArchiveStatus::OutdatedRequiresUpdate(None)
+        } else {
+            log::debug!(
+                "Archiver: check_archive_status - Archive {current_archive_path:?} is UP TO DATE."
+            );
+            ArchiveStatus::UpToDate
+        }
+    }
+
+    fn save(&self, path: &Path, content: &str) -> io::Result<()> {
+        atomic_write::write_file_atomically(path, |out| out.write_all(content.as_bytes()))
+    }
+}
+
+#[cfg(test)]
+mod archiver_tests {
+    use super::*;
+    use crate::core::archive_format::ArchiveFormat;
+    use crate::core::archive_prompt::{PromptOptions, PromptVariant};
+    use crate::core::archive_split::ArchiveSplitStrategy;
+    use crate::core::archive_template::ArchiveTemplates;
+    use crate::core::archive_tree::DirectoryTreeOptions;
+    use crate::core::content_class::ContentLimits;
+    use crate::core::content_transform;
+    use crate::core::file_node::{ArchiveChanges, FileNode, SelectionState};
+    use crate::core::line_numbers::LineNumberOptions;
+    use crate::core::text_encoding::{EncodingRule, TextEncoding};
+    use crate::core::token_budget::FilePriority;
+    use crate::core::tokenizer_utils::SimpleWhitespaceTokenCounter;
+    use std::fs::File;
+    use std::io::Write;
+    use std::path::{Path, PathBuf};
+    use std::thread;
+    use std::time::Duration;
+    use tempfile::tempdir;
+
+    // Helper to create a FileNode for testing
+    fn new_test_file_node(
+        base_path: &Path,
+        name: &str,
+        is_dir: bool,
+        state: SelectionState,
+        children: Vec<FileNode>,
+    ) -> FileNode {
+        FileNode::new_full(
+            base_path.join(name),
+            name.to_string(),
+            is_dir,
+            state,
+            children,
+            "".to_string(),
+        )
+    }
+
+    // Test helper for ArchiverOperations using CoreArchiver
+    fn test_with_archiver<F, R>(test_fn: F) -> R
+    where
+        F: FnOnce(&dyn ArchiverOperations) -> R,
+    {
+        crate::initialize_logging();
+        let archiver = CoreArchiver::new(Arc::new(SimpleWhitespaceTokenCounter::new()));
+        test_fn(&archiver)
+    }
+
+    #[test]
+    fn test_file_listed_twice_is_archived_once() -> io::Result<()> {
+        test_with_archiver(|archiver| {
+            // Arrange
+            let dir = tempdir()?;
+            let base_path = dir.path();
+            fs::write(base_path.join("shared.rs"), "pub fn shared() {}\n")?;
+            let shared = new_test_file_node(
+                base_path,
+                "shared.rs",
+                false,
+                SelectionState::Selected,
+                vec![],
+            );
+            let nodes = vec![shared.clone(), shared];
+
+            // Act
+            let archive = archiver.create_content(&ArchiveRequest::new(&nodes, base_path))?;
+
+            // Assert
+            assert_eq!(archive.matches("// ===== File: shared.rs =====").count(), 1);
+            assert_eq!(collect_selected_files(&nodes).len(), 1);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_core_archiver_create_archive_from_selected_files() -> io::Result<()> {
+        test_with_archiver(|archiver| {
+            // Arrange
+            let dir = tempdir()?;
+            let base_path = dir.path();
 
             let file1_path = base_path.join("file1.txt");
             let mut f1 = File::create(&file1_path)?;
@@ -339,7 +1661,7 @@ mod archiver_tests {
             ];
 
             // Act
-            let archive = archiver.create_content(&nodes, base_path)?;
+            let archive = archiver.create_content(&ArchiveRequest::new(&nodes, base_path))?;
 
             // Assert
             let path1_display = "file1.txt";
@@ -404,296 +1726,1721 @@ mod archiver_tests {
             let file_path = dir.path().join("non_existent_ts.txt");
 
             // Act
-            let result = archiver.get_file_timestamp(&file_path);
+            let result = archiver.get_file_timestamp(&file_path);
+
+            // Assert
+            assert!(result.is_err());
+            assert_eq!(result.err().unwrap().kind(), io::ErrorKind::NotFound);
+        });
+    }
+
+    #[test]
+    fn test_core_archiver_check_archive_status_outdated() -> io::Result<()> {
+        test_with_archiver(|archiver| {
+            // Arrange
+            let dir = tempdir()?;
+            let archive_file_path = dir.path().join("archive.txt");
+            let src_file_path1 = dir.path().join("src_file1.txt");
+            let src_file_path2 = dir.path().join("src_file2.txt");
+
+            File::create(&archive_file_path)?.write_all(b"old archive content")?;
+            thread::sleep(Duration::from_millis(20));
+
+            File::create(&src_file_path1)?.write_all(b"source1")?;
+            thread::sleep(Duration::from_millis(20));
+            File::create(&src_file_path2)?.write_all(b"source2")?;
+
+            let file_nodes = vec![
+                new_test_file_node(
+                    dir.path(),
+                    "src_file1.txt",
+                    false,
+                    SelectionState::Selected,
+                    vec![],
+                ),
+                new_test_file_node(
+                    dir.path(),
+                    "src_file2.txt",
+                    false,
+                    SelectionState::Selected,
+                    vec![],
+                ),
+            ];
+
+            // Act
+            let status = archiver.check_status(Some(&archive_file_path), &file_nodes, None);
+
+            // Assert
+            assert_eq!(status, ArchiveStatus::OutdatedRequiresUpdate(None));
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_check_status_with_record_compares_content() -> io::Result<()> {
+        test_with_archiver(|archiver| {
+            // Arrange
+            let dir = tempdir()?;
+            let base_path = dir.path();
+            let archive_path = base_path.join("archive.txt");
+            let record_path = base_path
+                .join(".sourcepacker")
+                .join("archives")
+                .join("p.json");
+            for name in ["a.txt", "b.txt", "c.txt"] {
+                fs::write(base_path.join(name), name)?;
+            }
+            let mut nodes: Vec<FileNode> = ["a.txt", "b.txt", "c.txt"]
+                .iter()
+                .map(|name| {
+                    new_test_file_node(base_path, name, false, SelectionState::Selected, vec![])
+                })
+                .collect();
+            let mut request = ArchiveRequest::new(&nodes, base_path);
+            request.record_path = Some(record_path.clone());
+            archiver
+                .write_archive_to_file(&request, &archive_path)
+                .map_err(io::Error::from)?;
+            let status = |nodes: &[FileNode]| {
+                archiver.check_status(Some(&archive_path), nodes, Some(&record_path))
+            };
+            assert_eq!(status(&nodes), ArchiveStatus::UpToDate);
+
+            // Act - a newer timestamp without a content change keeps the archive current
+            let newer = SystemTime::now() + Duration::from_secs(60);
+            File::options()
+                .write(true)
+                .open(base_path.join("a.txt"))?
+                .set_modified(newer)?;
+            assert_eq!(status(&nodes), ArchiveStatus::UpToDate);
+
+            // Act - modify a.txt, deselect b.txt, add d.txt
+            fs::write(base_path.join("a.txt"), "changed")?;
+            fs::write(base_path.join("d.txt"), "d")?;
+            nodes[1].set_state(SelectionState::Deselected);
+            nodes.push(new_test_file_node(
+                base_path,
+                "d.txt",
+                false,
+                SelectionState::Selected,
+                vec![],
+            ));
+
+            // Assert
+            assert_eq!(
+                status(&nodes),
+                ArchiveStatus::OutdatedRequiresUpdate(Some(ArchiveChanges {
+                    added: vec![base_path.join("d.txt")],
+                    removed: vec![base_path.join("b.txt")],
+                    modified: vec![base_path.join("a.txt")],
+                }))
+            );
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_check_status_detects_change_that_restored_an_older_timestamp() -> io::Result<()> {
+        test_with_archiver(|archiver| {
+            // Arrange
+            let dir = tempdir()?;
+            let base_path = dir.path();
+            let source = base_path.join("a.txt");
+            let archive_path = base_path.join("archive.txt");
+            let record_path = base_path.join("record.json");
+            let hour_ago = SystemTime::now() - Duration::from_secs(3600);
+            fs::write(&source, "one")?;
+            File::options()
+                .write(true)
+                .open(&source)?
+                .set_modified(hour_ago)?;
+            let nodes = vec![new_test_file_node(
+                base_path,
+                "a.txt",
+                false,
+                SelectionState::Selected,
+                vec![],
+            )];
+            let mut request = ArchiveRequest::new(&nodes, base_path);
+            request.record_path = Some(record_path.clone());
+            archiver
+                .write_archive_to_file(&request, &archive_path)
+                .map_err(io::Error::from)?;
+            let record = ArchiveRecord::load(&record_path)?;
+            assert_eq!(record.stamps[&source], FileStamp::current(&source)?);
+
+            // Act - same size, and a timestamp older than the archive
+            fs::write(&source, "two")?;
+            File::options()
+                .write(true)
+                .open(&source)?
+                .set_modified(hour_ago - Duration::from_secs(3600))?;
+
+            // Assert
+            assert_eq!(
+                archiver.check_status(Some(&archive_path), &nodes, Some(&record_path)),
+                ArchiveStatus::OutdatedRequiresUpdate(Some(ArchiveChanges {
+                    modified: vec![source.clone()],
+                    ..Default::default()
+                }))
+            );
+            Ok(())
+        })
+    }
+
+    /* Rewrites `path` on its first call, i.e. while an archive is being generated. */
+    struct EditingTokenCounter {
+        path: PathBuf,
+        edited: std::sync::atomic::AtomicBool,
+    }
+
+    impl TokenCounterOperations for EditingTokenCounter {
+        fn count_tokens(&self, text: &str) -> usize {
+            if !self.edited.swap(true, std::sync::atomic::Ordering::SeqCst) {
+                fs::write(&self.path, "edited during generation").unwrap();
+            }
+            text.split_whitespace().count()
+        }
+    }
+
+    #[test]
+    fn test_record_and_history_hold_checksums_of_the_archived_content() -> io::Result<()> {
+        // Arrange
+        let dir = tempdir()?;
+        let base_path = dir.path();
+        let source = base_path.join("a.txt");
+        fs::write(&source, "original")?;
+        let archiver = CoreArchiver::new(Arc::new(EditingTokenCounter {
+            path: source.clone(),
+            edited: Default::default(),
+        }));
+        let nodes = vec![new_test_file_node(
+            base_path,
+            "a.txt",
+            false,
+            SelectionState::Selected,
+            vec![],
+        )];
+        let archive_path = base_path.join("archive.txt");
+        let record_path = base_path.join("record.json");
+        let mut request = ArchiveRequest::new(&nodes, base_path);
+        request.record_path = Some(record_path.clone());
+        request.history_dir = Some(base_path.join("history"));
+        request.options.token_budget = Some(1000);
+
+        // Act
+        let report = archiver
+            .write_archive_to_file(&request, &archive_path)
+            .map_err(io::Error::from)?;
+
+        // Assert - the edit came after the read, so neither record nor history may include it
+        assert!(fs::read_to_string(&archive_path)?.contains("original"));
+        assert_eq!(
+            report.checksums[&source],
+            checksum_utils::calculate_sha256_for_bytes(b"original")
+        );
+        let history = ArchiveHistory::new(base_path.join("history"));
+        assert_eq!(history.generation(1)?.files, report.checksums);
+        assert_eq!(
+            archiver.check_status(Some(&archive_path), &nodes, Some(&record_path)),
+            ArchiveStatus::OutdatedRequiresUpdate(Some(ArchiveChanges {
+                modified: vec![source.clone()],
+                ..Default::default()
+            }))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_delta_archive_holds_changed_files_and_deleted_paths() -> io::Result<()> {
+        test_with_archiver(|archiver| {
+            // Arrange
+            let dir = tempdir()?;
+            let base_path = dir.path();
+            let archive_path = base_path.join("archive.txt");
+            let delta_path = delta_file_path(&archive_path);
+            let record_path = base_path.join("record.json");
+            for name in ["a.txt", "b.txt", "c.txt"] {
+                fs::write(base_path.join(name), name)?;
+            }
+            let mut nodes: Vec<FileNode> = ["a.txt", "b.txt", "c.txt"]
+                .iter()
+                .map(|name| {
+                    new_test_file_node(base_path, name, false, SelectionState::Selected, vec![])
+                })
+                .collect();
+            let mut request = ArchiveRequest::new(&nodes, base_path);
+            request.record_path = Some(record_path.clone());
+            let no_baseline =
+                archiver.write_delta_archive_to_file(&request, &archive_path, &delta_path);
+            assert!(matches!(no_baseline, Err(ArchiveError::NoDeltaBaseline(_))));
+            archiver
+                .write_archive_to_file(&request, &archive_path)
+                .map_err(io::Error::from)?;
+
+            // Act - modify a.txt, delete c.txt, add d.txt
+            fs::write(base_path.join("a.txt"), "changed")?;
+            fs::remove_file(base_path.join("c.txt"))?;
+            nodes.remove(2);
+            fs::write(base_path.join("d.txt"), "d")?;
+            nodes.push(new_test_file_node(
+                base_path,
+                "d.txt",
+                false,
+                SelectionState::Selected,
+                vec![],
+            ));
+            let mut request = ArchiveRequest::new(&nodes, base_path);
+            request.record_path = Some(record_path.clone());
+            let report = archiver
+                .write_delta_archive_to_file(&request, &archive_path, &delta_path)
+                .map_err(io::Error::from)?;
+
+            // Assert
+            assert_eq!(delta_path, base_path.join("archive.delta.txt"));
+            let expected = ArchiveDeltaHeader {
+                added: vec!["d.txt".to_string()],
+                modified: vec!["a.txt".to_string()],
+                deleted: vec!["c.txt".to_string()],
+            };
+            assert_eq!(report.delta, Some(expected));
+            assert_eq!(report.files_written, 2);
+            let content = fs::read_to_string(&delta_path)?;
+            assert!(content.contains("//   deleted: c.txt\n"), "{content}");
+            let entries = ArchiveFormat::PlainText.parse(&content)?;
+            let paths: Vec<&str> = entries.iter().map(|e| e.relative_path.as_str()).collect();
+            assert_eq!(paths, ["a.txt", "d.txt"]);
+            assert_eq!(
+                archiver.check_status(Some(&archive_path), &nodes, Some(&record_path)),
+                ArchiveStatus::OutdatedRequiresUpdate(Some(ArchiveChanges {
+                    added: vec![base_path.join("d.txt")],
+                    removed: vec![base_path.join("c.txt")],
+                    modified: vec![base_path.join("a.txt")],
+                })),
+                "A delta leaves the record of the full archive unchanged"
+            );
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_written_archives_are_kept_in_history_and_restorable() -> io::Result<()> {
+        test_with_archiver(|archiver| {
+            // Arrange
+            let dir = tempdir()?;
+            let base_path = dir.path();
+            let archive_path = base_path.join("archive.txt");
+            let record_path = base_path.join("record.json");
+            let history_dir = base_path.join("history").join("p");
+            fs::write(base_path.join("a.txt"), "first")?;
+            let nodes = vec![new_test_file_node(
+                base_path,
+                "a.txt",
+                false,
+                SelectionState::Selected,
+                vec![],
+            )];
+            let mut request = ArchiveRequest::new(&nodes, base_path);
+            request.record_path = Some(record_path.clone());
+            request.history_dir = Some(history_dir.clone());
+
+            // Act
+            let first = archiver
+                .write_archive_to_file(&request, &archive_path)
+                .map_err(io::Error::from)?;
+            let first_archive = fs::read_to_string(&archive_path)?;
+            fs::write(base_path.join("a.txt"), "second")?;
+            let second = archiver
+                .write_archive_to_file(&request, &archive_path)
+                .map_err(io::Error::from)?;
+            request.options.history.retention = 0;
+            let untracked = archiver
+                .write_archive_to_file(&request, &archive_path)
+                .map_err(io::Error::from)?;
+
+            // Assert
+            assert_eq!(first.history_generation, Some(1));
+            assert_eq!(second.history_generation, Some(2));
+            assert_eq!(untracked.history_generation, None);
+            let history = ArchiveHistory::new(history_dir);
+            assert_eq!(history.list()?.len(), 2);
+            assert_eq!(history.diff(1, 2)?.modified, vec![base_path.join("a.txt")]);
+
+            history.restore(1, Some(&record_path))?;
+            assert_eq!(fs::read_to_string(&archive_path)?, first_archive);
+            assert!(matches!(
+                archiver.check_status(Some(&archive_path), &nodes, Some(&record_path)),
+                ArchiveStatus::OutdatedRequiresUpdate(Some(changes))
+                    if changes.modified == vec![base_path.join("a.txt")]
+            ));
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_core_archiver_check_archive_status_not_generated() {
+        test_with_archiver(|archiver| {
+            // Arrange
+            let file_nodes = vec![];
+
+            // Act
+            let status = archiver.check_status(None, &file_nodes, None);
+
+            // Assert
+            assert_eq!(status, ArchiveStatus::NotYetGenerated);
+        });
+    }
+
+    #[test]
+    fn test_core_archiver_check_archive_status_archive_file_missing() -> io::Result<()> {
+        test_with_archiver(|archiver| {
+            // Arrange
+            let dir = tempdir()?;
+            let missing_archive_path = dir.path().join("missing_archive.txt");
+            let file_nodes = vec![];
+
+            // Act
+            let status = archiver.check_status(Some(&missing_archive_path), &file_nodes, None);
+
+            // Assert
+            assert_eq!(status, ArchiveStatus::ArchiveFileMissing);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_core_archiver_check_archive_status_no_files_selected() -> io::Result<()> {
+        test_with_archiver(|archiver| {
+            // Arrange
+            let dir = tempdir()?;
+            let archive_file_path = dir.path().join("archive.txt");
+            File::create(&archive_file_path)?.write_all(b"archive content")?;
+
+            let file_nodes = vec![
+                new_test_file_node(
+                    dir.path(),
+                    "file1.txt",
+                    false,
+                    SelectionState::Deselected,
+                    vec![],
+                ),
+                new_test_file_node(dir.path(), "file2.txt", false, SelectionState::New, vec![]),
+            ];
+
+            // Act
+            let status = archiver.check_status(Some(&archive_file_path), &file_nodes, None);
+
+            // Assert
+            assert_eq!(status, ArchiveStatus::NoFilesSelected);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_check_archive_status_up_to_date() -> io::Result<()> {
+        test_with_archiver(|archiver| {
+            // Arrange
+            let dir = tempdir()?;
+            let archive_file_path = dir.path().join("archive.txt");
+            let src_file_path = dir.path().join("src_file.txt");
+
+            File::create(&src_file_path)?.write_all(b"source")?;
+            thread::sleep(Duration::from_millis(20));
+            File::create(&archive_file_path)?.write_all(b"archive content")?;
+
+            let file_nodes = vec![new_test_file_node(
+                dir.path(),
+                "src_file.txt",
+                false,
+                SelectionState::Selected,
+                vec![],
+            )];
+
+            // Act
+            let status = archiver.check_status(Some(&archive_file_path), &file_nodes, None);
+
+            // Assert
+            assert_eq!(status, ArchiveStatus::UpToDate);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_check_archive_status_up_to_date_empty_archive_selected_older() -> io::Result<()> {
+        test_with_archiver(|archiver| {
+            // Arrange
+            let dir = tempdir()?;
+            let archive_file_path = dir.path().join("archive.txt");
+            let src_file_path = dir.path().join("src_file.txt");
+
+            File::create(&src_file_path)?.write_all(b"source")?;
+            thread::sleep(Duration::from_millis(20));
+            File::create(&archive_file_path)?.write_all(b"")?;
+
+            let file_nodes = vec![new_test_file_node(
+                dir.path(),
+                "src_file.txt",
+                false,
+                SelectionState::Selected,
+                vec![],
+            )];
+
+            let src_ts = archiver.get_file_timestamp(&src_file_path)?;
+            let archive_ts = archiver.get_file_timestamp(&archive_file_path)?;
+            assert!(
+                archive_ts > src_ts,
+                "Test setup: archive should be newer than source"
+            );
+
+            // Act
+            let status = archiver.check_status(Some(&archive_file_path), &file_nodes, None);
+
+            // Assert
+            assert_eq!(
+                status,
+                ArchiveStatus::UpToDate,
+                "Expected UpToDate when archive is newer, even if empty, and selected files are older."
+            );
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_check_archive_status_error_checking_src_missing() -> io::Result<()> {
+        test_with_archiver(|archiver| {
+            // Arrange
+            let dir = tempdir()?;
+            let archive_file_path = dir.path().join("archive.txt");
+            File::create(&archive_file_path)?.write_all(b"archive")?;
+
+            let file_nodes = vec![new_test_file_node(
+                dir.path(),
+                "missing_src.txt",
+                false,
+                SelectionState::Selected,
+                vec![],
+            )];
+
+            // Act
+            let status = archiver.check_status(Some(&archive_file_path), &file_nodes, None);
+
+            // Assert
+            assert_eq!(
+                status,
+                ArchiveStatus::ErrorChecking(Some(io::ErrorKind::NotFound))
+            );
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_create_archive_no_selected_files() -> io::Result<()> {
+        test_with_archiver(|archiver| {
+            // Arrange
+            let dir = tempdir()?;
+            let base_path = dir.path();
+            let nodes = vec![
+                new_test_file_node(
+                    base_path,
+                    "file1.txt",
+                    false,
+                    SelectionState::Deselected,
+                    vec![],
+                ),
+                new_test_file_node(base_path, "file2.txt", false, SelectionState::New, vec![]),
+            ];
+
+            // Act
+            let archive = archiver.create_content(&ArchiveRequest::new(&nodes, base_path))?;
+
+            // Assert
+            let expected_content = format!("// Combined files from {}\n", base_path.display());
+            assert_eq!(archive, expected_content);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_create_archive_file_read_error() -> io::Result<()> {
+        test_with_archiver(|archiver| {
+            // Arrange
+            let dir = tempdir()?;
+            let base_path = dir.path();
+            let nodes = vec![new_test_file_node(
+                base_path,
+                "non_existent_file.txt",
+                false,
+                SelectionState::Selected,
+                vec![],
+            )];
+
+            // Act
+            let result = archiver.create_content(&ArchiveRequest::new(&nodes, base_path));
+
+            // Assert
+            assert!(result.is_err());
+            if let Err(e) = result {
+                assert_eq!(e.kind(), io::ErrorKind::NotFound);
+            }
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_create_archive_ensure_newline_before_footer() -> io::Result<()> {
+        test_with_archiver(|archiver| {
+            // Arrange
+            let dir = tempdir()?;
+            let base_path = dir.path();
+
+            let file_no_trailing_newline_path = base_path.join("no_newline.txt");
+            let mut f = File::create(&file_no_trailing_newline_path)?;
+            write!(f, "Line without trailing newline")?;
+            drop(f);
+
+            let nodes = vec![new_test_file_node(
+                base_path,
+                "no_newline.txt",
+                false,
+                SelectionState::Selected,
+                vec![],
+            )];
+
+            // Act
+            let archive = archiver.create_content(&ArchiveRequest::new(&nodes, base_path))?;
+
+            // Assert
+            let root_display_str = base_path.display();
+            let expected_content = format!(
+                "// Combined files from {root_display_str}\n\
+                 // ===== File: no_newline.txt =====\n\
+                 Line without trailing newline\n"
+            );
+            assert_eq!(archive, expected_content);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_create_archive_in_each_format_round_trips_selected_files() -> io::Result<()> {
+        test_with_archiver(|archiver| {
+            // Arrange
+            let dir = tempdir()?;
+            let base_path = dir.path();
+            fs::write(base_path.join("main.rs"), "fn main() {}\n")?;
+            fs::write(base_path.join("notes.md"), "```\nquoted\n```")?;
+            let nodes = vec![
+                new_test_file_node(
+                    base_path,
+                    "main.rs",
+                    false,
+                    SelectionState::Selected,
+                    vec![],
+                ),
+                new_test_file_node(
+                    base_path,
+                    "notes.md",
+                    false,
+                    SelectionState::Selected,
+                    vec![],
+                ),
+                new_test_file_node(base_path, "skip.txt", false, SelectionState::New, vec![]),
+            ];
+
+            for format in ArchiveFormat::ALL {
+                // Act
+                let mut request = ArchiveRequest::new(&nodes, base_path);
+                request.options.format = format;
+                let archive = archiver.create_content(&request)?;
+                let entries = format.parse(&archive)?;
+
+                // Assert
+                let paths: Vec<&str> = entries.iter().map(|e| e.relative_path.as_str()).collect();
+                assert_eq!(paths, vec!["main.rs", "notes.md"], "{format:?}");
+                assert_eq!(entries[0].content, "fn main() {}\n", "{format:?}");
+                assert!(
+                    entries[1].content.starts_with("```\nquoted\n```"),
+                    "{format:?}"
+                );
+            }
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_create_archive_applies_header_and_footer_templates() -> io::Result<()> {
+        test_with_archiver(|archiver| {
+            // Arrange
+            let dir = tempdir()?;
+            let base_path = dir.path();
+            let src_dir = base_path.join("src");
+            fs::create_dir_all(&src_dir)?;
+            fs::write(src_dir.join("lib.rs"), "pub fn a() {}\npub fn b() {}")?;
+            let nodes = vec![new_test_file_node(
+                base_path,
+                "src",
+                true,
+                SelectionState::Selected,
+                vec![new_test_file_node(
+                    &src_dir,
+                    "lib.rs",
+                    false,
+                    SelectionState::Selected,
+                    vec![],
+                )],
+            )];
+            let mut request = ArchiveRequest::new(&nodes, base_path);
+            request.profile_name = "Backend".to_string();
+            request.options.templates = ArchiveTemplates {
+                preamble: Some("# Profile {profile}".to_string()),
+                file_header: Some("--- START {file_name} ({extension}, {line_count} lines, {token_count} tokens) ---".to_string()),
+                file_footer: Some("--- END {{{relative_path}}} sha={sha256} ---".to_string()),
+            };
+
+            // Act
+            let archive = archiver.create_content(&request)?;
+
+            // Assert
+            let relative = PathBuf::from("src").join("lib.rs");
+            let sha = crate::core::checksum_utils::calculate_sha256_for_bytes(
+                b"pub fn a() {}\npub fn b() {}",
+            );
+            let expected = format!(
+                "# Profile Backend\n\
+                 --- START lib.rs (rs, 2 lines, 8 tokens) ---\n\
+                 pub fn a() {{}}\n\
+                 pub fn b() {{}}\n\
+                 --- END {{{}}} sha={sha} ---\n",
+                relative.display()
+            );
+            assert_eq!(archive, expected);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_create_archive_with_empty_templates_omits_lines() -> io::Result<()> {
+        test_with_archiver(|archiver| {
+            // Arrange
+            let dir = tempdir()?;
+            let base_path = dir.path();
+            fs::write(base_path.join("a.txt"), "alpha\n")?;
+            let nodes = vec![new_test_file_node(
+                base_path,
+                "a.txt",
+                false,
+                SelectionState::Selected,
+                vec![],
+            )];
+            let mut request = ArchiveRequest::new(&nodes, base_path);
+            request.options.templates.preamble = Some(String::new());
+            request.options.templates.file_header = Some(String::new());
+
+            // Act
+            let archive = archiver.create_content(&request)?;
+
+            // Assert
+            assert_eq!(archive, "alpha\n");
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_create_archive_rejects_invalid_template() {
+        test_with_archiver(|archiver| {
+            // Arrange
+            let nodes: Vec<FileNode> = Vec::new();
+            let mut request = ArchiveRequest::new(&nodes, Path::new("/root"));
+            request.options.templates.file_header = Some("{unknown}".to_string());
+
+            // Act
+            let result = archiver.create_content(&request);
+
+            // Assert
+            assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidInput);
+        });
+    }
+
+    #[test]
+    fn test_write_archive_to_file_replaces_archive_and_reports_totals() -> io::Result<()> {
+        test_with_archiver(|archiver| {
+            // Arrange
+            let dir = tempdir()?;
+            let base_path = dir.path();
+            fs::write(base_path.join("a.txt"), "alpha\n")?;
+            fs::write(base_path.join("b.txt"), "beta\n")?;
+            let archive_path = base_path.join("out").join("archive.txt");
+            fs::create_dir_all(archive_path.parent().unwrap())?;
+            fs::write(&archive_path, "previous archive")?;
+            let nodes = vec![
+                new_test_file_node(base_path, "a.txt", false, SelectionState::Selected, vec![]),
+                new_test_file_node(base_path, "b.txt", false, SelectionState::Selected, vec![]),
+            ];
+            let request = ArchiveRequest::new(&nodes, base_path);
+
+            // Act
+            let report = archiver.write_archive_to_file(&request, &archive_path)?;
+
+            // Assert
+            let written = fs::read_to_string(&archive_path)?;
+            assert_eq!(written, archiver.create_content(&request)?);
+            assert_eq!(report.files_written, 2);
+            assert_eq!(report.bytes_written, written.len() as u64);
+            let leftovers = fs::read_dir(archive_path.parent().unwrap())?.count();
+            assert_eq!(leftovers, 1, "Only the archive itself should remain");
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_write_archive_to_file_keeps_previous_archive_when_source_missing() -> io::Result<()> {
+        test_with_archiver(|archiver| {
+            // Arrange
+            let dir = tempdir()?;
+            let base_path = dir.path();
+            fs::write(base_path.join("a.txt"), "alpha\n")?;
+            let archive_path = base_path.join("archive.txt");
+            fs::write(&archive_path, "previous archive")?;
+            let nodes = vec![
+                new_test_file_node(base_path, "a.txt", false, SelectionState::Selected, vec![]),
+                new_test_file_node(
+                    base_path,
+                    "gone.txt",
+                    false,
+                    SelectionState::Selected,
+                    vec![],
+                ),
+            ];
+
+            // Act
+            let result = archiver
+                .write_archive_to_file(&ArchiveRequest::new(&nodes, base_path), &archive_path);
+
+            // Assert
+            match result {
+                Err(ArchiveError::ReadSource { path, source }) => {
+                    assert_eq!(path, base_path.join("gone.txt"));
+                    assert_eq!(source.kind(), io::ErrorKind::NotFound);
+                }
+                other => panic!("Expected ReadSource error, got {other:?}"),
+            }
+            assert_eq!(fs::read_to_string(&archive_path)?, "previous archive");
+            assert_eq!(
+                fs::read_dir(base_path)?.count(),
+                2,
+                "Temp file must be cleaned up"
+            );
+            Ok(())
+        })
+    }
+
+    fn words(prefix: &str, count: usize) -> String {
+        (0..count)
+            .map(|i| format!("{prefix}{i}"))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn split_request<'a>(
+        nodes: &'a [FileNode],
+        base_path: &'a Path,
+        split: ArchiveSplitOptions,
+    ) -> ArchiveRequest<'a> {
+        let mut request = ArchiveRequest::new(nodes, base_path);
+        request.options.split = Some(split);
+        request
+    }
+
+    #[test]
+    fn test_split_archive_by_token_budget_writes_bounded_parts() -> io::Result<()> {
+        test_with_archiver(|archiver| {
+            // Arrange
+            let dir = tempdir()?;
+            let base_path = dir.path();
+            let mut nodes = Vec::new();
+            for name in ["a.txt", "b.txt", "c.txt"] {
+                fs::write(base_path.join(name), words("w", 30))?;
+                nodes.push(new_test_file_node(
+                    base_path,
+                    name,
+                    false,
+                    SelectionState::Selected,
+                    vec![],
+                ));
+            }
+            let archive_path = base_path.join("archive.txt");
+            fs::write(&archive_path, "old single-file archive")?;
+            fs::write(base_path.join("archive.part4.txt"), "stale part")?;
+            let split = ArchiveSplitOptions {
+                strategy: ArchiveSplitStrategy::TokenBudget,
+                max_tokens_per_part: 60,
+            };
+
+            // Act
+            let report = archiver
+                .write_archive_to_file(&split_request(&nodes, base_path, split), &archive_path)
+                .map_err(io::Error::from)?;
+
+            // Assert
+            let expected_parts: Vec<PathBuf> = (1..=3)
+                .map(|n| base_path.join(format!("archive.part{n}.txt")))
+                .collect();
+            assert_eq!(report.part_paths, expected_parts);
+            assert_eq!(report.files_written, 3);
+            let counter = SimpleWhitespaceTokenCounter::new();
+            for part_path in &expected_parts {
+                let text = fs::read_to_string(part_path)?;
+                assert!(counter.count_tokens(&text) <= 60, "{part_path:?}:\n{text}");
+                assert_eq!(ArchiveFormat::PlainText.parse(&text)?.len(), 1);
+            }
+            let first = fs::read_to_string(&expected_parts[0])?;
+            assert!(first.contains("// Part 1 of 3\n"), "{first}");
+            assert!(first.contains("//   part 2: b.txt\n"), "{first}");
+            assert!(first.contains("//   part 3: c.txt\n"), "{first}");
+            assert!(
+                !archive_path.exists(),
+                "Single-file archive must be removed"
+            );
+            assert!(!base_path.join("archive.part4.txt").exists());
+            assert_eq!(
+                archiver.check_status(Some(&archive_path), &nodes, None),
+                ArchiveStatus::UpToDate
+            );
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_split_archive_divides_oversized_file_on_line_boundaries() -> io::Result<()> {
+        test_with_archiver(|archiver| {
+            // Arrange
+            let dir = tempdir()?;
+            let base_path = dir.path();
+            let big_content: String = (0..10)
+                .map(|i| words(&format!("l{i}_"), 10) + "\n")
+                .collect();
+            fs::write(base_path.join("big.txt"), &big_content)?;
+            let nodes = vec![new_test_file_node(
+                base_path,
+                "big.txt",
+                false,
+                SelectionState::Selected,
+                vec![],
+            )];
+            let split = ArchiveSplitOptions {
+                strategy: ArchiveSplitStrategy::TokenBudget,
+                max_tokens_per_part: 50,
+            };
+
+            // Act
+            let report = archiver
+                .write_archive_to_file(
+                    &split_request(&nodes, base_path, split),
+                    &base_path.join("archive.txt"),
+                )
+                .map_err(io::Error::from)?;
+
+            // Assert
+            assert!(report.part_paths.len() > 2, "{:?}", report.part_paths);
+            let mut reassembled = String::new();
+            for part_path in &report.part_paths {
+                let text = fs::read_to_string(part_path)?;
+                let entries = ArchiveFormat::PlainText.parse(&text)?;
+                assert_eq!(entries.len(), 1);
+                assert_eq!(entries[0].relative_path, "big.txt");
+                reassembled.push_str(&entries[0].content);
+            }
+            assert_eq!(reassembled, big_content);
+            let first = fs::read_to_string(&report.part_paths[0])?;
+            assert!(first.contains("//   part 2: big.txt (lines "), "{first}");
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_split_archive_divides_file_that_only_exceeds_the_budget_after_overhead()
+    -> io::Result<()> {
+        test_with_archiver(|archiver| {
+            // Arrange - the entry fits into 50 tokens, but not next to the part header
+            let dir = tempdir()?;
+            let base_path = dir.path();
+            let content: String = (0..4)
+                .map(|i| words(&format!("l{i}_"), 10) + "\n")
+                .collect();
+            fs::write(base_path.join("near.txt"), &content)?;
+            let nodes = vec![new_test_file_node(
+                base_path,
+                "near.txt",
+                false,
+                SelectionState::Selected,
+                vec![],
+            )];
+            let split = ArchiveSplitOptions {
+                strategy: ArchiveSplitStrategy::TokenBudget,
+                max_tokens_per_part: 50,
+            };
+
+            // Act
+            let report = archiver
+                .write_archive_to_file(
+                    &split_request(&nodes, base_path, split),
+                    &base_path.join("archive.txt"),
+                )
+                .map_err(io::Error::from)?;
 
             // Assert
-            assert!(result.is_err());
-            assert_eq!(result.err().unwrap().kind(), io::ErrorKind::NotFound);
-        });
+            assert!(report.part_paths.len() > 1, "{:?}", report.part_paths);
+            let counter = SimpleWhitespaceTokenCounter::new();
+            let mut reassembled = String::new();
+            for part_path in &report.part_paths {
+                let text = fs::read_to_string(part_path)?;
+                assert!(counter.count_tokens(&text) <= 50, "{part_path:?}:\n{text}");
+                reassembled.push_str(&ArchiveFormat::PlainText.parse(&text)?[0].content);
+            }
+            assert_eq!(reassembled, content);
+            Ok(())
+        })
     }
 
     #[test]
-    fn test_core_archiver_check_archive_status_outdated() -> io::Result<()> {
+    fn test_split_archive_by_top_level_directory() -> io::Result<()> {
         test_with_archiver(|archiver| {
             // Arrange
             let dir = tempdir()?;
-            let archive_file_path = dir.path().join("archive.txt");
-            let src_file_path1 = dir.path().join("src_file1.txt");
-            let src_file_path2 = dir.path().join("src_file2.txt");
-
-            File::create(&archive_file_path)?.write_all(b"old archive content")?;
-            thread::sleep(Duration::from_millis(20));
-
-            File::create(&src_file_path1)?.write_all(b"source1")?;
-            thread::sleep(Duration::from_millis(20));
-            File::create(&src_file_path2)?.write_all(b"source2")?;
-
-            let file_nodes = vec![
+            let base_path = dir.path();
+            let src_path = base_path.join("src");
+            let docs_path = base_path.join("docs");
+            fs::create_dir_all(&src_path)?;
+            fs::create_dir_all(&docs_path)?;
+            fs::write(src_path.join("a.rs"), "fn a() {}\n")?;
+            fs::write(src_path.join("b.rs"), "fn b() {}\n")?;
+            fs::write(docs_path.join("guide.md"), "# Guide\n")?;
+            fs::write(base_path.join("README.md"), "readme\n")?;
+            let file = |base: &Path, name: &str| {
+                new_test_file_node(base, name, false, SelectionState::Selected, vec![])
+            };
+            let nodes = vec![
                 new_test_file_node(
-                    dir.path(),
-                    "src_file1.txt",
-                    false,
-                    SelectionState::Selected,
-                    vec![],
+                    base_path,
+                    "docs",
+                    true,
+                    SelectionState::New,
+                    vec![file(&docs_path, "guide.md")],
                 ),
                 new_test_file_node(
-                    dir.path(),
-                    "src_file2.txt",
-                    false,
-                    SelectionState::Selected,
-                    vec![],
+                    base_path,
+                    "src",
+                    true,
+                    SelectionState::New,
+                    vec![file(&src_path, "a.rs"), file(&src_path, "b.rs")],
                 ),
+                file(base_path, "README.md"),
             ];
+            let split = ArchiveSplitOptions {
+                strategy: ArchiveSplitStrategy::TopLevelDirectory,
+                max_tokens_per_part: 0,
+            };
 
             // Act
-            let status = archiver.check_status(Some(&archive_file_path), &file_nodes);
+            let report = archiver
+                .write_archive_to_file(
+                    &split_request(&nodes, base_path, split),
+                    &base_path.join("archive.txt"),
+                )
+                .map_err(io::Error::from)?;
 
             // Assert
-            assert_eq!(status, ArchiveStatus::OutdatedRequiresUpdate);
+            let part_files: Vec<Vec<String>> = report
+                .part_paths
+                .iter()
+                .map(|p| {
+                    let text = fs::read_to_string(p).unwrap();
+                    ArchiveFormat::PlainText
+                        .parse(&text)
+                        .unwrap()
+                        .into_iter()
+                        .map(|e| e.relative_path.replace('\\', "/"))
+                        .collect()
+                })
+                .collect();
+            assert_eq!(
+                part_files,
+                vec![
+                    vec!["docs/guide.md".to_string()],
+                    vec!["src/a.rs".to_string(), "src/b.rs".to_string()],
+                    vec!["README.md".to_string()],
+                ]
+            );
             Ok(())
         })
     }
 
     #[test]
-    fn test_core_archiver_check_archive_status_not_generated() {
+    fn test_directory_tree_precedes_files_and_marks_selection() -> io::Result<()> {
         test_with_archiver(|archiver| {
             // Arrange
-            let file_nodes = vec![];
+            let dir = tempdir()?;
+            let base_path = dir.path();
+            let src_dir = base_path.join("src");
+            fs::create_dir_all(&src_dir)?;
+            fs::write(src_dir.join("main.rs"), "fn main() {}\n")?;
+            fs::write(src_dir.join("extra.rs"), "// not archived\n")?;
+            let nodes = vec![new_test_file_node(
+                base_path,
+                "src",
+                true,
+                SelectionState::New,
+                vec![
+                    new_test_file_node(
+                        &src_dir,
+                        "main.rs",
+                        false,
+                        SelectionState::Selected,
+                        vec![],
+                    ),
+                    new_test_file_node(&src_dir, "extra.rs", false, SelectionState::New, vec![]),
+                ],
+            )];
+            let mut request = ArchiveRequest::new(&nodes, base_path);
+            request.options.directory_tree = Some(DirectoryTreeOptions {
+                include_unselected: true,
+                max_depth: None,
+            });
 
             // Act
-            let status = archiver.check_status(None, &file_nodes);
+            let content = archiver.create_content(&request)?;
 
             // Assert
-            assert_eq!(status, ArchiveStatus::NotYetGenerated);
-        });
+            assert!(
+                content.contains(
+                    "// Project tree:\n\
+                     // Legend: [x] included, [o] outline, [ ] not included\n\
+                     // .\n\
+                     // └── src/\n\
+                     //     ├── [x] main.rs\n\
+                     //     └── [ ] extra.rs\n\
+                     // ===== File: src/main.rs =====\n"
+                ),
+                "{content}"
+            );
+            let entries = ArchiveFormat::PlainText.parse(&content)?;
+            assert_eq!(entries.len(), 1);
+            Ok(())
+        })
     }
 
     #[test]
-    fn test_core_archiver_check_archive_status_archive_file_missing() -> io::Result<()> {
+    fn test_outline_files_are_reduced_to_signatures() -> io::Result<()> {
         test_with_archiver(|archiver| {
             // Arrange
             let dir = tempdir()?;
-            let missing_archive_path = dir.path().join("missing_archive.txt");
-            let file_nodes = vec![];
+            let base_path = dir.path();
+            fs::write(
+                base_path.join("lib.rs"),
+                "pub fn answer() -> u8 {\n    42\n}\nfn hidden() {}\n",
+            )?;
+            fs::write(base_path.join("notes.txt"), "kept in full\n")?;
+            let nodes = vec![
+                new_test_file_node(base_path, "lib.rs", false, SelectionState::Outline, vec![]),
+                new_test_file_node(
+                    base_path,
+                    "notes.txt",
+                    false,
+                    SelectionState::Outline,
+                    vec![],
+                ),
+            ];
+            let request = ArchiveRequest::new(&nodes, base_path);
 
             // Act
-            let status = archiver.check_status(Some(&missing_archive_path), &file_nodes);
+            let content = archiver.create_content(&request)?;
 
             // Assert
-            assert_eq!(status, ArchiveStatus::ArchiveFileMissing);
+            let entries = ArchiveFormat::PlainText.parse(&content)?;
+            assert_eq!(entries.len(), 2);
+            assert_eq!(entries[0].content, "pub fn answer() -> u8 { ... }\n");
+            assert_eq!(
+                entries[1].content, "kept in full\n",
+                "Files without an outline extractor are included in full"
+            );
             Ok(())
         })
     }
 
     #[test]
-    fn test_core_archiver_check_archive_status_no_files_selected() -> io::Result<()> {
+    fn test_transforms_apply_before_emit_and_budget() -> io::Result<()> {
         test_with_archiver(|archiver| {
             // Arrange
             let dir = tempdir()?;
-            let archive_file_path = dir.path().join("archive.txt");
-            File::create(&archive_file_path)?.write_all(b"archive content")?;
-
-            let file_nodes = vec![
-                new_test_file_node(
-                    dir.path(),
-                    "file1.txt",
-                    false,
-                    SelectionState::Deselected,
-                    vec![],
-                ),
-                new_test_file_node(dir.path(), "file2.txt", false, SelectionState::New, vec![]),
-            ];
+            let base_path = dir.path();
+            fs::write(base_path.join("a.rs"), "// one two three\nfn main() {}\n")?;
+            fs::write(base_path.join("notes.md"), "// alpha  \n")?;
+            let nodes: Vec<FileNode> = ["a.rs", "notes.md"]
+                .iter()
+                .map(|name| {
+                    new_test_file_node(base_path, name, false, SelectionState::Selected, vec![])
+                })
+                .collect();
+            let mut request = ArchiveRequest::new(&nodes, base_path);
+            request.options.transforms =
+                content_transform::parse_setting("strip-comments@*.rs, trim-trailing-whitespace")
+                    .unwrap();
+            request.options.token_budget = Some(5);
 
             // Act
-            let status = archiver.check_status(Some(&archive_file_path), &file_nodes);
+            let mut sink = Vec::new();
+            let report = archiver
+                .write_archive(&request, &mut sink)
+                .map_err(io::Error::from)?;
 
             // Assert
-            assert_eq!(status, ArchiveStatus::NoFilesSelected);
+            let content = String::from_utf8(sink).unwrap();
+            assert!(content.contains("fn main() {}\n"));
+            assert!(!content.contains("one two three"));
+            assert!(
+                content.contains("// alpha\n"),
+                "Only .rs files lose comments"
+            );
+            assert_eq!(
+                report.transform,
+                Some(TransformTokenReport {
+                    raw_tokens: 9,
+                    transformed_tokens: 5,
+                })
+            );
+            assert!(
+                report.budget.unwrap().omitted.is_empty(),
+                "The budget applies to the transformed counts"
+            );
+
+            // Act - a malformed glob is reported instead of being ignored
+            request.options.transforms[0].glob = Some("[".to_string());
+            let result = archiver.write_archive(&request, &mut Vec::new());
+
+            // Assert
+            assert!(matches!(result, Err(ArchiveError::InvalidTransform(_))));
             Ok(())
         })
     }
 
     #[test]
-    fn test_check_archive_status_up_to_date() -> io::Result<()> {
+    fn test_secret_policy_warns_redacts_or_aborts() -> io::Result<()> {
         test_with_archiver(|archiver| {
             // Arrange
             let dir = tempdir()?;
-            let archive_file_path = dir.path().join("archive.txt");
-            let src_file_path = dir.path().join("src_file.txt");
-
-            File::create(&src_file_path)?.write_all(b"source")?;
-            thread::sleep(Duration::from_millis(20));
-            File::create(&archive_file_path)?.write_all(b"archive content")?;
-
-            let file_nodes = vec![new_test_file_node(
-                dir.path(),
-                "src_file.txt",
+            let base_path = dir.path();
+            let archive_path = base_path.join("archive.txt");
+            fs::write(
+                base_path.join(".env"),
+                "DEBUG=1\nAPI_KEY=\"k3y-ab12cd34ef56\"\n",
+            )?;
+            let nodes = vec![new_test_file_node(
+                base_path,
+                ".env",
                 false,
                 SelectionState::Selected,
                 vec![],
             )];
+            let mut request = ArchiveRequest::new(&nodes, base_path);
+            let expected_finding = SecretFinding {
+                relative_path: ".env".to_string(),
+                line: 2,
+                rule: "generic-assignment".to_string(),
+            };
+
+            // Act & Assert - warn keeps the content
+            let mut sink = Vec::new();
+            let report = archiver
+                .write_archive(&request, &mut sink)
+                .map_err(io::Error::from)?;
+            assert!(
+                String::from_utf8(sink)
+                    .unwrap()
+                    .contains("k3y-ab12cd34ef56")
+            );
+            assert_eq!(
+                report.secrets,
+                Some(SecretScanReport {
+                    policy: SecretPolicy::Warn,
+                    findings: vec![expected_finding.clone()],
+                })
+            );
 
-            // Act
-            let status = archiver.check_status(Some(&archive_file_path), &file_nodes);
+            // Act & Assert - redact replaces the value
+            request.options.secret_scan.policy = SecretPolicy::Redact;
+            let mut sink = Vec::new();
+            archiver
+                .write_archive(&request, &mut sink)
+                .map_err(io::Error::from)?;
+            let content = String::from_utf8(sink).unwrap();
+            assert!(content.contains("API_KEY=\"[REDACTED:generic-assignment]\"\n"));
+            assert!(!content.contains("k3y-ab12cd34ef56"));
+
+            // Act & Assert - abort writes nothing, not even a previous archive is replaced
+            fs::write(&archive_path, "previous")?;
+            request.options.secret_scan.policy = SecretPolicy::Abort;
+            match archiver.write_archive_to_file(&request, &archive_path) {
+                Err(ArchiveError::SecretsFound(findings)) => {
+                    assert_eq!(findings, vec![expected_finding])
+                }
+                other => panic!("Expected SecretsFound, got {other:?}"),
+            }
+            assert_eq!(fs::read_to_string(&archive_path)?, "previous");
 
-            // Assert
-            assert_eq!(status, ArchiveStatus::UpToDate);
+            // Act & Assert - an allowlisted path is not scanned
+            request.options.secret_scan.allowed_paths = vec![".env".to_string()];
+            assert!(archiver.write_archive(&request, &mut Vec::new()).is_ok());
             Ok(())
         })
     }
 
     #[test]
-    fn test_check_archive_status_up_to_date_empty_archive_selected_older() -> io::Result<()> {
+    fn test_secret_findings_give_source_lines_when_transforms_remove_lines() -> io::Result<()> {
         test_with_archiver(|archiver| {
             // Arrange
             let dir = tempdir()?;
-            let archive_file_path = dir.path().join("archive.txt");
-            let src_file_path = dir.path().join("src_file.txt");
-
-            File::create(&src_file_path)?.write_all(b"source")?;
-            thread::sleep(Duration::from_millis(20));
-            File::create(&archive_file_path)?.write_all(b"")?;
-
-            let file_nodes = vec![new_test_file_node(
-                dir.path(),
-                "src_file.txt",
+            let base_path = dir.path();
+            fs::write(
+                base_path.join("config.rs"),
+                "// Settings.\n// Loaded at startup.\nlet api_key = \"k3y-ab12cd34ef56\";\n",
+            )?;
+            let nodes = vec![new_test_file_node(
+                base_path,
+                "config.rs",
                 false,
                 SelectionState::Selected,
                 vec![],
             )];
-
-            let src_ts = archiver.get_file_timestamp(&src_file_path)?;
-            let archive_ts = archiver.get_file_timestamp(&archive_file_path)?;
-            assert!(
-                archive_ts > src_ts,
-                "Test setup: archive should be newer than source"
-            );
+            let mut request = ArchiveRequest::new(&nodes, base_path);
+            request.options.transforms =
+                content_transform::parse_setting("strip-comments").unwrap();
+            request.options.secret_scan.policy = SecretPolicy::Redact;
 
             // Act
-            let status = archiver.check_status(Some(&archive_file_path), &file_nodes);
+            let mut sink = Vec::new();
+            let report = archiver
+                .write_archive(&request, &mut sink)
+                .map_err(io::Error::from)?;
 
-            // Assert
+            // Assert - the finding is on line 3 of the file, the archive holds no comments
+            let findings = report.secrets.expect("scan report").findings;
             assert_eq!(
-                status,
-                ArchiveStatus::UpToDate,
-                "Expected UpToDate when archive is newer, even if empty, and selected files are older."
+                findings,
+                vec![SecretFinding {
+                    relative_path: "config.rs".to_string(),
+                    line: 3,
+                    rule: "generic-assignment".to_string(),
+                }]
+            );
+            let content = String::from_utf8(sink).unwrap();
+            assert!(
+                content.contains(
+                    "config.rs =====\nlet api_key = \"[REDACTED:generic-assignment]\";\n"
+                ),
+                "{content}"
             );
             Ok(())
         })
     }
 
     #[test]
-    fn test_check_archive_status_error_checking_src_missing() -> io::Result<()> {
+    fn test_line_numbers_are_written_counted_and_follow_truncation() -> io::Result<()> {
         test_with_archiver(|archiver| {
             // Arrange
             let dir = tempdir()?;
-            let archive_file_path = dir.path().join("archive.txt");
-            File::create(&archive_file_path)?.write_all(b"archive")?;
+            let base_path = dir.path();
+            fs::write(base_path.join("main.rs"), "fn main() {\n    run();\n}\n")?;
+            fs::write(base_path.join("notes.txt"), "plain notes\n")?;
+            let nodes: Vec<FileNode> = ["main.rs", "notes.txt"]
+                .into_iter()
+                .map(|name| {
+                    new_test_file_node(base_path, name, false, SelectionState::Selected, vec![])
+                })
+                .collect();
+            let mut request = ArchiveRequest::new(&nodes, base_path);
+            request.options.line_numbers = Some(LineNumberOptions {
+                globs: vec!["*.rs".to_string()],
+            });
 
-            let file_nodes = vec![new_test_file_node(
-                dir.path(),
-                "missing_src.txt",
-                false,
-                SelectionState::Selected,
-                vec![],
-            )];
+            // Act
+            let mut sink = Vec::new();
+            archiver
+                .write_archive(&request, &mut sink)
+                .map_err(io::Error::from)?;
+
+            // Assert - only the matching file is numbered, and its header gives the range
+            let content = String::from_utf8(sink).unwrap();
+            assert!(content.contains(
+                "// ===== File: main.rs =====\n// Lines 1-3 of 3\n1 | fn main() {\n2 |     run();\n3 | }\n"
+            ));
+            assert!(content.contains("// ===== File: notes.txt =====\nplain notes\n"));
+
+            // Act & Assert - the budget counts the gutter, and truncation narrows the range
+            request.options.token_budget = Some(11);
+            request
+                .options
+                .file_priorities
+                .insert(base_path.join("main.rs"), FilePriority::Optional);
+            let mut sink = Vec::new();
+            let report = archiver
+                .write_archive(&request, &mut sink)
+                .map_err(io::Error::from)?;
+            let budget = report.budget.expect("budget report");
+            assert_eq!(budget.tokens_before, 2 + 11);
+            let content = String::from_utf8(sink).unwrap();
+            assert!(
+                content.contains("// Lines 1-1 of 3\n1 | fn main() {\n[truncated: 2 more lines]\n"),
+                "{content}"
+            );
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_files_are_decoded_by_bom_or_fallback_encoding() -> io::Result<()> {
+        test_with_archiver(|archiver| {
+            // Arrange
+            let dir = tempdir()?;
+            let base_path = dir.path();
+            let mut resource = vec![0xFF, 0xFE];
+            resource.extend(
+                "IDS_TITLE \"Größe\""
+                    .encode_utf16()
+                    .flat_map(u16::to_le_bytes),
+            );
+            fs::write(base_path.join("app.rc"), &resource)?;
+            fs::write(base_path.join("legacy.c"), b"/* \x93caf\xe9\x94 */\n")?;
+            let nodes: Vec<FileNode> = ["app.rc", "legacy.c"]
+                .into_iter()
+                .map(|name| {
+                    new_test_file_node(base_path, name, false, SelectionState::Selected, vec![])
+                })
+                .collect();
+            let mut request = ArchiveRequest::new(&nodes, base_path);
+            request.options.encodings = vec![EncodingRule {
+                encoding: TextEncoding::Windows1252,
+                glob: "*.c".to_string(),
+            }];
 
             // Act
-            let status = archiver.check_status(Some(&archive_file_path), &file_nodes);
+            let mut sink = Vec::new();
+            let report = archiver
+                .write_archive(&request, &mut sink)
+                .map_err(io::Error::from)?;
 
             // Assert
-            assert_eq!(
-                status,
-                ArchiveStatus::ErrorChecking(Some(io::ErrorKind::NotFound))
-            );
+            let content = String::from_utf8(sink).unwrap();
+            assert!(content.contains("IDS_TITLE \"Größe\""));
+            assert!(content.contains("/* \u{201C}café\u{201D} */"));
+            assert!(report.skipped.is_empty());
             Ok(())
         })
     }
 
     #[test]
-    fn test_create_archive_no_selected_files() -> io::Result<()> {
+    fn test_active_prompt_wraps_archive_and_first_and_last_parts() -> io::Result<()> {
         test_with_archiver(|archiver| {
             // Arrange
             let dir = tempdir()?;
             let base_path = dir.path();
-            let nodes = vec![
-                new_test_file_node(
+            let mut nodes = Vec::new();
+            for name in ["a.txt", "b.txt", "c.txt"] {
+                fs::write(base_path.join(name), words("w", 30))?;
+                nodes.push(new_test_file_node(
                     base_path,
-                    "file1.txt",
+                    name,
                     false,
-                    SelectionState::Deselected,
+                    SelectionState::Selected,
                     vec![],
-                ),
-                new_test_file_node(base_path, "file2.txt", false, SelectionState::New, vec![]),
-            ];
+                ));
+            }
+            let mut request = ArchiveRequest::new(&nodes, base_path);
+            request.profile_name = "Core".to_string();
+            request.options.prompts = PromptOptions {
+                variants: vec![PromptVariant {
+                    name: "Review".to_string(),
+                    preamble:
+                        "Review {profile}: {file_count} files, {token_count} tokens\n{file_list}"
+                            .to_string(),
+                    postamble: "Reply with findings.".to_string(),
+                }],
+                active: Some("Review".to_string()),
+            };
 
             // Act
-            let archive = archiver.create_content(&nodes, base_path)?;
+            let mut sink = Vec::new();
+            archiver
+                .write_archive(&request, &mut sink)
+                .map_err(io::Error::from)?;
 
             // Assert
-            let expected_content = format!("// Combined files from {}\n", base_path.display());
-            assert_eq!(archive, expected_content);
+            let content = String::from_utf8(sink).unwrap();
+            assert!(
+                content.starts_with(
+                    "// ===== Prompt =====\nReview Core: 3 files, 90 tokens\na.txt\nb.txt\nc.txt\n"
+                ),
+                "{content}"
+            );
+            assert!(content.ends_with("Reply with findings.\n"), "{content}");
+            assert_eq!(ArchiveFormat::PlainText.parse(&content)?.len(), 3);
+
+            // Act - a split archive carries the preamble in its first part and the
+            // postamble in its last
+            let archive_path = base_path.join("archive.txt");
+            request.options.split = Some(ArchiveSplitOptions {
+                strategy: ArchiveSplitStrategy::TokenBudget,
+                max_tokens_per_part: 60,
+            });
+            let report = archiver
+                .write_archive_to_file(&request, &archive_path)
+                .map_err(io::Error::from)?;
+
+            // Assert
+            let parts: Vec<String> = report
+                .part_paths
+                .iter()
+                .map(fs::read_to_string)
+                .collect::<io::Result<_>>()?;
+            assert_eq!(parts.len(), 3);
+            assert!(parts[0].contains("Review Core"));
+            assert!(!parts[0].contains("Reply with findings."));
+            assert!(!parts[1].contains("Review Core") && !parts[1].contains("Reply with"));
+            assert!(parts[2].ends_with("Reply with findings.\n"));
             Ok(())
         })
     }
 
     #[test]
-    fn test_create_archive_file_read_error() -> io::Result<()> {
+    fn test_binary_and_oversize_files_follow_content_policy() -> io::Result<()> {
         test_with_archiver(|archiver| {
             // Arrange
             let dir = tempdir()?;
             let base_path = dir.path();
-            let nodes = vec![new_test_file_node(
-                base_path,
-                "non_existent_file.txt",
-                false,
-                SelectionState::Selected,
-                vec![],
-            )];
+            fs::write(base_path.join("main.rs"), "fn main() {}\n")?;
+            fs::write(base_path.join("notes.txt"), b"caf\xe9 notes in Latin-1\n")?;
+            let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+            png.resize(2048, 0);
+            fs::write(base_path.join("logo.png"), &png)?;
+            let nodes: Vec<FileNode> = ["main.rs", "notes.txt", "logo.png"]
+                .into_iter()
+                .map(|name| {
+                    new_test_file_node(base_path, name, false, SelectionState::Selected, vec![])
+                })
+                .collect();
+            let mut request = ArchiveRequest::new(&nodes, base_path);
+
+            // Act & Assert - by default binary files become a placeholder line and stray
+            // invalid bytes no longer abort the archive
+            let mut sink = Vec::new();
+            let report = archiver
+                .write_archive(&request, &mut sink)
+                .map_err(io::Error::from)?;
+            let content = String::from_utf8(sink).unwrap();
+            assert!(content.contains("[binary file, 2 KB, image/png]"));
+            assert!(content.contains("caf\u{fffd} notes in Latin-1"));
+            assert_eq!(report.files_written, 3);
+            let png_class = ContentClass::Binary { mime: "image/png" };
+            assert_eq!(
+                report.skipped,
+                vec![SkippedFile {
+                    relative_path: "logo.png".to_string(),
+                    class: png_class,
+                    size: 2048,
+                    policy: ContentPolicy::Placeholder,
+                }]
+            );
 
-            // Act
-            let result = archiver.create_content(&nodes, base_path);
+            // Act & Assert - skip leaves binary and oversize files out
+            request.options.content_limits = ContentLimits {
+                max_file_size: Some(15),
+                binary: ContentPolicy::Skip,
+                oversize: ContentPolicy::Skip,
+            };
+            let mut sink = Vec::new();
+            let report = archiver
+                .write_archive(&request, &mut sink)
+                .map_err(io::Error::from)?;
+            let content = String::from_utf8(sink).unwrap();
+            assert!(content.contains("fn main() {}"));
+            assert!(!content.contains("notes.txt"));
+            assert!(!content.contains("logo.png"));
+            assert_eq!(report.files_written, 1);
+            let skipped: Vec<_> = report
+                .skipped
+                .iter()
+                .map(|s| (s.relative_path.as_str(), s.class))
+                .collect();
+            assert_eq!(
+                skipped,
+                vec![
+                    ("notes.txt", ContentClass::Oversize),
+                    ("logo.png", ContentClass::Oversize)
+                ]
+            );
 
-            // Assert
-            assert!(result.is_err());
-            if let Err(e) = result {
-                assert_eq!(e.kind(), io::ErrorKind::NotFound);
+            // Act & Assert - fail stops the generation
+            request.options.content_limits = ContentLimits {
+                binary: ContentPolicy::Fail,
+                ..ContentLimits::default()
+            };
+            match archiver.write_archive(&request, &mut Vec::new()) {
+                Err(ArchiveError::UnsupportedContent { path, class, size }) => {
+                    assert_eq!(path, base_path.join("logo.png"));
+                    assert_eq!((class, size), (png_class, 2048));
+                }
+                other => panic!("Expected UnsupportedContent, got {other:?}"),
             }
             Ok(())
         })
     }
 
     #[test]
-    fn test_create_archive_ensure_newline_before_footer() -> io::Result<()> {
+    fn test_manifest_describes_written_files() -> io::Result<()> {
         test_with_archiver(|archiver| {
             // Arrange
             let dir = tempdir()?;
             let base_path = dir.path();
+            fs::write(
+                base_path.join("a.txt"),
+                "one two
+three
+",
+            )?;
+            fs::write(base_path.join("b.txt"), words("b", 40))?;
+            let nodes: Vec<FileNode> = ["a.txt", "b.txt"]
+                .iter()
+                .map(|name| {
+                    new_test_file_node(base_path, name, false, SelectionState::Selected, vec![])
+                })
+                .collect();
+            let mut request = ArchiveRequest::new(&nodes, base_path);
+            request.profile_name = "Demo".to_string();
+            request.options.format = ArchiveFormat::Json;
+            request.options.include_manifest = true;
 
-            let file_no_trailing_newline_path = base_path.join("no_newline.txt");
-            let mut f = File::create(&file_no_trailing_newline_path)?;
-            write!(f, "Line without trailing newline")?;
-            drop(f);
+            // Act
+            let content = archiver.create_content(&request)?;
 
-            let nodes = vec![new_test_file_node(
-                base_path,
-                "no_newline.txt",
-                false,
-                SelectionState::Selected,
-                vec![],
-            )];
+            // Assert
+            let archive: serde_json::Value = serde_json::from_str(&content)?;
+            let manifest: ArchiveManifest =
+                serde_json::from_value(archive["manifest"].clone()).map_err(io::Error::from)?;
+            assert_eq!(manifest.profile, "Demo");
+            assert_eq!(manifest.files.len(), 2);
+            assert_eq!(manifest.files[0].path, "a.txt");
+            assert_eq!(manifest.files[0].bytes, 14);
+            assert_eq!(manifest.files[0].lines, 2);
+            assert_eq!(manifest.files[0].tokens, 3);
+            assert_eq!(
+                manifest.files[0].sha256,
+                crate::core::checksum_utils::calculate_sha256_for_bytes(b"one two\nthree\n")
+            );
+            assert_eq!(manifest.total_tokens, 43);
+            assert_eq!(ArchiveFormat::Json.parse(&content)?.len(), 2);
+
+            // Act - each part of a split archive lists its own files
+            request.options.format = ArchiveFormat::PlainText;
+            request.options.split = Some(ArchiveSplitOptions {
+                strategy: ArchiveSplitStrategy::TokenBudget,
+                max_tokens_per_part: 100,
+            });
+            let archive_path = base_path.join("out").join("archive.txt");
+            fs::create_dir(base_path.join("out"))?;
+            let report = archiver
+                .write_archive_to_file(&request, &archive_path)
+                .map_err(io::Error::from)?;
+
+            // Assert
+            assert_eq!(report.part_paths.len(), 2);
+            let first = fs::read_to_string(&report.part_paths[0])?;
+            let second = fs::read_to_string(&report.part_paths[1])?;
+            assert!(first.contains("// a.txt | 14 | 2 | 3 | "), "{first}");
+            assert!(first.contains("// Total: 1 files, "), "{first}");
+            assert!(second.contains("// b.txt | "), "{second}");
+            for part in [&first, &second] {
+                assert_eq!(ArchiveFormat::PlainText.parse(part)?.len(), 1);
+            }
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_token_budget_drops_and_truncates_lowest_priority_first() -> io::Result<()> {
+        test_with_archiver(|archiver| {
+            // Arrange
+            let dir = tempdir()?;
+            let base_path = dir.path();
+            fs::write(base_path.join("a.txt"), words("a", 10))?;
+            fs::write(
+                base_path.join("b.txt"),
+                format!("{}\n{}\n", words("b", 10), words("c", 10)),
+            )?;
+            fs::write(base_path.join("c.txt"), words("p", 10))?;
+            let nodes: Vec<FileNode> = ["a.txt", "b.txt", "c.txt"]
+                .iter()
+                .map(|name| {
+                    new_test_file_node(base_path, name, false, SelectionState::Selected, vec![])
+                })
+                .collect();
+            let mut request = ArchiveRequest::new(&nodes, base_path);
+            request.options.token_budget = Some(27);
+            request
+                .options
+                .file_priorities
+                .insert(base_path.join("a.txt"), FilePriority::Optional);
+            request
+                .options
+                .file_priorities
+                .insert(base_path.join("c.txt"), FilePriority::Pinned);
+            // The cached count (checksum matches the node) is preferred over re-counting.
+            request.token_cache.insert(
+                base_path.join("a.txt"),
+                FileTokenDetails {
+                    checksum: String::new(),
+                    token_count: 12,
+                },
+            );
 
             // Act
-            let archive = archiver.create_content(&nodes, base_path)?;
+            let mut sink = Vec::new();
+            let report = archiver
+                .write_archive(&request, &mut sink)
+                .map_err(io::Error::from)?;
 
             // Assert
-            let root_display_str = base_path.display();
-            let expected_content = format!(
-                "// Combined files from {root_display_str}\n\
-                 // ===== File: no_newline.txt =====\n\
-                 Line without trailing newline\n"
+            let budget = report.budget.expect("Budget report expected");
+            assert_eq!(budget.tokens_before, 42);
+            assert_eq!(budget.tokens_after, 27);
+            assert_eq!(
+                budget
+                    .omitted
+                    .iter()
+                    .map(|o| (o.path.clone(), o.original_tokens, o.action))
+                    .collect::<Vec<_>>(),
+                vec![
+                    (base_path.join("a.txt"), 12, BudgetAction::Dropped),
+                    (
+                        base_path.join("b.txt"),
+                        20,
+                        BudgetAction::Truncated { kept_tokens: 17 }
+                    ),
+                ]
+            );
+            assert_eq!(report.files_written, 2);
+            let entries = ArchiveFormat::PlainText.parse(&String::from_utf8(sink).unwrap())?;
+            let contents: Vec<(&str, &str)> = entries
+                .iter()
+                .map(|e| (e.relative_path.as_str(), e.content.as_str()))
+                .collect();
+            let b_first_line = format!("{}\n[truncated: 1 more line]\n", words("b", 10));
+            let c_content = format!("{}\n", words("p", 10));
+            assert_eq!(
+                contents,
+                vec![
+                    ("b.txt", b_first_line.as_str()),
+                    ("c.txt", c_content.as_str())
+                ]
             );
-            assert_eq!(archive, expected_content);
             Ok(())
         })
     }
@@ -0,0 +1,354 @@
+/*
+ * Provides crash-safe file replacement. Content is streamed into a uniquely named
+ * temporary file in the destination directory, flushed and synced, and only then renamed
+ * over the destination. Readers therefore see either the previous file or the complete new
+ * one, never a truncated file. On any error the temporary file is removed. Files that
+ * belong together are staged as an `AtomicFileSet` and only renamed once all are written.
+ */
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+static TEMP_FILE_COUNTER: AtomicU64 = AtomicU64::new(0);
+const MAX_TEMP_NAME_ATTEMPTS: u32 = 16;
+
+/*
+ * Deletes the temporary file when dropped, unless it has been renamed into place.
+ */
+struct TempFileGuard {
+    path: PathBuf,
+    persisted: bool,
+}
+
+impl Drop for TempFileGuard {
+    fn drop(&mut self) {
+        if !self.persisted
+            && let Err(e) = fs::remove_file(&self.path)
+        {
+            log::warn!(
+                "AtomicWrite: Failed to remove temporary file {:?}: {e}",
+                self.path
+            );
+        }
+    }
+}
+
+/*
+ * Creates a uniquely named, empty temporary file in the directory of `destination`,
+ * opened for reading and writing.
+ */
+pub(crate) fn create_temp_file_next_to(destination: &Path) -> io::Result<(File, PathBuf)> {
+    let directory = match destination.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    let file_name = destination
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Destination {destination:?} has no file name"),
+            )
+        })?;
+
+    let mut last_error = None;
+    for _ in 0..MAX_TEMP_NAME_ATTEMPTS {
+        let unique = TEMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed);
+        let candidate = directory.join(format!(".{file_name}.{}.{unique}.tmp", std::process::id()));
+        match OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&candidate)
+        {
+            Ok(file) => return Ok((file, candidate)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => last_error = Some(e),
+            Err(e) => return Err(e),
+        }
+    }
+    Err(last_error.unwrap_or_else(|| io::Error::other("Could not create a temporary file")))
+}
+
+/*
+ * Streams the bytes produced by `write_fn` into a temporary file next to `destination`,
+ * flushed and synced. The returned guard removes the file unless it is renamed into place.
+ */
+fn write_temp_file<T, E>(
+    destination: &Path,
+    write_fn: impl FnOnce(&mut dyn Write) -> Result<T, E>,
+) -> Result<(T, TempFileGuard), E>
+where
+    E: From<io::Error>,
+{
+    let (file, temp_path) = create_temp_file_next_to(destination)?;
+    let guard = TempFileGuard {
+        path: temp_path,
+        persisted: false,
+    };
+
+    let mut writer = BufWriter::new(file);
+    let value = write_fn(&mut writer)?;
+    let file = writer.into_inner().map_err(|e| e.into_error())?;
+    file.sync_all()?;
+    drop(file);
+    Ok((value, guard))
+}
+
+/*
+ * Replaces `destination` with the bytes produced by `write_fn`.
+ * The closure receives a buffered writer backed by a temporary file in the destination
+ * directory (so the final rename never crosses file systems). The error type is generic
+ * so callers can keep their own error classification; I/O failures of the temp file itself
+ * are converted with `From<io::Error>`.
+ */
+pub fn write_file_atomically<T, E>(
+    destination: &Path,
+    write_fn: impl FnOnce(&mut dyn Write) -> Result<T, E>,
+) -> Result<T, E>
+where
+    E: From<io::Error>,
+{
+    let (value, mut guard) = write_temp_file(destination, write_fn)?;
+    fs::rename(&guard.path, destination)?;
+    guard.persisted = true;
+    log::debug!("AtomicWrite: Replaced {destination:?} via {:?}", guard.path);
+    Ok(value)
+}
+
+/*
+ * Replaces several files as a set, such as the parts of a split archive. Every file is
+ * first written to its own temporary file; only when all of them were written does
+ * `commit` rename them into place. If any write fails, or the set is dropped without a
+ * commit, the temporary files are removed and all destinations keep their previous
+ * content.
+ */
+#[derive(Default)]
+pub struct AtomicFileSet {
+    staged: Vec<(TempFileGuard, PathBuf)>,
+}
+
+impl AtomicFileSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /* Stages the content of `destination`; nothing is replaced until `commit`. */
+    pub fn write<T, E>(
+        &mut self,
+        destination: &Path,
+        write_fn: impl FnOnce(&mut dyn Write) -> Result<T, E>,
+    ) -> Result<T, E>
+    where
+        E: From<io::Error>,
+    {
+        let (value, guard) = write_temp_file(destination, write_fn)?;
+        self.staged.push((guard, destination.to_path_buf()));
+        Ok(value)
+    }
+
+    /*
+     * Renames every staged file into place. The existing destinations are first moved
+     * aside to backups; if any rename fails, the files already renamed are removed and the
+     * backups restored, so either all destinations are replaced or none.
+     */
+    pub fn commit(self) -> io::Result<()> {
+        self.commit_with(|from, to| fs::rename(from, to))
+    }
+
+    fn commit_with(
+        mut self,
+        mut rename: impl FnMut(&Path, &Path) -> io::Result<()>,
+    ) -> io::Result<()> {
+        let mut backups: Vec<(PathBuf, PathBuf)> = Vec::new();
+        let mut placed: Vec<PathBuf> = Vec::new();
+        let mut result = Ok(());
+        for (_, destination) in &self.staged {
+            if !destination.exists() {
+                continue;
+            }
+            let backup = create_temp_file_next_to(destination).map(|(_, path)| path);
+            match backup.and_then(|backup| {
+                rename(destination, &backup)
+                    .inspect_err(|_| remove_or_warn(&backup))
+                    .map(|()| backup)
+            }) {
+                Ok(backup) => backups.push((backup, destination.clone())),
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            }
+        }
+        if result.is_ok() {
+            for (guard, destination) in &mut self.staged {
+                if let Err(e) = rename(&guard.path, destination) {
+                    result = Err(e);
+                    break;
+                }
+                guard.persisted = true;
+                placed.push(destination.clone());
+            }
+        }
+
+        if let Err(e) = result {
+            for destination in &placed {
+                remove_or_warn(destination);
+            }
+            for (backup, destination) in &backups {
+                if let Err(restore_error) = rename(backup, destination) {
+                    log::error!(
+                        "AtomicWrite: Failed to restore {destination:?} from {backup:?}: {restore_error}"
+                    );
+                }
+            }
+            return Err(e);
+        }
+        for (backup, _) in &backups {
+            remove_or_warn(backup);
+        }
+        for (guard, destination) in &self.staged {
+            log::debug!("AtomicWrite: Replaced {destination:?} via {:?}", guard.path);
+        }
+        Ok(())
+    }
+}
+
+fn remove_or_warn(path: &Path) {
+    if let Err(e) = fs::remove_file(path) {
+        log::warn!("AtomicWrite: Failed to remove {path:?}: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn leftover_temp_files(dir: &Path) -> Vec<PathBuf> {
+        fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().path())
+            .filter(|p| p.extension().is_some_and(|ext| ext == "tmp"))
+            .collect()
+    }
+
+    #[test]
+    fn test_write_file_atomically_replaces_existing_file() {
+        // Arrange
+        let dir = tempdir().unwrap();
+        let target = dir.path().join("archive.txt");
+        fs::write(&target, "old content").unwrap();
+
+        // Act
+        let result: io::Result<usize> = write_file_atomically(&target, |out| {
+            out.write_all(b"new ")?;
+            out.write_all(b"content")?;
+            Ok(2)
+        });
+
+        // Assert
+        assert_eq!(result.unwrap(), 2);
+        assert_eq!(fs::read_to_string(&target).unwrap(), "new content");
+        assert!(leftover_temp_files(dir.path()).is_empty());
+    }
+
+    #[test]
+    fn test_write_file_atomically_keeps_old_file_on_error() {
+        // Arrange
+        let dir = tempdir().unwrap();
+        let target = dir.path().join("archive.txt");
+        fs::write(&target, "old content").unwrap();
+
+        // Act
+        let result: io::Result<()> = write_file_atomically(&target, |out| {
+            out.write_all(b"partial")?;
+            Err(io::Error::other("simulated failure"))
+        });
+
+        // Assert
+        assert!(result.is_err());
+        assert_eq!(fs::read_to_string(&target).unwrap(), "old content");
+        assert!(leftover_temp_files(dir.path()).is_empty());
+    }
+
+    #[test]
+    fn test_file_set_replaces_nothing_when_one_file_fails() {
+        // Arrange
+        let dir = tempdir().unwrap();
+        let (first, second) = (dir.path().join("part1.txt"), dir.path().join("part2.txt"));
+        fs::write(&first, "old first").unwrap();
+        fs::write(&second, "old second").unwrap();
+
+        // Act
+        let mut set = AtomicFileSet::new();
+        set.write(&first, |out| out.write_all(b"new first"))
+            .unwrap();
+        let result: io::Result<()> = set.write(&second, |out| {
+            out.write_all(b"partial")?;
+            Err(io::Error::other("simulated failure"))
+        });
+        drop(set);
+
+        // Assert
+        assert!(result.is_err());
+        assert_eq!(fs::read_to_string(&first).unwrap(), "old first");
+        assert_eq!(fs::read_to_string(&second).unwrap(), "old second");
+        assert!(leftover_temp_files(dir.path()).is_empty());
+
+        let mut set = AtomicFileSet::new();
+        set.write(&first, |out| out.write_all(b"new first"))
+            .unwrap();
+        set.write(&second, |out| out.write_all(b"new second"))
+            .unwrap();
+        set.commit().unwrap();
+        assert_eq!(fs::read_to_string(&first).unwrap(), "new first");
+        assert_eq!(fs::read_to_string(&second).unwrap(), "new second");
+        assert!(leftover_temp_files(dir.path()).is_empty());
+    }
+
+    #[test]
+    fn test_file_set_restores_all_files_when_one_rename_fails() {
+        // Arrange
+        let dir = tempdir().unwrap();
+        let (first, second) = (dir.path().join("part1.txt"), dir.path().join("part2.txt"));
+        let third = dir.path().join("part3.txt");
+        fs::write(&first, "old first").unwrap();
+        fs::write(&second, "old second").unwrap();
+        let mut set = AtomicFileSet::new();
+        for (path, content) in [
+            (&first, "new first"),
+            (&second, "new second"),
+            (&third, "new third"),
+        ] {
+            set.write(path, |out| out.write_all(content.as_bytes()))
+                .unwrap();
+        }
+
+        // Act - the rename of the second part into place fails
+        let result = set.commit_with(|from, to| {
+            if to == second && fs::read_to_string(from).is_ok_and(|c| c == "new second") {
+                return Err(io::Error::other("simulated rename failure"));
+            }
+            fs::rename(from, to)
+        });
+
+        // Assert
+        assert!(result.is_err());
+        assert_eq!(fs::read_to_string(&first).unwrap(), "old first");
+        assert_eq!(fs::read_to_string(&second).unwrap(), "old second");
+        assert!(!third.exists());
+        assert!(leftover_temp_files(dir.path()).is_empty());
+    }
+
+    #[test]
+    fn test_write_file_atomically_fails_for_missing_directory() {
+        let dir = tempdir().unwrap();
+        let target = dir.path().join("missing").join("archive.txt");
+
+        let result: io::Result<()> = write_file_atomically(&target, |out| out.write_all(b"x"));
+
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::NotFound);
+    }
+}
@@ -0,0 +1,344 @@
+use super::archive_path_template::{self, ArchivePathContext};
+use super::archiver::{ArchiveRequest, ArchiverOperations};
+use super::composite_profile::ProjectComponentLoader;
+use super::file_system::FileSystemScannerOperations;
+use super::node_state_applicator::NodeStateApplicatorOperations;
+use super::profile_runtime_data::{ProfileRuntimeData, ProfileRuntimeDataOperations};
+use super::profiles::ProfileManagerOperations;
+use super::project_context::{ProfileName, ProjectContext};
+use super::tokenizer_utils::TokenCounterOperations;
+use std::fmt::Write as _;
+use std::path::PathBuf;
+use std::sync::{Arc, mpsc};
+use std::thread;
+
+/*
+ * This module regenerates the archives of every profile of a project in one go.
+ * [ArchiveBatchGenerateV1] Each profile is loaded into a `ProfileRuntimeData` of its own,
+ * so the session the user is working in is left untouched. The profile is scanned, its
+ * saved selection applied, and its archive written to its resolved `archive_path` with the
+ * profile's own options, archive record and history, as "Generate Archive" would for it.
+ * Profiles without an archive path are skipped. The work runs on a background thread that
+ * reports each profile as it completes, followed by a summary of all of them.
+ */
+
+/* The services a batch run needs, shared with the worker thread. */
+#[derive(Clone)]
+pub struct BatchServices {
+    pub profile_manager: Arc<dyn ProfileManagerOperations>,
+    pub file_system_scanner: Arc<dyn FileSystemScannerOperations>,
+    pub state_manager: Arc<dyn NodeStateApplicatorOperations>,
+    pub archiver: Arc<dyn ArchiverOperations>,
+    pub token_counter: Arc<dyn TokenCounterOperations>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BatchOutcome {
+    Written {
+        archive_path: PathBuf,
+        files_written: usize,
+        part_count: usize,
+    },
+    SkippedNoArchivePath,
+    Failed(String),
+}
+
+impl BatchOutcome {
+    /* The heading the outcome is listed under in the summary. */
+    fn group_title(&self) -> &'static str {
+        match self {
+            BatchOutcome::Written { .. } => "Written",
+            BatchOutcome::SkippedNoArchivePath => "Skipped",
+            BatchOutcome::Failed(_) => "Failed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchProfileResult {
+    pub profile_name: String,
+    pub outcome: BatchOutcome,
+}
+
+impl BatchProfileResult {
+    /* A one-line description, used both for progress and in the summary. */
+    pub fn describe(&self) -> String {
+        match &self.outcome {
+            BatchOutcome::Written {
+                archive_path,
+                files_written,
+                part_count,
+            } => {
+                let parts = if *part_count > 0 {
+                    format!(" in {part_count} parts")
+                } else {
+                    String::new()
+                };
+                format!(
+                    "'{}': {files_written} file(s) written to '{}'{parts}",
+                    self.profile_name,
+                    archive_path.display()
+                )
+            }
+            BatchOutcome::SkippedNoArchivePath => {
+                format!("'{}': skipped, no archive path set", self.profile_name)
+            }
+            BatchOutcome::Failed(message) => format!("'{}': failed: {message}", self.profile_name),
+        }
+    }
+}
+
+/* The results of a batch run, in the order the profiles were listed. */
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BatchSummary {
+    pub results: Vec<BatchProfileResult>,
+}
+
+impl BatchSummary {
+    fn in_group(&self, title: &str) -> impl Iterator<Item = &BatchProfileResult> {
+        self.results
+            .iter()
+            .filter(move |r| r.outcome.group_title() == title)
+    }
+
+    pub fn written_count(&self) -> usize {
+        self.in_group("Written").count()
+    }
+
+    pub fn failed_count(&self) -> usize {
+        self.in_group("Failed").count()
+    }
+
+    pub fn skipped_count(&self) -> usize {
+        self.in_group("Skipped").count()
+    }
+
+    /* The text shown in the viewer once the run has finished, grouped by outcome. */
+    pub fn render(&self) -> String {
+        let mut text = format!(
+            "Generated {} archive(s), {} failed, {} skipped.\n",
+            self.written_count(),
+            self.failed_count(),
+            self.skipped_count()
+        );
+        for title in ["Failed", "Skipped", "Written"] {
+            let members: Vec<_> = self.in_group(title).collect();
+            if members.is_empty() {
+                continue;
+            }
+            let _ = write!(text, "\n{title}:\n");
+            for result in members {
+                let _ = writeln!(text, "  {}", result.describe());
+            }
+        }
+        text
+    }
+}
+
+/* Sent by the worker after each profile, and once more with the summary when all are done. */
+#[derive(Debug, Clone)]
+pub enum BatchProgress {
+    Profile {
+        completed: usize,
+        total: usize,
+        result: BatchProfileResult,
+    },
+    Finished(BatchSummary),
+}
+
+/* The receiving end of a batch run, and the worker to join once it has finished. */
+pub struct BatchGenerationChannel {
+    pub receiver: mpsc::Receiver<BatchProgress>,
+    pub worker_handle: Option<thread::JoinHandle<()>>,
+    pub total_profiles: usize,
+}
+
+/* Loads, scans and archives a single profile in a session of its own. */
+pub fn generate_profile_archive(
+    project: &ProjectContext,
+    profile_name: &ProfileName,
+    app_name: &str,
+    services: &BatchServices,
+) -> BatchProfileResult {
+    let result = |outcome| BatchProfileResult {
+        profile_name: profile_name.as_str().to_string(),
+        outcome,
+    };
+    let profile = match services
+        .profile_manager
+        .load_profile(project, profile_name, app_name)
+    {
+        Ok(profile) => profile,
+        Err(e) => return result(BatchOutcome::Failed(format!("could not load profile: {e}"))),
+    };
+    let Some(archive_path) = profile.archive_path.clone() else {
+        return result(BatchOutcome::SkippedNoArchivePath);
+    };
+    let path_context = ArchivePathContext::new(project.root_path(), profile_name.as_str());
+    let archive_path =
+        match archive_path_template::resolve_archive_path(&archive_path, &path_context) {
+            Ok(resolved) => resolved,
+            Err(e) => return result(BatchOutcome::Failed(e.to_string())),
+        };
+
+    let mut session = ProfileRuntimeData::new();
+    let component_loader =
+        ProjectComponentLoader::new(project, services.profile_manager.as_ref(), app_name);
+    if let Err(message) = session.load_profile_into_session(
+        profile,
+        services.file_system_scanner.as_ref(),
+        services.state_manager.as_ref(),
+        services.token_counter.as_ref(),
+        &component_loader,
+    ) {
+        return result(BatchOutcome::Failed(message));
+    }
+
+    let root_path = session.get_root_path_for_scan();
+    let mut request = ArchiveRequest::new(session.get_snapshot_nodes(), &root_path);
+    request.profile_name = profile_name.as_str().to_string();
+    request.options = session.get_archive_options();
+    request.token_cache = session.get_cached_file_token_details();
+    request.record_path = Some(project.resolve_archive_record_file(profile_name));
+    request.history_dir = Some(project.resolve_archive_history_dir(profile_name));
+    match services
+        .archiver
+        .write_archive_to_file(&request, &archive_path)
+    {
+        Ok(report) => result(BatchOutcome::Written {
+            archive_path,
+            files_written: report.files_written,
+            part_count: report.part_paths.len(),
+        }),
+        Err(e) => result(BatchOutcome::Failed(e.to_string())),
+    }
+}
+
+/* Starts generating the archives of `profile_names`, one after the other, on a worker thread. */
+pub fn generate_all_async(
+    project: ProjectContext,
+    profile_names: Vec<ProfileName>,
+    app_name: String,
+    services: BatchServices,
+) -> BatchGenerationChannel {
+    let (sender, receiver) = mpsc::channel();
+    let total_profiles = profile_names.len();
+    let worker_handle = thread::spawn(move || {
+        let mut summary = BatchSummary::default();
+        for (index, profile_name) in profile_names.iter().enumerate() {
+            let result = generate_profile_archive(&project, profile_name, &app_name, &services);
+            log::debug!("Batch generation: {}", result.describe());
+            summary.results.push(result.clone());
+            let progress = BatchProgress::Profile {
+                completed: index + 1,
+                total: total_profiles,
+                result,
+            };
+            if sender.send(progress).is_err() {
+                log::debug!("Batch generation: receiver dropped; stopping.");
+                return;
+            }
+        }
+        let _ = sender.send(BatchProgress::Finished(summary));
+    });
+    BatchGenerationChannel {
+        receiver,
+        worker_handle: Some(worker_handle),
+        total_profiles,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{
+        CoreArchiver, CoreFileSystemScanner, CoreProfileManager, NodeStateApplicator, Profile,
+        tokenizer_utils::SimpleWhitespaceTokenCounter,
+    };
+    use std::fs;
+    use tempfile::tempdir;
+
+    const APP_NAME_FOR_TESTS: &str = "SourcePackerTests";
+
+    #[test]
+    fn test_generate_all_writes_skips_and_reports_failures() {
+        // Arrange
+        let dir = tempdir().unwrap();
+        let root = dir.path().to_path_buf();
+        fs::write(root.join("main.rs"), "fn main() {}\n").unwrap();
+        let project = ProjectContext::new(root.clone());
+        let services = BatchServices {
+            profile_manager: Arc::new(CoreProfileManager::new()),
+            file_system_scanner: Arc::new(CoreFileSystemScanner::new()),
+            state_manager: Arc::new(NodeStateApplicator::new()),
+            archiver: Arc::new(CoreArchiver::new(Arc::new(
+                SimpleWhitespaceTokenCounter::new(),
+            ))),
+            token_counter: Arc::new(SimpleWhitespaceTokenCounter::new()),
+        };
+        let profile = |name: &str, archive: Option<&str>, root_folder: PathBuf| {
+            let mut profile = Profile::new(ProfileName::new(name).unwrap(), root_folder);
+            profile.selected_paths.insert(root.join("main.rs"));
+            profile.archive_path = archive.map(PathBuf::from);
+            profile
+        };
+        let profiles = [
+            profile("Core", Some("out/{profile}.txt"), root.clone()),
+            profile("Draft", None, root.clone()),
+            profile("Gone", Some("gone.txt"), root.join("missing")),
+        ];
+        for profile in &profiles {
+            services
+                .profile_manager
+                .save_profile(&project, profile, APP_NAME_FOR_TESTS)
+                .unwrap();
+        }
+        let names: Vec<ProfileName> = profiles.iter().map(|p| p.name.clone()).collect();
+
+        // Act
+        let channel = generate_all_async(
+            project.clone(),
+            names,
+            APP_NAME_FOR_TESTS.to_string(),
+            services,
+        );
+        let messages: Vec<BatchProgress> = channel.receiver.iter().collect();
+        channel.worker_handle.unwrap().join().unwrap();
+
+        // Assert
+        assert_eq!(messages.len(), 4);
+        assert!(matches!(
+            &messages[0],
+            BatchProgress::Profile {
+                completed: 1,
+                total: 3,
+                ..
+            }
+        ));
+        let Some(BatchProgress::Finished(summary)) = messages.last() else {
+            panic!("Expected a summary last: {messages:?}");
+        };
+        assert_eq!(
+            (
+                summary.written_count(),
+                summary.failed_count(),
+                summary.skipped_count()
+            ),
+            (1, 1, 1)
+        );
+        assert!(
+            fs::read_to_string(root.join("out").join("Core.txt"))
+                .unwrap()
+                .contains("fn main() {}")
+        );
+        assert!(
+            project
+                .resolve_archive_record_file(&ProfileName::new("Core").unwrap())
+                .is_file()
+        );
+        let rendered = summary.render();
+        assert!(rendered.starts_with("Generated 1 archive(s), 1 failed, 1 skipped.\n"));
+        assert!(rendered.contains("  'Draft': skipped, no archive path set\n"));
+        assert!(rendered.contains("  'Gone': failed: "), "{rendered}");
+    }
+}
@@ -49,6 +49,15 @@ pub fn calculate_sha256_checksum(file_path: &Path) -> io::Result<String> {
     Ok(hex_checksum)
 }
 
+/*
+ * Calculates the SHA256 checksum of in-memory bytes, hex-encoded in the same form as
+ * `calculate_sha256_checksum`, so values can be compared with checksums taken from disk.
+ */
+pub fn calculate_sha256_for_bytes(bytes: &[u8]) -> String {
+    let hash_bytes = Sha256::digest(bytes);
+    format!("{hash_bytes:x}")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
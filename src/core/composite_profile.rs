@@ -0,0 +1,350 @@
+use super::file_node::Profile;
+use super::profile_settings::CompositeOperation;
+use super::profiles::{ProfileError, ProfileManagerOperations};
+use super::project_context::{ProfileName, ProjectContext};
+use std::collections::HashSet;
+use std::fmt;
+use std::path::PathBuf;
+
+/*
+ * This module resolves composite profiles. [ProfileCompositeV1] A profile can list other
+ * profiles of the project as components. Starting from the profile's own selection, the
+ * components are applied in order: a union component adds the files selected (or
+ * outlined) by that profile, a difference component removes them. Components are resolved
+ * recursively, so a component can itself be a composite; a profile that refers back to
+ * itself through its components is rejected, as is a component that cannot be loaded.
+ * Paths are compared as stored, so components must share the composite's root folder; a
+ * component with another root is rejected. A file selected by several components is
+ * still selected once.
+ */
+
+#[derive(Debug)]
+pub enum CompositeError {
+    /* `chain` starts and ends with the same profile. */
+    Cycle {
+        chain: Vec<ProfileName>,
+    },
+    ComponentUnavailable {
+        component: ProfileName,
+        referenced_by: ProfileName,
+        source: ProfileError,
+    },
+    /* The component's root folder differs from the one of the profile that refers to it. */
+    RootMismatch {
+        component: ProfileName,
+        referenced_by: ProfileName,
+        component_root: PathBuf,
+        expected_root: PathBuf,
+    },
+}
+
+impl fmt::Display for CompositeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompositeError::Cycle { chain } => {
+                let names: Vec<&str> = chain.iter().map(ProfileName::as_str).collect();
+                write!(
+                    f,
+                    "Composite profiles refer to each other in a cycle: {}",
+                    names.join(" -> ")
+                )
+            }
+            CompositeError::ComponentUnavailable {
+                component,
+                referenced_by,
+                source,
+            } => write!(
+                f,
+                "Profile '{component}', a component of '{referenced_by}', could not be loaded: {source}"
+            ),
+            CompositeError::RootMismatch {
+                component,
+                referenced_by,
+                component_root,
+                expected_root,
+            } => write!(
+                f,
+                "Profile '{component}', a component of '{referenced_by}', is for folder '{}' instead of '{}'",
+                component_root.display(),
+                expected_root.display()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CompositeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CompositeError::ComponentUnavailable { source, .. } => Some(source),
+            CompositeError::Cycle { .. } | CompositeError::RootMismatch { .. } => None,
+        }
+    }
+}
+
+/*
+ * Loads the profiles a composite refers to. Abstracted so the session can resolve
+ * components without knowing where profiles are stored.
+ */
+pub trait ComponentLoaderOperations {
+    fn load_component(&self, name: &ProfileName) -> Result<Profile, ProfileError>;
+}
+
+/* Loads components from the profiles of a project through the profile manager. */
+pub struct ProjectComponentLoader<'a> {
+    project: &'a ProjectContext,
+    profile_manager: &'a dyn ProfileManagerOperations,
+    app_name: &'a str,
+}
+
+impl<'a> ProjectComponentLoader<'a> {
+    pub fn new(
+        project: &'a ProjectContext,
+        profile_manager: &'a dyn ProfileManagerOperations,
+        app_name: &'a str,
+    ) -> Self {
+        ProjectComponentLoader {
+            project,
+            profile_manager,
+            app_name,
+        }
+    }
+}
+
+impl ComponentLoaderOperations for ProjectComponentLoader<'_> {
+    fn load_component(&self, name: &ProfileName) -> Result<Profile, ProfileError> {
+        self.profile_manager
+            .load_profile(self.project, name, self.app_name)
+    }
+}
+
+/* The effective selection of a profile once its components have been applied. */
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResolvedSelection {
+    pub selected_paths: HashSet<PathBuf>,
+    pub deselected_paths: HashSet<PathBuf>,
+    pub outline_paths: HashSet<PathBuf>,
+}
+
+impl ResolvedSelection {
+    fn own(profile: &Profile) -> Self {
+        ResolvedSelection {
+            selected_paths: profile.selected_paths.clone(),
+            deselected_paths: profile.deselected_paths.clone(),
+            outline_paths: profile.outline_paths.clone(),
+        }
+    }
+
+    /*
+     * The included paths that came from components rather than from the profile itself.
+     * They are left out when the composite is saved, so later changes to the components
+     * are picked up on the next load.
+     */
+    pub fn contributed_by_components(&self, profile: &Profile) -> HashSet<PathBuf> {
+        self.selected_paths
+            .iter()
+            .chain(&self.outline_paths)
+            .filter(|path| {
+                !profile.selected_paths.contains(*path) && !profile.outline_paths.contains(*path)
+            })
+            .cloned()
+            .collect()
+    }
+}
+
+/* Resolves the effective selection of `profile`; a profile without components is returned as is. */
+pub fn resolve_selection(
+    profile: &Profile,
+    loader: &dyn ComponentLoaderOperations,
+) -> Result<ResolvedSelection, CompositeError> {
+    let mut chain = vec![profile.name.clone()];
+    resolve_recursive(profile, loader, &mut chain)
+}
+
+fn resolve_recursive(
+    profile: &Profile,
+    loader: &dyn ComponentLoaderOperations,
+    chain: &mut Vec<ProfileName>,
+) -> Result<ResolvedSelection, CompositeError> {
+    let mut resolved = ResolvedSelection::own(profile);
+    for component in &profile.components {
+        if chain.contains(&component.profile) {
+            let mut cycle = chain.clone();
+            cycle.push(component.profile.clone());
+            return Err(CompositeError::Cycle { chain: cycle });
+        }
+        let component_profile = loader
+            .load_component(&component.profile)
+            .map_err(|source| CompositeError::ComponentUnavailable {
+                component: component.profile.clone(),
+                referenced_by: profile.name.clone(),
+                source,
+            })?;
+        if component_profile.root_folder != profile.root_folder {
+            return Err(CompositeError::RootMismatch {
+                component: component.profile.clone(),
+                referenced_by: profile.name.clone(),
+                component_root: component_profile.root_folder,
+                expected_root: profile.root_folder.clone(),
+            });
+        }
+        chain.push(component.profile.clone());
+        let part = resolve_recursive(&component_profile, loader, chain)?;
+        chain.pop();
+
+        match component.operation {
+            CompositeOperation::Union => {
+                resolved.selected_paths.extend(part.selected_paths);
+                resolved.outline_paths.extend(part.outline_paths);
+                resolved.deselected_paths.extend(part.deselected_paths);
+            }
+            CompositeOperation::Difference => {
+                for path in part.selected_paths.into_iter().chain(part.outline_paths) {
+                    let was_included = resolved.selected_paths.remove(&path)
+                        | resolved.outline_paths.remove(&path);
+                    if was_included {
+                        resolved.deselected_paths.insert(path);
+                    }
+                }
+            }
+        }
+    }
+    let included: HashSet<&PathBuf> = resolved
+        .selected_paths
+        .iter()
+        .chain(&resolved.outline_paths)
+        .collect();
+    let deselected = resolved
+        .deselected_paths
+        .iter()
+        .filter(|path| !included.contains(path))
+        .cloned()
+        .collect();
+    resolved.deselected_paths = deselected;
+    Ok(resolved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::profile_settings::ProfileComponent;
+    use std::collections::HashMap;
+    use std::path::Path;
+
+    struct MapLoader(HashMap<String, Profile>);
+
+    impl ComponentLoaderOperations for MapLoader {
+        fn load_component(&self, name: &ProfileName) -> Result<Profile, ProfileError> {
+            self.0
+                .get(name.as_str())
+                .cloned()
+                .ok_or_else(|| ProfileError::ProfileNotFound(name.as_str().to_string()))
+        }
+    }
+
+    fn profile(
+        name: &str,
+        selected: &[&str],
+        components: &[(&str, CompositeOperation)],
+    ) -> Profile {
+        let mut profile = Profile::new(ProfileName::new(name).unwrap(), PathBuf::from("/p"));
+        profile.selected_paths = selected
+            .iter()
+            .map(|p| PathBuf::from("/p").join(p))
+            .collect();
+        profile.components = components
+            .iter()
+            .map(|(name, operation)| ProfileComponent {
+                profile: ProfileName::new(*name).unwrap(),
+                operation: *operation,
+            })
+            .collect();
+        profile
+    }
+
+    fn paths(names: &[&str]) -> HashSet<PathBuf> {
+        names.iter().map(|p| PathBuf::from("/p").join(p)).collect()
+    }
+
+    #[test]
+    fn test_union_and_difference_are_applied_in_order() {
+        use CompositeOperation::{Difference, Union};
+        let loader = MapLoader(HashMap::from([
+            (
+                "backend".to_string(),
+                profile("backend", &["api.rs", "shared.rs"], &[]),
+            ),
+            (
+                "frontend".to_string(),
+                profile("frontend", &["ui.ts", "shared.rs"], &[]),
+            ),
+            (
+                "tests".to_string(),
+                profile("tests", &["api_test.rs", "ui.ts"], &[]),
+            ),
+            (
+                "all".to_string(),
+                profile("all", &[], &[("backend", Union), ("frontend", Union)]),
+            ),
+        ]));
+        let release = profile(
+            "release",
+            &["README.md"],
+            &[("all", Union), ("tests", Difference)],
+        );
+
+        let resolved = resolve_selection(&release, &loader).unwrap();
+
+        assert_eq!(
+            resolved.selected_paths,
+            paths(&["README.md", "api.rs", "shared.rs"])
+        );
+        assert_eq!(resolved.deselected_paths, paths(&["ui.ts"]));
+        assert_eq!(
+            resolved.contributed_by_components(&release),
+            paths(&["api.rs", "shared.rs"])
+        );
+    }
+
+    #[test]
+    fn test_cycles_and_missing_components_are_reported() {
+        use CompositeOperation::Union;
+        let loader = MapLoader(HashMap::from([
+            ("a".to_string(), profile("a", &[], &[("b", Union)])),
+            ("b".to_string(), profile("b", &[], &[("a", Union)])),
+        ]));
+
+        let cycle = resolve_selection(&loader.0["a"], &loader).unwrap_err();
+        assert_eq!(
+            cycle.to_string(),
+            "Composite profiles refer to each other in a cycle: a -> b -> a"
+        );
+
+        let dangling = profile("c", &[], &[("gone", Union)]);
+        let missing = resolve_selection(&dangling, &loader).unwrap_err();
+        assert_eq!(
+            missing.to_string(),
+            "Profile 'gone', a component of 'c', could not be loaded: Profile not found: gone"
+        );
+    }
+
+    #[test]
+    fn test_component_with_another_root_folder_is_rejected() {
+        use CompositeOperation::Union;
+        let mut elsewhere = profile("elsewhere", &["lib.rs"], &[]);
+        elsewhere.root_folder = PathBuf::from("/other");
+        let loader = MapLoader(HashMap::from([("elsewhere".to_string(), elsewhere)]));
+        let composite = profile("combined", &[], &[("elsewhere", Union)]);
+
+        let error = resolve_selection(&composite, &loader).unwrap_err();
+
+        assert!(matches!(error, CompositeError::RootMismatch { .. }));
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "Profile 'elsewhere', a component of 'combined', is for folder '{}' instead of '{}'",
+                Path::new("/other").display(),
+                Path::new("/p").display()
+            )
+        );
+    }
+}
@@ -0,0 +1,441 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::checksum_utils;
+use super::text_encoding::{self, EncodingRules, TextEncoding};
+
+/*
+ * This module decides whether a file can be shown and archived as text.
+ * [ArchiveGenBinaryFilesV1] A file is binary when its first `SNIFF_LENGTH` bytes contain a
+ * NUL byte or too large a share of bytes that are not valid UTF-8; a file is oversize when
+ * it exceeds the profile's size limit. Text with the odd invalid byte is decoded lossily
+ * instead of failing. Files with a BOM or a UTF-16 or Windows-1252 fallback encoding are
+ * decoded by `text_encoding` and are only binary if they contain NUL bytes where that
+ * encoding has none. The archiver, the viewer, the token worker and the content search
+ * all read through `ContentReader`, and files that cannot be shown are described by the
+ * same placeholder line, e.g. `[binary file, 12 KB, image/png]`.
+ */
+
+/* Number of leading bytes inspected to decide whether a file is binary. */
+pub const SNIFF_LENGTH: usize = 8192;
+/* Share of invalid UTF-8 bytes in the sniffed prefix above which a file counts as binary. */
+pub const MAX_INVALID_UTF8_RATIO: f64 = 0.1;
+
+/* Why a file is not included as text. */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentClass {
+    Binary { mime: &'static str },
+    Oversize,
+}
+
+/* What archive generation does with a file of a given class. */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContentPolicy {
+    Skip,
+    #[default]
+    Placeholder,
+    Fail,
+}
+
+impl ContentPolicy {
+    pub const ALL_NAMES: [&'static str; 3] = ["skip", "placeholder", "fail"];
+
+    pub fn setting_name(self) -> &'static str {
+        match self {
+            ContentPolicy::Skip => "skip",
+            ContentPolicy::Placeholder => "placeholder",
+            ContentPolicy::Fail => "fail",
+        }
+    }
+
+    fn parse(text: &str) -> Option<Self> {
+        match text.to_ascii_lowercase().as_str() {
+            "skip" => Some(ContentPolicy::Skip),
+            "placeholder" => Some(ContentPolicy::Placeholder),
+            "fail" => Some(ContentPolicy::Fail),
+            _ => None,
+        }
+    }
+}
+
+/* Per-profile limits. Without `max_file_size` no file is oversize. */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct ContentLimits {
+    #[serde(default)]
+    pub max_file_size: Option<u64>,
+    #[serde(default)]
+    pub binary: ContentPolicy,
+    #[serde(default)]
+    pub oversize: ContentPolicy,
+}
+
+impl ContentLimits {
+    pub fn policy_for(&self, class: ContentClass) -> ContentPolicy {
+        match class {
+            ContentClass::Binary { .. } => self.binary,
+            ContentClass::Oversize => self.oversize,
+        }
+    }
+}
+
+/* The content of a file as far as the archive and the viewer are concerned. */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileContent {
+    Text(String),
+    NotText { class: ContentClass, size: u64 },
+}
+
+/* A selected file that was skipped or replaced by a placeholder, for the archive report. */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkippedFile {
+    pub relative_path: String,
+    pub class: ContentClass,
+    pub size: u64,
+    pub policy: ContentPolicy,
+}
+
+impl fmt::Display for SkippedFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({}, {})",
+            self.relative_path,
+            self.class,
+            format_size(self.size)
+        )
+    }
+}
+
+/* Formats a size the way placeholders show it: bytes, whole KB, or MB with one decimal. */
+pub fn format_size(size: u64) -> String {
+    const KB: u64 = 1024;
+    const MB: u64 = 1024 * 1024;
+    if size < KB {
+        format!("{size} bytes")
+    } else if size < MB {
+        format!("{} KB", size.div_ceil(KB))
+    } else {
+        format!("{:.1} MB", size as f64 / MB as f64)
+    }
+}
+
+/* The line written in place of a file that is not included as text. */
+pub fn placeholder(class: ContentClass, size: u64) -> String {
+    match class {
+        ContentClass::Binary { mime } => format!("[binary file, {}, {mime}]", format_size(size)),
+        ContentClass::Oversize => format!("[file too large, {}]", format_size(size)),
+    }
+}
+
+impl fmt::Display for ContentClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ContentClass::Binary { mime } => write!(f, "binary, {mime}"),
+            ContentClass::Oversize => write!(f, "too large"),
+        }
+    }
+}
+
+/* Recognizes common binary formats by their signature, then by extension. */
+fn guess_mime(path: &Path, head: &[u8]) -> &'static str {
+    const SIGNATURES: [(&[u8], &str); 14] = [
+        (b"\x89PNG\r\n\x1a\n", "image/png"),
+        (b"\xff\xd8\xff", "image/jpeg"),
+        (b"GIF87a", "image/gif"),
+        (b"GIF89a", "image/gif"),
+        (b"BM", "image/bmp"),
+        (b"\x00\x00\x01\x00", "image/x-icon"),
+        (b"%PDF-", "application/pdf"),
+        (b"PK\x03\x04", "application/zip"),
+        (b"\x1f\x8b", "application/gzip"),
+        (b"7z\xbc\xaf\x27\x1c", "application/x-7z-compressed"),
+        (b"\x7fELF", "application/x-elf"),
+        (b"MZ", "application/x-msdownload"),
+        (b"\x00asm", "application/wasm"),
+        (b"SQLite format 3\x00", "application/vnd.sqlite3"),
+    ];
+    if let Some((_, mime)) = SIGNATURES.iter().find(|(sig, _)| head.starts_with(sig)) {
+        return mime;
+    }
+    if head.starts_with(b"RIFF") && head.get(8..12) == Some(b"WEBP") {
+        return "image/webp";
+    }
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "ico" => "image/x-icon",
+        "pdf" => "application/pdf",
+        "zip" | "jar" => "application/zip",
+        "exe" | "dll" => "application/x-msdownload",
+        "wasm" => "application/wasm",
+        "ttf" | "otf" | "woff" | "woff2" => "font/ttf",
+        _ => "application/octet-stream",
+    }
+}
+
+fn invalid_utf8_ratio(bytes: &[u8]) -> f64 {
+    if bytes.is_empty() {
+        return 0.0;
+    }
+    let invalid: usize = bytes.utf8_chunks().map(|chunk| chunk.invalid().len()).sum();
+    invalid as f64 / bytes.len() as f64
+}
+
+/*
+ * Classifies a file from its first bytes (at most `SNIFF_LENGTH` are looked at), given
+ * the encoding used when the file has no BOM. Returns `None` for text.
+ */
+pub fn sniff_binary(path: &Path, head: &[u8], fallback: TextEncoding) -> Option<ContentClass> {
+    let head = &head[..head.len().min(SNIFF_LENGTH)];
+    let encoding = text_encoding::detect_bom(head).map_or(fallback, |(bom, _)| bom);
+    let binary = match encoding {
+        TextEncoding::Utf16le | TextEncoding::Utf16be => false,
+        TextEncoding::Windows1252 => head.contains(&0),
+        TextEncoding::Utf8 => {
+            head.contains(&0) || invalid_utf8_ratio(head) > MAX_INVALID_UTF8_RATIO
+        }
+    };
+    binary.then(|| ContentClass::Binary {
+        mime: guess_mime(path, head),
+    })
+}
+
+/*
+ * Reads project files as text for the archiver, the viewer, the token worker and the
+ * content search: applies the size limit, sniffs binary files and decodes with the
+ * fallback encoding that the profile's rules give for the path relative to `root`.
+ */
+#[derive(Debug, Clone, Default)]
+pub struct ContentReader {
+    root: PathBuf,
+    max_file_size: Option<u64>,
+    encodings: EncodingRules,
+}
+
+impl ContentReader {
+    pub fn new(root: &Path, max_file_size: Option<u64>, encodings: EncodingRules) -> Self {
+        ContentReader {
+            root: root.to_path_buf(),
+            max_file_size,
+            encodings,
+        }
+    }
+
+    pub fn fallback_encoding(&self, path: &Path) -> TextEncoding {
+        let relative_path = path.strip_prefix(&self.root).unwrap_or(path);
+        self.encodings
+            .fallback_for(&relative_path.to_string_lossy())
+    }
+
+    /* Reads and decodes a file, or only classifies it when it is oversize or binary. */
+    pub fn read(&self, path: &Path) -> io::Result<FileContent> {
+        match self.oversize(path)? {
+            Some(content) => Ok(content),
+            None => Ok(self.decode(path, fs::read(path)?)),
+        }
+    }
+
+    /*
+     * Reads a file like `read` and also returns the SHA-256 of the bytes that were read,
+     * so the checksum describes exactly the content that was decoded. An oversize file is
+     * hashed without keeping it in memory.
+     */
+    pub fn read_with_checksum(&self, path: &Path) -> io::Result<(FileContent, String)> {
+        if let Some(content) = self.oversize(path)? {
+            return Ok((content, checksum_utils::calculate_sha256_checksum(path)?));
+        }
+        let bytes = fs::read(path)?;
+        let checksum = checksum_utils::calculate_sha256_for_bytes(&bytes);
+        Ok((self.decode(path, bytes), checksum))
+    }
+
+    fn oversize(&self, path: &Path) -> io::Result<Option<FileContent>> {
+        let size = fs::metadata(path)?.len();
+        Ok(self
+            .max_file_size
+            .is_some_and(|max| size > max)
+            .then_some(FileContent::NotText {
+                class: ContentClass::Oversize,
+                size,
+            }))
+    }
+
+    fn decode(&self, path: &Path, bytes: Vec<u8>) -> FileContent {
+        let fallback = self.fallback_encoding(path);
+        match sniff_binary(path, &bytes, fallback) {
+            Some(class) => FileContent::NotText {
+                class,
+                size: bytes.len() as u64,
+            },
+            None => FileContent::Text(text_encoding::decode(bytes, fallback)),
+        }
+    }
+}
+
+/* Parses a size such as `500`, `64KB` or `1.5 MB` (KB and MB are binary units). */
+fn parse_size(text: &str) -> Option<u64> {
+    let text = text.trim().to_ascii_uppercase();
+    let (number, multiplier) = if let Some(number) = text.strip_suffix("MB") {
+        (number, 1024.0 * 1024.0)
+    } else if let Some(number) = text.strip_suffix("KB") {
+        (number, 1024.0)
+    } else {
+        (text.strip_suffix('B').unwrap_or(&text), 1.0)
+    };
+    let value: f64 = number.trim().parse().ok()?;
+    (value.is_finite() && value > 0.0).then(|| (value * multiplier).round() as u64)
+}
+
+/*
+ * Parses the text entered in the "Binary and Large Files..." dialog: comma-separated
+ * `binary:<policy>`, `oversize:<policy>` and `max:<size>` (or `max:none`) entries, e.g.
+ * `binary:skip, max:512KB, oversize:placeholder`. Entries not given keep their default.
+ */
+pub fn parse_setting(text: &str) -> Result<ContentLimits, String> {
+    let mut limits = ContentLimits::default();
+    for item in text.split(',').map(str::trim).filter(|i| !i.is_empty()) {
+        let Some((key, value)) = item.split_once(':') else {
+            return Err(format!(
+                "'{item}' is not 'binary:<policy>', 'oversize:<policy>' or 'max:<size>'"
+            ));
+        };
+        let value = value.trim();
+        let policy = || {
+            ContentPolicy::parse(value).ok_or_else(|| {
+                format!(
+                    "Unknown policy '{value}' (expected one of: {})",
+                    ContentPolicy::ALL_NAMES.join(", ")
+                )
+            })
+        };
+        match key.trim().to_ascii_lowercase().as_str() {
+            "binary" => limits.binary = policy()?,
+            "oversize" => limits.oversize = policy()?,
+            "max" if value.eq_ignore_ascii_case("none") => limits.max_file_size = None,
+            "max" => {
+                limits.max_file_size = Some(
+                    parse_size(value).ok_or_else(|| format!("'{value}' is not a valid size"))?,
+                )
+            }
+            other => return Err(format!("Unknown setting '{other}'")),
+        }
+    }
+    Ok(limits)
+}
+
+/* The inverse of `parse_setting`, used as the dialog's default text. */
+pub fn to_setting_text(limits: &ContentLimits) -> String {
+    let max = match limits.max_file_size {
+        None => "none".to_string(),
+        Some(size) if size % (1024 * 1024) == 0 => format!("{}MB", size / (1024 * 1024)),
+        Some(size) if size % 1024 == 0 => format!("{}KB", size / 1024),
+        Some(size) => size.to_string(),
+    };
+    format!(
+        "binary:{}, oversize:{}, max:{max}",
+        limits.binary.setting_name(),
+        limits.oversize.setting_name()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_sniff_detects_nul_bytes_and_invalid_utf8() {
+        let path = Path::new("image.dat");
+        let utf8 = TextEncoding::Utf8;
+        assert_eq!(sniff_binary(path, b"plain text\n", utf8), None);
+        assert_eq!(
+            sniff_binary(path, b"\x89PNG\r\n\x1a\n\x00\x00", utf8),
+            Some(ContentClass::Binary { mime: "image/png" })
+        );
+        assert_eq!(
+            sniff_binary(Path::new("a.jpg"), &[0xc3, 0x28, 0xa0, 0xa1, b'a'], utf8),
+            Some(ContentClass::Binary { mime: "image/jpeg" })
+        );
+        // A single Latin-1 byte in otherwise ASCII text is still text.
+        let latin1 = b"caf\xe9 au lait, a rather long line of ordinary text\n";
+        assert_eq!(sniff_binary(path, latin1, utf8), None);
+        assert_eq!(
+            text_encoding::decode(latin1.to_vec(), utf8),
+            "caf\u{fffd} au lait, a rather long line of ordinary text\n"
+        );
+        // UTF-16 text is full of NUL bytes but is not binary.
+        assert_eq!(sniff_binary(path, b"\xff\xfea\x00b\x00", utf8), None);
+        assert_eq!(
+            sniff_binary(path, b"a\x00b\x00", TextEncoding::Utf16le),
+            None
+        );
+    }
+
+    #[test]
+    fn test_read_file_classifies_and_formats_placeholders() {
+        let dir = tempdir().unwrap();
+        let text = dir.path().join("a.txt");
+        let logo = dir.path().join("logo.png");
+        fs::write(&text, "hello\n").unwrap();
+        let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+        png.resize(12 * 1024, 0);
+        fs::write(&logo, &png).unwrap();
+        let reader = ContentReader::new(dir.path(), None, EncodingRules::default());
+
+        assert_eq!(
+            reader.read(&text).unwrap(),
+            FileContent::Text("hello\n".to_string())
+        );
+        let FileContent::NotText { class, size } = reader.read(&logo).unwrap() else {
+            panic!("Expected a binary file");
+        };
+        assert_eq!(placeholder(class, size), "[binary file, 12 KB, image/png]");
+        let limited = ContentReader::new(dir.path(), Some(3), EncodingRules::default());
+        assert_eq!(
+            limited.read(&text).unwrap(),
+            FileContent::NotText {
+                class: ContentClass::Oversize,
+                size: 6,
+            }
+        );
+        let limited = ContentReader::new(dir.path(), Some(6), EncodingRules::default());
+        assert_eq!(
+            limited.read(&text).unwrap(),
+            FileContent::Text("hello\n".to_string())
+        );
+        assert_eq!(format_size(3 * 1024 * 1024 + 300_000), "3.3 MB");
+    }
+
+    #[test]
+    fn test_parse_setting_round_trips() {
+        let limits = parse_setting("binary:skip, max:512KB, oversize:fail").unwrap();
+        assert_eq!(
+            limits,
+            ContentLimits {
+                max_file_size: Some(512 * 1024),
+                binary: ContentPolicy::Skip,
+                oversize: ContentPolicy::Fail,
+            }
+        );
+        assert_eq!(
+            to_setting_text(&limits),
+            "binary:skip, oversize:fail, max:512KB"
+        );
+        assert_eq!(parse_setting("").unwrap(), ContentLimits::default());
+        assert_eq!(
+            parse_setting("max:1.5MB").unwrap().max_file_size,
+            Some(1_572_864)
+        );
+        assert!(parse_setting("binary:drop").is_err());
+        assert!(parse_setting("max:-1").is_err());
+    }
+}
@@ -0,0 +1,689 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/*
+ * This module implements the per-file content transforms applied while an archive is
+ * written. [ArchiveGenContentTransformsV1]
+ * A profile holds an ordered list of rules; each rule names a built-in transform and may
+ * be restricted to files matching a glob (matched against the path relative to the
+ * project root, with `/` separators). Transforms only change what goes into the archive,
+ * never the source files. They are deliberately conservative: comment stripping
+ * understands string literals of the supported languages and leaves files of unknown
+ * languages untouched.
+ */
+
+pub const DEFAULT_TAB_WIDTH: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContentTransform {
+    /* Removes line and block comments (including doc comments) for known languages. */
+    StripComments,
+    /* Replaces each run of blank lines with a single blank line. */
+    CollapseBlankLines,
+    TrimTrailingWhitespace,
+    /* Removes a leading comment block that mentions a license or copyright. */
+    RemoveLicenseHeader,
+    /* Expands tabs to spaces, honoring tab stops every `width` columns. */
+    ConvertTabs { width: usize },
+}
+
+impl ContentTransform {
+    pub const ALL_NAMES: [&'static str; 5] = [
+        "strip-comments",
+        "collapse-blank-lines",
+        "trim-trailing-whitespace",
+        "remove-license-header",
+        "convert-tabs",
+    ];
+
+    pub fn setting_name(&self) -> &'static str {
+        match self {
+            ContentTransform::StripComments => Self::ALL_NAMES[0],
+            ContentTransform::CollapseBlankLines => Self::ALL_NAMES[1],
+            ContentTransform::TrimTrailingWhitespace => Self::ALL_NAMES[2],
+            ContentTransform::RemoveLicenseHeader => Self::ALL_NAMES[3],
+            ContentTransform::ConvertTabs { .. } => Self::ALL_NAMES[4],
+        }
+    }
+
+    pub fn apply(&self, relative_path: &str, content: &str) -> String {
+        match self {
+            ContentTransform::StripComments => match comment_syntax_for(relative_path) {
+                Some(syntax) => strip_comments(content, &syntax),
+                None => content.to_string(),
+            },
+            ContentTransform::CollapseBlankLines => collapse_blank_lines(content),
+            ContentTransform::TrimTrailingWhitespace => trim_trailing_whitespace(content),
+            ContentTransform::RemoveLicenseHeader => remove_license_header(content),
+            ContentTransform::ConvertTabs { width } => convert_tabs(content, *width),
+        }
+    }
+}
+
+/*
+ * One entry of a profile's transform list. Without a glob the transform applies to
+ * every file.
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransformRule {
+    pub transform: ContentTransform,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub glob: Option<String>,
+}
+
+/*
+ * Token counts of the archived files before and after the transforms ran, so the user can
+ * see what the transforms saved.
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TransformTokenReport {
+    pub raw_tokens: usize,
+    pub transformed_tokens: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransformError {
+    InvalidGlob { pattern: String, message: String },
+}
+
+impl fmt::Display for TransformError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransformError::InvalidGlob { pattern, message } => {
+                write!(f, "Invalid transform glob '{pattern}': {message}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for TransformError {}
+
+/*
+ * The compiled form of a rule list, ready to be applied to many files.
+ */
+#[derive(Debug, Clone, Default)]
+pub struct TransformPipeline {
+    steps: Vec<(ContentTransform, Option<glob::Pattern>)>,
+}
+
+impl TransformPipeline {
+    pub fn compile(rules: &[TransformRule]) -> Result<Self, TransformError> {
+        let steps = rules
+            .iter()
+            .map(|rule| {
+                let pattern = rule
+                    .glob
+                    .as_deref()
+                    .map(|pattern| {
+                        glob::Pattern::new(pattern).map_err(|e| TransformError::InvalidGlob {
+                            pattern: pattern.to_string(),
+                            message: e.msg.to_string(),
+                        })
+                    })
+                    .transpose()?;
+                Ok((rule.transform, pattern))
+            })
+            .collect::<Result<_, TransformError>>()?;
+        Ok(TransformPipeline { steps })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    /* Whether any rule matches `relative_path`, i.e. `apply` may change its content. */
+    pub fn applies_to(&self, relative_path: &str) -> bool {
+        let normalized_path = relative_path.replace('\\', "/");
+        self.steps
+            .iter()
+            .any(|(_, pattern)| pattern.as_ref().is_none_or(|p| p.matches(&normalized_path)))
+    }
+
+    /* Runs every rule whose glob matches `relative_path`, in order. */
+    pub fn apply(&self, relative_path: &str, content: String) -> String {
+        let normalized_path = relative_path.replace('\\', "/");
+        self.steps
+            .iter()
+            .filter(|(_, pattern)| pattern.as_ref().is_none_or(|p| p.matches(&normalized_path)))
+            .fold(content, |text, (transform, _)| {
+                transform.apply(&normalized_path, &text)
+            })
+    }
+}
+
+/*
+ * Parses the text entered in the "Content Transforms..." dialog: a comma-separated list of
+ * `name[:width][@glob]`, e.g. `strip-comments@*.rs, collapse-blank-lines, convert-tabs:2`.
+ * "off" or an empty input clears the list.
+ */
+pub fn parse_setting(text: &str) -> Result<Vec<TransformRule>, String> {
+    let trimmed = text.trim();
+    if trimmed.is_empty() || trimmed.eq_ignore_ascii_case("off") {
+        return Ok(Vec::new());
+    }
+    let mut rules = Vec::new();
+    for item in trimmed.split(',').map(str::trim).filter(|i| !i.is_empty()) {
+        let (spec, glob) = match item.split_once('@') {
+            Some((spec, glob)) => (spec.trim(), Some(glob.trim().to_string())),
+            None => (item, None),
+        };
+        let (name, argument) = match spec.split_once(':') {
+            Some((name, argument)) => (name.trim(), Some(argument.trim())),
+            None => (spec, None),
+        };
+        let transform = match (name.to_ascii_lowercase().as_str(), argument) {
+            ("strip-comments", None) => ContentTransform::StripComments,
+            ("collapse-blank-lines", None) => ContentTransform::CollapseBlankLines,
+            ("trim-trailing-whitespace", None) => ContentTransform::TrimTrailingWhitespace,
+            ("remove-license-header", None) => ContentTransform::RemoveLicenseHeader,
+            ("convert-tabs", None) => ContentTransform::ConvertTabs {
+                width: DEFAULT_TAB_WIDTH,
+            },
+            ("convert-tabs", Some(width)) => match width.parse::<usize>() {
+                Ok(width) if width > 0 => ContentTransform::ConvertTabs { width },
+                _ => return Err(format!("'{width}' is not a valid tab width")),
+            },
+            (known, Some(_)) if ContentTransform::ALL_NAMES.contains(&known) => {
+                return Err(format!("Transform '{known}' takes no argument"));
+            }
+            _ => {
+                return Err(format!(
+                    "Unknown transform '{name}' (expected one of: {})",
+                    ContentTransform::ALL_NAMES.join(", ")
+                ));
+            }
+        };
+        if let Some(pattern) = &glob
+            && let Err(e) = glob::Pattern::new(pattern)
+        {
+            return Err(format!("Invalid glob '{pattern}': {}", e.msg));
+        }
+        rules.push(TransformRule { transform, glob });
+    }
+    Ok(rules)
+}
+
+/* The inverse of `parse_setting`, used as the dialog's default text. */
+pub fn to_setting_text(rules: &[TransformRule]) -> String {
+    if rules.is_empty() {
+        return "off".to_string();
+    }
+    rules
+        .iter()
+        .map(|rule| {
+            let mut text = rule.transform.setting_name().to_string();
+            if let ContentTransform::ConvertTabs { width } = rule.transform {
+                text.push_str(&format!(":{width}"));
+            }
+            if let Some(glob) = &rule.glob {
+                text.push('@');
+                text.push_str(glob);
+            }
+            text
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/* A string literal delimiter and whether the literal may continue on the next line. */
+#[derive(Debug, Clone, Copy)]
+struct QuoteSpec {
+    delimiter: char,
+    multiline: bool,
+    /* Upper bound on the literal length; keeps Rust lifetimes from being read as chars. */
+    max_len: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+struct CommentSyntax {
+    line_markers: &'static [&'static str],
+    block: Option<(&'static str, &'static str)>,
+    quotes: &'static [QuoteSpec],
+    triple_quotes: bool,
+    rust_raw_strings: bool,
+    keep_shebang: bool,
+}
+
+const C_LIKE_QUOTES: &[QuoteSpec] = &[
+    QuoteSpec {
+        delimiter: '"',
+        multiline: false,
+        max_len: None,
+    },
+    QuoteSpec {
+        delimiter: '\'',
+        multiline: false,
+        max_len: None,
+    },
+    QuoteSpec {
+        delimiter: '`',
+        multiline: true,
+        max_len: None,
+    },
+];
+
+const RUST_QUOTES: &[QuoteSpec] = &[
+    QuoteSpec {
+        delimiter: '"',
+        multiline: true,
+        max_len: None,
+    },
+    QuoteSpec {
+        delimiter: '\'',
+        multiline: false,
+        max_len: Some(12),
+    },
+];
+
+const HASH_QUOTES: &[QuoteSpec] = &[
+    QuoteSpec {
+        delimiter: '"',
+        multiline: false,
+        max_len: None,
+    },
+    QuoteSpec {
+        delimiter: '\'',
+        multiline: false,
+        max_len: None,
+    },
+];
+
+fn comment_syntax_for(relative_path: &str) -> Option<CommentSyntax> {
+    let file_name = relative_path.rsplit('/').next().unwrap_or(relative_path);
+    let extension = file_name
+        .rsplit_once('.')
+        .map(|(_, ext)| ext.to_ascii_lowercase())
+        .unwrap_or_default();
+    let c_like = |quotes| CommentSyntax {
+        line_markers: &["//"],
+        block: Some(("/*", "*/")),
+        quotes,
+        triple_quotes: false,
+        rust_raw_strings: false,
+        keep_shebang: false,
+    };
+    let hash = |triple_quotes| CommentSyntax {
+        line_markers: &["#"],
+        block: None,
+        quotes: HASH_QUOTES,
+        triple_quotes,
+        rust_raw_strings: false,
+        keep_shebang: true,
+    };
+    match extension.as_str() {
+        "rs" => Some(CommentSyntax {
+            rust_raw_strings: true,
+            ..c_like(RUST_QUOTES)
+        }),
+        "c" | "h" | "hpp" | "hh" | "cpp" | "cc" | "cxx" | "cs" | "java" | "kt" | "kts"
+        | "swift" | "scala" | "dart" | "go" | "js" | "mjs" | "cjs" | "ts" | "tsx" | "jsx" => {
+            Some(c_like(C_LIKE_QUOTES))
+        }
+        "py" | "pyi" => Some(hash(true)),
+        "sh" | "bash" | "zsh" | "rb" | "pl" | "yml" | "yaml" | "toml" | "ps1" | "r" | "cmake" => {
+            Some(hash(false))
+        }
+        _ if matches!(file_name, "Makefile" | "Dockerfile" | "CMakeLists.txt") => Some(hash(false)),
+        _ => None,
+    }
+}
+
+fn starts_with_at(chars: &[char], index: usize, pattern: &str) -> bool {
+    pattern
+        .chars()
+        .enumerate()
+        .all(|(offset, expected)| chars.get(index + offset) == Some(&expected))
+}
+
+fn find_from(chars: &[char], start: usize, pattern: &str) -> Option<usize> {
+    (start..chars.len()).find(|&i| starts_with_at(chars, i, pattern))
+}
+
+/*
+ * Returns the index just past the string literal starting at `index`, or `None` if no
+ * literal starts there (or it is not terminated where the language requires it to be).
+ */
+fn string_literal_end(chars: &[char], index: usize, syntax: &CommentSyntax) -> Option<usize> {
+    let current = chars[index];
+    if syntax.rust_raw_strings
+        && current == 'r'
+        && (index == 0
+            || !(chars[index - 1].is_alphanumeric() || chars[index - 1] == '_')
+            || chars[index - 1] == 'b')
+    {
+        let hashes = chars[index + 1..].iter().take_while(|c| **c == '#').count();
+        if chars.get(index + 1 + hashes) == Some(&'"') {
+            let closing = format!("\"{}", "#".repeat(hashes));
+            return find_from(chars, index + 2 + hashes, &closing).map(|i| i + closing.len());
+        }
+    }
+    if syntax.triple_quotes {
+        for triple in ["\"\"\"", "'''"] {
+            if starts_with_at(chars, index, triple) {
+                return find_from(chars, index + 3, triple).map(|i| i + 3);
+            }
+        }
+    }
+    let quote = syntax.quotes.iter().find(|q| q.delimiter == current)?;
+    let mut i = index + 1;
+    while i < chars.len() {
+        if quote.max_len.is_some_and(|max| i - index > max) {
+            return None;
+        }
+        match chars[i] {
+            '\\' => i += 2,
+            '\n' if !quote.multiline => return None,
+            c if c == quote.delimiter => return Some(i + 1),
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+/*
+ * Appends one finished line to `out`. A line that held a comment loses its trailing
+ * whitespace, and disappears entirely if nothing but whitespace is left.
+ */
+fn finish_line(out: &mut String, line: &mut String, had_comment: bool, newline: bool) {
+    let carriage_return = line.ends_with('\r');
+    if had_comment {
+        let kept = line.trim_end();
+        if kept.is_empty() {
+            line.clear();
+            return;
+        }
+        out.push_str(kept);
+        if carriage_return {
+            out.push('\r');
+        }
+    } else {
+        out.push_str(line);
+    }
+    if newline {
+        out.push('\n');
+    }
+    line.clear();
+}
+
+fn strip_comments(content: &str, syntax: &CommentSyntax) -> String {
+    let chars: Vec<char> = content.chars().collect();
+    let mut out = String::with_capacity(content.len());
+    let mut line = String::new();
+    let mut line_had_comment = false;
+    let mut i = 0;
+
+    if syntax.keep_shebang && starts_with_at(&chars, 0, "#!") {
+        while i < chars.len() && chars[i] != '\n' {
+            line.push(chars[i]);
+            i += 1;
+        }
+    }
+
+    while i < chars.len() {
+        let current = chars[i];
+        if current == '\n' {
+            finish_line(&mut out, &mut line, line_had_comment, true);
+            line_had_comment = false;
+            i += 1;
+            continue;
+        }
+        if let Some((open, close)) = syntax.block
+            && starts_with_at(&chars, i, open)
+        {
+            let end = find_from(&chars, i + open.len(), close)
+                .map(|e| e + close.len())
+                .unwrap_or(chars.len());
+            line_had_comment = true;
+            for c in &chars[i..end] {
+                if *c == '\n' {
+                    finish_line(&mut out, &mut line, true, true);
+                }
+            }
+            i = end;
+            continue;
+        }
+        if syntax
+            .line_markers
+            .iter()
+            .any(|marker| starts_with_at(&chars, i, marker))
+        {
+            line_had_comment = true;
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+            continue;
+        }
+        if let Some(end) = string_literal_end(&chars, i, syntax) {
+            for c in &chars[i..end] {
+                if *c == '\n' {
+                    finish_line(&mut out, &mut line, line_had_comment, true);
+                    line_had_comment = false;
+                } else {
+                    line.push(*c);
+                }
+            }
+            i = end;
+            continue;
+        }
+        line.push(current);
+        i += 1;
+    }
+    if !line.is_empty() {
+        finish_line(&mut out, &mut line, line_had_comment, false);
+    }
+    out
+}
+
+fn collapse_blank_lines(content: &str) -> String {
+    let mut out = String::with_capacity(content.len());
+    let mut previous_blank = false;
+    for line in content.split_inclusive('\n') {
+        let blank = line.trim().is_empty();
+        if !(blank && previous_blank) {
+            out.push_str(line);
+        }
+        previous_blank = blank;
+    }
+    out
+}
+
+fn trim_trailing_whitespace(content: &str) -> String {
+    let mut out = String::with_capacity(content.len());
+    for line in content.split_inclusive('\n') {
+        let (body, ending) = match line.strip_suffix("\r\n") {
+            Some(body) => (body, "\r\n"),
+            None => match line.strip_suffix('\n') {
+                Some(body) => (body, "\n"),
+                None => (line, ""),
+            },
+        };
+        out.push_str(body.trim_end_matches([' ', '\t']));
+        out.push_str(ending);
+    }
+    out
+}
+
+const LICENSE_MARKERS: [&str; 4] = ["license", "copyright", "spdx-license-identifier", "(c)"];
+
+/*
+ * Finds the end (exclusive line index) of the comment block starting at line `start`:
+ * a `/* ... */` block that ends a line, or a run of lines sharing a line-comment prefix.
+ */
+fn leading_comment_block_end(lines: &[&str], start: usize) -> Option<usize> {
+    let first = lines.get(start)?.trim_start();
+    if first.starts_with("/*") {
+        for (offset, line) in lines[start..].iter().enumerate() {
+            let search_from = if offset == 0 { 2 } else { 0 };
+            let trimmed = line.trim_start();
+            if let Some(close) = trimmed.get(search_from..).and_then(|t| t.find("*/")) {
+                let rest = &trimmed[search_from + close + 2..];
+                return rest.trim().is_empty().then_some(start + offset + 1);
+            }
+        }
+        return None;
+    }
+    let prefix = ["//", "--", "#"]
+        .into_iter()
+        .find(|p| first.starts_with(p) && !first.starts_with("#!"))?;
+    let run = lines[start..]
+        .iter()
+        .take_while(|line| line.trim_start().starts_with(prefix))
+        .count();
+    Some(start + run)
+}
+
+fn remove_license_header(content: &str) -> String {
+    let lines: Vec<&str> = content.split_inclusive('\n').collect();
+    let shebang_lines = usize::from(lines.first().is_some_and(|l| l.starts_with("#!")));
+    let start = shebang_lines
+        + lines[shebang_lines..]
+            .iter()
+            .take_while(|line| line.trim().is_empty())
+            .count();
+    let Some(end) = leading_comment_block_end(&lines, start) else {
+        return content.to_string();
+    };
+    let header = lines[start..end].concat().to_lowercase();
+    if !LICENSE_MARKERS.iter().any(|marker| header.contains(marker)) {
+        return content.to_string();
+    }
+    let resume = end
+        + lines[end..]
+            .iter()
+            .take_while(|line| line.trim().is_empty())
+            .count();
+    let mut out = lines[..shebang_lines].concat();
+    out.push_str(&lines[resume..].concat());
+    out
+}
+
+fn convert_tabs(content: &str, width: usize) -> String {
+    let width = width.max(1);
+    let mut out = String::with_capacity(content.len());
+    let mut column = 0;
+    for c in content.chars() {
+        match c {
+            '\t' => {
+                let spaces = width - column % width;
+                out.extend(std::iter::repeat_n(' ', spaces));
+                column += spaces;
+            }
+            '\n' => {
+                out.push(c);
+                column = 0;
+            }
+            _ => {
+                out.push(c);
+                column += 1;
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_comments_rust_keeps_strings_and_code() {
+        let source = "// header\n\
+                      /// Doc comment\n\
+                      fn main() { // trailing\n    \
+                      let url = \"http://example.com\"; /* inline */ let c = '/';\n    \
+                      let raw = r#\"no // comment\"#;\n    \
+                      /* multi\n       line */\n    \
+                      call::<'a>(x); // done\n\
+                      }\n";
+
+        let stripped = ContentTransform::StripComments.apply("src/main.rs", source);
+
+        assert_eq!(
+            stripped,
+            "fn main() {\n    \
+             let url = \"http://example.com\";  let c = '/';\n    \
+             let raw = r#\"no // comment\"#;\n    \
+             call::<'a>(x);\n\
+             }\n"
+        );
+    }
+
+    #[test]
+    fn test_strip_comments_python_keeps_shebang_and_strings() {
+        let source = "#!/usr/bin/env python\n# comment\nx = \"# not a comment\"  # note\n\"\"\"doc # kept\"\"\"\n";
+
+        let stripped = ContentTransform::StripComments.apply("tool.py", source);
+
+        assert_eq!(
+            stripped,
+            "#!/usr/bin/env python\nx = \"# not a comment\"\n\"\"\"doc # kept\"\"\"\n"
+        );
+        assert_eq!(
+            ContentTransform::StripComments.apply("notes.txt", "// kept\n"),
+            "// kept\n"
+        );
+    }
+
+    #[test]
+    fn test_whitespace_transforms() {
+        assert_eq!(
+            ContentTransform::CollapseBlankLines.apply("a", "a\n\n \n\nb\n\n"),
+            "a\n\nb\n\n"
+        );
+        assert_eq!(
+            ContentTransform::TrimTrailingWhitespace.apply("a", "a  \r\nb\t\nc "),
+            "a\r\nb\nc"
+        );
+        assert_eq!(
+            ContentTransform::ConvertTabs { width: 4 }.apply("a", "\tx\n ab\ty\n"),
+            "    x\n ab y\n"
+        );
+    }
+
+    #[test]
+    fn test_remove_license_header() {
+        let source = "/*\n * Copyright (c) 2024 Someone\n * MIT License\n */\n\nuse std::io;\n";
+        assert_eq!(
+            ContentTransform::RemoveLicenseHeader.apply("a.rs", source),
+            "use std::io;\n"
+        );
+        let hashed = "#!/bin/sh\n# SPDX-License-Identifier: MIT\n# Copyright Me\necho hi\n";
+        assert_eq!(
+            ContentTransform::RemoveLicenseHeader.apply("a.sh", hashed),
+            "#!/bin/sh\necho hi\n"
+        );
+        let documentation = "// Parses the input.\nfn parse() {}\n";
+        assert_eq!(
+            ContentTransform::RemoveLicenseHeader.apply("a.rs", documentation),
+            documentation
+        );
+    }
+
+    #[test]
+    fn test_pipeline_applies_rules_in_order_and_respects_globs() {
+        let rules =
+            parse_setting("strip-comments@src/*.rs, collapse-blank-lines, convert-tabs:2").unwrap();
+        let pipeline = TransformPipeline::compile(&rules).unwrap();
+        let source = "// c\n\n\n\tx\n";
+
+        assert_eq!(pipeline.apply("src/a.rs", source.to_string()), "\n  x\n");
+        assert_eq!(
+            pipeline.apply("README.md", source.to_string()),
+            "// c\n\n  x\n"
+        );
+        assert_eq!(
+            to_setting_text(&rules),
+            "strip-comments@src/*.rs, collapse-blank-lines, convert-tabs:2"
+        );
+    }
+
+    #[test]
+    fn test_parse_setting_rejects_invalid_input() {
+        assert_eq!(parse_setting(" off "), Ok(Vec::new()));
+        assert!(parse_setting("shrink").is_err());
+        assert!(parse_setting("convert-tabs:0").is_err());
+        assert!(parse_setting("strip-comments:2").is_err());
+        assert!(parse_setting("strip-comments@[").is_err());
+    }
+}
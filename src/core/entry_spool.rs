@@ -0,0 +1,98 @@
+use super::atomic_write;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+
+/*
+ * A scratch file for the prepared entries of one archive generation. Each source file is
+ * read and prepared once and its entry appended here; the archive is then written by
+ * reading the entries back one at a time, so the content of only one file is held in
+ * memory at a time. The file is created in the system's temporary folder and removed
+ * when the spool is dropped.
+ */
+pub struct EntrySpool {
+    // Declared before `scratch` so the file is closed before it is removed.
+    file: File,
+    scratch: ScratchFile,
+    len: u64,
+}
+
+/* Removes the spool's file when dropped. */
+struct ScratchFile {
+    path: PathBuf,
+}
+
+impl Drop for ScratchFile {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_file(&self.path) {
+            log::warn!(
+                "EntrySpool: Failed to remove scratch file {:?}: {e}",
+                self.path
+            );
+        }
+    }
+}
+
+/* The location of one spooled text. */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpooledText {
+    offset: u64,
+    len: usize,
+}
+
+impl EntrySpool {
+    pub fn create() -> io::Result<Self> {
+        let (file, path) = atomic_write::create_temp_file_next_to(
+            &std::env::temp_dir().join("sourcepacker-entries"),
+        )?;
+        Ok(EntrySpool {
+            file,
+            scratch: ScratchFile { path },
+            len: 0,
+        })
+    }
+
+    pub fn push(&mut self, text: &str) -> io::Result<SpooledText> {
+        self.file.seek(SeekFrom::Start(self.len))?;
+        self.file.write_all(text.as_bytes())?;
+        let spooled = SpooledText {
+            offset: self.len,
+            len: text.len(),
+        };
+        self.len += text.len() as u64;
+        Ok(spooled)
+    }
+
+    pub fn read(&self, text: SpooledText) -> io::Result<String> {
+        let mut file = &self.file;
+        file.seek(SeekFrom::Start(text.offset))?;
+        let mut bytes = vec![0; text.len];
+        file.read_exact(&mut bytes)?;
+        String::from_utf8(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spooled_texts_read_back_in_any_order_and_file_is_removed() {
+        // Arrange
+        let mut spool = EntrySpool::create().unwrap();
+        let path = spool.scratch.path.clone();
+
+        // Act
+        let first = spool.push("first entry\n").unwrap();
+        let empty = spool.push("").unwrap();
+        let second = spool.push("zweiter Eintrag – ü\n").unwrap();
+
+        // Assert
+        assert_eq!(spool.read(second).unwrap(), "zweiter Eintrag – ü\n");
+        assert_eq!(spool.read(first).unwrap(), "first entry\n");
+        assert_eq!(spool.read(empty).unwrap(), "");
+        assert!(path.is_file());
+        drop(spool);
+        assert!(!path.exists());
+    }
+}
@@ -4,6 +4,7 @@ use std::io;
 use std::path::{Path, PathBuf};
 
 use crate::app_logic::{handler::PathToTreeItemIdMap, ui_constants};
+use crate::core::profile_settings::{ArchiveOptions, ProfileComponent};
 use crate::core::project_context::ProfileName;
 use crate::platform_layer::{CheckState, TreeItemDescriptor, TreeItemId};
 /*
@@ -14,6 +15,8 @@ use crate::platform_layer::{CheckState, TreeItemDescriptor, TreeItemId};
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
 pub enum SelectionState {
     Selected,
+    /* Included in the archive as an outline (signatures only). [FileSelOutlineV1] */
+    Outline,
     Deselected,
     #[default]
     New,
@@ -51,8 +54,12 @@ impl FileNode {
         }
     }
 
+    /* True for files that go into the archive, in full or as an outline. */
     pub fn is_selected(&self) -> bool {
-        self.state == SelectionState::Selected
+        matches!(
+            self.state,
+            SelectionState::Selected | SelectionState::Outline
+        )
     }
 
     pub fn state(&self) -> SelectionState {
@@ -79,6 +86,41 @@ impl FileNode {
         &self.checksum
     }
 
+    pub fn set_checksum(&mut self, checksum: String) {
+        self.checksum = checksum;
+    }
+
+    /*
+     * A copy of the node moved from `from` to `to`, with the paths of its descendants
+     * rewritten to match. States and checksums are kept. [FileSystemWatchV1]
+     */
+    pub fn relocated(&self, from: &Path, to: &Path) -> FileNode {
+        let path = match self.path.strip_prefix(from) {
+            Ok(rest) if rest.as_os_str().is_empty() => to.to_path_buf(),
+            Ok(rest) => to.join(rest),
+            Err(_) => self.path.clone(),
+        };
+        let name = if self.path == from {
+            to.file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_else(|| self.name.clone())
+        } else {
+            self.name.clone()
+        };
+        FileNode {
+            path,
+            name,
+            is_dir: self.is_dir,
+            state: self.state,
+            children: self
+                .children
+                .iter()
+                .map(|child| child.relocated(from, to))
+                .collect(),
+            checksum: self.checksum.clone(),
+        }
+    }
+
     #[allow(dead_code)]
     pub fn new_file_token_details(&self, token_count: usize) -> FileTokenDetails {
         FileTokenDetails {
@@ -98,6 +140,10 @@ impl FileNode {
             text.push(' ');
             text.push(ui_constants::NEW_ITEM_INDICATOR_CHAR);
         }
+        if self.state == SelectionState::Outline {
+            text.push(' ');
+            text.push(ui_constants::OUTLINE_ITEM_INDICATOR_CHAR);
+        }
 
         TreeItemDescriptor {
             id,
@@ -356,6 +402,9 @@ pub struct Profile {
     // especially when the tree structure can change.
     pub selected_paths: HashSet<PathBuf>,
     pub deselected_paths: HashSet<PathBuf>,
+    /* Files included as an outline only. Older profiles have none. */
+    #[serde(default)]
+    pub outline_paths: HashSet<PathBuf>,
     pub archive_path: Option<PathBuf>,
     /* Stores cached token counts and checksums for files.
      * The `#[serde(default)]` attribute ensures that profiles saved before this field existed can still be loaded. */
@@ -365,6 +414,12 @@ pub struct Profile {
      * The `#[serde(default)]` attribute preserves compatibility with profiles saved before patterns existed. */
     #[serde(default)]
     pub exclude_patterns: Vec<String>,
+    /* Settings controlling how the archive is generated, such as the output format. */
+    #[serde(default)]
+    pub archive_options: ArchiveOptions,
+    /* Other profiles whose selections are combined with this one. [ProfileCompositeV1] */
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub components: Vec<ProfileComponent>,
 }
 
 impl Profile {
@@ -379,9 +434,12 @@ impl Profile {
             root_folder,
             selected_paths: HashSet::new(),
             deselected_paths: HashSet::new(),
+            outline_paths: HashSet::new(),
             archive_path: None,
             file_details: HashMap::new(),
             exclude_patterns: Vec::new(),
+            archive_options: ArchiveOptions::default(),
+            components: Vec::new(),
         }
     }
 }
@@ -391,16 +449,35 @@ impl Profile {
  * This enum indicates whether the archive is up-to-date with selected source files,
  * needs regeneration, or if there were issues determining its status.
  */
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ArchiveStatus {
     UpToDate,
-    OutdatedRequiresUpdate,
+    /* Carries the changes when a content record exists; `None` means a selected file is
+     * newer than the archive and no record was available to tell what changed. */
+    OutdatedRequiresUpdate(Option<ArchiveChanges>),
     NotYetGenerated,                      // Profile has no archive_path associated
     ArchiveFileMissing,                   // archive_path is set, but file doesn't exist
     NoFilesSelected, // No files are selected, so archive status is moot or "up to date" by default.
     ErrorChecking(Option<io::ErrorKind>), // An I/O error occurred (optional: kind of error)
 }
 
+/*
+ * The difference between the files recorded for an archive and the current selection,
+ * as absolute paths. [ArchiveSyncNotifyUserV1]
+ */
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ArchiveChanges {
+    pub added: Vec<PathBuf>,
+    pub removed: Vec<PathBuf>,
+    pub modified: Vec<PathBuf>,
+}
+
+impl ArchiveChanges {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::{FileNode, FileTokenDetails, Profile, SelectionState};
@@ -0,0 +1,518 @@
+use super::checksum_utils;
+use super::file_node::FileNode;
+use super::file_system::{self, ScanFilter};
+use notify::event::{EventKind, Flag, ModifyKind, RenameMode};
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, mpsc};
+use std::time::{Duration, Instant};
+
+/*
+ * This module keeps the file tree up to date while the profile root changes on disk.
+ * [FileSystemWatchV1] The root is watched recursively with the platform's notification
+ * API (inotify, FSEvents, ReadDirectoryChangesW) through the `notify` crate. Events are
+ * debounced: a burst is applied once the folder has been quiet for `WATCH_DEBOUNCE`, or at
+ * the latest `WATCH_MAX_DELAY` after its first event. The burst is turned into added,
+ * removed, modified and renamed paths and applied to the `FileNode` snapshot without a full
+ * scan; whether a path belongs in the tree is decided with the scanner's own rules (see
+ * `file_system::ScanFilter`), and events under `.sourcepacker` are dropped as they arrive.
+ * When events were lost, or an ignore file changed, a full rescan is requested instead.
+ */
+
+pub const WATCH_DEBOUNCE: Duration = Duration::from_millis(300);
+pub const WATCH_MAX_DELAY: Duration = Duration::from_secs(2);
+
+const IGNORE_FILE_NAMES: [&str; 2] = [".gitignore", ".ignore"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileChange {
+    Added(PathBuf),
+    Removed(PathBuf),
+    Modified(PathBuf),
+    Renamed { from: PathBuf, to: PathBuf },
+    /* Events were lost or ignore rules changed; only a full scan gives the right tree. */
+    Rescan,
+}
+
+/*
+ * Turns a burst of watcher events into changes. A rename reported as a pair of halves and
+ * as a whole (as inotify does) is taken once, as a whole; a half without its other half is
+ * a removal or an addition. Repeated additions and modifications of a path are merged.
+ */
+pub fn changes_from_events(events: &[Event]) -> Vec<FileChange> {
+    let whole_renames: HashSet<usize> = events
+        .iter()
+        .filter(|e| e.kind == EventKind::Modify(ModifyKind::Name(RenameMode::Both)))
+        .filter_map(Event::tracker)
+        .collect();
+
+    let mut changes = Vec::new();
+    for event in events {
+        if event.need_rescan() {
+            return vec![FileChange::Rescan];
+        }
+        let half_of_whole = event
+            .tracker()
+            .is_some_and(|tracker| whole_renames.contains(&tracker));
+        let paths = event.paths.iter().cloned();
+        match event.kind {
+            EventKind::Access(_) => {}
+            EventKind::Create(_) => changes.extend(paths.map(FileChange::Added)),
+            EventKind::Remove(_) => changes.extend(paths.map(FileChange::Removed)),
+            EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => {
+                if let [from, to] = event.paths.as_slice() {
+                    changes.push(FileChange::Renamed {
+                        from: from.clone(),
+                        to: to.clone(),
+                    });
+                }
+            }
+            EventKind::Modify(ModifyKind::Name(RenameMode::From | RenameMode::To))
+                if half_of_whole => {}
+            EventKind::Modify(ModifyKind::Name(RenameMode::From)) => {
+                changes.extend(paths.map(FileChange::Removed))
+            }
+            EventKind::Modify(ModifyKind::Name(RenameMode::To)) => {
+                changes.extend(paths.map(FileChange::Added))
+            }
+            EventKind::Modify(ModifyKind::Name(_)) | EventKind::Any | EventKind::Other => changes
+                .extend(paths.map(|path| {
+                    if path.exists() {
+                        FileChange::Modified(path)
+                    } else {
+                        FileChange::Removed(path)
+                    }
+                })),
+            EventKind::Modify(_) => changes.extend(paths.map(FileChange::Modified)),
+        }
+    }
+    coalesce(changes)
+}
+
+fn touches_ignore_file(change: &FileChange) -> bool {
+    let is_ignore_file = |path: &PathBuf| {
+        path.file_name()
+            .is_some_and(|name| IGNORE_FILE_NAMES.iter().any(|n| name == *n))
+            || path.ends_with(".git/info/exclude")
+    };
+    match change {
+        FileChange::Added(p) | FileChange::Removed(p) | FileChange::Modified(p) => {
+            is_ignore_file(p)
+        }
+        FileChange::Renamed { from, to } => is_ignore_file(from) || is_ignore_file(to),
+        FileChange::Rescan => true,
+    }
+}
+
+fn coalesce(changes: Vec<FileChange>) -> Vec<FileChange> {
+    if changes.iter().any(touches_ignore_file) {
+        return vec![FileChange::Rescan];
+    }
+    // Paths whose current content is read anyway by an earlier change in the burst.
+    let mut fresh: HashSet<PathBuf> = HashSet::new();
+    let mut merged = Vec::new();
+    for change in changes {
+        match &change {
+            FileChange::Added(path) | FileChange::Modified(path) => {
+                if !fresh.insert(path.clone()) {
+                    continue;
+                }
+            }
+            FileChange::Removed(path) => {
+                fresh.remove(path);
+            }
+            FileChange::Renamed { from, to } => {
+                fresh.remove(from);
+                fresh.remove(to);
+            }
+            FileChange::Rescan => {}
+        }
+        merged.push(change);
+    }
+    merged
+}
+
+/* Collects events until the folder has been quiet for a while. */
+#[derive(Debug)]
+pub struct EventDebouncer {
+    quiet: Duration,
+    max_delay: Duration,
+    pending: Vec<Event>,
+    first_event_at: Option<Instant>,
+    last_event_at: Option<Instant>,
+}
+
+impl EventDebouncer {
+    pub fn new(quiet: Duration, max_delay: Duration) -> Self {
+        EventDebouncer {
+            quiet,
+            max_delay,
+            pending: Vec::new(),
+            first_event_at: None,
+            last_event_at: None,
+        }
+    }
+
+    pub fn push(&mut self, event: Event, now: Instant) {
+        self.first_event_at.get_or_insert(now);
+        self.last_event_at = Some(now);
+        self.pending.push(event);
+    }
+
+    /* The changes of the pending burst once it is over, or `None` while it goes on. */
+    pub fn take_if_quiet(&mut self, now: Instant) -> Option<Vec<FileChange>> {
+        let (first, last) = self.first_event_at.zip(self.last_event_at)?;
+        if now.duration_since(last) < self.quiet && now.duration_since(first) < self.max_delay {
+            return None;
+        }
+        self.first_event_at = None;
+        self.last_event_at = None;
+        let changes = changes_from_events(&std::mem::take(&mut self.pending));
+        (!changes.is_empty()).then_some(changes)
+    }
+}
+
+/* A running watch of a profile root. Dropping it stops the watch. */
+pub struct FileWatcher {
+    root: PathBuf,
+    _watcher: RecommendedWatcher,
+    receiver: Mutex<mpsc::Receiver<notify::Result<Event>>>,
+    debouncer: EventDebouncer,
+}
+
+impl FileWatcher {
+    pub fn start(root: &Path) -> notify::Result<Self> {
+        let (sender, receiver) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(sender)?;
+        watcher.watch(root, RecursiveMode::Recursive)?;
+        log::debug!("FileWatcher: Watching {root:?}.");
+        Ok(FileWatcher {
+            root: root.to_path_buf(),
+            _watcher: watcher,
+            receiver: Mutex::new(receiver),
+            debouncer: EventDebouncer::new(WATCH_DEBOUNCE, WATCH_MAX_DELAY),
+        })
+    }
+
+    /* Takes the events received so far; returns the changes of a burst once it is over. */
+    pub fn poll(&mut self) -> Option<Vec<FileChange>> {
+        let now = Instant::now();
+        let receiver = self.receiver.lock().unwrap();
+        for result in receiver.try_iter() {
+            match result {
+                Ok(event) => {
+                    let internal_only = !event.paths.is_empty()
+                        && event
+                            .paths
+                            .iter()
+                            .all(|path| file_system::is_internal_config_path(&self.root, path));
+                    if !internal_only {
+                        self.debouncer.push(event, now);
+                    }
+                }
+                Err(e) => {
+                    log::warn!("FileWatcher: Watch error for {:?}: {e}", self.root);
+                    self.debouncer
+                        .push(Event::new(EventKind::Other).set_flag(Flag::Rescan), now);
+                }
+            }
+        }
+        self.debouncer.take_if_quiet(now)
+    }
+}
+
+/* What applying a burst of changes did to the tree. */
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WatchUpdate {
+    pub changed_paths: Vec<PathBuf>,
+    pub renamed: Vec<(PathBuf, PathBuf)>,
+    pub needs_rescan: bool,
+}
+
+impl WatchUpdate {
+    pub fn is_empty(&self) -> bool {
+        self.changed_paths.is_empty() && self.renamed.is_empty() && !self.needs_rescan
+    }
+}
+
+/*
+ * Applies `changes` to the snapshot of `scan_root`. Added files are hashed, added folders
+ * scanned, renamed nodes moved with their state; paths the scanner would skip are left out
+ * (and dropped if they were in the tree). New nodes get the default state, so the caller
+ * re-applies the selection as after a refresh.
+ */
+pub fn apply_changes(
+    nodes: &mut Vec<FileNode>,
+    scan_root: &Path,
+    exclude_patterns: &[String],
+    changes: &[FileChange],
+) -> WatchUpdate {
+    let mut update = WatchUpdate::default();
+    if changes.contains(&FileChange::Rescan) {
+        update.needs_rescan = true;
+        return update;
+    }
+    // The whole burst has happened by now, so one view of the folders serves all changes.
+    let mut tree = TreeUpdater {
+        nodes,
+        scan_root,
+        exclude_patterns,
+        filter: ScanFilter::new(scan_root, exclude_patterns),
+        update: &mut update,
+    };
+    for change in changes {
+        match change {
+            FileChange::Added(path) | FileChange::Modified(path) => tree.upsert(path),
+            FileChange::Removed(path) => tree.remove(path),
+            FileChange::Renamed { from, to } => tree.rename(from, to),
+            FileChange::Rescan => {}
+        }
+    }
+    update
+}
+
+struct TreeUpdater<'a> {
+    nodes: &'a mut Vec<FileNode>,
+    scan_root: &'a Path,
+    exclude_patterns: &'a [String],
+    filter: ScanFilter,
+    update: &'a mut WatchUpdate,
+}
+
+impl TreeUpdater<'_> {
+    fn upsert(&mut self, path: &Path) {
+        if !self.filter.is_scanned(path) {
+            self.remove(path);
+            return;
+        }
+        let replaced = match find_node_mut(self.nodes, path) {
+            Some(node) if node.is_dir() == path.is_dir() || !path.exists() => {
+                if !node.is_dir() {
+                    match checksum_utils::calculate_sha256_checksum(path) {
+                        Ok(checksum) if checksum != node.checksum() => {
+                            node.set_checksum(checksum);
+                            self.update.changed_paths.push(path.to_path_buf());
+                        }
+                        Ok(_) => {}
+                        Err(e) => log::warn!("FileWatcher: Failed to hash {path:?}: {e}"),
+                    }
+                }
+                return;
+            }
+            Some(_) => true,
+            None => false,
+        };
+        if replaced {
+            // A file became a folder of the same name or the other way round.
+            take_node(self.nodes, path);
+        }
+        let Some(parent) = path.parent() else {
+            return;
+        };
+        if parent != self.scan_root && find_node_mut(self.nodes, parent).is_none() {
+            // The folder is new as well; scanning it picks up this path too.
+            self.upsert(parent);
+            return;
+        }
+
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let node = if path.is_dir() {
+            let mut node = FileNode::new(path.to_path_buf(), name, true, String::new());
+            match file_system::scan_subtree(self.scan_root, path, self.exclude_patterns) {
+                Ok(children) => node.children = children,
+                Err(e) => log::warn!("FileWatcher: Failed to scan new folder {path:?}: {e}"),
+            }
+            node
+        } else {
+            let checksum = checksum_utils::calculate_sha256_checksum(path).unwrap_or_else(|e| {
+                log::warn!("FileWatcher: Failed to hash {path:?}: {e}");
+                String::new()
+            });
+            FileNode::new(path.to_path_buf(), name, false, checksum)
+        };
+        self.insert(node);
+    }
+
+    fn remove(&mut self, path: &Path) {
+        if take_node(self.nodes, path).is_some() {
+            self.update.changed_paths.push(path.to_path_buf());
+        }
+    }
+
+    fn rename(&mut self, from: &Path, to: &Path) {
+        match take_node(self.nodes, from) {
+            Some(node) if self.filter.is_scanned(to) => {
+                let parent_known = to
+                    .parent()
+                    .is_some_and(|p| p == self.scan_root || find_node_mut(self.nodes, p).is_some());
+                if parent_known {
+                    self.insert(node.relocated(from, to));
+                    self.update
+                        .renamed
+                        .push((from.to_path_buf(), to.to_path_buf()));
+                } else {
+                    self.update.changed_paths.push(from.to_path_buf());
+                    self.upsert(to);
+                }
+            }
+            Some(_) => self.update.changed_paths.push(from.to_path_buf()),
+            None => self.upsert(to),
+        }
+    }
+
+    fn insert(&mut self, node: FileNode) {
+        let path = node.path().to_path_buf();
+        let siblings = match path.parent() {
+            Some(parent) if parent != self.scan_root => match find_node_mut(self.nodes, parent) {
+                Some(parent_node) => &mut parent_node.children,
+                None => return,
+            },
+            _ => &mut *self.nodes,
+        };
+        siblings.push(node);
+        file_system::sort_file_nodes_recursively(siblings);
+        self.update.changed_paths.push(path);
+    }
+}
+
+fn find_node_mut<'a>(nodes: &'a mut [FileNode], path: &Path) -> Option<&'a mut FileNode> {
+    for node in nodes.iter_mut() {
+        if node.path() == path {
+            return Some(node);
+        }
+        if node.is_dir() && path.starts_with(node.path()) {
+            return find_node_mut(&mut node.children, path);
+        }
+    }
+    None
+}
+
+fn take_node(nodes: &mut Vec<FileNode>, path: &Path) -> Option<FileNode> {
+    if let Some(index) = nodes.iter().position(|node| node.path() == path) {
+        return Some(nodes.remove(index));
+    }
+    nodes
+        .iter_mut()
+        .find(|node| node.is_dir() && path.starts_with(node.path()))
+        .and_then(|node| take_node(&mut node.children, path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{CoreFileSystemScanner, FileSystemScannerOperations};
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn event(kind: EventKind, paths: &[&str], tracker: Option<usize>) -> Event {
+        let mut event = Event::new(kind);
+        for path in paths {
+            event = event.add_path(PathBuf::from(path));
+        }
+        match tracker {
+            Some(tracker) => event.set_tracker(tracker),
+            None => event,
+        }
+    }
+
+    #[test]
+    fn test_bursts_are_debounced_and_renames_taken_once() {
+        use notify::event::{CreateKind, DataChange};
+        let start = Instant::now();
+        let mut debouncer = EventDebouncer::new(WATCH_DEBOUNCE, WATCH_MAX_DELAY);
+        let name = |mode| EventKind::Modify(ModifyKind::Name(mode));
+        let data = EventKind::Modify(ModifyKind::Data(DataChange::Content));
+        for e in [
+            event(EventKind::Create(CreateKind::File), &["/r/new.rs"], None),
+            event(data, &["/r/new.rs"], None),
+            event(data, &["/r/new.rs"], None),
+            event(name(RenameMode::From), &["/r/a.rs"], Some(7)),
+            event(name(RenameMode::To), &["/r/b.rs"], Some(7)),
+            event(name(RenameMode::Both), &["/r/a.rs", "/r/b.rs"], Some(7)),
+            event(name(RenameMode::From), &["/r/gone.rs"], Some(8)),
+        ] {
+            debouncer.push(e, start);
+        }
+
+        assert_eq!(debouncer.take_if_quiet(start + WATCH_DEBOUNCE / 2), None);
+        assert_eq!(
+            debouncer.take_if_quiet(start + WATCH_DEBOUNCE),
+            Some(vec![
+                FileChange::Added(PathBuf::from("/r/new.rs")),
+                FileChange::Renamed {
+                    from: PathBuf::from("/r/a.rs"),
+                    to: PathBuf::from("/r/b.rs"),
+                },
+                FileChange::Removed(PathBuf::from("/r/gone.rs")),
+            ])
+        );
+        assert_eq!(debouncer.take_if_quiet(start + WATCH_MAX_DELAY), None);
+
+        debouncer.push(event(data, &["/r/.gitignore"], None), start);
+        assert_eq!(
+            debouncer.take_if_quiet(start + WATCH_MAX_DELAY),
+            Some(vec![FileChange::Rescan])
+        );
+    }
+
+    #[test]
+    fn test_changes_update_the_tree_like_a_scan_would() {
+        // Arrange
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        fs::write(root.join(".gitignore"), "*.log\n").unwrap();
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(root.join("src/a.rs"), "a").unwrap();
+        fs::write(root.join("src/old.rs"), "old").unwrap();
+        fs::write(root.join("notes.txt"), "notes").unwrap();
+        fs::write(root.join("lib"), "a file for now").unwrap();
+        fs::create_dir_all(root.join("assets")).unwrap();
+        fs::write(root.join("assets/logo.svg"), "<svg/>").unwrap();
+        let scanner = CoreFileSystemScanner::new();
+        let excludes = vec!["*.tmp".to_string()];
+        let mut nodes = scanner.scan_directory(root, &excludes).unwrap();
+
+        // Act - the changes a watcher would report
+        fs::write(root.join("src/a.rs"), "a, edited").unwrap();
+        fs::rename(root.join("src/old.rs"), root.join("src/renamed.rs")).unwrap();
+        fs::remove_file(root.join("notes.txt")).unwrap();
+        fs::create_dir_all(root.join("docs/guide")).unwrap();
+        fs::write(root.join("docs/guide/intro.md"), "intro").unwrap();
+        fs::write(root.join("build.log"), "ignored by .gitignore").unwrap();
+        fs::write(root.join("scratch.tmp"), "ignored by the profile").unwrap();
+        fs::create_dir_all(root.join(".sourcepacker")).unwrap();
+        fs::write(root.join(".sourcepacker/state.json"), "{}").unwrap();
+        fs::remove_file(root.join("lib")).unwrap();
+        fs::create_dir_all(root.join("lib")).unwrap();
+        fs::write(root.join("lib/mod.rs"), "mod").unwrap();
+        fs::remove_dir_all(root.join("assets")).unwrap();
+        fs::write(root.join("assets"), "a file now").unwrap();
+        let changes = [
+            FileChange::Modified(root.join("src/a.rs")),
+            FileChange::Renamed {
+                from: root.join("src/old.rs"),
+                to: root.join("src/renamed.rs"),
+            },
+            FileChange::Removed(root.join("notes.txt")),
+            FileChange::Added(root.join("docs/guide/intro.md")),
+            FileChange::Added(root.join("build.log")),
+            FileChange::Added(root.join("scratch.tmp")),
+            FileChange::Added(root.join(".sourcepacker/state.json")),
+            FileChange::Modified(root.join("lib")),
+            FileChange::Added(root.join("assets")),
+        ];
+        let update = apply_changes(&mut nodes, root, &excludes, &changes);
+
+        // Assert
+        assert_eq!(
+            update.renamed,
+            vec![(root.join("src/old.rs"), root.join("src/renamed.rs"))]
+        );
+        assert!(!update.needs_rescan);
+        assert_eq!(nodes, scanner.scan_directory(root, &excludes).unwrap());
+    }
+}
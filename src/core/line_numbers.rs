@@ -0,0 +1,230 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/*
+ * This module implements the optional line-number gutter of archived files.
+ * [ArchiveGenLineNumbersV1] Each emitted line is prefixed with its 1-based number,
+ * right-aligned to the width of the file's last line number, followed by ` | `. The
+ * numbers are those of the content as it is archived, i.e. after content transforms;
+ * a file that is truncated by the token budget or sliced across split parts keeps the
+ * numbers of the whole file, and its header states the range it carries. Parsing an
+ * archive removes the gutter again, so edited archives can still be applied.
+ */
+
+const GUTTER_SEPARATOR: &str = " | ";
+
+/*
+ * Which files get a gutter. Without globs every file does; otherwise only files whose
+ * path relative to the project root matches one of them.
+ */
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LineNumberOptions {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub globs: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LineNumberError {
+    InvalidGlob { pattern: String, message: String },
+}
+
+impl fmt::Display for LineNumberError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LineNumberError::InvalidGlob { pattern, message } => {
+                write!(f, "Invalid line number glob '{pattern}': {message}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for LineNumberError {}
+
+/* The compiled form of `LineNumberOptions`; the default numbers no file at all. */
+#[derive(Debug, Clone, Default)]
+pub struct LineNumbering {
+    enabled: bool,
+    patterns: Vec<glob::Pattern>,
+}
+
+impl LineNumbering {
+    pub fn compile(options: Option<&LineNumberOptions>) -> Result<Self, LineNumberError> {
+        let Some(options) = options else {
+            return Ok(LineNumbering::default());
+        };
+        let patterns = options
+            .globs
+            .iter()
+            .map(|pattern| {
+                glob::Pattern::new(pattern).map_err(|e| LineNumberError::InvalidGlob {
+                    pattern: pattern.clone(),
+                    message: e.msg.to_string(),
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(LineNumbering {
+            enabled: true,
+            patterns,
+        })
+    }
+
+    pub fn applies_to(&self, relative_path: &str) -> bool {
+        let normalized_path = relative_path.replace('\\', "/");
+        self.enabled
+            && (self.patterns.is_empty()
+                || self.patterns.iter().any(|p| p.matches(&normalized_path)))
+    }
+}
+
+/*
+ * The 1-based, inclusive range of lines an archive entry carries, and the number of lines
+ * of the whole file. Written to entry headers as `lines 1-120 of 120`.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LineRange {
+    pub first: usize,
+    pub last: usize,
+    pub total: usize,
+}
+
+impl LineRange {
+    /* The range of a whole file, or `None` for an empty one. */
+    pub fn whole(total: usize) -> Option<Self> {
+        (total > 0).then_some(LineRange {
+            first: 1,
+            last: total,
+            total,
+        })
+    }
+
+    /* Parses the `1-120 of 120` form written by `Display`. */
+    pub fn parse(text: &str) -> Option<Self> {
+        let (range, total) = text.trim().split_once(" of ")?;
+        let (first, last) = range.split_once('-')?;
+        let range = LineRange {
+            first: first.trim().parse().ok()?,
+            last: last.trim().parse().ok()?,
+            total: total.trim().parse().ok()?,
+        };
+        (range.first >= 1 && range.first <= range.last && range.last <= range.total)
+            .then_some(range)
+    }
+}
+
+impl fmt::Display for LineRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{} of {}", self.first, self.last, self.total)
+    }
+}
+
+/* Prefixes every line of `content` with its number. Returns the text and its line count. */
+pub fn number_lines(content: &str) -> (String, usize) {
+    let total = content.split_inclusive('\n').count();
+    let width = total.to_string().len();
+    let mut numbered = String::with_capacity(content.len() + total * (width + 3));
+    for (index, line) in content.split_inclusive('\n').enumerate() {
+        numbered.push_str(&format!("{:>width$}{GUTTER_SEPARATOR}{line}", index + 1));
+    }
+    (numbered, total)
+}
+
+/* Removes a gutter written by `number_lines`; lines without one are kept as they are. */
+pub fn strip_line_numbers(content: &str) -> String {
+    content
+        .split_inclusive('\n')
+        .map(|line| {
+            let indent = line.len() - line.trim_start().len();
+            let rest = &line[indent..];
+            let digit_count = rest.chars().take_while(char::is_ascii_digit).count();
+            match rest[digit_count..].strip_prefix(GUTTER_SEPARATOR) {
+                Some(text) if digit_count > 0 => text,
+                _ => line,
+            }
+        })
+        .collect()
+}
+
+/*
+ * Parses the text entered in the "Line Numbers..." dialog: "off", "all", or a
+ * comma-separated list of globs such as `*.rs, *.toml`.
+ */
+pub fn parse_setting(text: &str) -> Result<Option<LineNumberOptions>, String> {
+    let trimmed = text.trim();
+    if trimmed.is_empty() || trimmed.eq_ignore_ascii_case("off") {
+        return Ok(None);
+    }
+    if trimmed.eq_ignore_ascii_case("all") {
+        return Ok(Some(LineNumberOptions::default()));
+    }
+    let mut globs = Vec::new();
+    for pattern in trimmed.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        if let Err(e) = glob::Pattern::new(pattern) {
+            return Err(format!("Invalid glob '{pattern}': {}", e.msg));
+        }
+        globs.push(pattern.to_string());
+    }
+    Ok(Some(LineNumberOptions { globs }))
+}
+
+/* The inverse of `parse_setting`, used as the dialog's default text. */
+pub fn to_setting_text(options: Option<&LineNumberOptions>) -> String {
+    match options {
+        None => "off".to_string(),
+        Some(options) if options.globs.is_empty() => "all".to_string(),
+        Some(options) => options.globs.join(", "),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_number_lines_uses_fixed_width_and_strips_back() {
+        let content: String = (1..=10).map(|i| format!("line {i}\n")).collect();
+        let (numbered, total) = number_lines(&content);
+        assert_eq!(total, 10);
+        assert!(numbered.starts_with(" 1 | line 1\n 2 | line 2\n"));
+        assert!(numbered.ends_with("10 | line 10\n"));
+        assert_eq!(strip_line_numbers(&numbered), content);
+        assert_eq!(
+            number_lines("no newline"),
+            ("1 | no newline".to_string(), 1)
+        );
+        assert_eq!(number_lines(""), (String::new(), 0));
+        assert_eq!(strip_line_numbers("x | y\n"), "x | y\n");
+    }
+
+    #[test]
+    fn test_line_range_round_trips() {
+        let range = LineRange {
+            first: 301,
+            last: 600,
+            total: 812,
+        };
+        assert_eq!(range.to_string(), "301-600 of 812");
+        assert_eq!(LineRange::parse("301-600 of 812"), Some(range));
+        assert_eq!(LineRange::parse("5-2 of 9"), None);
+        assert_eq!(LineRange::whole(0), None);
+    }
+
+    #[test]
+    fn test_setting_selects_files_by_glob() {
+        let options = parse_setting("*.rs, docs*").unwrap();
+        let numbering = LineNumbering::compile(options.as_ref()).unwrap();
+        assert!(numbering.applies_to("src\\main.rs"));
+        assert!(numbering.applies_to("docs/guide.md"));
+        assert!(!numbering.applies_to("Cargo.toml"));
+        assert_eq!(to_setting_text(options.as_ref()), "*.rs, docs*");
+
+        let all = parse_setting("all").unwrap();
+        assert!(
+            LineNumbering::compile(all.as_ref())
+                .unwrap()
+                .applies_to("Cargo.toml")
+        );
+        assert_eq!(parse_setting("off").unwrap(), None);
+        assert!(!LineNumbering::compile(None).unwrap().applies_to("a.rs"));
+        assert!(parse_setting("[").is_err());
+    }
+}
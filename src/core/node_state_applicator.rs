@@ -18,7 +18,8 @@ use std::path::PathBuf;
 pub trait NodeStateApplicatorOperations: Send + Sync {
     /*
      * Applies the selection states from a `Profile`'s path sets to a tree of `FileNode`s.
-     * Sets `FileState::Selected` for paths in `selected_paths`,
+     * Sets `FileState::Outline` for paths in `outline_paths`,
+     * `FileState::Selected` for paths in `selected_paths`,
      * `FileState::Deselected` for paths in `deselected_paths`,
      * and `FileState::New` for others. Modifies the `tree` in place.
      */
@@ -27,6 +28,7 @@ pub trait NodeStateApplicatorOperations: Send + Sync {
         tree: &mut Vec<FileNode>,
         selected_paths: &HashSet<PathBuf>,
         deselected_paths: &HashSet<PathBuf>,
+        outline_paths: &HashSet<PathBuf>,
     );
 
     /*
@@ -66,9 +68,12 @@ impl NodeStateApplicatorOperations for NodeStateApplicator {
         tree: &mut Vec<FileNode>,
         selected_paths: &HashSet<PathBuf>,
         deselected_paths: &HashSet<PathBuf>,
+        outline_paths: &HashSet<PathBuf>,
     ) {
         for node in tree.iter_mut() {
-            if selected_paths.contains(node.path()) {
+            if outline_paths.contains(node.path()) && !node.is_dir() {
+                node.set_state(SelectionState::Outline);
+            } else if selected_paths.contains(node.path()) {
                 node.set_state(SelectionState::Selected);
             } else if deselected_paths.contains(node.path()) {
                 node.set_state(SelectionState::Deselected);
@@ -81,6 +86,7 @@ impl NodeStateApplicatorOperations for NodeStateApplicator {
                     &mut node.children,
                     selected_paths,
                     deselected_paths,
+                    outline_paths,
                 );
             }
         }
@@ -180,7 +186,12 @@ mod tests {
         deselected_paths.insert(PathBuf::from("/root/dir1/file2.txt"));
 
         // Act
-        manager.apply_selection_states_to_nodes(&mut tree, &selected_paths, &deselected_paths);
+        manager.apply_selection_states_to_nodes(
+            &mut tree,
+            &selected_paths,
+            &deselected_paths,
+            &HashSet::new(),
+        );
 
         // Assert
         assert_eq!(tree[0].state(), SelectionState::Selected); // file1.txt
@@ -194,6 +205,31 @@ mod tests {
         assert_eq!(tree[2].state(), SelectionState::New); // file4.ext
     }
 
+    #[test]
+    fn test_core_state_manager_apply_profile_outline_paths() {
+        // Arrange
+        let manager = NodeStateApplicator::new();
+        let mut tree = create_test_tree();
+        let selected_paths = HashSet::from([PathBuf::from("/root/dir1")]);
+        let outline_paths = HashSet::from([
+            PathBuf::from("/root/file1.txt"),
+            PathBuf::from("/root/dir1"),
+        ]);
+
+        // Act
+        manager.apply_selection_states_to_nodes(
+            &mut tree,
+            &selected_paths,
+            &HashSet::new(),
+            &outline_paths,
+        );
+
+        // Assert
+        assert_eq!(tree[0].state(), SelectionState::Outline);
+        assert!(tree[0].is_selected());
+        assert_eq!(tree[1].state(), SelectionState::Selected); // Folders are never outlines
+    }
+
     #[test]
     fn test_core_state_manager_apply_profile_reverts_to_new() {
         // Arrange
@@ -206,7 +242,12 @@ mod tests {
         let deselected_paths = HashSet::new();
 
         // Act
-        manager.apply_selection_states_to_nodes(&mut tree, &selected_paths, &deselected_paths);
+        manager.apply_selection_states_to_nodes(
+            &mut tree,
+            &selected_paths,
+            &deselected_paths,
+            &HashSet::new(),
+        );
 
         // Assert
         assert_eq!(tree[0].state(), SelectionState::New); // Should revert to New as it's not in selected_paths
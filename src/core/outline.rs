@@ -0,0 +1,616 @@
+use std::sync::Arc;
+
+/*
+ * This module extracts outlines of source files for the "outline" inclusion level.
+ * [FileSelOutlineV1]
+ * An outline keeps a file's API surface (signatures, type definitions and module
+ * declarations) and elides function bodies as `{ ... }`, giving a model the shape of a
+ * code base for a fraction of the tokens. Extraction is pluggable per language through
+ * `OutlineExtractor`; Rust is supported out of the box. Files without a matching
+ * extractor are archived in full.
+ */
+
+/*
+ * Produces the outline of files of one language. Implementations must not fail: source
+ * they cannot make sense of should be passed through rather than dropped.
+ */
+pub trait OutlineExtractor: Send + Sync {
+    /* Returns true if this extractor handles the file at `relative_path`. */
+    fn supports(&self, relative_path: &str) -> bool;
+
+    fn extract(&self, content: &str) -> String;
+}
+
+/*
+ * The set of extractors known to the archiver. The first extractor that supports a file
+ * is used.
+ */
+#[derive(Clone)]
+pub struct OutlineExtractors {
+    extractors: Vec<Arc<dyn OutlineExtractor>>,
+}
+
+impl OutlineExtractors {
+    /* Creates the set with the built-in extractors. */
+    pub fn new() -> Self {
+        OutlineExtractors {
+            extractors: vec![Arc::new(RustOutlineExtractor)],
+        }
+    }
+
+    /* Adds an extractor, taking precedence over those registered before it. */
+    #[allow(dead_code)]
+    pub fn register(&mut self, extractor: Arc<dyn OutlineExtractor>) {
+        self.extractors.insert(0, extractor);
+    }
+
+    /* Returns the outline of the file, or `None` if no extractor supports it. */
+    pub fn extract(&self, relative_path: &str, content: &str) -> Option<String> {
+        self.extractors
+            .iter()
+            .find(|extractor| extractor.supports(relative_path))
+            .map(|extractor| extractor.extract(content))
+    }
+}
+
+impl Default for OutlineExtractors {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/*
+ * Outlines Rust source. Public items are kept with their attributes and doc comments:
+ * functions as signatures, structs/enums/unions in full, traits and impls with their
+ * member functions elided, and `mod` declarations (inline modules are outlined
+ * recursively). Members of traits and trait impls are kept regardless of visibility.
+ * Private items, `use` declarations that are not re-exports and `#[cfg(test)]` items
+ * are dropped. This is a lightweight scanner, not a parser; it only needs to find item
+ * boundaries, skipping strings, character literals and comments.
+ */
+pub struct RustOutlineExtractor;
+
+impl OutlineExtractor for RustOutlineExtractor {
+    fn supports(&self, relative_path: &str) -> bool {
+        relative_path.ends_with(".rs")
+    }
+
+    fn extract(&self, content: &str) -> String {
+        let mut out = String::new();
+        let mut scanner = RustScanner {
+            src: content,
+            pos: 0,
+        };
+        outline_items(
+            &mut scanner,
+            content.len(),
+            0,
+            ItemFilter::PublicOnly,
+            &mut out,
+        );
+        out
+    }
+}
+
+/* Which items of a scope are kept. */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ItemFilter {
+    PublicOnly,
+    All,
+}
+
+/* How the header of an item ended. */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum HeaderEnd {
+    Semicolon,
+    Block,
+    Eof,
+}
+
+struct RustScanner<'a> {
+    src: &'a str,
+    pos: usize,
+}
+
+impl<'a> RustScanner<'a> {
+    fn rest(&self) -> &'a str {
+        &self.src[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn bump(&mut self) {
+        if let Some(c) = self.peek() {
+            self.pos += c.len_utf8();
+        }
+    }
+
+    /*
+     * Skips a comment, string or character literal at the current position. Returns
+     * false (without moving) if there is none.
+     */
+    fn skip_literal_or_comment(&mut self) -> bool {
+        let rest = self.rest();
+        if rest.starts_with("//") {
+            self.pos += rest.find('\n').unwrap_or(rest.len());
+            return true;
+        }
+        if rest.starts_with("/*") {
+            let mut depth = 0usize;
+            let mut i = 0;
+            while i < rest.len() {
+                if rest[i..].starts_with("/*") {
+                    depth += 1;
+                    i += 2;
+                } else if rest[i..].starts_with("*/") {
+                    depth -= 1;
+                    i += 2;
+                    if depth == 0 {
+                        break;
+                    }
+                } else {
+                    i += rest[i..].chars().next().map_or(1, char::len_utf8);
+                }
+            }
+            self.pos += i.min(rest.len());
+            return true;
+        }
+        let raw_prefix = rest
+            .strip_prefix("br")
+            .or_else(|| rest.strip_prefix('r'))
+            .filter(|after| after.starts_with('"') || after.starts_with('#'));
+        let preceded_by_ident = self.src[..self.pos]
+            .chars()
+            .next_back()
+            .is_some_and(|c| c.is_alphanumeric() || c == '_');
+        if let Some(after) = raw_prefix
+            && !preceded_by_ident
+        {
+            let hashes = after.chars().take_while(|c| *c == '#').count();
+            if after[hashes..].starts_with('"') {
+                let closing = format!("\"{}", "#".repeat(hashes));
+                let body_start = rest.len() - after.len() + hashes + 1;
+                self.pos += rest[body_start..]
+                    .find(&closing)
+                    .map_or(rest.len(), |end| body_start + end + closing.len());
+                return true;
+            }
+        }
+        if rest.starts_with('"') || rest.starts_with("b\"") {
+            let mut chars = rest
+                .char_indices()
+                .skip(if rest.starts_with('b') { 2 } else { 1 });
+            while let Some((i, c)) = chars.next() {
+                match c {
+                    '\\' => {
+                        chars.next();
+                    }
+                    '"' => {
+                        self.pos += i + 1;
+                        return true;
+                    }
+                    _ => {}
+                }
+            }
+            self.pos += rest.len();
+            return true;
+        }
+        if let Some(after) = rest.strip_prefix('\'') {
+            /* A character literal, as opposed to a lifetime or label. */
+            let literal_len = if let Some(escaped) = after.strip_prefix('\\') {
+                escaped.find('\'').map(|end| end + 3)
+            } else {
+                let mut chars = after.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), Some('\'')) => Some(c.len_utf8() + 2),
+                    _ => None,
+                }
+            };
+            if let Some(len) = literal_len {
+                self.pos += len;
+                return true;
+            }
+        }
+        false
+    }
+
+    /* Skips whitespace and comments, returning the doc comments passed on the way. */
+    fn skip_trivia(&mut self, docs: &mut Vec<&'a str>) {
+        loop {
+            let rest = self.rest();
+            let trimmed = rest.trim_start();
+            self.pos += rest.len() - trimmed.len();
+            if trimmed.starts_with("///") || trimmed.starts_with("//!") {
+                let end = trimmed.find('\n').unwrap_or(trimmed.len());
+                docs.push(trimmed[..end].trim_end());
+            }
+            if !(trimmed.starts_with("//") || trimmed.starts_with("/*"))
+                || !self.skip_literal_or_comment()
+            {
+                return;
+            }
+        }
+    }
+
+    /*
+     * Advances past the bracketed group starting at the current position, which must be
+     * one of `(`, `[` or `{`.
+     */
+    fn skip_group(&mut self) {
+        let mut depth = 0usize;
+        while let Some(c) = self.peek() {
+            if self.skip_literal_or_comment() {
+                continue;
+            }
+            self.bump();
+            match c {
+                '(' | '[' | '{' => depth += 1,
+                ')' | ']' | '}' => {
+                    depth = depth.saturating_sub(1);
+                    if depth == 0 {
+                        return;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /*
+     * Advances to the end of an item header: the first `;` or `{` outside of brackets.
+     * The scanner is left on the terminator.
+     */
+    fn scan_header(&mut self, limit: usize) -> HeaderEnd {
+        while self.pos < limit {
+            if self.skip_literal_or_comment() {
+                continue;
+            }
+            match self.peek() {
+                Some(';') => return HeaderEnd::Semicolon,
+                Some('{') => return HeaderEnd::Block,
+                Some('(') | Some('[') => self.skip_group(),
+                Some(_) => self.bump(),
+                None => break,
+            }
+        }
+        HeaderEnd::Eof
+    }
+}
+
+/* Collapses the whitespace of a header spanning several lines into single spaces. */
+fn normalize(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/*
+ * Splits a normalized header into its leading qualifiers (visibility, `unsafe`, `async`,
+ * ...) and the item keyword.
+ */
+fn item_keyword(header: &str) -> (bool, &str) {
+    let mut is_public = false;
+    let mut rest = header;
+    loop {
+        if let Some(after) = rest.strip_prefix("pub") {
+            if after.starts_with('(') {
+                is_public = true;
+                rest = after[after.find(')').map_or(after.len(), |i| i + 1)..].trim_start();
+                continue;
+            }
+            if after.starts_with(' ') {
+                is_public = true;
+                rest = after.trim_start();
+                continue;
+            }
+        }
+        let word_end = rest
+            .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '!'))
+            .unwrap_or(rest.len());
+        let word = &rest[..word_end];
+        match word {
+            "unsafe" | "async" | "default" | "extern" if word_end < rest.len() => {
+                rest = rest[word_end..].trim_start();
+                if word == "extern" && rest.starts_with('"') {
+                    let abi_end = rest[1..].find('"').map_or(rest.len(), |i| i + 2);
+                    rest = rest[abi_end..].trim_start();
+                }
+            }
+            "const" if rest[word_end..].trim_start().starts_with("fn") => {
+                rest = rest[word_end..].trim_start();
+            }
+            _ => return (is_public, word),
+        }
+    }
+}
+
+fn push_line(out: &mut String, indent: usize, text: &str) {
+    out.push_str(&"    ".repeat(indent));
+    out.push_str(text);
+    out.push('\n');
+}
+
+/*
+ * Appends a verbatim block (the body of a struct or enum), re-indented so its closing
+ * brace lines up with the header.
+ */
+fn push_verbatim_block(out: &mut String, indent: usize, header: &str, block: &str) {
+    let lines: Vec<&str> = block.lines().collect();
+    if lines.len() <= 1 {
+        push_line(out, indent, &format!("{header} {}", normalize(block)));
+        return;
+    }
+    let body_indent = lines[1..lines.len() - 1]
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    push_line(out, indent, &format!("{header} {{"));
+    for line in &lines[1..lines.len() - 1] {
+        if line.trim().is_empty() {
+            out.push('\n');
+        } else {
+            let stripped = &line[body_indent.min(line.len() - line.trim_start().len())..];
+            push_line(out, indent + 1, stripped.trim_end());
+        }
+    }
+    push_line(out, indent, "}");
+}
+
+fn outline_items(
+    scanner: &mut RustScanner,
+    limit: usize,
+    indent: usize,
+    filter: ItemFilter,
+    out: &mut String,
+) {
+    loop {
+        let mut docs = Vec::new();
+        let mut attributes = Vec::new();
+        loop {
+            scanner.skip_trivia(&mut docs);
+            let rest = scanner.rest();
+            if scanner.pos < limit && (rest.starts_with("#[") || rest.starts_with("#![")) {
+                let start = scanner.pos;
+                scanner.pos += rest.find('[').unwrap_or(0);
+                scanner.skip_group();
+                attributes.push(normalize(&scanner.src[start..scanner.pos]));
+            } else {
+                break;
+            }
+        }
+        /* Inner docs and attributes describe the enclosing module and are always kept. */
+        for inner in docs.iter().filter(|doc| doc.starts_with("//!")) {
+            push_line(out, indent, inner);
+        }
+        docs.retain(|doc| !doc.starts_with("//!"));
+        for inner in attributes.iter().filter(|a| a.starts_with("#!")) {
+            push_line(out, indent, inner);
+        }
+        attributes.retain(|a| !a.starts_with("#!"));
+        if scanner.pos >= limit {
+            return;
+        }
+        if scanner.rest().starts_with('}') || scanner.rest().starts_with(';') {
+            scanner.bump();
+            continue;
+        }
+
+        let start = scanner.pos;
+        let end = scanner.scan_header(limit);
+        let header = normalize(&scanner.src[start..scanner.pos]);
+        let block = if end == HeaderEnd::Block {
+            let block_start = scanner.pos;
+            scanner.skip_group();
+            Some((block_start, scanner.pos))
+        } else {
+            scanner.bump();
+            None
+        };
+
+        let (is_public, keyword) = item_keyword(&header);
+        /* `const` and `static` may contain blocks in their initializer; skip to the `;`. */
+        if block.is_some() && matches!(keyword, "const" | "static") {
+            scanner.scan_header(limit);
+            scanner.bump();
+        }
+        let is_test_only = attributes.iter().any(|a| a.starts_with("#[cfg(test)]"));
+        let keep = !is_test_only
+            && match keyword {
+                "mod" | "impl" => true,
+                "macro_rules!" => attributes.iter().any(|a| a.contains("macro_export")),
+                "fn" | "struct" | "enum" | "union" | "trait" | "type" | "const" | "static" => {
+                    is_public || filter == ItemFilter::All
+                }
+                "use" => is_public,
+                _ => false,
+            };
+        if !keep {
+            continue;
+        }
+
+        let mut item = String::new();
+        for doc in &docs {
+            push_line(&mut item, indent, doc);
+        }
+        for attribute in &attributes {
+            push_line(&mut item, indent, attribute);
+        }
+        match (keyword, block) {
+            ("fn", Some(_)) | ("macro_rules!", Some(_)) => {
+                push_line(&mut item, indent, &format!("{header} {{ ... }}"));
+            }
+            ("const" | "static", _) => {
+                let declaration = header.find(" =").map_or(header.as_str(), |i| &header[..i]);
+                push_line(&mut item, indent, &format!("{declaration} = ...;"));
+            }
+            ("struct" | "enum" | "union", Some((block_start, block_end))) => {
+                push_verbatim_block(
+                    &mut item,
+                    indent,
+                    &header,
+                    &scanner.src[block_start..block_end],
+                );
+            }
+            ("trait" | "impl" | "mod", Some((block_start, block_end))) => {
+                let inner_filter = match keyword {
+                    "trait" => ItemFilter::All,
+                    "impl" if header.contains(" for ") => ItemFilter::All,
+                    _ => ItemFilter::PublicOnly,
+                };
+                let mut inner = String::new();
+                let mut inner_scanner = RustScanner {
+                    src: scanner.src,
+                    pos: block_start + 1,
+                };
+                outline_items(
+                    &mut inner_scanner,
+                    block_end - 1,
+                    indent + 1,
+                    inner_filter,
+                    &mut inner,
+                );
+                if inner.is_empty() {
+                    /* An inherent impl without public members adds nothing. */
+                    if keyword == "impl" && inner_filter == ItemFilter::PublicOnly {
+                        continue;
+                    }
+                    push_line(&mut item, indent, &format!("{header} {{ ... }}"));
+                } else {
+                    push_line(&mut item, indent, &format!("{header} {{"));
+                    item.push_str(&inner);
+                    push_line(&mut item, indent, "}");
+                }
+            }
+            (_, Some(_)) => push_line(&mut item, indent, &format!("{header} {{ ... }}")),
+            (_, None) => push_line(&mut item, indent, &format!("{header};")),
+        }
+        out.push_str(&item);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = r##"//! Crate docs.
+use std::fmt;
+pub use crate::other::Thing;
+
+pub mod public_module;
+mod private_module;
+
+/// A point.
+#[derive(Debug, Clone)]
+pub struct Point {
+    pub x: i32,
+    pub y: i32,
+}
+
+struct Hidden;
+
+pub const LIMIT: usize = { 3 + 4 };
+
+impl Point {
+    /// Creates a point.
+    pub fn new(x: i32, y: i32) -> Self {
+        let brace = '{';
+        let text = "} fn fake() {";
+        Point { x, y }
+    }
+
+    fn helper(&self) -> &'static str {
+        r#"}"#
+    }
+}
+
+impl fmt::Display for Point {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "({}, {})", self.x, self.y)
+    }
+}
+
+pub trait Shape {
+    fn area(&self) -> f64;
+    fn name(&self) -> String {
+        "shape".to_string()
+    }
+}
+
+pub fn distance<T>(a: &Point, b: &Point) -> f64
+where
+    T: Copy,
+{
+    0.0
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn it_works() {}
+}
+"##;
+
+    #[test]
+    fn test_rust_outline_keeps_api_and_elides_bodies() {
+        let outline = RustOutlineExtractor.extract(SOURCE);
+
+        assert_eq!(
+            outline,
+            "//! Crate docs.
+pub use crate::other::Thing;
+pub mod public_module;
+mod private_module;
+/// A point.
+#[derive(Debug, Clone)]
+pub struct Point {
+    pub x: i32,
+    pub y: i32,
+}
+pub const LIMIT: usize = ...;
+impl Point {
+    /// Creates a point.
+    pub fn new(x: i32, y: i32) -> Self { ... }
+}
+impl fmt::Display for Point {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { ... }
+}
+pub trait Shape {
+    fn area(&self) -> f64;
+    fn name(&self) -> String { ... }
+}
+pub fn distance<T>(a: &Point, b: &Point) -> f64 where T: Copy, { ... }
+"
+        );
+    }
+
+    #[test]
+    fn test_extractors_fall_back_for_unknown_languages_and_accept_plugins() {
+        struct FirstLineExtractor;
+        impl OutlineExtractor for FirstLineExtractor {
+            fn supports(&self, relative_path: &str) -> bool {
+                relative_path.ends_with(".py")
+            }
+            fn extract(&self, content: &str) -> String {
+                content.lines().next().unwrap_or_default().to_string()
+            }
+        }
+
+        let mut extractors = OutlineExtractors::new();
+        assert_eq!(
+            extractors.extract("script.py", "def f():\n    pass\n"),
+            None
+        );
+
+        extractors.register(Arc::new(FirstLineExtractor));
+        assert_eq!(
+            extractors.extract("script.py", "def f():\n    pass\n"),
+            Some("def f():".to_string())
+        );
+        assert_eq!(
+            extractors.extract("src/lib.rs", "fn private() {}\n"),
+            Some(String::new())
+        );
+    }
+}
@@ -0,0 +1,833 @@
+use super::archive_apply::{read_if_exists, resolve_target};
+use super::atomic_write;
+use super::checksum_utils;
+use super::file_node::FileNode;
+use super::project_context::ProjectContext;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/*
+ * This module applies unified diffs found in a model's answer to the project.
+ * [ArchiveApplyPatchV1] `extract_patches` picks every `--- a/… +++ b/…` section with its
+ * `@@` hunks out of arbitrary text (chat prose, markdown fences, several files in a row).
+ * `PatchPlan::build` matches each patch against the files of the scanned snapshot and
+ * applies the hunks in memory, which is the dry run: nothing touches the disk until
+ * `PatchPlan::write`. Hunks are located with fuzzy matching, because models rarely get
+ * line numbers right and sometimes alter whitespace or a context line: the search starts
+ * at the stated line and widens outward, then retries ignoring whitespace, then with up
+ * to `MAX_CONTEXT_FUZZ` context lines dropped from either end of the hunk.
+ * Paths are confined to the project root the same way an edited archive is.
+ */
+
+/* How many leading or trailing context lines may be ignored when locating a hunk. */
+pub const MAX_CONTEXT_FUZZ: usize = 2;
+
+const DEV_NULL: &str = "/dev/null";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HunkLine {
+    Context(String),
+    Remove(String),
+    Add(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hunk {
+    /* The 1-based start line in the original file, when the `@@` header states one. */
+    pub old_start: Option<usize>,
+    pub lines: Vec<HunkLine>,
+}
+
+impl Hunk {
+    fn old_lines(&self) -> impl Iterator<Item = &str> {
+        self.lines.iter().filter_map(|line| match line {
+            HunkLine::Context(text) | HunkLine::Remove(text) => Some(text.as_str()),
+            HunkLine::Add(_) => None,
+        })
+    }
+
+    fn leading_context(&self) -> usize {
+        self.lines
+            .iter()
+            .take_while(|line| matches!(line, HunkLine::Context(_)))
+            .count()
+    }
+
+    fn trailing_context(&self) -> usize {
+        self.lines
+            .iter()
+            .rev()
+            .take_while(|line| matches!(line, HunkLine::Context(_)))
+            .count()
+    }
+}
+
+/*
+ * One file's section of a diff. A side is `None` for `/dev/null`, i.e. a file that is
+ * created (`old_path` is `None`) or deleted (`new_path` is `None`).
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilePatch {
+    pub old_path: Option<String>,
+    pub new_path: Option<String>,
+    pub hunks: Vec<Hunk>,
+}
+
+impl FilePatch {
+    pub fn path(&self) -> &str {
+        self.new_path
+            .as_deref()
+            .or(self.old_path.as_deref())
+            .unwrap_or(DEV_NULL)
+    }
+}
+
+/* Reads the path of a `---`/`+++` line, dropping a trailing timestamp and git's quoting. */
+fn parse_patch_path(text: &str) -> Option<String> {
+    let path = text.split('\t').next().unwrap_or("").trim();
+    if path == DEV_NULL {
+        return None;
+    }
+    if path.len() >= 2
+        && path.starts_with('"')
+        && path.ends_with('"')
+        && let Ok(unquoted) = serde_json::from_str::<String>(path)
+    {
+        return Some(unquoted);
+    }
+    Some(path.to_string())
+}
+
+/* The parts of a `@@ -a,b +c,d @@` line; both are `None` when the ranges are missing. */
+struct HunkHeader {
+    old_start: Option<usize>,
+    /* The old and new line counts. */
+    counts: Option<(usize, usize)>,
+}
+
+fn parse_hunk_header(line: &str) -> Option<HunkHeader> {
+    let rest = line.strip_prefix("@@")?;
+    let unknown = HunkHeader {
+        old_start: None,
+        counts: None,
+    };
+    let Some(end) = rest.find("@@") else {
+        return Some(unknown);
+    };
+    let ranges: Vec<&str> = rest[..end].split_whitespace().collect();
+    let parse_range = |text: &str, sign: char| -> Option<(usize, usize)> {
+        let text = text.strip_prefix(sign)?;
+        match text.split_once(',') {
+            Some((start, count)) => Some((start.parse().ok()?, count.parse().ok()?)),
+            None => Some((text.parse().ok()?, 1)),
+        }
+    };
+    match ranges.as_slice() {
+        [old, new] => match (parse_range(old, '-'), parse_range(new, '+')) {
+            (Some((old_start, old_count)), Some((_, new_count))) => Some(HunkHeader {
+                old_start: Some(old_start),
+                counts: Some((old_count, new_count)),
+            }),
+            _ => Some(unknown),
+        },
+        _ => Some(unknown),
+    }
+}
+
+fn is_file_header(lines: &[&str], index: usize) -> bool {
+    lines[index].starts_with("--- ")
+        && lines
+            .get(index + 1)
+            .is_some_and(|next| next.starts_with("+++ "))
+}
+
+/*
+ * Extracts every file patch from `text`. Everything outside a `---`/`+++` pair and its
+ * hunks is ignored. When a hunk header states line counts they decide where the hunk
+ * ends, so empty lines inside it are read as empty context lines (chat clients often
+ * strip the leading space); without counts the hunk ends at the first line that is not
+ * a diff line. `\ No newline at end of file` markers are skipped: a patched file keeps
+ * the final-newline state of the original.
+ */
+pub fn extract_patches(text: &str) -> Vec<FilePatch> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut patches = Vec::new();
+    let mut index = 0;
+    while index < lines.len() {
+        if !is_file_header(&lines, index) {
+            index += 1;
+            continue;
+        }
+        let old_path = parse_patch_path(&lines[index][4..]);
+        let new_path = parse_patch_path(&lines[index + 1][4..]);
+        index += 2;
+
+        let mut hunks = Vec::new();
+        while let Some(HunkHeader { old_start, counts }) =
+            lines.get(index).and_then(|line| parse_hunk_header(line))
+        {
+            index += 1;
+            let (mut old_left, mut new_left) = counts.unwrap_or((0, 0));
+            let mut hunk_lines = Vec::new();
+            while index < lines.len() && !is_file_header(&lines, index) {
+                if counts.is_some() && old_left == 0 && new_left == 0 {
+                    break;
+                }
+                let line = lines[index];
+                let parsed = match line.chars().next() {
+                    Some(' ') => HunkLine::Context(line[1..].to_string()),
+                    Some('-') => HunkLine::Remove(line[1..].to_string()),
+                    Some('+') => HunkLine::Add(line[1..].to_string()),
+                    Some('\\') => {
+                        index += 1;
+                        continue;
+                    }
+                    None if counts.is_some() => HunkLine::Context(String::new()),
+                    _ => break,
+                };
+                match parsed {
+                    HunkLine::Context(_) => {
+                        old_left = old_left.saturating_sub(1);
+                        new_left = new_left.saturating_sub(1);
+                    }
+                    HunkLine::Remove(_) => old_left = old_left.saturating_sub(1),
+                    HunkLine::Add(_) => new_left = new_left.saturating_sub(1),
+                }
+                hunk_lines.push(parsed);
+                index += 1;
+            }
+            if lines.get(index).is_some_and(|line| line.starts_with('\\')) {
+                index += 1;
+            }
+            // A pure insertion is placed after `old_start`; other hunks start at it.
+            let old_start = match counts {
+                Some((0, _)) => old_start.map(|start| start + 1),
+                _ => old_start,
+            };
+            if !hunk_lines.is_empty() {
+                hunks.push(Hunk {
+                    old_start,
+                    lines: hunk_lines,
+                });
+            }
+        }
+        if !hunks.is_empty() {
+            patches.push(FilePatch {
+                old_path,
+                new_path,
+                hunks,
+            });
+        }
+    }
+    patches
+}
+
+/* How closely a hunk's original lines matched the file. */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HunkMatch {
+    Exact,
+    IgnoringWhitespace,
+    /* Matched (ignoring whitespace) after dropping this many context lines. */
+    ReducedContext(usize),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HunkOutcome {
+    /* `line` is the 1-based line in the original file; `offset` is the distance from the
+    line stated in the hunk header. */
+    Applied {
+        line: usize,
+        offset: isize,
+        matched: HunkMatch,
+    },
+    Failed(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatchKind {
+    Modify,
+    Create,
+    Delete,
+}
+
+impl PatchKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            PatchKind::Modify => "modify",
+            PatchKind::Create => "create",
+            PatchKind::Delete => "delete",
+        }
+    }
+}
+
+/* The dry-run result for one file. */
+#[derive(Debug, Clone)]
+pub struct PatchedFile {
+    pub relative_path: String,
+    /* `None` when the path could not be resolved inside the project; see `error`. */
+    pub absolute_path: Option<PathBuf>,
+    pub kind: PatchKind,
+    pub hunks: Vec<HunkOutcome>,
+    /* A problem with the file itself, which means no hunk was attempted. */
+    pub error: Option<String>,
+    /* The patched content; `None` for a deletion or when any hunk failed. */
+    pub result: Option<String>,
+    disk_checksum: Option<String>,
+}
+
+impl PatchedFile {
+    /* Whether every hunk applied, so the file can be written. */
+    pub fn is_applicable(&self) -> bool {
+        self.error.is_none()
+            && self
+                .hunks
+                .iter()
+                .all(|hunk| matches!(hunk, HunkOutcome::Applied { .. }))
+            && (self.result.is_some() || self.kind == PatchKind::Delete)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PatchPlan {
+    pub files: Vec<PatchedFile>,
+}
+
+/* Outcome of `PatchPlan::write`, as relative paths. */
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PatchWriteReport {
+    pub written: Vec<String>,
+    pub created: Vec<String>,
+    pub deleted: Vec<String>,
+    /* Files with a failed hunk or an invalid path. */
+    pub skipped: Vec<String>,
+    /* Files that changed on disk after the dry run. */
+    pub refused: Vec<String>,
+    pub failed: Vec<(String, String)>,
+}
+
+fn collect_snapshot_files(nodes: &[FileNode], files: &mut HashSet<PathBuf>) {
+    for node in nodes {
+        if node.is_dir() {
+            collect_snapshot_files(&node.children, files);
+        } else {
+            files.insert(node.path().to_path_buf());
+        }
+    }
+}
+
+/*
+ * Chooses the project file a diff path refers to. Git's `a/` and `b/` prefixes are
+ * dropped unless the path with the prefix is itself a file in the snapshot. Existing
+ * files must be part of the scanned snapshot; only a created file may be new.
+ */
+fn resolve_patch_path(
+    project: &ProjectContext,
+    snapshot_files: &HashSet<PathBuf>,
+    path: &str,
+    kind: PatchKind,
+) -> Result<(String, PathBuf), String> {
+    let stripped = path.strip_prefix("a/").or_else(|| path.strip_prefix("b/"));
+    let candidates: Vec<&str> = match stripped {
+        Some(stripped) => vec![stripped, path],
+        None => vec![path],
+    };
+    let mut first_error = None;
+    let mut resolved = Vec::new();
+    for candidate in &candidates {
+        match resolve_target(project, candidate) {
+            Ok(absolute) => resolved.push((candidate.to_string(), absolute)),
+            Err(e) => {
+                first_error.get_or_insert_with(|| e.to_string());
+            }
+        }
+    }
+    if let Some(found) = resolved
+        .iter()
+        .find(|(_, absolute)| snapshot_files.contains(absolute))
+    {
+        return Ok(found.clone());
+    }
+    match (kind, resolved.into_iter().next()) {
+        (PatchKind::Create, Some(found)) => Ok(found),
+        (_, Some(_)) => Err("the file is not part of the scanned project".to_string()),
+        (_, None) => Err(first_error.unwrap_or_default()),
+    }
+}
+
+fn lines_equal(file_line: &str, hunk_line: &str, ignore_whitespace: bool) -> bool {
+    if ignore_whitespace {
+        file_line
+            .split_whitespace()
+            .eq(hunk_line.split_whitespace())
+    } else {
+        file_line == hunk_line
+    }
+}
+
+/*
+ * Finds `needle` in `lines` at or after `min`, trying positions nearest to `hint` first.
+ */
+fn find_block(
+    lines: &[String],
+    needle: &[&str],
+    hint: usize,
+    min: usize,
+    ignore_whitespace: bool,
+) -> Option<usize> {
+    if needle.len() > lines.len() {
+        return None;
+    }
+    let max = lines.len() - needle.len();
+    if min > max {
+        return None;
+    }
+    let hint = hint.clamp(min, max);
+    let matches_at = |pos: usize| {
+        lines[pos..pos + needle.len()]
+            .iter()
+            .zip(needle)
+            .all(|(file_line, hunk_line)| lines_equal(file_line, hunk_line, ignore_whitespace))
+    };
+    (0..=max - min).find_map(|distance| {
+        [hint.checked_add(distance), hint.checked_sub(distance)]
+            .into_iter()
+            .flatten()
+            .filter(|&pos| pos >= min && pos <= max)
+            .find(|&pos| matches_at(pos))
+    })
+}
+
+/*
+ * Applies the hunks to `original`'s lines in order. Each hunk is searched for after the
+ * previous one; context lines keep the file's own text, so whitespace-insensitive matches
+ * never rewrite them.
+ */
+fn apply_hunks(original: &[String], hunks: &[Hunk]) -> (Vec<String>, Vec<HunkOutcome>) {
+    let mut lines = original.to_vec();
+    let mut outcomes = Vec::with_capacity(hunks.len());
+    let mut min = 0usize;
+    let mut shift = 0isize;
+    let mut growth = 0isize;
+    for hunk in hunks {
+        let stated = hunk.old_start.map(|start| start.saturating_sub(1));
+        let hint = stated.map_or(min, |start| {
+            (start as isize + shift).clamp(0, lines.len() as isize) as usize
+        });
+        let old: Vec<&str> = hunk.old_lines().collect();
+
+        let mut found = None;
+        if old.is_empty() {
+            found = Some((hint.max(min).min(lines.len()), 0, 0, HunkMatch::Exact));
+        }
+        for (ignore_whitespace, matched) in [
+            (false, HunkMatch::Exact),
+            (true, HunkMatch::IgnoringWhitespace),
+        ] {
+            if found.is_some() {
+                break;
+            }
+            found = find_block(&lines, &old, hint, min, ignore_whitespace)
+                .map(|pos| (pos, 0, 0, matched));
+        }
+        let (leading, trailing) = (hunk.leading_context(), hunk.trailing_context());
+        for fuzz in 1..=MAX_CONTEXT_FUZZ {
+            if found.is_some() {
+                break;
+            }
+            let (drop_front, drop_back) = (fuzz.min(leading), fuzz.min(trailing));
+            if drop_front + drop_back == 0 || drop_front + drop_back >= old.len() {
+                continue;
+            }
+            let needle = &old[drop_front..old.len() - drop_back];
+            found = find_block(&lines, needle, hint + drop_front, min, true).map(|pos| {
+                (
+                    pos,
+                    drop_front,
+                    drop_back,
+                    HunkMatch::ReducedContext(drop_front.max(drop_back)),
+                )
+            });
+        }
+
+        let Some((pos, drop_front, drop_back, matched)) = found else {
+            outcomes.push(HunkOutcome::Failed(
+                "the original lines were not found in the file".to_string(),
+            ));
+            continue;
+        };
+        let kept = &hunk.lines[drop_front..hunk.lines.len() - drop_back];
+        let mut cursor = pos;
+        let mut replacement = Vec::with_capacity(kept.len());
+        for line in kept {
+            match line {
+                HunkLine::Context(_) => {
+                    replacement.push(lines[cursor].clone());
+                    cursor += 1;
+                }
+                HunkLine::Remove(_) => cursor += 1,
+                HunkLine::Add(text) => replacement.push(text.clone()),
+            }
+        }
+        let inserted = replacement.len();
+        let removed = cursor - pos;
+        lines.splice(pos..cursor, replacement);
+
+        let expected = stated.map_or(pos as isize, |start| start as isize + shift);
+        let offset = pos as isize - drop_front as isize - expected;
+        let original_line = (pos as isize - drop_front as isize - growth).max(0) as usize;
+        outcomes.push(HunkOutcome::Applied {
+            line: original_line + 1,
+            offset,
+            matched,
+        });
+        growth += inserted as isize - removed as isize;
+        shift += inserted as isize - removed as isize + offset;
+        min = pos + inserted;
+    }
+    (lines, outcomes)
+}
+
+/* Splits text into lines, remembering the line ending and whether the text ends with one. */
+fn split_text(text: &str) -> (Vec<String>, &'static str, bool) {
+    let eol = if text.contains("\r\n") { "\r\n" } else { "\n" };
+    let lines = text.lines().map(str::to_string).collect();
+    (lines, eol, text.is_empty() || text.ends_with('\n'))
+}
+
+fn join_lines(lines: &[String], eol: &str, final_newline: bool) -> String {
+    let mut text = lines.join(eol);
+    if final_newline && !lines.is_empty() {
+        text.push_str(eol);
+    }
+    text
+}
+
+impl PatchPlan {
+    /*
+     * Extracts the patches in `text` and applies them in memory to the files of the
+     * snapshot. Problems are recorded per file and per hunk rather than aborting, so the
+     * dry run shows everything at once. An empty plan means no diff was found.
+     */
+    pub fn build(project: &ProjectContext, snapshot_nodes: &[FileNode], text: &str) -> Self {
+        let mut snapshot_files = HashSet::new();
+        collect_snapshot_files(snapshot_nodes, &mut snapshot_files);
+        let files = extract_patches(text)
+            .iter()
+            .map(|patch| Self::plan_file(project, &snapshot_files, patch))
+            .collect();
+        PatchPlan { files }
+    }
+
+    fn plan_file(
+        project: &ProjectContext,
+        snapshot_files: &HashSet<PathBuf>,
+        patch: &FilePatch,
+    ) -> PatchedFile {
+        let kind = match (&patch.old_path, &patch.new_path) {
+            (None, _) => PatchKind::Create,
+            (_, None) => PatchKind::Delete,
+            _ => PatchKind::Modify,
+        };
+        let mut planned = PatchedFile {
+            relative_path: patch.path().to_string(),
+            absolute_path: None,
+            kind,
+            hunks: Vec::new(),
+            error: None,
+            result: None,
+            disk_checksum: None,
+        };
+        let (relative_path, absolute_path) =
+            match resolve_patch_path(project, snapshot_files, patch.path(), kind) {
+                Ok(resolved) => resolved,
+                Err(e) => {
+                    planned.error = Some(e);
+                    return planned;
+                }
+            };
+        planned.relative_path = relative_path;
+        planned.absolute_path = Some(absolute_path.clone());
+
+        let old_bytes = match read_if_exists(&absolute_path) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                planned.error = Some(format!("cannot read the file: {e}"));
+                return planned;
+            }
+        };
+        planned.disk_checksum = old_bytes
+            .as_deref()
+            .map(checksum_utils::calculate_sha256_for_bytes);
+        let old_text = match old_bytes {
+            Some(bytes) => match String::from_utf8(bytes) {
+                Ok(text) => text,
+                Err(_) => {
+                    planned.error = Some("the file is not valid UTF-8 text".to_string());
+                    return planned;
+                }
+            },
+            None if kind == PatchKind::Create => String::new(),
+            None => {
+                planned.error = Some("the file does not exist".to_string());
+                return planned;
+            }
+        };
+        if kind == PatchKind::Create && !old_text.is_empty() {
+            planned.error = Some("the patch creates a file that already exists".to_string());
+            return planned;
+        }
+
+        let (original, eol, final_newline) = split_text(&old_text);
+        let (patched, outcomes) = apply_hunks(&original, &patch.hunks);
+        planned.hunks = outcomes;
+        let all_applied = planned
+            .hunks
+            .iter()
+            .all(|hunk| matches!(hunk, HunkOutcome::Applied { .. }));
+        if kind == PatchKind::Delete {
+            if all_applied && !patched.iter().all(|line| line.trim().is_empty()) {
+                planned.error =
+                    Some("the patch deletes the file but does not remove all of it".to_string());
+            }
+        } else if all_applied {
+            planned.result = Some(join_lines(&patched, eol, final_newline));
+        }
+        planned
+    }
+
+    /* Number of files that `write` would change. */
+    pub fn applicable_count(&self) -> usize {
+        self.files.iter().filter(|f| f.is_applicable()).count()
+    }
+
+    /* The per-hunk report followed by the resulting content of every applicable file. */
+    pub fn render_dry_run(&self) -> String {
+        let mut text = String::new();
+        for file in &self.files {
+            text.push_str(&format!(
+                "{} ({}): {}\n",
+                file.relative_path,
+                file.kind.label(),
+                if file.is_applicable() {
+                    "ready"
+                } else {
+                    "will not be written"
+                }
+            ));
+            if let Some(error) = &file.error {
+                text.push_str(&format!("  error: {error}\n"));
+            }
+            for (index, hunk) in file.hunks.iter().enumerate() {
+                let description = match hunk {
+                    HunkOutcome::Applied {
+                        line,
+                        offset,
+                        matched,
+                    } => {
+                        let mut description = format!("applied at line {line}");
+                        if *offset != 0 {
+                            description.push_str(&format!(" (offset {offset:+})"));
+                        }
+                        match matched {
+                            HunkMatch::Exact => {}
+                            HunkMatch::IgnoringWhitespace => {
+                                description.push_str(", ignoring whitespace")
+                            }
+                            HunkMatch::ReducedContext(lines) => description
+                                .push_str(&format!(", with {lines} context line(s) ignored")),
+                        }
+                        description
+                    }
+                    HunkOutcome::Failed(reason) => format!("FAILED: {reason}"),
+                };
+                text.push_str(&format!("  hunk {}: {description}\n", index + 1));
+            }
+        }
+        for file in self.files.iter().filter(|f| f.is_applicable()) {
+            match &file.result {
+                Some(result) => {
+                    text.push_str(&format!(
+                        "\n===== Result: {} =====\n{result}",
+                        file.relative_path
+                    ));
+                    if !result.is_empty() && !result.ends_with('\n') {
+                        text.push('\n');
+                    }
+                }
+                None => text.push_str(&format!(
+                    "\n===== Result: {} (deleted) =====\n",
+                    file.relative_path
+                )),
+            }
+        }
+        text
+    }
+
+    /*
+     * Writes every file whose hunks all applied. A file that changed on disk since the
+     * dry run is refused; each file is replaced atomically.
+     */
+    pub fn write(&self) -> PatchWriteReport {
+        let mut report = PatchWriteReport::default();
+        for file in &self.files {
+            let Some(absolute_path) = file.absolute_path.as_deref() else {
+                report.skipped.push(file.relative_path.clone());
+                continue;
+            };
+            if !file.is_applicable() {
+                report.skipped.push(file.relative_path.clone());
+                continue;
+            }
+            match read_if_exists(absolute_path) {
+                Ok(bytes) => {
+                    let current = bytes
+                        .as_deref()
+                        .map(checksum_utils::calculate_sha256_for_bytes);
+                    if current != file.disk_checksum {
+                        report.refused.push(file.relative_path.clone());
+                        continue;
+                    }
+                }
+                Err(e) => {
+                    report
+                        .failed
+                        .push((file.relative_path.clone(), e.to_string()));
+                    continue;
+                }
+            }
+            let result = match &file.result {
+                Some(content) => write_patched(absolute_path, content),
+                None => fs::remove_file(absolute_path),
+            };
+            let target = match file.kind {
+                PatchKind::Modify => &mut report.written,
+                PatchKind::Create => &mut report.created,
+                PatchKind::Delete => &mut report.deleted,
+            };
+            match result {
+                Ok(()) => target.push(file.relative_path.clone()),
+                Err(e) => report
+                    .failed
+                    .push((file.relative_path.clone(), e.to_string())),
+            }
+        }
+        report
+    }
+}
+
+fn write_patched(path: &Path, content: &str) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    atomic_write::write_file_atomically(path, |out| out.write_all(content.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn test_extract_patches_from_chat_text() {
+        let text = "Sure! Here is the fix:\n\n```diff\ndiff --git a/src/lib.rs b/src/lib.rs\nindex 1..2 100644\n--- a/src/lib.rs\n+++ b/src/lib.rs\n@@ -1,3 +1,3 @@\n fn a() {}\n-fn b() {}\n+fn b() { todo!() }\n\n```\nAnd a new file:\n--- /dev/null\n+++ b/notes.md\n@@ -0,0 +1,2 @@\n+# Notes\n+\n\\ No newline at end of file\nThat's all.\n";
+        let patches = extract_patches(text);
+        assert_eq!(patches.len(), 2);
+        assert_eq!(patches[0].path(), "b/src/lib.rs");
+        assert_eq!(
+            patches[0].hunks[0].lines,
+            vec![
+                HunkLine::Context("fn a() {}".to_string()),
+                HunkLine::Remove("fn b() {}".to_string()),
+                HunkLine::Add("fn b() { todo!() }".to_string()),
+                HunkLine::Context(String::new()),
+            ]
+        );
+        assert_eq!(patches[1].old_path, None);
+        assert_eq!(patches[1].hunks[0].old_start, Some(1));
+        assert_eq!(patches[1].hunks[0].lines.len(), 2);
+    }
+
+    #[test]
+    fn test_apply_hunks_with_offset_and_fuzz() {
+        let original = lines("one\ntwo\nthree\nfour\nfive\nsix\nseven\n");
+        // Stated two lines too early, with an indented context line and a wrong last line.
+        let hunk = Hunk {
+            old_start: Some(2),
+            lines: vec![
+                HunkLine::Context("  three".to_string()),
+                HunkLine::Context("four".to_string()),
+                HunkLine::Remove("five".to_string()),
+                HunkLine::Add("FIVE".to_string()),
+                HunkLine::Context("six".to_string()),
+                HunkLine::Context("eight".to_string()),
+            ],
+        };
+        let (patched, outcomes) = apply_hunks(&original, &[hunk]);
+        assert_eq!(patched, lines("one\ntwo\nthree\nfour\nFIVE\nsix\nseven\n"));
+        assert_eq!(
+            outcomes,
+            vec![HunkOutcome::Applied {
+                line: 3,
+                offset: 1,
+                matched: HunkMatch::ReducedContext(1),
+            }]
+        );
+
+        let missing = Hunk {
+            old_start: Some(1),
+            lines: vec![
+                HunkLine::Remove("nowhere".to_string()),
+                HunkLine::Add("x".to_string()),
+            ],
+        };
+        let (unchanged, outcomes) = apply_hunks(&original, &[missing]);
+        assert_eq!(unchanged, original);
+        assert!(matches!(outcomes[0], HunkOutcome::Failed(_)));
+    }
+
+    #[test]
+    fn test_plan_and_write_are_confined_to_the_snapshot() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        let project = ProjectContext::new(root.clone());
+        fs::write(root.join("a.txt"), "alpha\r\nbeta\r\n").unwrap();
+        fs::write(root.join("ignored.txt"), "x\n").unwrap();
+        let snapshot = vec![FileNode::new_test(
+            root.join("a.txt"),
+            "a.txt".to_string(),
+            false,
+        )];
+        let text = "--- a/a.txt\n+++ b/a.txt\n@@ -2 +2 @@\n-beta\n+gamma\n--- a/ignored.txt\n+++ b/ignored.txt\n@@ -1 +1 @@\n-x\n+y\n--- a/../outside.txt\n+++ b/../outside.txt\n@@ -1 +1 @@\n-x\n+y\n--- /dev/null\n+++ b/new/file.txt\n@@ -0,0 +1 @@\n+hello\n";
+
+        let plan = PatchPlan::build(&project, &snapshot, text);
+        assert_eq!(plan.files.len(), 4);
+        assert_eq!(plan.files[0].result.as_deref(), Some("alpha\r\ngamma\r\n"));
+        assert!(plan.files[1].error.is_some(), "not in the snapshot");
+        assert!(plan.files[2].error.is_some(), "outside the root");
+        assert_eq!(plan.applicable_count(), 2);
+        assert!(plan.render_dry_run().contains("===== Result: a.txt ====="));
+        assert_eq!(
+            fs::read_to_string(root.join("a.txt")).unwrap(),
+            "alpha\r\nbeta\r\n",
+            "The dry run must not write"
+        );
+
+        let report = plan.write();
+        assert_eq!(report.written, vec!["a.txt".to_string()]);
+        assert_eq!(report.created, vec!["new/file.txt".to_string()]);
+        assert_eq!(report.skipped.len(), 2);
+        assert_eq!(
+            fs::read_to_string(root.join("new/file.txt")).unwrap(),
+            "hello\n"
+        );
+        assert_eq!(fs::read_to_string(root.join("ignored.txt")).unwrap(), "x\n");
+
+        // Writing the same plan again is refused because the file changed since.
+        assert_eq!(plan.write().refused.len(), 2);
+    }
+}
@@ -6,16 +6,24 @@
  * this session data, facilitating dependency injection and testing.
  */
 use crate::core::{
-    ContentSearchProgress, ContentSearchResult, FileNode, FileSystemScannerOperations,
-    NodeStateApplicatorOperations, Profile, ProfileName, SelectionState, TokenCounterOperations,
+    ArchiveOptions, ContentSearchProgress, ContentSearchResult, FileNode,
+    FileSystemScannerOperations, NodeStateApplicatorOperations, Profile, ProfileName,
+    SelectionState, TokenCounterOperations,
+    composite_profile::{self, ComponentLoaderOperations},
+    content_class::{ContentReader, FileContent},
+    content_transform::TransformPipeline,
     file_node::FileTokenDetails,
+    line_numbers::{self, LineNumbering},
+    outline::OutlineExtractors,
+    profile_settings::ProfileComponent,
+    text_encoding::EncodingRules,
     token_progress::{TokenProgress, TokenProgressEntry},
 };
 use rayon::prelude::*;
 use rayon::{ThreadPool, ThreadPoolBuilder};
 use std::{
     collections::{HashMap, HashSet},
-    fs,
+    fs, io,
     path::{Path, PathBuf},
     sync::{Arc, mpsc},
     thread,
@@ -42,6 +50,10 @@ pub trait ProfileRuntimeDataOperations: Send + Sync {
     fn get_exclude_patterns(&self) -> Vec<String>;
     fn set_exclude_patterns(&mut self, patterns: Vec<String>);
 
+    // Archive generation settings
+    fn get_archive_options(&self) -> ArchiveOptions;
+    fn set_archive_options(&mut self, options: ArchiveOptions);
+
     // File system snapshot (nodes)
     fn get_snapshot_nodes(&self) -> &Vec<FileNode>;
     fn set_snapshot_nodes(&mut self, nodes: Vec<FileNode>);
@@ -50,6 +62,7 @@ pub trait ProfileRuntimeDataOperations: Send + Sync {
         state_manager: &dyn NodeStateApplicatorOperations,
         selected_paths: &HashSet<PathBuf>,
         deselected_paths: &HashSet<PathBuf>,
+        outline_paths: &HashSet<PathBuf>,
     );
     fn get_node_attributes_for_path(&self, path: &Path) -> Option<(SelectionState, bool)>; // (state, is_dir)
     fn update_node_state_and_collect_changes(
@@ -79,6 +92,12 @@ pub trait ProfileRuntimeDataOperations: Send + Sync {
 
     fn apply_token_progress(&mut self, progress: TokenProgress) -> usize;
 
+    /*
+     * Returns the cached token counts (with the checksum they were computed for) of the
+     * files counted so far, e.g. for enforcing a token budget without re-counting.
+     */
+    fn get_cached_file_token_details(&self) -> HashMap<PathBuf, FileTokenDetails>;
+
     fn search_content_async(
         &self,
         search_term: String,
@@ -93,8 +112,11 @@ pub trait ProfileRuntimeDataOperations: Send + Sync {
         file_system_scanner: &dyn FileSystemScannerOperations,
         state_manager: &dyn NodeStateApplicatorOperations,
         token_counter: &dyn TokenCounterOperations,
+        component_loader: &dyn ComponentLoaderOperations,
     ) -> Result<(), String>; // String is error message
-    fn get_current_selection_paths(&self) -> (HashSet<PathBuf>, HashSet<PathBuf>);
+    // Returns the (selected, deselected, outline) paths of the current snapshot.
+    fn get_current_selection_paths(&self)
+    -> (HashSet<PathBuf>, HashSet<PathBuf>, HashSet<PathBuf>);
 }
 
 /*
@@ -111,6 +133,10 @@ pub struct ProfileRuntimeData {
     cached_token_count: usize,
     cached_file_token_details: HashMap<PathBuf, FileTokenDetails>,
     exclude_patterns: Vec<String>,
+    archive_options: ArchiveOptions,
+    // The components of a composite profile, and the included paths they contributed on load.
+    components: Vec<ProfileComponent>,
+    component_paths: HashSet<PathBuf>,
 }
 
 /*
@@ -136,13 +162,88 @@ struct TokenWorkItem {
     path: PathBuf,
     checksum: String,
     is_selected: bool,
+    is_outline: bool,
     cached: Option<FileTokenDetails>,
 }
 
+/*
+ * Counts the tokens of a file in the form the archive holds it, so the displayed estimate
+ * matches the generated archive. A file included as an outline counts at its outline size,
+ * the profile's content transforms run on every file they match, and files that get line
+ * numbers count with their gutter. The token cache holds counts of the file as it is on
+ * disk, so reshaped files are read each time.
+ */
+struct TokenEstimator {
+    reader: ContentReader,
+    outline_extractors: OutlineExtractors,
+    transforms: TransformPipeline,
+    line_numbering: LineNumbering,
+    root: PathBuf,
+}
+
+impl TokenEstimator {
+    fn relative_path(&self, path: &Path) -> String {
+        path.strip_prefix(&self.root)
+            .unwrap_or(path)
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    /* Whether the archived form of the file differs from its text on disk. */
+    fn reshapes(&self, path: &Path, is_outline: bool) -> bool {
+        let relative_path = self.relative_path(path);
+        is_outline
+            || self.transforms.applies_to(&relative_path)
+            || self.line_numbering.applies_to(&relative_path)
+    }
+
+    /* Reads the file and counts its archived form. Binary and oversize files count zero. */
+    fn count_archived(
+        &self,
+        path: &Path,
+        is_outline: bool,
+        token_counter: &dyn TokenCounterOperations,
+    ) -> io::Result<usize> {
+        let FileContent::Text(text) = self.reader.read(path)? else {
+            return Ok(0);
+        };
+        let relative_path = self.relative_path(path);
+        let content = if is_outline {
+            self.outline_extractors
+                .extract(&relative_path, &text)
+                .unwrap_or(text)
+        } else {
+            text
+        };
+        let mut content = self.transforms.apply(&relative_path, content);
+        if self.line_numbering.applies_to(&relative_path) {
+            content = line_numbers::number_lines(&content).0;
+        }
+        Ok(token_counter.count_tokens(&content))
+    }
+}
+
 fn process_work_item(
     item: &TokenWorkItem,
     token_counter: &Arc<dyn TokenCounterOperations>,
+    estimator: &TokenEstimator,
 ) -> TokenProgressEntry {
+    if estimator.reshapes(&item.path, item.is_outline) {
+        let token_count = estimator
+            .count_archived(&item.path, item.is_outline, token_counter.as_ref())
+            .unwrap_or_else(|err| {
+                log::warn!("Token worker failed to read file {:?}: {err:?}.", item.path);
+                0
+            });
+        return TokenProgressEntry {
+            path: item.path.clone(),
+            token_count,
+            is_selected: item.is_selected,
+            details: None,
+            invalidate_cache: false,
+        };
+    }
+
     let mut invalidate_cache = false;
     let mut details_to_store = None;
     let mut token_count_opt = item.cached.as_ref().and_then(|cached| {
@@ -154,9 +255,13 @@ fn process_work_item(
     });
 
     if token_count_opt.is_none() {
-        match fs::read_to_string(&item.path) {
+        match estimator.reader.read(&item.path) {
             Ok(content) => {
-                let counted = token_counter.count_tokens(&content);
+                // Binary and oversize files contribute no tokens of their own.
+                let counted = match content {
+                    FileContent::Text(text) => token_counter.count_tokens(&text),
+                    FileContent::NotText { .. } => 0,
+                };
                 token_count_opt = Some(counted);
                 details_to_store = Some(FileTokenDetails {
                     checksum: item.checksum.clone(),
@@ -199,6 +304,9 @@ impl ProfileRuntimeData {
             cached_token_count: 0,
             cached_file_token_details: HashMap::new(),
             exclude_patterns: Vec::new(),
+            archive_options: ArchiveOptions::default(),
+            components: Vec::new(),
+            component_paths: HashSet::new(),
         }
     }
 
@@ -278,17 +386,21 @@ impl ProfileRuntimeData {
         None
     }
 
-    // Helper: Gathers selected and deselected paths from a node tree.
+    // Helper: Gathers selected, deselected and outline paths from a node tree.
     fn gather_selected_deselected_paths_recursive_internal(
         nodes: &[FileNode],
         selected: &mut HashSet<PathBuf>,
         deselected: &mut HashSet<PathBuf>,
+        outline: &mut HashSet<PathBuf>,
     ) {
         for node in nodes {
             match node.state() {
                 SelectionState::Selected => {
                     selected.insert(node.path().to_path_buf());
                 }
+                SelectionState::Outline => {
+                    outline.insert(node.path().to_path_buf());
+                }
                 SelectionState::Deselected => {
                     deselected.insert(node.path().to_path_buf());
                 }
@@ -299,6 +411,7 @@ impl ProfileRuntimeData {
                     &node.children,
                     selected,
                     deselected,
+                    outline,
                 );
             }
         }
@@ -327,7 +440,7 @@ impl ProfileRuntimeData {
                 continue;
             }
 
-            let is_selected = node.state() == SelectionState::Selected;
+            let is_selected = node.is_selected();
             if only_selected && !is_selected {
                 continue;
             }
@@ -336,14 +449,58 @@ impl ProfileRuntimeData {
                 path: node.path().to_path_buf(),
                 checksum: node.checksum().to_string(),
                 is_selected,
+                is_outline: node.state() == SelectionState::Outline,
                 cached: cache.get(node.path()).cloned(),
             });
         }
     }
 
+    /*
+     * Builds the reader used by the token worker and the content search, so they decode
+     * files as the archiver does. Invalid encoding rules are ignored with a warning here;
+     * archive generation reports them as errors.
+     */
+    fn content_reader(&self) -> ContentReader {
+        let encodings =
+            EncodingRules::compile(&self.archive_options.encodings).unwrap_or_else(|e| {
+                log::warn!("ProfileRuntimeData: Ignoring encoding rules: {e}");
+                EncodingRules::default()
+            });
+        ContentReader::new(
+            &self.root_path_for_scan,
+            self.archive_options.content_limits.max_file_size,
+            encodings,
+        )
+    }
+
+    /*
+     * Builds the estimator for the token worker. Invalid transform rules and line number
+     * globs are ignored with a warning here, as in `content_reader`.
+     */
+    fn token_estimator(&self) -> TokenEstimator {
+        let transforms = TransformPipeline::compile(&self.archive_options.transforms)
+            .unwrap_or_else(|e| {
+                log::warn!("ProfileRuntimeData: Ignoring content transforms: {e}");
+                TransformPipeline::default()
+            });
+        let line_numbering = LineNumbering::compile(self.archive_options.line_numbers.as_ref())
+            .unwrap_or_else(|e| {
+                log::warn!("ProfileRuntimeData: Ignoring line numbers: {e}");
+                LineNumbering::default()
+            });
+        TokenEstimator {
+            reader: self.content_reader(),
+            outline_extractors: OutlineExtractors::new(),
+            transforms,
+            line_numbering,
+            root: self.root_path_for_scan.clone(),
+        }
+    }
+
     fn spawn_token_worker(
         items: Vec<TokenWorkItem>,
         token_counter: Arc<dyn TokenCounterOperations>,
+        estimator: TokenEstimator,
         sender: mpsc::Sender<TokenProgress>,
     ) {
         let total_files = items.len();
@@ -370,7 +527,7 @@ impl ProfileRuntimeData {
 
                 let chunk_results: Vec<TokenProgressEntry> = chunk
                     .par_iter()
-                    .map(|item| process_work_item(item, &token_counter))
+                    .map(|item| process_work_item(item, &token_counter, &estimator))
                     .collect();
 
                 processed += chunk.len();
@@ -460,11 +617,6 @@ impl ProfileRuntimeData {
         }
     }
 
-    #[cfg(test)]
-    fn get_cached_file_token_details(&self) -> HashMap<PathBuf, FileTokenDetails> {
-        self.cached_file_token_details.clone()
-    }
-
     #[cfg(test)]
     fn get_cached_total_token_count(&self) -> usize {
         self.cached_token_count
@@ -500,6 +652,14 @@ impl ProfileRuntimeDataOperations for ProfileRuntimeData {
         self.exclude_patterns = patterns;
     }
 
+    fn get_archive_options(&self) -> ArchiveOptions {
+        self.archive_options.clone()
+    }
+
+    fn set_archive_options(&mut self, options: ArchiveOptions) {
+        self.archive_options = options;
+    }
+
     fn get_snapshot_nodes(&self) -> &Vec<FileNode> {
         &self.file_system_snapshot_nodes
     }
@@ -513,11 +673,13 @@ impl ProfileRuntimeDataOperations for ProfileRuntimeData {
         state_manager: &dyn NodeStateApplicatorOperations,
         selected_paths: &HashSet<PathBuf>,
         deselected_paths: &HashSet<PathBuf>,
+        outline_paths: &HashSet<PathBuf>,
     ) {
         state_manager.apply_selection_states_to_nodes(
             &mut self.file_system_snapshot_nodes,
             selected_paths,
             deselected_paths,
+            outline_paths,
         );
     }
 
@@ -585,6 +747,7 @@ impl ProfileRuntimeDataOperations for ProfileRuntimeData {
         fn sum_tokens_recursive(
             nodes_to_scan: &[FileNode],
             token_counter_service: &dyn TokenCounterOperations,
+            estimator: &TokenEstimator,
             cache: &mut HashMap<PathBuf, FileTokenDetails>,
             current_total_tokens: &mut usize,
             processed_count: &mut usize,
@@ -595,18 +758,27 @@ impl ProfileRuntimeDataOperations for ProfileRuntimeData {
                     sum_tokens_recursive(
                         &node.children,
                         token_counter_service,
+                        estimator,
                         cache,
                         current_total_tokens,
                         processed_count,
                         failed_count,
                     );
-                } else if node.state() == SelectionState::Selected {
+                } else if node.is_selected() {
                     *processed_count += 1;
-                    if let Some(count) = ProfileRuntimeData::get_token_count_with_cache(
-                        token_counter_service,
-                        node,
-                        cache,
-                    ) {
+                    let is_outline = node.state() == SelectionState::Outline;
+                    let count = if estimator.reshapes(node.path(), is_outline) {
+                        estimator
+                            .count_archived(node.path(), is_outline, token_counter_service)
+                            .ok()
+                    } else {
+                        ProfileRuntimeData::get_token_count_with_cache(
+                            token_counter_service,
+                            node,
+                            cache,
+                        )
+                    };
+                    if let Some(count) = count {
                         *current_total_tokens += count;
                     } else {
                         *failed_count += 1;
@@ -619,9 +791,11 @@ impl ProfileRuntimeDataOperations for ProfileRuntimeData {
             }
         }
 
+        let estimator = self.token_estimator();
         sum_tokens_recursive(
             &self.file_system_snapshot_nodes,
             token_counter,
+            &estimator,
             &mut self.cached_file_token_details, // Pass mutable cache
             &mut total_tokens,
             &mut files_considered_for_total,
@@ -652,9 +826,10 @@ impl ProfileRuntimeDataOperations for ProfileRuntimeData {
 
         let (tx, rx) = mpsc::channel();
         let worker_counter = Arc::clone(&token_counter);
+        let estimator = self.token_estimator();
         let join_handle = thread::Builder::new()
             .name("token-recalc-worker".to_string())
-            .spawn(move || Self::spawn_token_worker(items, worker_counter, tx))
+            .spawn(move || Self::spawn_token_worker(items, worker_counter, estimator, tx))
             .expect("Failed to spawn token worker thread");
 
         Some(TokenProgressChannel {
@@ -682,6 +857,10 @@ impl ProfileRuntimeDataOperations for ProfileRuntimeData {
         self.cached_token_count
     }
 
+    fn get_cached_file_token_details(&self) -> HashMap<PathBuf, FileTokenDetails> {
+        self.cached_file_token_details.clone()
+    }
+
     fn search_content_async(
         &self,
         search_term: String,
@@ -701,6 +880,7 @@ impl ProfileRuntimeDataOperations for ProfileRuntimeData {
 
         let (sender, receiver) = mpsc::channel();
         let search_term_lower = trimmed.to_lowercase();
+        let reader = self.content_reader();
         thread::spawn(move || {
             log::debug!(
                 "ProfileRuntimeData: Starting async content search across {} files.",
@@ -709,8 +889,11 @@ impl ProfileRuntimeDataOperations for ProfileRuntimeData {
             let results: Vec<ContentSearchResult> = file_paths
                 .par_iter()
                 .map(|path| {
-                    let matches = match fs::read_to_string(path) {
-                        Ok(contents) => contents.to_lowercase().contains(&search_term_lower),
+                    let matches = match reader.read(path) {
+                        Ok(FileContent::Text(contents)) => {
+                            contents.to_lowercase().contains(&search_term_lower)
+                        }
+                        Ok(FileContent::NotText { .. }) => false,
                         Err(err) => {
                             log::warn!(
                                 "ProfileRuntimeData: Failed to read {path:?} during content search: {err:?}"
@@ -748,6 +931,9 @@ impl ProfileRuntimeDataOperations for ProfileRuntimeData {
         self.cached_token_count = 0;
         self.cached_file_token_details.clear();
         self.exclude_patterns.clear();
+        self.archive_options = ArchiveOptions::default();
+        self.components.clear();
+        self.component_paths.clear();
     }
 
     /*
@@ -764,6 +950,7 @@ impl ProfileRuntimeDataOperations for ProfileRuntimeData {
         );
         let mut selected_paths_for_profile = HashSet::new();
         let mut deselected_paths_for_profile = HashSet::new();
+        let mut outline_paths_for_profile = HashSet::new();
         let mut file_details_for_save = HashMap::new(); // This will be populated
 
         // Recursive helper to gather selection states and populate file_details_for_save
@@ -773,6 +960,7 @@ impl ProfileRuntimeDataOperations for ProfileRuntimeData {
             cached_details: &HashMap<PathBuf, FileTokenDetails>, // Read-only access to the current cache
             selected_paths_out: &mut HashSet<PathBuf>,
             deselected_paths_out: &mut HashSet<PathBuf>,
+            outline_paths_out: &mut HashSet<PathBuf>,
             file_details_out: &mut HashMap<PathBuf, FileTokenDetails>, // Populate this
         ) {
             for node in nodes {
@@ -786,7 +974,7 @@ impl ProfileRuntimeDataOperations for ProfileRuntimeData {
                         SelectionState::Deselected => {
                             deselected_paths_out.insert(node.path().to_path_buf());
                         }
-                        SelectionState::New => {}
+                        SelectionState::Outline | SelectionState::New => {}
                     }
                     if !node.children.is_empty() {
                         gather_states_and_cached_details_recursive(
@@ -794,6 +982,7 @@ impl ProfileRuntimeDataOperations for ProfileRuntimeData {
                             cached_details,
                             selected_paths_out,
                             deselected_paths_out,
+                            outline_paths_out,
                             file_details_out,
                         );
                     }
@@ -823,6 +1012,9 @@ impl ProfileRuntimeDataOperations for ProfileRuntimeData {
                                 );
                             }
                         }
+                        SelectionState::Outline => {
+                            outline_paths_out.insert(node.path().to_path_buf());
+                        }
                         SelectionState::Deselected => {
                             deselected_paths_out.insert(node.path().to_path_buf());
                         }
@@ -837,8 +1029,12 @@ impl ProfileRuntimeDataOperations for ProfileRuntimeData {
             &self.cached_file_token_details, // Provide read-only access to the current cache
             &mut selected_paths_for_profile,
             &mut deselected_paths_for_profile,
+            &mut outline_paths_for_profile,
             &mut file_details_for_save,
         );
+        // A composite stores only its own selection; its components are applied on load.
+        selected_paths_for_profile.retain(|path| !self.component_paths.contains(path));
+        outline_paths_for_profile.retain(|path| !self.component_paths.contains(path));
 
         let name = self
             .profile_name
@@ -851,16 +1047,20 @@ impl ProfileRuntimeDataOperations for ProfileRuntimeData {
             root_folder: self.root_path_for_scan.clone(),
             selected_paths: selected_paths_for_profile,
             deselected_paths: deselected_paths_for_profile,
+            outline_paths: outline_paths_for_profile,
             archive_path: self.archive_path.clone(),
             file_details: file_details_for_save, // Use the selectively populated map
             exclude_patterns: self.exclude_patterns.clone(),
+            archive_options: self.archive_options.clone(),
+            components: self.components.clone(),
         }
     }
 
     /*
      * Activates the given profile: copies its data into `ProfileRuntimeData`,
      * loads its associated file system data, applies the profile's selection
-     * state, updates token caches, and the total token count.
+     * state, updates token caches, and the total token count. The selection of a composite
+     * profile is first resolved from its components. [ProfileCompositeV1]
      * Returns `Ok(())` on success, or an `Err(String)` with an error message on failure.
      * TODO: The call to scan_directory() should be moved upwards.
      */
@@ -870,16 +1070,33 @@ impl ProfileRuntimeDataOperations for ProfileRuntimeData {
         file_system_scanner: &dyn FileSystemScannerOperations,
         state_manager: &dyn NodeStateApplicatorOperations,
         _token_counter: &dyn TokenCounterOperations,
+        component_loader: &dyn ComponentLoaderOperations,
     ) -> Result<(), String> {
         log::debug!(
             "ProfileRuntimeData: Loading profile '{}' into session.",
             loaded_profile.name
         );
+        let selection =
+            match composite_profile::resolve_selection(&loaded_profile, component_loader) {
+                Ok(selection) => selection,
+                Err(e) => {
+                    let error_message = format!(
+                        "Failed to resolve the selection of profile '{}': {e}",
+                        loaded_profile.name
+                    );
+                    log::error!("ProfileRuntimeData: {error_message}");
+                    self.clear();
+                    return Err(error_message);
+                }
+            };
+        self.component_paths = selection.contributed_by_components(&loaded_profile);
+        self.components = loaded_profile.components.clone();
         self.profile_name = Some(loaded_profile.name.as_str().to_string());
         self.root_path_for_scan = loaded_profile.root_folder.clone();
         self.archive_path = loaded_profile.archive_path.clone();
         self.cached_file_token_details = loaded_profile.file_details.clone(); // Initial copy
         self.exclude_patterns = loaded_profile.exclude_patterns.clone();
+        self.archive_options = loaded_profile.archive_options.clone();
 
         match file_system_scanner.scan_directory(&self.root_path_for_scan, &self.exclude_patterns) {
             Ok(nodes) => {
@@ -892,8 +1109,9 @@ impl ProfileRuntimeDataOperations for ProfileRuntimeData {
 
                 state_manager.apply_selection_states_to_nodes(
                     &mut self.file_system_snapshot_nodes,
-                    &loaded_profile.selected_paths,
-                    &loaded_profile.deselected_paths,
+                    &selection.selected_paths,
+                    &selection.deselected_paths,
+                    &selection.outline_paths,
                 );
                 log::debug!(
                     "ProfileRuntimeData: Applied profile selection states from '{:?}' to the scanned tree.",
@@ -918,16 +1136,20 @@ impl ProfileRuntimeDataOperations for ProfileRuntimeData {
         }
     }
 
-    fn get_current_selection_paths(&self) -> (HashSet<PathBuf>, HashSet<PathBuf>) {
+    fn get_current_selection_paths(
+        &self,
+    ) -> (HashSet<PathBuf>, HashSet<PathBuf>, HashSet<PathBuf>) {
         let mut selected = HashSet::new();
         let mut deselected = HashSet::new();
+        let mut outline = HashSet::new();
         // Use your existing internal helper
         Self::gather_selected_deselected_paths_recursive_internal(
             &self.file_system_snapshot_nodes,
             &mut selected,
             &mut deselected,
+            &mut outline,
         );
-        (selected, deselected)
+        (selected, deselected, outline)
     }
 }
 
@@ -935,9 +1157,12 @@ impl ProfileRuntimeDataOperations for ProfileRuntimeData {
 mod tests {
     use super::*;
     use crate::core::checksum_utils;
+    use crate::core::content_transform::{ContentTransform, TransformRule};
+    use crate::core::line_numbers::LineNumberOptions;
     use crate::core::{
-        FileNode, FileSystemError, FileSystemScannerOperations, NodeStateApplicatorOperations,
-        Profile, SelectionState, TokenCounterOperations,
+        FileNode, FileSystemError, FileSystemScannerOperations, NodeStateApplicator,
+        NodeStateApplicatorOperations, Profile, SelectionState, TokenCounterOperations,
+        profile_settings::CompositeOperation, profiles::ProfileError,
         tokenizer_utils::SimpleWhitespaceTokenCounter,
     };
     use std::collections::{HashMap, HashSet};
@@ -1004,6 +1229,9 @@ mod tests {
                         ignore::Error::from(io::Error::other("mock ignore error")),
                     ),
                     FileSystemError::InvalidPath(p) => FileSystemError::InvalidPath(p.clone()),
+                    FileSystemError::Watch(_) => {
+                        FileSystemError::Watch(notify::Error::generic("mock watch error"))
+                    }
                 }),
                 None => Ok(Vec::new()),
             }
@@ -1037,6 +1265,7 @@ mod tests {
             tree: &mut Vec<FileNode>,
             selected_paths: &HashSet<PathBuf>,
             deselected_paths: &HashSet<PathBuf>,
+            outline_paths: &HashSet<PathBuf>,
         ) {
             self.apply_profile_to_tree_calls.lock().unwrap().push((
                 selected_paths.clone(),
@@ -1045,7 +1274,9 @@ mod tests {
             ));
             // Simulate actual behavior for test consistency
             for node in tree.iter_mut() {
-                if selected_paths.contains(node.path()) {
+                if outline_paths.contains(node.path()) {
+                    node.set_state(SelectionState::Outline);
+                } else if selected_paths.contains(node.path()) {
                     node.set_state(SelectionState::Selected);
                 } else if deselected_paths.contains(node.path()) {
                     node.set_state(SelectionState::Deselected);
@@ -1057,6 +1288,7 @@ mod tests {
                         &mut node.children,
                         selected_paths,
                         deselected_paths,
+                        outline_paths,
                     );
                 }
             }
@@ -1231,6 +1463,9 @@ mod tests {
             cached_token_count: 0, // Not directly used by create_profile_snapshot itself
             cached_file_token_details: HashMap::new(),
             exclude_patterns: Vec::new(),
+            archive_options: ArchiveOptions::default(),
+            components: Vec::new(),
+            component_paths: HashSet::new(),
         };
         // Populate cached_file_token_details as update_total_token_count_for_selected_files would
         session_data.cached_file_token_details.insert(
@@ -1320,6 +1555,9 @@ mod tests {
             ],
             cached_token_count: 0,
             exclude_patterns: Vec::new(),
+            archive_options: ArchiveOptions::default(),
+            components: Vec::new(),
+            component_paths: HashSet::new(),
         };
         let mock_token_counter = MockTokenCounter::new(0); // Default, should not be used
 
@@ -1423,6 +1661,118 @@ mod tests {
         );
     }
 
+    // Runs the token worker for the selected files and returns the final total.
+    fn recalc_selected_tokens(session_data: &mut ProfileRuntimeData) -> usize {
+        let token_counter: Arc<dyn TokenCounterOperations> = Arc::new(SimpleWhitespaceTokenCounter);
+        let channel = session_data
+            .recalc_tokens_async(token_counter, true)
+            .expect("Expected work items for selected files");
+        let mut total = 0;
+        for progress in channel.receiver.iter() {
+            let is_final = progress.is_final;
+            total = session_data.apply_token_progress(progress);
+            if is_final {
+                break;
+            }
+        }
+        if let Some(handle) = channel.worker_handle {
+            handle.join().expect("Worker thread should finish");
+        }
+        total
+    }
+
+    #[test]
+    fn test_outline_files_count_at_their_outline_size() {
+        // Arrange
+        let temp_dir = tempdir().unwrap();
+        let root = temp_dir.path();
+        let outlined_path = root.join("lib.rs");
+        fs::write(
+            &outlined_path,
+            "pub fn api() {\n    let hidden = 1;\n    hidden + 1;\n}\n",
+        )
+        .unwrap();
+        let full_path = root.join("notes.txt");
+        fs::write(&full_path, "three plain words").unwrap();
+        let mut outlined = FileNode::new(outlined_path, "lib.rs".into(), false, "cs1".into());
+        outlined.set_state(SelectionState::Outline);
+        let mut full = FileNode::new(full_path, "notes.txt".into(), false, "cs2".into());
+        full.set_state(SelectionState::Selected);
+        let mut session_data = ProfileRuntimeData::new();
+        session_data.root_path_for_scan = root.to_path_buf();
+        session_data.set_snapshot_nodes(vec![outlined, full]);
+
+        // Act
+        let async_total = recalc_selected_tokens(&mut session_data);
+        let sync_total =
+            session_data.update_total_token_count_for_selected_files(&SimpleWhitespaceTokenCounter);
+
+        // Assert - "pub fn api() { ... }" is 6 tokens instead of the file's 11, the notes 3
+        assert_eq!(async_total, 9);
+        assert_eq!(sync_total, 9);
+    }
+
+    #[test]
+    fn test_selected_files_count_after_content_transforms() {
+        // Arrange
+        let temp_dir = tempdir().unwrap();
+        let root = temp_dir.path();
+        let code_path = root.join("lib.rs");
+        fs::write(&code_path, "// five words of comment here\nfn api() {}\n").unwrap();
+        let notes_path = root.join("notes.txt");
+        fs::write(&notes_path, "// not a comment here").unwrap();
+        let mut code = FileNode::new(code_path, "lib.rs".into(), false, "cs1".into());
+        code.set_state(SelectionState::Selected);
+        let mut notes = FileNode::new(notes_path, "notes.txt".into(), false, "cs2".into());
+        notes.set_state(SelectionState::Selected);
+        let mut session_data = ProfileRuntimeData::new();
+        session_data.root_path_for_scan = root.to_path_buf();
+        session_data.set_snapshot_nodes(vec![code, notes]);
+        session_data.archive_options.transforms = vec![TransformRule {
+            transform: ContentTransform::StripComments,
+            glob: Some("*.rs".to_string()),
+        }];
+
+        // Act
+        let async_total = recalc_selected_tokens(&mut session_data);
+        let sync_total =
+            session_data.update_total_token_count_for_selected_files(&SimpleWhitespaceTokenCounter);
+
+        // Assert - lib.rs keeps "fn api() {}" (3 of 9 tokens), the notes are not matched (5)
+        assert_eq!(async_total, 8);
+        assert_eq!(sync_total, 8);
+    }
+
+    #[test]
+    fn test_numbered_files_count_with_their_gutter() {
+        // Arrange
+        let temp_dir = tempdir().unwrap();
+        let root = temp_dir.path();
+        let code_path = root.join("main.rs");
+        fs::write(&code_path, "fn main() {\n    run();\n}\n").unwrap();
+        let notes_path = root.join("notes.txt");
+        fs::write(&notes_path, "plain notes\n").unwrap();
+        let mut code = FileNode::new(code_path, "main.rs".into(), false, "cs1".into());
+        code.set_state(SelectionState::Selected);
+        let mut notes = FileNode::new(notes_path, "notes.txt".into(), false, "cs2".into());
+        notes.set_state(SelectionState::Selected);
+        let mut session_data = ProfileRuntimeData::new();
+        session_data.root_path_for_scan = root.to_path_buf();
+        session_data.set_snapshot_nodes(vec![code, notes]);
+        session_data.archive_options.line_numbers = Some(LineNumberOptions {
+            globs: vec!["*.rs".to_string()],
+        });
+
+        // Act
+        let async_total = recalc_selected_tokens(&mut session_data);
+        let sync_total =
+            session_data.update_total_token_count_for_selected_files(&SimpleWhitespaceTokenCounter);
+
+        // Assert - each of the 3 lines of main.rs gains "n |" (5 + 6 tokens), the notes 2
+        assert_eq!(async_total, 13);
+        assert_eq!(sync_total, 13);
+    }
+
     #[test]
     fn test_load_profile_into_session_defers_token_recalculation() {
         // Arrange
@@ -1467,9 +1817,12 @@ mod tests {
             root_folder: root_folder.clone(),
             selected_paths: HashSet::new(),
             deselected_paths: HashSet::new(),
+            outline_paths: HashSet::new(),
             archive_path: Some(PathBuf::from("/dummy/archive.txt")),
             file_details: initial_profile_file_details,
             exclude_patterns: Vec::new(),
+            archive_options: ArchiveOptions::default(),
+            components: Vec::new(),
         };
         loaded_profile.selected_paths.insert(file1_path.clone());
         loaded_profile.selected_paths.insert(file2_path.clone());
@@ -1501,6 +1854,7 @@ mod tests {
             &mock_scanner,
             &mock_state_manager,
             &mock_token_counter,
+            &MapComponentLoader::default(),
         );
 
         // Assert
@@ -1540,6 +1894,93 @@ mod tests {
         );
     }
 
+    #[derive(Default)]
+    struct MapComponentLoader(HashMap<String, Profile>);
+
+    impl ComponentLoaderOperations for MapComponentLoader {
+        fn load_component(&self, name: &ProfileName) -> Result<Profile, ProfileError> {
+            self.0
+                .get(name.as_str())
+                .cloned()
+                .ok_or_else(|| ProfileError::ProfileNotFound(name.as_str().to_string()))
+        }
+    }
+
+    #[test]
+    fn test_composite_profile_loads_union_and_saves_only_own_selection() {
+        // Arrange
+        crate::initialize_logging();
+        let mut session_data = ProfileRuntimeData::new();
+        let mock_scanner = MockFileSystemScanner::new();
+        let root = PathBuf::from("/composite");
+        let file = |name: &str| {
+            FileNode::new_full(
+                root.join(name),
+                name.to_string(),
+                false,
+                SelectionState::New,
+                Vec::new(),
+                format!("cs_{name}"),
+            )
+        };
+        mock_scanner.set_scan_directory_result(
+            &root,
+            Ok(vec![file("api.rs"), file("ui.ts"), file("notes.md")]),
+        );
+        let mut backend = Profile::new(ProfileName::new("backend").unwrap(), root.clone());
+        backend.selected_paths.insert(root.join("api.rs"));
+        let mut frontend = Profile::new(ProfileName::new("frontend").unwrap(), root.clone());
+        frontend.selected_paths.insert(root.join("ui.ts"));
+        let loader = MapComponentLoader(HashMap::from([
+            ("backend".to_string(), backend),
+            ("frontend".to_string(), frontend),
+        ]));
+        let mut composite = Profile::new(ProfileName::new("everything").unwrap(), root.clone());
+        composite.selected_paths.insert(root.join("notes.md"));
+        composite.components = ["backend", "frontend"]
+            .into_iter()
+            .map(|name| ProfileComponent {
+                profile: ProfileName::new(name).unwrap(),
+                operation: CompositeOperation::Union,
+            })
+            .collect();
+
+        // Act
+        session_data
+            .load_profile_into_session(
+                composite.clone(),
+                &mock_scanner,
+                &NodeStateApplicator::new(),
+                &MockTokenCounter::new(0),
+                &loader,
+            )
+            .unwrap();
+
+        // Assert
+        let (selected, _, _) = session_data.get_current_selection_paths();
+        assert_eq!(selected.len(), 3);
+        let snapshot = session_data.create_profile_snapshot();
+        assert_eq!(snapshot.selected_paths, composite.selected_paths);
+        assert_eq!(snapshot.components, composite.components);
+
+        // A missing component fails the load with a clear error.
+        composite.components[1].profile = ProfileName::new("mobile").unwrap();
+        let error = session_data
+            .load_profile_into_session(
+                composite,
+                &mock_scanner,
+                &NodeStateApplicator::new(),
+                &MockTokenCounter::new(0),
+                &loader,
+            )
+            .unwrap_err();
+        assert!(
+            error.contains("Profile 'mobile', a component of 'everything', could not be loaded"),
+            "{error}"
+        );
+        assert_eq!(session_data.get_profile_name(), None);
+    }
+
     #[test]
     fn test_update_node_state_and_collect_changes_updates_and_collects() {
         // Arrange
@@ -1659,6 +2100,9 @@ mod tests {
             )],
             cached_token_count: 0,
             exclude_patterns: Vec::new(),
+            archive_options: ArchiveOptions::default(),
+            components: Vec::new(),
+            component_paths: HashSet::new(),
         };
         mock_token_counter.clear_call_log();
 
@@ -1720,6 +2164,9 @@ mod tests {
             )],
             cached_token_count: 0,
             exclude_patterns: Vec::new(),
+            archive_options: ArchiveOptions::default(),
+            components: Vec::new(),
+            component_paths: HashSet::new(),
         };
         mock_token_counter.clear_call_log();
 
@@ -0,0 +1,109 @@
+use super::archive_format::ArchiveFormat;
+use super::archive_history::ArchiveHistoryOptions;
+use super::archive_prompt::PromptOptions;
+use super::archive_split::ArchiveSplitOptions;
+use super::archive_template::ArchiveTemplates;
+use super::archive_tree::DirectoryTreeOptions;
+use super::content_class::ContentLimits;
+use super::content_transform::{TransformPipeline, TransformRule};
+use super::line_numbers::{LineNumberOptions, LineNumbering};
+use super::project_context::ProfileName;
+use super::secret_scan::{SecretScanOptions, SecretScanner};
+use super::text_encoding::{EncodingRule, EncodingRules};
+use super::token_budget::FilePriority;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/*
+ * The settings stored on a `Profile` besides its selection: the archive options and the
+ * composite components. They live apart from the archiver and the composite resolver so
+ * that `file_node` can hold them without depending on either.
+ */
+
+/*
+ * Per-profile settings that control how an archive is generated.
+ * Stored on `Profile` so that each profile can target a different consumer.
+ * Every field carries `#[serde(default)]` so older profiles keep loading.
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct ArchiveOptions {
+    #[serde(default)]
+    pub format: ArchiveFormat,
+    /* Preamble and per-file header/footer templates for the plain text format. */
+    #[serde(default)]
+    pub templates: ArchiveTemplates,
+    /* When set, the archive is written as `archive.part1.txt`, `archive.part2.txt`, ... */
+    #[serde(default)]
+    pub split: Option<ArchiveSplitOptions>,
+    /* Maximum number of tokens for the selected files; `None` disables enforcement. */
+    #[serde(default)]
+    pub token_budget: Option<usize>,
+    /* Priorities that differ from `FilePriority::Normal`, keyed by absolute file path. */
+    #[serde(default)]
+    pub file_priorities: HashMap<PathBuf, FilePriority>,
+    /* Writes a table of contents with per-file sizes and checksums before the first file. */
+    #[serde(default)]
+    pub include_manifest: bool,
+    /* Ordered content transforms (comment stripping, whitespace cleanup, ...) per file. */
+    #[serde(default)]
+    pub transforms: Vec<TransformRule>,
+    /* Writes an ASCII tree of the scanned project before the first file. */
+    #[serde(default)]
+    pub directory_tree: Option<DirectoryTreeOptions>,
+    /* Secret detection policy, custom rules and allowlist. [ArchiveGenSecretScanV1] */
+    #[serde(default)]
+    pub secret_scan: SecretScanOptions,
+    /* Size limit and what to do with binary and oversize files. [ArchiveGenBinaryFilesV1] */
+    #[serde(default)]
+    pub content_limits: ContentLimits,
+    /* Fallback encodings by glob for files without a BOM. [FutureEncodingSupportOtherV1] */
+    #[serde(default)]
+    pub encodings: Vec<EncodingRule>,
+    /* Prefixes archived lines with their numbers; `None` is off. [ArchiveGenLineNumbersV1] */
+    #[serde(default)]
+    pub line_numbers: Option<LineNumberOptions>,
+    /* How many generated archives to keep in the history. [ArchiveHistoryV1] */
+    #[serde(default)]
+    pub history: ArchiveHistoryOptions,
+    /* Named prompt preambles and postambles around the archive. [ArchiveGenPromptsV1] */
+    #[serde(default)]
+    pub prompts: PromptOptions,
+}
+
+impl ArchiveOptions {
+    /*
+     * Checks the options for errors that would otherwise only surface at generation
+     * time, such as unknown template placeholders or malformed transform globs.
+     * Called when a profile is loaded.
+     */
+    pub fn validate(&self) -> Result<(), String> {
+        self.templates.validate().map_err(|e| e.to_string())?;
+        self.prompts.validate().map_err(|e| e.to_string())?;
+        TransformPipeline::compile(&self.transforms).map_err(|e| e.to_string())?;
+        SecretScanner::compile(&self.secret_scan).map_err(|e| e.to_string())?;
+        EncodingRules::compile(&self.encodings).map_err(|e| e.to_string())?;
+        LineNumbering::compile(self.line_numbers.as_ref()).map_err(|e| e.to_string())?;
+        Ok(())
+    }
+}
+
+/*
+ * How a component's selection is combined with the selection built so far.
+ * [ProfileCompositeV1]
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CompositeOperation {
+    #[default]
+    Union,
+    Difference,
+}
+
+/* Another profile of the project whose selection is applied to the composite. */
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProfileComponent {
+    pub profile: ProfileName,
+    #[serde(default)]
+    pub operation: CompositeOperation,
+}
@@ -27,6 +27,7 @@ pub enum ProfileError {
     NoProjectDirectory,
     ProfileNotFound(String),
     InvalidProfileName(String),
+    InvalidArchiveOptions(String),
 }
 
 impl From<io::Error> for ProfileError {
@@ -54,6 +55,9 @@ impl std::fmt::Display for ProfileError {
                 f,
                 "Invalid profile name: {name}. Contains invalid characters or is empty."
             ),
+            ProfileError::InvalidArchiveOptions(msg) => {
+                write!(f, "Invalid archive settings in profile: {msg}")
+            }
         }
     }
 }
@@ -70,6 +74,18 @@ impl std::error::Error for ProfileError {
 
 pub type Result<T> = std::result::Result<T, ProfileError>;
 
+/*
+ * Rejects profiles whose archive settings cannot be used, e.g. templates that refer to
+ * unknown placeholders, so the problem is reported on load rather than on generation.
+ */
+fn validate_loaded_profile(profile: Profile) -> Result<Profile> {
+    profile
+        .archive_options
+        .validate()
+        .map_err(ProfileError::InvalidArchiveOptions)?;
+    Ok(profile)
+}
+
 pub fn sanitize_profile_name(name: &str) -> String {
     name.chars()
         .filter(|c| c.is_alphanumeric() || *c == '_' || *c == '-')
@@ -197,7 +213,7 @@ impl ProfileManagerOperations for CoreProfileManager {
 
         let file = File::open(&file_path)?;
         let reader = BufReader::new(file);
-        let profile: Profile = serde_json::from_reader(reader)?;
+        let profile = validate_loaded_profile(serde_json::from_reader(reader)?)?;
         log::debug!(
             "CoreProfileManager: Successfully loaded profile '{}' from {:?}.",
             profile.name, // Use profile.name as it's authoritative after load
@@ -210,7 +226,7 @@ impl ProfileManagerOperations for CoreProfileManager {
         log::trace!("CoreProfileManager: Loading profile from path {path:?}");
         let file = File::open(path)?;
         let reader = BufReader::new(file);
-        let profile: Profile = serde_json::from_reader(reader)?;
+        let profile = validate_loaded_profile(serde_json::from_reader(reader)?)?;
         log::debug!(
             "CoreProfileManager: Successfully loaded profile '{}' from path {:?}.",
             profile.name,
@@ -353,6 +369,7 @@ impl ProfileManagerOperations for CoreProfileManager {
 #[cfg(test)]
 mod profile_tests {
     use super::*;
+    use crate::core::ArchiveOptions;
     use crate::core::project_context::{PROFILES_SUBFOLDER_NAME, PROJECT_CONFIG_DIR_NAME};
     use std::collections::{HashMap, HashSet};
     use tempfile::TempDir;
@@ -399,9 +416,12 @@ mod profile_tests {
             root_folder: root.clone(),
             selected_paths: selected.clone(),
             deselected_paths: HashSet::new(),
+            outline_paths: HashSet::new(),
             archive_path: Some(PathBuf::from("/mock/archive.txt")),
             file_details: HashMap::new(),
             exclude_patterns: Vec::new(),
+            archive_options: ArchiveOptions::default(),
+            components: Vec::new(),
         };
 
         manager.save_profile(&project, &original_profile, APP_NAME_FOR_TESTS)?;
@@ -459,9 +479,12 @@ mod profile_tests {
             root_folder: root.clone(),
             selected_paths: HashSet::new(),
             deselected_paths: HashSet::new(),
+            outline_paths: HashSet::new(),
             archive_path: None,
             file_details: HashMap::new(),
             exclude_patterns: Vec::new(),
+            archive_options: ArchiveOptions::default(),
+            components: Vec::new(),
         };
 
         manager.save_profile(&project, &profile_to_save, APP_NAME_FOR_TESTS)?;
@@ -481,6 +504,58 @@ mod profile_tests {
         Ok(())
     }
 
+    #[test]
+    fn test_archive_templates_round_trip_through_profile_json() -> Result<()> {
+        // Arrange
+        let temp_dir = TempDir::new().expect("Failed to create temp dir for test");
+        let project = ProjectContext::new(temp_dir.path().to_path_buf());
+        let manager = CoreProfileManager::new();
+        let mut profile = Profile::new(
+            ProfileName::new("Templated").unwrap(),
+            PathBuf::from("/tmp/mock"),
+        );
+        profile.archive_options.templates.file_header =
+            Some("--- START FILE: \"{relative_path}\" ({token_count} tokens) ---".to_string());
+        profile.archive_options.templates.file_footer =
+            Some("--- END FILE: \"{relative_path}\" ---".to_string());
+
+        // Act
+        manager.save_profile(&project, &profile, APP_NAME_FOR_TESTS)?;
+        let loaded = manager.load_profile(&project, &profile.name, APP_NAME_FOR_TESTS)?;
+
+        // Assert
+        assert_eq!(loaded.archive_options, profile.archive_options);
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_profile_rejects_unknown_template_placeholder() {
+        // Arrange
+        let temp_dir = TempDir::new().expect("Failed to create temp dir for test");
+        let profile_path = temp_dir.path().join("bad.json");
+        let json = r#"{
+            "name": "Bad",
+            "root_folder": "/tmp/mock",
+            "selected_paths": [],
+            "deselected_paths": [],
+            "archive_path": null,
+            "archive_options": { "templates": { "file_header": "// {relative_path} by {author}" } }
+        }"#;
+        fs::write(&profile_path, json).unwrap();
+        let manager = CoreProfileManager::new();
+
+        // Act
+        let result = manager.load_profile_from_path(&profile_path);
+
+        // Assert
+        match result {
+            Err(ProfileError::InvalidArchiveOptions(msg)) => {
+                assert!(msg.contains("{author}"), "unexpected message: {msg}")
+            }
+            other => panic!("Expected InvalidArchiveOptions, got {other:?}"),
+        }
+    }
+
     #[test]
     fn test_list_profiles_project_local() -> Result<()> {
         let temp_dir = TempDir::new().expect("Failed to create temp dir for test");
@@ -13,6 +13,9 @@ pub(super) const PROJECT_CONFIG_DIR_NAME: &str = ".sourcepacker";
 pub(super) const PROFILES_SUBFOLDER_NAME: &str = "profiles";
 pub(super) const PROFILE_FILE_EXTENSION: &str = "json";
 pub(super) const LAST_PROFILE_FILENAME: &str = "last_profile.txt";
+pub(super) const ARCHIVE_RECORDS_SUBFOLDER_NAME: &str = "archives";
+pub(super) const ARCHIVE_HISTORY_SUBFOLDER_NAME: &str = "history";
+pub(super) const SCAN_CACHE_FILENAME: &str = "scan_cache.json";
 
 /*
  * Opaque handle to a project root. It wraps the root `PathBuf` and exposes
@@ -95,6 +98,31 @@ impl ProjectContext {
             profile_name.sanitized_for_filename()
         ))
     }
+
+    /*
+     * The content record of the profile's last generated archive, used for staleness
+     * checks (see `archive_record`).
+     */
+    pub(crate) fn resolve_archive_record_file(&self, profile_name: &ProfileName) -> PathBuf {
+        self.resolve_config_dir()
+            .join(ARCHIVE_RECORDS_SUBFOLDER_NAME)
+            .join(format!(
+                "{}.{PROFILE_FILE_EXTENSION}",
+                profile_name.sanitized_for_filename()
+            ))
+    }
+
+    /* The directory holding the profile's archive history (see `archive_history`). */
+    pub(crate) fn resolve_archive_history_dir(&self, profile_name: &ProfileName) -> PathBuf {
+        self.resolve_config_dir()
+            .join(ARCHIVE_HISTORY_SUBFOLDER_NAME)
+            .join(profile_name.sanitized_for_filename())
+    }
+
+    /* The checksums kept between scans of the project (see `scan_cache`). */
+    pub(crate) fn resolve_scan_cache_file(&self) -> PathBuf {
+        self.resolve_config_dir().join(SCAN_CACHE_FILENAME)
+    }
 }
 
 impl ProfileName {
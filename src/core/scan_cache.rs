@@ -0,0 +1,317 @@
+use super::atomic_write;
+use super::checksum_utils;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs::{self, Metadata};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/*
+ * This module keeps the checksums computed by a scan between runs. [FileSystemScanCacheV1]
+ * The cache is a JSON file under `.sourcepacker/`, keyed by the path relative to the scanned
+ * root and holding the file's size, modification time, inode (where the platform has one)
+ * and SHA-256. A file is hashed again only when any of these changed, so refreshing a large
+ * project reads just the files that were touched. A cache written with another schema
+ * version, or that cannot be parsed, is discarded. A file modified within the last
+ * `RECENT_WRITE_WINDOW` is not cached, since a second write in the same timestamp tick
+ * would otherwise go unnoticed.
+ */
+
+/* Bumped when the layout changes; caches with another version are discarded. */
+pub const SCAN_CACHE_VERSION: u32 = 1;
+
+const RECENT_WRITE_WINDOW: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScanCacheEntry {
+    pub size: u64,
+    pub modified_secs: u64,
+    pub modified_nanos: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inode: Option<u64>,
+    pub checksum: String,
+}
+
+impl ScanCacheEntry {
+    fn from_metadata(metadata: &Metadata, checksum: String) -> Option<Self> {
+        let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+        Some(ScanCacheEntry {
+            size: metadata.len(),
+            modified_secs: modified.as_secs(),
+            modified_nanos: modified.subsec_nanos(),
+            inode: inode_of(metadata),
+            checksum,
+        })
+    }
+
+    fn matches(&self, other: &ScanCacheEntry) -> bool {
+        self.size == other.size
+            && self.modified_secs == other.modified_secs
+            && self.modified_nanos == other.modified_nanos
+            && self.inode == other.inode
+    }
+}
+
+#[cfg(unix)]
+fn inode_of(metadata: &Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Some(metadata.ino())
+}
+
+#[cfg(not(unix))]
+fn inode_of(_metadata: &Metadata) -> Option<u64> {
+    None
+}
+
+/* How many checksums were reused and how many had to be computed, for diagnostics. */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ScanCacheStats {
+    pub hits: usize,
+    pub misses: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ScanCacheFile {
+    version: u32,
+    entries: BTreeMap<PathBuf, ScanCacheEntry>,
+}
+
+/* A checksum found in the cache, or computed along with the entry to cache for it. */
+enum Lookup {
+    Hit(String),
+    Computed {
+        checksum: String,
+        entry: Option<ScanCacheEntry>,
+    },
+}
+
+/* The cache of one scanned root, loaded for the duration of a scan. */
+#[derive(Debug)]
+pub struct ScanCache {
+    path: PathBuf,
+    root: PathBuf,
+    entries: BTreeMap<PathBuf, ScanCacheEntry>,
+    visited: HashSet<PathBuf>,
+    stats: ScanCacheStats,
+    changed: bool,
+}
+
+impl ScanCache {
+    /*
+     * Loads the cache stored at `path` for files under `root`. A missing, unreadable or
+     * outdated cache starts empty.
+     */
+    pub fn load(path: &Path, root: &Path) -> Self {
+        let entries = match fs::read_to_string(path) {
+            Ok(text) => match serde_json::from_str::<ScanCacheFile>(&text) {
+                Ok(file) if file.version == SCAN_CACHE_VERSION => file.entries,
+                Ok(file) => {
+                    log::debug!(
+                        "ScanCache: Discarding {path:?}, version {} (expected {SCAN_CACHE_VERSION}).",
+                        file.version
+                    );
+                    BTreeMap::new()
+                }
+                Err(e) => {
+                    log::warn!("ScanCache: Discarding unreadable cache {path:?}: {e}");
+                    BTreeMap::new()
+                }
+            },
+            Err(e) if e.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => {
+                log::warn!("ScanCache: Failed to read {path:?}: {e}");
+                BTreeMap::new()
+            }
+        };
+        ScanCache {
+            path: path.to_path_buf(),
+            root: root.to_path_buf(),
+            entries,
+            visited: HashSet::new(),
+            stats: ScanCacheStats::default(),
+            changed: false,
+        }
+    }
+
+    pub fn stats(&self) -> ScanCacheStats {
+        self.stats
+    }
+
+    /*
+     * The SHA-256 of the file at `path`, taken from the cache when its metadata is
+     * unchanged and computed (and cached) otherwise.
+     */
+    pub fn checksum(&mut self, path: &Path) -> io::Result<String> {
+        self.checksums(std::slice::from_ref(&path.to_path_buf()))
+            .pop()
+            .expect("one result per path")
+    }
+
+    /*
+     * The checksums of `paths`, in the same order. Files missing from the cache are hashed
+     * in parallel on the global rayon pool; the cache is updated afterwards.
+     */
+    pub fn checksums(&mut self, paths: &[PathBuf]) -> Vec<io::Result<String>> {
+        let lookups: Vec<(PathBuf, io::Result<Lookup>)> = paths
+            .par_iter()
+            .map(|path| {
+                let key = path.strip_prefix(&self.root).unwrap_or(path).to_path_buf();
+                let lookup = self.lookup(&key, path);
+                (key, lookup)
+            })
+            .collect();
+
+        let mut checksums = Vec::with_capacity(lookups.len());
+        for (key, lookup) in lookups {
+            self.visited.insert(key.clone());
+            checksums.push(lookup.map(|lookup| match lookup {
+                Lookup::Hit(checksum) => {
+                    self.stats.hits += 1;
+                    checksum
+                }
+                Lookup::Computed { checksum, entry } => {
+                    self.stats.misses += 1;
+                    self.changed = true;
+                    match entry {
+                        Some(entry) => self.entries.insert(key, entry),
+                        None => self.entries.remove(&key),
+                    };
+                    checksum
+                }
+            }));
+        }
+        checksums
+    }
+
+    fn lookup(&self, key: &Path, path: &Path) -> io::Result<Lookup> {
+        let metadata = fs::metadata(path)?;
+        if let Some(cached) = self.entries.get(key)
+            && ScanCacheEntry::from_metadata(&metadata, String::new())
+                .is_some_and(|current| cached.matches(&current))
+        {
+            return Ok(Lookup::Hit(cached.checksum.clone()));
+        }
+
+        let checksum = checksum_utils::calculate_sha256_checksum(path)?;
+        let recently_written = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+            .is_none_or(|age| age < RECENT_WRITE_WINDOW);
+        let entry = ScanCacheEntry::from_metadata(&metadata, checksum.clone())
+            .filter(|_| !recently_written);
+        Ok(Lookup::Computed { checksum, entry })
+    }
+
+    /*
+     * Writes the cache back if anything changed. Entries not visited by this scan are kept
+     * as long as their file exists, since another profile may scan the same root with
+     * different exclusions.
+     */
+    pub fn save(mut self) -> io::Result<ScanCacheStats> {
+        let root = self.root.clone();
+        let visited = std::mem::take(&mut self.visited);
+        let before = self.entries.len();
+        self.entries
+            .retain(|key, _| visited.contains(key) || root.join(key).is_file());
+        if !self.changed && self.entries.len() == before {
+            return Ok(self.stats);
+        }
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = ScanCacheFile {
+            version: SCAN_CACHE_VERSION,
+            entries: self.entries,
+        };
+        atomic_write::write_file_atomically(&self.path, |out| {
+            serde_json::to_writer(&mut *out, &file)?;
+            out.write_all(b"\n")
+        })?;
+        Ok(self.stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use tempfile::tempdir;
+
+    /* Moves the file's timestamp out of the recent-write window so it can be cached. */
+    fn age(path: &Path, seconds_ago: u64) {
+        let file = File::options().write(true).open(path).unwrap();
+        file.set_modified(SystemTime::now() - Duration::from_secs(seconds_ago))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_unchanged_files_are_hits_and_changed_files_are_rehashed() {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        let cache_path = root.join(".sourcepacker").join("scan_cache.json");
+        let (a, b) = (root.join("a.txt"), root.join("b.txt"));
+        fs::write(&a, "alpha").unwrap();
+        fs::write(&b, "beta").unwrap();
+        age(&a, 60);
+        age(&b, 60);
+
+        let mut cache = ScanCache::load(&cache_path, root);
+        let first = cache.checksum(&a).unwrap();
+        cache.checksum(&b).unwrap();
+        assert_eq!(cache.save().unwrap(), ScanCacheStats { hits: 0, misses: 2 });
+
+        fs::write(&b, "beta, longer").unwrap();
+        age(&b, 30);
+        let mut cache = ScanCache::load(&cache_path, root);
+        assert_eq!(cache.checksum(&a).unwrap(), first);
+        assert_eq!(
+            cache.checksum(&b).unwrap(),
+            checksum_utils::calculate_sha256_checksum(&b).unwrap()
+        );
+        assert_eq!(cache.stats(), ScanCacheStats { hits: 1, misses: 1 });
+        cache.save().unwrap();
+
+        fs::remove_file(&b).unwrap();
+        let mut cache = ScanCache::load(&cache_path, root);
+        cache.checksum(&a).unwrap();
+        assert_eq!(cache.save().unwrap(), ScanCacheStats { hits: 1, misses: 0 });
+        let saved: ScanCacheFile =
+            serde_json::from_str(&fs::read_to_string(&cache_path).unwrap()).unwrap();
+        assert_eq!(
+            saved.entries.keys().collect::<Vec<_>>(),
+            [Path::new("a.txt")]
+        );
+    }
+
+    #[test]
+    fn test_recent_writes_and_other_versions_are_not_trusted() {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        let cache_path = root.join("scan_cache.json");
+        let fresh = root.join("fresh.txt");
+        fs::write(&fresh, "just written").unwrap();
+
+        let mut cache = ScanCache::load(&cache_path, root);
+        cache.checksum(&fresh).unwrap();
+        cache.save().unwrap();
+        let mut cache = ScanCache::load(&cache_path, root);
+        cache.checksum(&fresh).unwrap();
+        assert_eq!(cache.stats(), ScanCacheStats { hits: 0, misses: 1 });
+
+        age(&fresh, 60);
+        let mut cache = ScanCache::load(&cache_path, root);
+        cache.checksum(&fresh).unwrap();
+        cache.save().unwrap();
+        let text = fs::read_to_string(&cache_path).unwrap().replace(
+            &format!("\"version\":{SCAN_CACHE_VERSION}"),
+            "\"version\":0",
+        );
+        fs::write(&cache_path, text).unwrap();
+        let mut cache = ScanCache::load(&cache_path, root);
+        cache.checksum(&fresh).unwrap();
+        assert_eq!(cache.stats(), ScanCacheStats { hits: 0, misses: 1 });
+    }
+}
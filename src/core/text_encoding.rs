@@ -0,0 +1,372 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/*
+ * This module decodes source files that are not UTF-8. [FutureEncodingSupportOtherV1]
+ * A byte order mark always wins: UTF-8, UTF-16LE and UTF-16BE files with a BOM are
+ * decoded without configuration (and the BOM is dropped). Otherwise the profile's
+ * fallback rules decide, matched by glob against the path relative to the project root
+ * with `/` separators; the first matching rule applies. A UTF-16 fallback is used as is,
+ * since UTF-16 text without a BOM can look like valid UTF-8. A Windows-1252 fallback is
+ * only used when the file is not valid UTF-8, so ASCII and UTF-8 files matched by a broad
+ * glob still decode correctly. Files matched by no rule are UTF-8, with invalid bytes
+ * replaced. Text written back to a file is encoded as the file was, BOM included.
+ */
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum TextEncoding {
+    #[default]
+    #[serde(rename = "utf-8")]
+    Utf8,
+    #[serde(rename = "utf-16le")]
+    Utf16le,
+    #[serde(rename = "utf-16be")]
+    Utf16be,
+    #[serde(rename = "windows-1252")]
+    Windows1252,
+}
+
+/* Code points of Windows-1252 bytes 0x80..=0x9F; the rest of the range is Latin-1. */
+const WINDOWS_1252_HIGH: [char; 32] = [
+    '\u{20AC}', '\u{0081}', '\u{201A}', '\u{0192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{02C6}', '\u{2030}', '\u{0160}', '\u{2039}', '\u{0152}', '\u{008D}', '\u{017D}', '\u{008F}',
+    '\u{0090}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{02DC}', '\u{2122}', '\u{0161}', '\u{203A}', '\u{0153}', '\u{009D}', '\u{017E}', '\u{0178}',
+];
+
+impl TextEncoding {
+    pub const ALL_NAMES: [&'static str; 4] = ["utf-8", "utf-16le", "utf-16be", "windows-1252"];
+
+    pub fn setting_name(self) -> &'static str {
+        match self {
+            TextEncoding::Utf8 => Self::ALL_NAMES[0],
+            TextEncoding::Utf16le => Self::ALL_NAMES[1],
+            TextEncoding::Utf16be => Self::ALL_NAMES[2],
+            TextEncoding::Windows1252 => Self::ALL_NAMES[3],
+        }
+    }
+
+    fn parse(text: &str) -> Option<Self> {
+        match text.to_ascii_lowercase().as_str() {
+            "utf-8" | "utf8" => Some(TextEncoding::Utf8),
+            "utf-16le" | "utf-16" | "utf16" => Some(TextEncoding::Utf16le),
+            "utf-16be" => Some(TextEncoding::Utf16be),
+            "windows-1252" | "cp1252" | "latin1" => Some(TextEncoding::Windows1252),
+            _ => None,
+        }
+    }
+
+    pub fn is_utf16(self) -> bool {
+        matches!(self, TextEncoding::Utf16le | TextEncoding::Utf16be)
+    }
+
+    /* Decodes `bytes` (without BOM), replacing anything that cannot be decoded. */
+    pub fn decode(self, bytes: &[u8]) -> String {
+        match self {
+            TextEncoding::Utf8 => String::from_utf8_lossy(bytes).into_owned(),
+            TextEncoding::Utf16le => decode_utf16(bytes, u16::from_le_bytes),
+            TextEncoding::Utf16be => decode_utf16(bytes, u16::from_be_bytes),
+            TextEncoding::Windows1252 => bytes
+                .iter()
+                .map(|&byte| match byte {
+                    0x80..=0x9F => WINDOWS_1252_HIGH[usize::from(byte - 0x80)],
+                    _ => char::from(byte),
+                })
+                .collect(),
+        }
+    }
+
+    /* Encodes `text` (without BOM). Fails with the first character the encoding lacks. */
+    pub fn encode(self, text: &str) -> Result<Vec<u8>, char> {
+        match self {
+            TextEncoding::Utf8 => Ok(text.as_bytes().to_vec()),
+            TextEncoding::Utf16le => Ok(text.encode_utf16().flat_map(u16::to_le_bytes).collect()),
+            TextEncoding::Utf16be => Ok(text.encode_utf16().flat_map(u16::to_be_bytes).collect()),
+            TextEncoding::Windows1252 => text
+                .chars()
+                .map(|c| windows_1252_byte(c).ok_or(c))
+                .collect(),
+        }
+    }
+
+    fn bom(self) -> &'static [u8] {
+        match self {
+            TextEncoding::Utf8 => b"\xEF\xBB\xBF",
+            TextEncoding::Utf16le => b"\xFF\xFE",
+            TextEncoding::Utf16be => b"\xFE\xFF",
+            TextEncoding::Windows1252 => b"",
+        }
+    }
+}
+
+fn windows_1252_byte(c: char) -> Option<u8> {
+    if let Some(index) = WINDOWS_1252_HIGH.iter().position(|&high| high == c) {
+        return Some(0x80 + index as u8);
+    }
+    match u32::from(c) {
+        code @ (0..=0x7F | 0xA0..=0xFF) => Some(code as u8),
+        _ => None,
+    }
+}
+
+/* The encoding a file was decoded with, and whether it started with a byte order mark. */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DetectedEncoding {
+    pub encoding: TextEncoding,
+    pub has_bom: bool,
+}
+
+impl DetectedEncoding {
+    /* Encodes `text` the way the file was stored, BOM included. */
+    pub fn encode(self, text: &str) -> Result<Vec<u8>, char> {
+        let mut bytes = if self.has_bom {
+            self.encoding.bom().to_vec()
+        } else {
+            Vec::new()
+        };
+        bytes.extend(self.encoding.encode(text)?);
+        Ok(bytes)
+    }
+}
+
+fn decode_utf16(bytes: &[u8], to_unit: fn([u8; 2]) -> u16) -> String {
+    let units = bytes
+        .chunks_exact(2)
+        .map(|pair| to_unit([pair[0], pair[1]]));
+    let mut text: String = char::decode_utf16(units)
+        .map(|unit| unit.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect();
+    if bytes.len() % 2 == 1 {
+        text.push(char::REPLACEMENT_CHARACTER);
+    }
+    text
+}
+
+/* The encoding announced by a byte order mark, and the length of the mark. */
+pub fn detect_bom(bytes: &[u8]) -> Option<(TextEncoding, usize)> {
+    if bytes.starts_with(b"\xEF\xBB\xBF") {
+        Some((TextEncoding::Utf8, 3))
+    } else if bytes.starts_with(b"\xFF\xFE") {
+        Some((TextEncoding::Utf16le, 2))
+    } else if bytes.starts_with(b"\xFE\xFF") {
+        Some((TextEncoding::Utf16be, 2))
+    } else {
+        None
+    }
+}
+
+/* Decodes a whole file to UTF-8: the BOM if there is one, else `fallback` as described above. */
+pub fn decode(bytes: Vec<u8>, fallback: TextEncoding) -> String {
+    decode_detected(bytes, fallback).0
+}
+
+/* Decodes like `decode` and also returns the encoding that was used. */
+pub fn decode_detected(bytes: Vec<u8>, fallback: TextEncoding) -> (String, DetectedEncoding) {
+    if let Some((encoding, bom_length)) = detect_bom(&bytes) {
+        let detected = DetectedEncoding {
+            encoding,
+            has_bom: true,
+        };
+        return (encoding.decode(&bytes[bom_length..]), detected);
+    }
+    let without_bom = |encoding| DetectedEncoding {
+        encoding,
+        has_bom: false,
+    };
+    if fallback.is_utf16() {
+        return (fallback.decode(&bytes), without_bom(fallback));
+    }
+    match String::from_utf8(bytes) {
+        Ok(text) => (text, without_bom(TextEncoding::Utf8)),
+        Err(e) => (fallback.decode(e.as_bytes()), without_bom(fallback)),
+    }
+}
+
+/* One fallback rule of a profile: files matching `glob` that have no BOM use `encoding`. */
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EncodingRule {
+    pub encoding: TextEncoding,
+    pub glob: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EncodingError {
+    InvalidGlob { pattern: String, message: String },
+}
+
+impl fmt::Display for EncodingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncodingError::InvalidGlob { pattern, message } => {
+                write!(f, "Invalid encoding glob '{pattern}': {message}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for EncodingError {}
+
+/* The compiled form of a profile's fallback rules. */
+#[derive(Debug, Clone, Default)]
+pub struct EncodingRules {
+    rules: Vec<(glob::Pattern, TextEncoding)>,
+}
+
+impl EncodingRules {
+    pub fn compile(rules: &[EncodingRule]) -> Result<Self, EncodingError> {
+        let rules = rules
+            .iter()
+            .map(|rule| {
+                glob::Pattern::new(&rule.glob)
+                    .map(|pattern| (pattern, rule.encoding))
+                    .map_err(|e| EncodingError::InvalidGlob {
+                        pattern: rule.glob.clone(),
+                        message: e.msg.to_string(),
+                    })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(EncodingRules { rules })
+    }
+
+    /* The fallback encoding for `relative_path`: the first matching rule's, else UTF-8. */
+    pub fn fallback_for(&self, relative_path: &str) -> TextEncoding {
+        let normalized_path = relative_path.replace('\\', "/");
+        self.rules
+            .iter()
+            .find(|(pattern, _)| pattern.matches(&normalized_path))
+            .map_or(TextEncoding::Utf8, |(_, encoding)| *encoding)
+    }
+}
+
+/*
+ * Parses the text entered in the "Source Encodings..." dialog: a comma-separated list of
+ * `encoding@glob`, e.g. `windows-1252@legacy*, utf-16le@*.rc`. "off" or an empty input
+ * clears the list.
+ */
+pub fn parse_setting(text: &str) -> Result<Vec<EncodingRule>, String> {
+    let trimmed = text.trim();
+    if trimmed.is_empty() || trimmed.eq_ignore_ascii_case("off") {
+        return Ok(Vec::new());
+    }
+    let mut rules = Vec::new();
+    for item in trimmed.split(',').map(str::trim).filter(|i| !i.is_empty()) {
+        let Some((name, glob)) = item.split_once('@') else {
+            return Err(format!("'{item}' is not 'encoding@glob'"));
+        };
+        let (name, glob) = (name.trim(), glob.trim());
+        let encoding = TextEncoding::parse(name).ok_or_else(|| {
+            format!(
+                "Unknown encoding '{name}' (expected one of: {})",
+                TextEncoding::ALL_NAMES.join(", ")
+            )
+        })?;
+        if let Err(e) = glob::Pattern::new(glob) {
+            return Err(format!("Invalid glob '{glob}': {}", e.msg));
+        }
+        rules.push(EncodingRule {
+            encoding,
+            glob: glob.to_string(),
+        });
+    }
+    Ok(rules)
+}
+
+/* The inverse of `parse_setting`, used as the dialog's default text. */
+pub fn to_setting_text(rules: &[EncodingRule]) -> String {
+    if rules.is_empty() {
+        return "off".to_string();
+    }
+    rules
+        .iter()
+        .map(|rule| format!("{}@{}", rule.encoding.setting_name(), rule.glob))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bom_wins_over_fallback() {
+        let mut utf16le = vec![0xFF, 0xFE];
+        utf16le.extend("Größe\r\n".encode_utf16().flat_map(u16::to_le_bytes));
+        assert_eq!(decode(utf16le, TextEncoding::Windows1252), "Größe\r\n");
+
+        let mut utf16be = vec![0xFE, 0xFF];
+        utf16be.extend("id".encode_utf16().flat_map(u16::to_be_bytes));
+        assert_eq!(decode(utf16be, TextEncoding::Utf8), "id");
+
+        assert_eq!(decode(b"\xEF\xBB\xBFfn".to_vec(), TextEncoding::Utf8), "fn");
+    }
+
+    #[test]
+    fn test_fallback_decodes_windows_1252_and_utf16() {
+        let legacy = b"// \x93quoted\x94 caf\xe9 \x80 5".to_vec();
+        assert_eq!(
+            decode(legacy.clone(), TextEncoding::Windows1252),
+            "// \u{201C}quoted\u{201D} café € 5"
+        );
+        assert_eq!(
+            decode(legacy, TextEncoding::Utf8),
+            "// \u{FFFD}quoted\u{FFFD} caf\u{FFFD} \u{FFFD} 5"
+        );
+        // Valid UTF-8 is kept even where the fallback is Windows-1252.
+        assert_eq!(
+            decode("café".as_bytes().to_vec(), TextEncoding::Windows1252),
+            "café"
+        );
+        let bare_utf16: Vec<u8> = "ok".encode_utf16().flat_map(u16::to_le_bytes).collect();
+        assert_eq!(decode(bare_utf16, TextEncoding::Utf16le), "ok");
+        assert_eq!(TextEncoding::Utf16le.decode(b"a\x00b"), "a\u{FFFD}");
+    }
+
+    #[test]
+    fn test_text_is_encoded_back_as_detected() {
+        for (bytes, fallback) in [
+            (b"\xFF\xFEG\x00\xF6\x00".to_vec(), TextEncoding::Utf8),
+            (b"\xEF\xBB\xBFfn".to_vec(), TextEncoding::Utf8),
+            (b"\x00i\x00d".to_vec(), TextEncoding::Utf16be),
+            (b"\x93caf\xe9\x94 \x80".to_vec(), TextEncoding::Windows1252),
+            ("café".as_bytes().to_vec(), TextEncoding::Windows1252),
+        ] {
+            let (text, detected) = decode_detected(bytes.clone(), fallback);
+            assert_eq!(detected.encode(&text), Ok(bytes), "{text}");
+        }
+
+        let (_, legacy) = decode_detected(b"caf\xe9".to_vec(), TextEncoding::Windows1252);
+        assert_eq!(legacy.encode("naïve → ok"), Err('→'));
+    }
+
+    #[test]
+    fn test_rules_match_first_glob_and_round_trip() {
+        let rules = parse_setting("windows-1252@legacy*, utf-16@*.rc").unwrap();
+        assert_eq!(
+            rules,
+            vec![
+                EncodingRule {
+                    encoding: TextEncoding::Windows1252,
+                    glob: "legacy*".to_string(),
+                },
+                EncodingRule {
+                    encoding: TextEncoding::Utf16le,
+                    glob: "*.rc".to_string(),
+                },
+            ]
+        );
+        let compiled = EncodingRules::compile(&rules).unwrap();
+        assert_eq!(
+            compiled.fallback_for("legacy\\main.c"),
+            TextEncoding::Windows1252
+        );
+        assert_eq!(compiled.fallback_for("res/app.rc"), TextEncoding::Utf16le);
+        assert_eq!(compiled.fallback_for("src/main.rs"), TextEncoding::Utf8);
+        assert_eq!(
+            to_setting_text(&rules),
+            "windows-1252@legacy*, utf-16le@*.rc"
+        );
+        assert_eq!(parse_setting("off").unwrap(), Vec::new());
+        assert!(parse_setting("ebcdic@*.cob").is_err());
+        assert!(parse_setting("utf-8").is_err());
+    }
+}
@@ -0,0 +1,298 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/*
+ * This module decides which files fit into a profile's token budget. [ArchiveGenTokenBudgetV1]
+ * Every selected file carries a priority. When the selection is over budget, optional
+ * files are removed first, then normal files; pinned files are never touched. Within a
+ * priority the files are taken from the end of the archive backwards. A file that is
+ * larger than the remaining excess is truncated instead of dropped, so the archive ends
+ * up as close to the budget as possible. The kept lines of a truncated file are followed
+ * by a marker line that says how many lines were cut.
+ * Planning works on token counts only; the archiver applies the plan while writing.
+ */
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize,
+)]
+pub enum FilePriority {
+    /* Dropped or truncated first when over budget. */
+    Optional,
+    #[default]
+    Normal,
+    /* Always included in full, even if that exceeds the budget. */
+    Pinned,
+}
+
+impl FilePriority {
+    pub const ALL: [FilePriority; 3] = [
+        FilePriority::Pinned,
+        FilePriority::Normal,
+        FilePriority::Optional,
+    ];
+
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            FilePriority::Optional => "optional",
+            FilePriority::Normal => "normal",
+            FilePriority::Pinned => "pinned",
+        }
+    }
+
+    pub fn from_display_name(name: &str) -> Option<FilePriority> {
+        let trimmed = name.trim();
+        Self::ALL
+            .into_iter()
+            .find(|priority| priority.display_name().eq_ignore_ascii_case(trimmed))
+    }
+}
+
+/*
+ * The token count and priority of one selected file, in archive order.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BudgetCandidate {
+    pub path: PathBuf,
+    pub tokens: usize,
+    pub priority: FilePriority,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BudgetAction {
+    Dropped,
+    /* The file keeps its first `kept_tokens` tokens (on line boundaries). */
+    Truncated { kept_tokens: usize },
+}
+
+/*
+ * A file that did not make it into the archive in full.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OmittedFile {
+    pub path: PathBuf,
+    pub priority: FilePriority,
+    pub original_tokens: usize,
+    pub action: BudgetAction,
+}
+
+impl fmt::Display for OmittedFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.action {
+            BudgetAction::Dropped => write!(
+                f,
+                "{} ({}, {} tokens dropped)",
+                self.path.display(),
+                self.priority.display_name(),
+                self.original_tokens
+            ),
+            BudgetAction::Truncated { kept_tokens } => write!(
+                f,
+                "{} ({}, truncated to {} of {} tokens)",
+                self.path.display(),
+                self.priority.display_name(),
+                kept_tokens,
+                self.original_tokens
+            ),
+        }
+    }
+}
+
+/*
+ * The outcome of applying a budget. `tokens_after` may still exceed `budget` when the
+ * pinned files alone are larger than the budget.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenBudgetReport {
+    pub budget: usize,
+    pub tokens_before: usize,
+    pub tokens_after: usize,
+    pub omitted: Vec<OmittedFile>,
+}
+
+impl TokenBudgetReport {
+    pub fn is_over_budget(&self) -> bool {
+        self.tokens_after > self.budget
+    }
+
+    /* The action taken on each file that did not make it in full, by path. */
+    pub fn actions(&self) -> HashMap<&Path, BudgetAction> {
+        self.omitted
+            .iter()
+            .map(|omitted| (omitted.path.as_path(), omitted.action))
+            .collect()
+    }
+}
+
+/*
+ * Decides what to drop or truncate so that the candidates fit into `budget`.
+ * The returned `omitted` list is in the order the decisions were made.
+ */
+pub fn plan_token_budget(candidates: &[BudgetCandidate], budget: usize) -> TokenBudgetReport {
+    let tokens_before: usize = candidates.iter().map(|c| c.tokens).sum();
+    let mut tokens_after = tokens_before;
+    let mut omitted = Vec::new();
+
+    for priority in [FilePriority::Optional, FilePriority::Normal] {
+        for candidate in candidates.iter().rev().filter(|c| c.priority == priority) {
+            if tokens_after <= budget {
+                break;
+            }
+            if candidate.tokens == 0 {
+                continue;
+            }
+            let excess = tokens_after - budget;
+            let action = if candidate.tokens <= excess {
+                BudgetAction::Dropped
+            } else {
+                BudgetAction::Truncated {
+                    kept_tokens: candidate.tokens - excess,
+                }
+            };
+            tokens_after -= match action {
+                BudgetAction::Dropped => candidate.tokens,
+                BudgetAction::Truncated { .. } => excess,
+            };
+            omitted.push(OmittedFile {
+                path: candidate.path.clone(),
+                priority,
+                original_tokens: candidate.tokens,
+                action,
+            });
+        }
+    }
+
+    TokenBudgetReport {
+        budget,
+        tokens_before,
+        tokens_after,
+        omitted,
+    }
+}
+
+/*
+ * Keeps whole lines from the start of `content` while their token count stays within
+ * `kept_tokens`, as counted by `count_tokens`.
+ */
+pub fn truncate_to_tokens(
+    content: &str,
+    kept_tokens: usize,
+    count_tokens: impl Fn(&str) -> usize,
+) -> String {
+    let mut kept = String::new();
+    let mut used = 0;
+    for line in content.split_inclusive('\n') {
+        let line_tokens = count_tokens(line);
+        if used + line_tokens > kept_tokens {
+            break;
+        }
+        used += line_tokens;
+        kept.push_str(line);
+    }
+    kept
+}
+
+/* The line written after the kept lines of a truncated file. */
+pub fn truncation_marker(omitted_lines: usize) -> String {
+    let noun = if omitted_lines == 1 { "line" } else { "lines" };
+    format!("[truncated: {omitted_lines} more {noun}]\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(path: &str, tokens: usize, priority: FilePriority) -> BudgetCandidate {
+        BudgetCandidate {
+            path: PathBuf::from(path),
+            tokens,
+            priority,
+        }
+    }
+
+    #[test]
+    fn test_plan_within_budget_omits_nothing() {
+        let candidates = vec![candidate("a", 10, FilePriority::Normal)];
+        let report = plan_token_budget(&candidates, 10);
+        assert!(report.omitted.is_empty());
+        assert_eq!(report.tokens_after, 10);
+        assert!(!report.is_over_budget());
+    }
+
+    #[test]
+    fn test_plan_drops_optional_before_normal_and_never_pinned() {
+        let candidates = vec![
+            candidate("pinned", 50, FilePriority::Pinned),
+            candidate("opt1", 20, FilePriority::Optional),
+            candidate("normal1", 30, FilePriority::Normal),
+            candidate("opt2", 10, FilePriority::Optional),
+            candidate("normal2", 40, FilePriority::Normal),
+        ];
+
+        let report = plan_token_budget(&candidates, 75);
+
+        // 150 tokens: drop opt2 (10) and opt1 (20), drop normal2 (40), truncate normal1 by 5.
+        let decisions: Vec<(&str, BudgetAction)> = report
+            .omitted
+            .iter()
+            .map(|o| (o.path.to_str().unwrap(), o.action))
+            .collect();
+        assert_eq!(
+            decisions,
+            vec![
+                ("opt2", BudgetAction::Dropped),
+                ("opt1", BudgetAction::Dropped),
+                ("normal2", BudgetAction::Dropped),
+                ("normal1", BudgetAction::Truncated { kept_tokens: 25 }),
+            ]
+        );
+        assert_eq!(report.tokens_before, 150);
+        assert_eq!(report.tokens_after, 75);
+        let actions = report.actions();
+        assert_eq!(actions.len(), 4);
+        assert_eq!(actions.get(Path::new("pinned")), None);
+        assert_eq!(
+            actions.get(Path::new("normal1")),
+            Some(&BudgetAction::Truncated { kept_tokens: 25 })
+        );
+    }
+
+    #[test]
+    fn test_plan_reports_pinned_files_over_budget() {
+        let candidates = vec![
+            candidate("pinned", 100, FilePriority::Pinned),
+            candidate("normal", 10, FilePriority::Normal),
+        ];
+
+        let report = plan_token_budget(&candidates, 50);
+
+        assert_eq!(report.omitted.len(), 1);
+        assert_eq!(report.tokens_after, 100);
+        assert!(report.is_over_budget());
+    }
+
+    #[test]
+    fn test_truncate_to_tokens_keeps_whole_lines() {
+        let words = |s: &str| s.split_whitespace().count();
+        assert_eq!(
+            truncate_to_tokens("a b\nc d e\nf\n", 4, words),
+            "a b\n".to_string()
+        );
+        assert_eq!(
+            truncate_to_tokens("a b\nc d e\nf\n", 6, words),
+            "a b\nc d e\nf\n"
+        );
+    }
+
+    #[test]
+    fn test_priority_display_names_round_trip() {
+        for priority in FilePriority::ALL {
+            assert_eq!(
+                FilePriority::from_display_name(&priority.display_name().to_uppercase()),
+                Some(priority)
+            );
+        }
+        assert_eq!(FilePriority::from_display_name("urgent"), None);
+    }
+}
@@ -73,8 +73,8 @@ fn main() -> PlatformResult<()> {
     let core_config_manager = Arc::new(CoreConfigManagerForConfig::new());
     let core_profile_manager = Arc::new(CoreProfileManager::new());
     let core_file_system_scanner = Arc::new(CoreFileSystemScanner::new());
-    let core_archiver = Arc::new(CoreArchiver::new());
     let core_token_counter = Arc::new(CoreTikTokenCounter::new());
+    let core_archiver = Arc::new(CoreArchiver::new(core_token_counter.clone()));
     let core_state_manager = Arc::new(NodeStateApplicator::new());
 
     // Instantiate ProfileRuntimeData and wrap it for dependency injection
@@ -63,6 +63,66 @@ pub fn build_main_window_static_layout(window_id: WindowId) -> Vec<PlatformComma
             text: "Set Archive Path...".to_string(),
             children: Vec::new(),
         },
+        MenuItemConfig {
+            action: Some(ui_constants::MENU_ACTION_SET_ARCHIVE_FORMAT),
+            text: "Archive Format...".to_string(),
+            children: Vec::new(),
+        },
+        MenuItemConfig {
+            action: Some(ui_constants::MENU_ACTION_SET_ARCHIVE_SPLIT),
+            text: "Split Archive...".to_string(),
+            children: Vec::new(),
+        },
+        MenuItemConfig {
+            action: Some(ui_constants::MENU_ACTION_SET_TOKEN_BUDGET),
+            text: "Token Budget...".to_string(),
+            children: Vec::new(),
+        },
+        MenuItemConfig {
+            action: Some(ui_constants::MENU_ACTION_SET_FILE_PRIORITY),
+            text: "File Priority...".to_string(),
+            children: Vec::new(),
+        },
+        MenuItemConfig {
+            action: Some(ui_constants::MENU_ACTION_TOGGLE_OUTLINE),
+            text: "Include as Outline".to_string(),
+            children: Vec::new(),
+        },
+        MenuItemConfig {
+            action: Some(ui_constants::MENU_ACTION_TOGGLE_ARCHIVE_MANIFEST),
+            text: "Include Manifest".to_string(),
+            children: Vec::new(),
+        },
+        MenuItemConfig {
+            action: Some(ui_constants::MENU_ACTION_SET_DIRECTORY_TREE),
+            text: "Directory Tree...".to_string(),
+            children: Vec::new(),
+        },
+        MenuItemConfig {
+            action: Some(ui_constants::MENU_ACTION_SET_LINE_NUMBERS),
+            text: "Line Numbers...".to_string(),
+            children: Vec::new(),
+        },
+        MenuItemConfig {
+            action: Some(ui_constants::MENU_ACTION_SET_CONTENT_TRANSFORMS),
+            text: "Content Transforms...".to_string(),
+            children: Vec::new(),
+        },
+        MenuItemConfig {
+            action: Some(ui_constants::MENU_ACTION_SET_SECRET_SCAN),
+            text: "Secret Scanning...".to_string(),
+            children: Vec::new(),
+        },
+        MenuItemConfig {
+            action: Some(ui_constants::MENU_ACTION_SET_CONTENT_LIMITS),
+            text: "Binary and Large Files...".to_string(),
+            children: Vec::new(),
+        },
+        MenuItemConfig {
+            action: Some(ui_constants::MENU_ACTION_SET_ENCODINGS),
+            text: "Source Encodings...".to_string(),
+            children: Vec::new(),
+        },
         MenuItemConfig {
             action: Some(ui_constants::MENU_ACTION_EDIT_EXCLUDE_PATTERNS),
             text: "Edit Exclude Patterns...".to_string(),
@@ -88,6 +148,31 @@ pub fn build_main_window_static_layout(window_id: WindowId) -> Vec<PlatformComma
                 text: "&Generate Archive".to_string(),
                 children: Vec::new(),
             },
+            MenuItemConfig {
+                action: Some(ui_constants::MENU_ACTION_GENERATE_DELTA_ARCHIVE),
+                text: "Generate &Delta Archive".to_string(),
+                children: Vec::new(),
+            },
+            MenuItemConfig {
+                action: Some(ui_constants::MENU_ACTION_GENERATE_ALL_ARCHIVES),
+                text: "Generate A&ll Archives".to_string(),
+                children: Vec::new(),
+            },
+            MenuItemConfig {
+                action: Some(ui_constants::MENU_ACTION_SHOW_ARCHIVE_HISTORY),
+                text: "Archive &History...".to_string(),
+                children: Vec::new(),
+            },
+            MenuItemConfig {
+                action: Some(ui_constants::MENU_ACTION_APPLY_EDITED_ARCHIVE),
+                text: "&Apply Edited Archive...".to_string(),
+                children: Vec::new(),
+            },
+            MenuItemConfig {
+                action: Some(ui_constants::MENU_ACTION_APPLY_PATCH),
+                text: "Apply &Patch...".to_string(),
+                children: Vec::new(),
+            },
         ],
     };
     commands.push(main_menu_command);